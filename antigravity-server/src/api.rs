@@ -3,16 +3,27 @@
 //! REST API endpoints that mirror the Tauri IPC commands.
 
 use axum::{
-    extract::State,
-    http::StatusCode,
-    response::Json,
-    routing::{get, post},
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Html, IntoResponse, Json,
+    },
+    routing::{delete, get, post},
     Router,
 };
+use futures::Stream;
 use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use tokio::sync::broadcast::error::RecvError;
 
-use antigravity_core::models::AppConfig;
+use antigravity_core::models::{Account, AppConfig, QuotaData, RefreshStats};
 use antigravity_core::modules::config as core_config;
+use antigravity_core::modules::proxy_db::{LogQuery, UsageQuery};
+use antigravity_core::proxy::client_keys::{self, ClientKeyUsage};
+use antigravity_core::proxy::config::{ClientApiKey, Protocol};
+use antigravity_core::proxy::{AccountAvailability, CircuitBreakerSummary};
+use antigravity_shared::models::{UsageBucket, UsageGroupBy, UsageRollup};
 
 use crate::state::{get_model_quota, AppState};
 
@@ -21,15 +32,40 @@ pub fn router() -> Router<AppState> {
         // Status
         .route("/status", get(get_status))
         // Accounts
-        .route("/accounts", get(list_accounts))
+        .route("/accounts", get(list_accounts).post(add_account))
         .route("/accounts/current", get(get_current_account))
         .route("/accounts/switch", post(switch_account))
+        .route("/accounts/delete", post(delete_accounts))
+        .route("/accounts/reorder", post(reorder_accounts))
+        .route("/accounts/refresh", post(refresh_all_quotas))
+        .route("/accounts/health", get(get_accounts_health))
+        .route("/accounts/:id", delete(delete_account))
+        .route("/accounts/:id/quota", post(refresh_account_quota))
+        .route("/accounts/:id/proxy", post(toggle_proxy_status))
+        .route("/accounts/:id/health/enable", post(force_enable_account))
+        .route("/accounts/:id/circuit/reset", post(reset_account_circuit))
+        .route(
+            "/accounts/:id/rate-limit/clear",
+            post(clear_account_rate_limit),
+        )
+        // OAuth (headless)
+        .route("/oauth/url", get(get_oauth_url))
+        .route("/oauth/callback", get(oauth_callback))
+        .route("/oauth/exchange", post(exchange_oauth_code))
+        .route("/oauth/status", get(get_oauth_status))
+        .route("/oauth/cancel", post(cancel_oauth))
         // Proxy
         .route("/proxy/status", get(get_proxy_status))
         // Monitor
         .route("/monitor/requests", get(get_monitor_requests))
         .route("/monitor/stats", get(get_monitor_stats))
+        .route("/monitor/stream", get(stream_monitor_requests))
         .route("/monitor/clear", post(clear_monitor_logs))
+        // Usage analytics
+        .route("/stats/usage", get(get_usage_stats))
+        // Client API keys
+        .route("/keys", get(list_client_keys).post(create_client_key))
+        .route("/keys/:id", delete(revoke_client_key))
         // Config
         .route("/config", get(get_config))
         .route("/config", post(save_config))
@@ -64,12 +100,29 @@ struct AccountInfo {
     email: String,
     name: Option<String>,
     disabled: bool,
+    proxy_disabled: bool,
     is_current: bool,
     gemini_quota: Option<i32>,
     claude_quota: Option<i32>,
     subscription_tier: Option<String>,
 }
 
+impl AccountInfo {
+    fn from_account(a: &Account, is_current: bool) -> Self {
+        Self {
+            id: a.id.clone(),
+            email: a.email.clone(),
+            name: a.name.clone(),
+            disabled: a.disabled,
+            proxy_disabled: a.proxy_disabled,
+            is_current,
+            gemini_quota: get_model_quota(a, "gemini"),
+            claude_quota: get_model_quota(a, "claude"),
+            subscription_tier: a.quota.as_ref().and_then(|q| q.subscription_tier.clone()),
+        }
+    }
+}
+
 async fn list_accounts(
     State(state): State<AppState>,
 ) -> Result<Json<Vec<AccountInfo>>, (StatusCode, String)> {
@@ -78,17 +131,8 @@ async fn list_accounts(
     match state.list_accounts() {
         Ok(accounts) => {
             let infos: Vec<AccountInfo> = accounts
-                .into_iter()
-                .map(|a| AccountInfo {
-                    id: a.id.clone(),
-                    email: a.email.clone(),
-                    name: a.name.clone(),
-                    disabled: a.disabled,
-                    is_current: current_id.as_ref() == Some(&a.id),
-                    gemini_quota: get_model_quota(&a, "gemini"),
-                    claude_quota: get_model_quota(&a, "claude"),
-                    subscription_tier: a.quota.as_ref().and_then(|q| q.subscription_tier.clone()),
-                })
+                .iter()
+                .map(|a| AccountInfo::from_account(a, current_id.as_ref() == Some(&a.id)))
                 .collect();
             Ok(Json(infos))
         }
@@ -100,16 +144,7 @@ async fn get_current_account(
     State(state): State<AppState>,
 ) -> Result<Json<Option<AccountInfo>>, (StatusCode, String)> {
     match state.get_current_account() {
-        Ok(Some(a)) => Ok(Json(Some(AccountInfo {
-            id: a.id.clone(),
-            email: a.email.clone(),
-            name: a.name.clone(),
-            disabled: a.disabled,
-            is_current: true,
-            gemini_quota: get_model_quota(&a, "gemini"),
-            claude_quota: get_model_quota(&a, "claude"),
-            subscription_tier: a.quota.as_ref().and_then(|q| q.subscription_tier.clone()),
-        }))),
+        Ok(Some(a)) => Ok(Json(Some(AccountInfo::from_account(&a, true)))),
         Ok(None) => Ok(Json(None)),
         Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
     }
@@ -130,6 +165,390 @@ async fn switch_account(
     }
 }
 
+#[derive(Deserialize)]
+struct AddAccountRequest {
+    refresh_token: String,
+}
+
+async fn add_account(
+    State(state): State<AppState>,
+    Json(payload): Json<AddAccountRequest>,
+) -> Result<Json<AccountInfo>, (StatusCode, String)> {
+    let refresh_token = payload.refresh_token.trim();
+    if refresh_token.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "refresh_token is required".to_string(),
+        ));
+    }
+
+    let current_id = state.get_current_account().ok().flatten().map(|a| a.id);
+
+    match state.add_account(refresh_token).await {
+        Ok(a) => Ok(Json(AccountInfo::from_account(
+            &a,
+            current_id.as_ref() == Some(&a.id),
+        ))),
+        Err(e) => Err((StatusCode::BAD_REQUEST, e)),
+    }
+}
+
+async fn delete_account(
+    State(state): State<AppState>,
+    Path(account_id): Path<String>,
+) -> Result<Json<bool>, (StatusCode, String)> {
+    match state.delete_account(&account_id).await {
+        Ok(()) => Ok(Json(true)),
+        Err(e) if e.contains("not found") => Err((StatusCode::NOT_FOUND, e)),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
+
+#[derive(Deserialize)]
+struct AccountIdsRequest {
+    account_ids: Vec<String>,
+}
+
+async fn delete_accounts(
+    State(state): State<AppState>,
+    Json(payload): Json<AccountIdsRequest>,
+) -> Result<Json<bool>, (StatusCode, String)> {
+    match state.delete_accounts(&payload.account_ids).await {
+        Ok(()) => Ok(Json(true)),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
+
+async fn reorder_accounts(
+    State(state): State<AppState>,
+    Json(payload): Json<AccountIdsRequest>,
+) -> Result<Json<bool>, (StatusCode, String)> {
+    match state.reorder_accounts(&payload.account_ids).await {
+        Ok(()) => Ok(Json(true)),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
+
+#[derive(Deserialize)]
+struct ToggleProxyRequest {
+    enable: bool,
+    reason: Option<String>,
+}
+
+async fn toggle_proxy_status(
+    State(state): State<AppState>,
+    Path(account_id): Path<String>,
+    Json(payload): Json<ToggleProxyRequest>,
+) -> Result<Json<bool>, (StatusCode, String)> {
+    match state
+        .set_proxy_enabled(&account_id, payload.enable, payload.reason)
+        .await
+    {
+        Ok(_) => Ok(Json(true)),
+        Err(e) if e.contains("not found") => Err((StatusCode::NOT_FOUND, e)),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
+
+async fn refresh_account_quota(
+    State(state): State<AppState>,
+    Path(account_id): Path<String>,
+) -> Result<Json<QuotaData>, (StatusCode, String)> {
+    match state.refresh_account_quota(&account_id).await {
+        Ok(quota) => Ok(Json(quota)),
+        Err(e) if e.contains("not found") => Err((StatusCode::NOT_FOUND, e)),
+        Err(e) => Err((StatusCode::BAD_GATEWAY, e)),
+    }
+}
+
+async fn refresh_all_quotas(
+    State(state): State<AppState>,
+) -> Result<Json<RefreshStats>, (StatusCode, String)> {
+    match state.refresh_all_quotas().await {
+        Ok(stats) => Ok(Json(stats)),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
+
+// ============ Account Health ============
+
+#[derive(Serialize)]
+struct AccountsHealthResponse {
+    accounts: Vec<AccountAvailability>,
+    circuit_summary: CircuitBreakerSummary,
+    healthy_count: usize,
+    disabled_count: usize,
+}
+
+async fn get_accounts_health(State(state): State<AppState>) -> Json<AccountsHealthResponse> {
+    Json(AccountsHealthResponse {
+        accounts: state.get_account_availability().await,
+        circuit_summary: state.circuit_breaker().get_summary(),
+        healthy_count: state.health_monitor().healthy_count(),
+        disabled_count: state.health_monitor().disabled_count(),
+    })
+}
+
+/// Re-enable an account auto-disabled by the health monitor.
+/// Returns `false` if the account was not disabled.
+async fn force_enable_account(
+    State(state): State<AppState>,
+    Path(account_id): Path<String>,
+) -> Json<bool> {
+    Json(state.health_monitor().force_enable(&account_id).await)
+}
+
+async fn reset_account_circuit(
+    State(state): State<AppState>,
+    Path(account_id): Path<String>,
+) -> Result<Json<bool>, (StatusCode, String)> {
+    if state.reset_circuit(&account_id) {
+        Ok(Json(true))
+    } else {
+        Err((
+            StatusCode::NOT_FOUND,
+            format!("Account not found: {}", account_id),
+        ))
+    }
+}
+
+/// Returns `false` if the account had no rate-limit record.
+async fn clear_account_rate_limit(
+    State(state): State<AppState>,
+    Path(account_id): Path<String>,
+) -> Json<bool> {
+    Json(state.clear_rate_limit(&account_id))
+}
+
+// ============ OAuth ============
+
+#[derive(Deserialize)]
+struct OAuthUrlQuery {
+    redirect_uri: Option<String>,
+}
+
+#[derive(Serialize)]
+struct OAuthUrlResponse {
+    auth_url: String,
+    redirect_uri: String,
+    state: String,
+}
+
+/// Google only accepts loopback redirects for this client, so the default callback is
+/// `localhost` on the port the UI was reached through (works directly or via an SSH tunnel).
+/// When the browser cannot reach it, the user pastes the final URL into `/oauth/exchange`.
+fn default_redirect_uri(headers: &HeaderMap) -> String {
+    let port = headers
+        .get(header::HOST)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|host| host.rsplit_once(':'))
+        .and_then(|(_, port)| port.parse::<u16>().ok())
+        .unwrap_or(crate::DEFAULT_PORT);
+    format!("http://localhost:{}/api/oauth/callback", port)
+}
+
+async fn get_oauth_url(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<OAuthUrlQuery>,
+) -> Json<OAuthUrlResponse> {
+    let redirect_uri = query
+        .redirect_uri
+        .filter(|u| !u.trim().is_empty())
+        .unwrap_or_else(|| default_redirect_uri(&headers));
+    let (oauth_state, auth_url) = state.prepare_oauth(&redirect_uri).await;
+
+    Json(OAuthUrlResponse {
+        auth_url,
+        redirect_uri,
+        state: oauth_state,
+    })
+}
+
+#[derive(Deserialize)]
+struct OAuthCallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+fn oauth_result_page(ok: bool, message: &str) -> Html<String> {
+    let (color, title) = if ok {
+        ("green", "✅ Authorization successful")
+    } else {
+        ("red", "❌ Authorization failed")
+    };
+    let message = message
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    Html(format!(
+        "<html><body style='font-family: sans-serif; text-align: center; padding: 50px;'>\
+         <h1 style='color: {};'>{}</h1><p>{}</p></body></html>",
+        color, title, message
+    ))
+}
+
+async fn oauth_callback(
+    State(state): State<AppState>,
+    Query(query): Query<OAuthCallbackQuery>,
+) -> (StatusCode, Html<String>) {
+    if let Some(error) = query.error {
+        return (StatusCode::BAD_REQUEST, oauth_result_page(false, &error));
+    }
+    let Some(code) = query.code else {
+        return (
+            StatusCode::BAD_REQUEST,
+            oauth_result_page(false, "Missing authorization code"),
+        );
+    };
+
+    let Some(oauth_state) = query.state else {
+        return (
+            StatusCode::BAD_REQUEST,
+            oauth_result_page(false, "Missing OAuth state"),
+        );
+    };
+
+    match state.complete_oauth(&oauth_state, &code).await {
+        Ok(account) => (
+            StatusCode::OK,
+            oauth_result_page(
+                true,
+                &format!("{} added. You can close this window.", account.email),
+            ),
+        ),
+        Err(e) => (StatusCode::BAD_REQUEST, oauth_result_page(false, &e)),
+    }
+}
+
+#[derive(Deserialize)]
+struct OAuthExchangeRequest {
+    /// Bare authorization code (requires `state`)
+    code: Option<String>,
+    /// Full redirect URL copied from the browser address bar
+    callback_url: Option<String>,
+    state: Option<String>,
+}
+
+/// Extract `(code, state)` from a pasted redirect URL (or just its query string).
+/// Input without any `key=value` pairs is treated as a bare code.
+fn parse_callback_url(input: &str) -> Option<(String, Option<String>)> {
+    let input = input.trim();
+    if input.is_empty() {
+        return None;
+    }
+    if !input.contains('=') {
+        return Some((input.to_string(), None));
+    }
+    let url = url::Url::parse(input)
+        .or_else(|_| {
+            url::Url::parse(&format!(
+                "http://localhost/?{}",
+                input.trim_start_matches('?')
+            ))
+        })
+        .ok()?;
+    let mut code = None;
+    let mut state = None;
+    for (k, v) in url.query_pairs() {
+        match k.as_ref() {
+            "code" => code = Some(v.into_owned()),
+            "state" => state = Some(v.into_owned()),
+            _ => {}
+        }
+    }
+    code.map(|c| (c, state))
+}
+
+async fn exchange_oauth_code(
+    State(state): State<AppState>,
+    Json(payload): Json<OAuthExchangeRequest>,
+) -> Result<Json<AccountInfo>, (StatusCode, String)> {
+    let (code, oauth_state) = match (payload.code, payload.callback_url) {
+        (Some(code), _) if !code.trim().is_empty() => (code.trim().to_string(), payload.state),
+        (_, Some(url)) => match parse_callback_url(&url) {
+            Some((code, url_state)) => (code, url_state.or(payload.state)),
+            None => {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    "No authorization code found in callback_url".to_string(),
+                ))
+            }
+        },
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Either code or callback_url is required".to_string(),
+            ))
+        }
+    };
+
+    // Without a state there is no way to tell which pending flow the code belongs to.
+    let Some(oauth_state) = oauth_state.filter(|s| !s.trim().is_empty()) else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "state is required: paste the full callback URL or pass state with the code"
+                .to_string(),
+        ));
+    };
+
+    match state.complete_oauth(&oauth_state, &code).await {
+        Ok(account) => Ok(Json(AccountInfo::from_account(&account, false))),
+        Err(e) => Err((StatusCode::BAD_REQUEST, e)),
+    }
+}
+
+#[derive(Deserialize)]
+struct OAuthStatusQuery {
+    state: String,
+}
+
+#[derive(Serialize)]
+struct OAuthStatusResponse {
+    status: &'static str,
+    account: Option<AccountInfo>,
+    error: Option<String>,
+}
+
+async fn get_oauth_status(
+    State(state): State<AppState>,
+    Query(query): Query<OAuthStatusQuery>,
+) -> Result<Json<OAuthStatusResponse>, (StatusCode, String)> {
+    let response = match state.oauth_outcome(&query.state).await {
+        None => return Err((StatusCode::NOT_FOUND, "Unknown OAuth state".to_string())),
+        Some(None) => OAuthStatusResponse {
+            status: "pending",
+            account: None,
+            error: None,
+        },
+        Some(Some(Ok(account))) => OAuthStatusResponse {
+            status: "completed",
+            account: Some(AccountInfo::from_account(&account, false)),
+            error: None,
+        },
+        Some(Some(Err(e))) => OAuthStatusResponse {
+            status: "failed",
+            account: None,
+            error: Some(e),
+        },
+    };
+    Ok(Json(response))
+}
+
+#[derive(Deserialize)]
+struct OAuthCancelRequest {
+    state: String,
+}
+
+/// Cancel the caller's OAuth flow only. Returns `false` if the state is unknown.
+async fn cancel_oauth(
+    State(state): State<AppState>,
+    Json(payload): Json<OAuthCancelRequest>,
+) -> Json<bool> {
+    Json(state.cancel_oauth(&payload.state).await)
+}
+
 // ============ Proxy ============
 
 #[derive(Serialize)]
@@ -153,17 +572,72 @@ async fn get_proxy_status(State(state): State<AppState>) -> Json<ProxyStatusResp
 
 // ============ Monitor ============
 
+/// Filtered, paginated request history (newest first).
+///
+/// Query: `model`, `account`, `status` (`429`, `5xx`, `error`, `success`),
+/// `since`/`until` (ms), `q`, `limit`, `offset`. The total number of matches is
+/// returned in `X-Total-Count`.
+async fn get_monitor_requests(
+    State(state): State<AppState>,
+    axum::extract::Query(query): axum::extract::Query<LogQuery>,
+) -> Result<
+    (
+        HeaderMap,
+        Json<Vec<antigravity_shared::models::ProxyRequestLog>>,
+    ),
+    (StatusCode, String),
+> {
+    let (logs, total) = state
+        .query_proxy_logs(&query)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    let mut headers = HeaderMap::new();
+    if let Ok(value) = total.to_string().parse() {
+        headers.insert("X-Total-Count", value);
+    }
+    Ok((headers, Json(logs)))
+}
+
 #[derive(Deserialize)]
-struct MonitorQuery {
-    limit: Option<usize>,
+struct MonitorStreamQuery {
+    /// Strip request/response bodies from streamed logs
+    #[serde(default)]
+    redact: bool,
 }
 
-async fn get_monitor_requests(
+/// Live tail of proxy request logs as Server-Sent Events.
+///
+/// Each completed request is pushed as a `request` event with the
+/// `ProxyRequestLog` JSON. Subscribers that fall behind receive a `lagged`
+/// event carrying the number of skipped logs.
+async fn stream_monitor_requests(
     State(state): State<AppState>,
-    axum::extract::Query(query): axum::extract::Query<MonitorQuery>,
-) -> Json<Vec<antigravity_shared::models::ProxyRequestLog>> {
-    let logs = state.get_proxy_logs(query.limit).await;
-    Json(logs)
+    Query(query): Query<MonitorStreamQuery>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let receiver = state.subscribe_proxy_logs();
+    let redact = query.redact;
+
+    let stream = futures::stream::unfold(receiver, move |mut receiver| async move {
+        let event = match receiver.recv().await {
+            Ok(log) => {
+                let event = Event::default().event("request");
+                let data = if redact {
+                    event.json_data(antigravity_core::proxy::redact_request_log(&log))
+                } else {
+                    event.json_data(&*log)
+                };
+                data.unwrap_or_else(|e| Event::default().event("error").data(e.to_string()))
+            }
+            Err(RecvError::Lagged(skipped)) => {
+                Event::default().event("lagged").data(skipped.to_string())
+            }
+            Err(RecvError::Closed) => return None,
+        };
+        Some((Ok(event), receiver))
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
 }
 
 async fn get_monitor_stats(
@@ -178,21 +652,251 @@ async fn clear_monitor_logs(State(state): State<AppState>) -> Json<bool> {
     Json(true)
 }
 
+// ============ Usage analytics ============
+
+#[derive(Deserialize)]
+struct UsageStatsQuery {
+    since: Option<i64>,
+    until: Option<i64>,
+    #[serde(default)]
+    bucket: UsageBucket,
+    #[serde(default)]
+    group_by: UsageGroupBy,
+    /// `json` (default) or `csv`
+    format: Option<String>,
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn usage_to_csv(rows: &[UsageRollup]) -> String {
+    let mut csv = String::from(
+        "period_start,period,group,requests,errors,input_tokens,output_tokens,cached_tokens,estimated_cost_usd\n",
+    );
+    for row in rows {
+        let period = chrono::DateTime::from_timestamp_millis(row.period_start)
+            .map(|t| t.to_rfc3339())
+            .unwrap_or_default();
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{:.6}\n",
+            row.period_start,
+            period,
+            csv_field(row.group.as_deref().unwrap_or("")),
+            row.requests,
+            row.errors,
+            row.input_tokens,
+            row.output_tokens,
+            row.cached_tokens,
+            row.estimated_cost_usd
+        ));
+    }
+    csv
+}
+
+/// Token / cost rollups by hour or day, grouped by model, mapped model,
+/// account or client key. `?format=csv` downloads the same rows as CSV.
+async fn get_usage_stats(
+    State(state): State<AppState>,
+    Query(params): Query<UsageStatsQuery>,
+) -> Result<axum::response::Response, (StatusCode, String)> {
+    let query = UsageQuery {
+        since: params.since,
+        until: params.until,
+        bucket: params.bucket,
+        group_by: params.group_by,
+    };
+    let rows = state
+        .usage_rollup(&query)
+        .await
+        .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e))?;
+
+    if params.format.as_deref() == Some("csv") {
+        Ok((
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+                (
+                    header::CONTENT_DISPOSITION,
+                    "attachment; filename=\"usage.csv\"",
+                ),
+            ],
+            usage_to_csv(&rows),
+        )
+            .into_response())
+    } else {
+        Ok(Json(rows).into_response())
+    }
+}
+
+// ============ Client API keys ============
+
+/// Client key as listed by the admin API (secret masked) with its usage.
+#[derive(Serialize)]
+struct ClientKeyView {
+    id: String,
+    name: String,
+    key_preview: String,
+    enabled: bool,
+    allowed_models: Vec<String>,
+    allowed_protocols: Vec<Protocol>,
+    requests_per_minute: Option<u32>,
+    daily_token_budget: Option<u64>,
+    expires_at: Option<i64>,
+    created_at: i64,
+    usage: ClientKeyUsage,
+}
+
+impl From<&ClientApiKey> for ClientKeyView {
+    fn from(key: &ClientApiKey) -> Self {
+        Self {
+            id: key.id.clone(),
+            name: key.name.clone(),
+            key_preview: client_keys::mask_key(&key.key),
+            enabled: key.enabled,
+            allowed_models: key.allowed_models.clone(),
+            allowed_protocols: key.allowed_protocols.clone(),
+            requests_per_minute: key.requests_per_minute,
+            daily_token_budget: key.daily_token_budget,
+            expires_at: key.expires_at,
+            created_at: key.created_at,
+            usage: client_keys::tracker().usage(&key.id),
+        }
+    }
+}
+
+/// Returned once on creation; the full secret is not retrievable later.
+#[derive(Serialize)]
+struct CreatedClientKey {
+    key: String,
+    #[serde(flatten)]
+    view: ClientKeyView,
+}
+
+#[derive(Deserialize)]
+struct CreateClientKeyRequest {
+    name: String,
+    #[serde(default)]
+    allowed_models: Vec<String>,
+    #[serde(default)]
+    allowed_protocols: Vec<Protocol>,
+    requests_per_minute: Option<u32>,
+    daily_token_budget: Option<u64>,
+    /// Unix seconds
+    expires_at: Option<i64>,
+}
+
+async fn list_client_keys() -> Result<Json<Vec<ClientKeyView>>, (StatusCode, String)> {
+    let config = core_config::load_config().map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(Json(
+        config
+            .proxy
+            .api_keys
+            .iter()
+            .map(ClientKeyView::from)
+            .collect(),
+    ))
+}
+
+async fn create_client_key(
+    State(state): State<AppState>,
+    Json(payload): Json<CreateClientKeyRequest>,
+) -> Result<Json<CreatedClientKey>, (StatusCode, String)> {
+    let name = payload.name.trim().to_string();
+    if name.is_empty() || name.chars().count() > 64 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "name must be 1-64 characters".to_string(),
+        ));
+    }
+    if payload.requests_per_minute == Some(0) || payload.daily_token_budget == Some(0) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "requests_per_minute and daily_token_budget must be positive".to_string(),
+        ));
+    }
+
+    let mut config =
+        core_config::load_config().map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    if config.proxy.api_keys.iter().any(|k| k.name == name) {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("A key named '{}' already exists", name),
+        ));
+    }
+
+    let key = ClientApiKey {
+        id: uuid::Uuid::new_v4().to_string(),
+        name,
+        key: client_keys::generate_key_secret(),
+        enabled: true,
+        allowed_models: payload.allowed_models,
+        allowed_protocols: payload.allowed_protocols,
+        requests_per_minute: payload.requests_per_minute,
+        daily_token_budget: payload.daily_token_budget,
+        expires_at: payload.expires_at,
+        created_at: chrono::Utc::now().timestamp(),
+    };
+    config.proxy.api_keys.push(key.clone());
+    core_config::save_config(&config).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    state.hot_reload_proxy_config().await;
+
+    tracing::info!("🔑 Created client API key '{}'", key.name);
+    Ok(Json(CreatedClientKey {
+        key: key.key.clone(),
+        view: ClientKeyView::from(&key),
+    }))
+}
+
+async fn revoke_client_key(
+    State(state): State<AppState>,
+    Path(key_id): Path<String>,
+) -> Result<Json<bool>, (StatusCode, String)> {
+    let mut config =
+        core_config::load_config().map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let Some(index) = config.proxy.api_keys.iter().position(|k| k.id == key_id) else {
+        return Err((StatusCode::NOT_FOUND, format!("Key {} not found", key_id)));
+    };
+    let key = config.proxy.api_keys.remove(index);
+    core_config::save_config(&config).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    state.hot_reload_proxy_config().await;
+    client_keys::tracker().remove(&key.id);
+
+    tracing::info!("🔑 Revoked client API key '{}'", key.name);
+    Ok(Json(true))
+}
+
 // ============ Config (Placeholders) ============
 
+/// Client key secrets are only shown once on creation, so the config view masks them.
 async fn get_config(
     State(_state): State<AppState>,
 ) -> Result<Json<AppConfig>, (StatusCode, String)> {
     match core_config::load_config() {
-        Ok(config) => Ok(Json(config)),
+        Ok(mut config) => {
+            for key in &mut config.proxy.api_keys {
+                key.key = client_keys::mask_key(&key.key);
+            }
+            Ok(Json(config))
+        }
         Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
     }
 }
 
 async fn save_config(
     State(state): State<AppState>,
-    Json(payload): Json<AppConfig>,
+    Json(mut payload): Json<AppConfig>,
 ) -> Result<Json<bool>, (StatusCode, String)> {
+    // The payload carries masked secrets from get_config; keep the stored ones.
+    let current = core_config::load_config().map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    for key in &mut payload.proxy.api_keys {
+        if let Some(stored) = current.proxy.api_keys.iter().find(|k| k.id == key.id) {
+            key.key = stored.key.clone();
+        }
+    }
     match core_config::save_config(&payload) {
         Ok(_) => {
             state.hot_reload_proxy_config().await;
@@ -13,6 +13,7 @@ use axum::{
 };
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use tower_http::{
     cors::{Any, CorsLayer},
     services::{ServeDir, ServeFile},
@@ -27,7 +28,10 @@ mod state;
 use antigravity_core::proxy::server::AxumServer;
 use state::AppState;
 
-const DEFAULT_PORT: u16 = 8045;
+pub(crate) const DEFAULT_PORT: u16 = 8045;
+
+/// How often AIMD limits and rate-limit lockouts are snapshotted to disk
+const LIMIT_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(60);
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -52,12 +56,29 @@ async fn main() -> Result<()> {
             tracing::warn!("⚠️ Could not load accounts into token manager: {}", e);
         }
     }
-
-    let monitor = Arc::new(antigravity_core::proxy::ProxyMonitor::new());
+    token_manager.start_limit_persistence_task(LIMIT_SNAPSHOT_INTERVAL);
+
+    let monitor_events = Arc::new(antigravity_core::proxy::BroadcastEventBus::default());
+    let monitor = antigravity_core::proxy::ProxyMonitor::with_event_bus(monitor_events.clone());
+    // Persist request logs to data_dir/proxy_logs.db so history survives restarts
+    let monitor = match antigravity_core::modules::proxy_db::default_store() {
+        Ok(store) => {
+            store.set_retention(initial_proxy_config.request_log.clone());
+            antigravity_core::proxy::client_keys::tracker()
+                .seed_from_log_store(&store, &initial_proxy_config.api_keys);
+            monitor.with_store(store)
+        }
+        Err(e) => {
+            tracing::warn!("⚠️ Request log persistence disabled: {}", e);
+            monitor
+        }
+    };
+    let monitor = Arc::new(monitor);
 
     // Create AxumServer for hot reload capabilities (without starting listener)
     let axum_server = Arc::new(AxumServer::new(
         initial_proxy_config.custom_mapping.clone(),
+        initial_proxy_config.router_rules.clone(),
         initial_proxy_config.upstream_proxy.clone(),
         antigravity_core::proxy::ProxySecurityConfig::from_proxy_config(&initial_proxy_config),
         initial_proxy_config.zai.clone(),
@@ -66,6 +87,7 @@ async fn main() -> Result<()> {
     let state = AppState::new_with_components(
         token_manager.clone(),
         monitor.clone(),
+        monitor_events,
         initial_proxy_config.clone(),
         axum_server.clone(),
     )
@@ -88,11 +110,45 @@ async fn main() -> Result<()> {
         DEFAULT_PORT
     );
 
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal())
+        .await?;
+
+    info!("🛑 Shutting down, saving rate limit state...");
+    if let Err(e) = token_manager.save_limits() {
+        tracing::warn!("⚠️ Failed to save rate limit state: {}", e);
+    }
+    if let Some(store) = monitor.store() {
+        store.flush();
+    }
 
     Ok(())
 }
 
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
 async fn build_router(state: AppState, _axum_server: Arc<AxumServer>) -> Router {
     // Get proxy router from state (uses shared Arc for hot-reload)
     let proxy_router = state.build_proxy_router();
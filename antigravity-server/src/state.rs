@@ -4,17 +4,33 @@
 
 use anyhow::Result;
 use axum::Router;
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 
-use antigravity_core::models::Account;
-use antigravity_core::modules::account;
+use antigravity_core::models::{Account, QuotaData, RefreshStats, TokenData};
+use antigravity_core::modules::{account, oauth, quota};
 use antigravity_core::proxy::{
-    build_proxy_router_with_shared_state, server::AxumServer, AdaptiveLimitTracker,
-    CircuitBreakerManager, HealthMonitor, ProxyMonitor, ProxySecurityConfig, TokenManager,
+    build_proxy_router_with_shared_state, project_resolver, server::AxumServer,
+    AccountAvailability, AdaptiveLimitManager, BroadcastEventBus, CircuitBreakerManager,
+    HealthMonitor, ProxyMonitor, ProxySecurityConfig, TokenManager,
 };
 use antigravity_shared::proxy::config::ProxyConfig;
 
+/// Pending OAuth flows expire if the callback never arrives
+const OAUTH_FLOW_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// A headless OAuth flow, keyed by its `state` parameter
+pub struct OAuthFlow {
+    pub redirect_uri: String,
+    pub created_at: Instant,
+    /// `None` while waiting for the callback / pasted code
+    pub outcome: Option<Result<Account, String>>,
+    /// Set while a code exchange is running so concurrent completions are rejected
+    pub exchanging: bool,
+}
+
 /// Shared application state
 #[derive(Clone)]
 pub struct AppState {
@@ -24,22 +40,25 @@ pub struct AppState {
 pub struct AppStateInner {
     pub token_manager: Arc<TokenManager>,
     pub monitor: Arc<ProxyMonitor>,
+    // Live request feed (/api/monitor/stream)
+    pub monitor_events: Arc<BroadcastEventBus>,
     pub proxy_config: Arc<RwLock<ProxyConfig>>,
     #[allow(dead_code)] // Reserved for future hot-reload (listener restart)
     pub axum_server: Arc<AxumServer>,
     // Shared state for hot-reload
     pub custom_mapping: Arc<RwLock<std::collections::HashMap<String, String>>>,
+    pub router_rules: Arc<RwLock<Vec<antigravity_shared::proxy::config::ModelRouteRule>>>,
     pub security_config: Arc<RwLock<ProxySecurityConfig>>,
     pub zai_config: Arc<RwLock<antigravity_shared::proxy::config::ZaiConfig>>,
+    pub providers: Arc<RwLock<Vec<antigravity_shared::proxy::config::UpstreamProvider>>>,
     pub experimental_config: Arc<RwLock<antigravity_shared::proxy::config::ExperimentalConfig>>,
-    // AIMD Predictive Rate Limiting System
-    // TODO: Wire these into handlers for pre-request/post-request feedback
-    #[allow(dead_code)]
-    pub adaptive_limits: Arc<AdaptiveLimitTracker>,
-    #[allow(dead_code)]
+    pub retry_policy: Arc<RwLock<antigravity_shared::proxy::config::RetryPolicyConfig>>,
+    // AIMD Predictive Rate Limiting System (shared with TokenManager's account selection)
+    pub adaptive_limits: Arc<AdaptiveLimitManager>,
     pub health_monitor: Arc<HealthMonitor>,
-    #[allow(dead_code)]
     pub circuit_breaker: Arc<CircuitBreakerManager>,
+    // Headless OAuth flows (state -> flow)
+    pub oauth_flows: RwLock<HashMap<String, OAuthFlow>>,
 }
 
 impl AppState {
@@ -47,24 +66,28 @@ impl AppState {
     pub async fn new_with_components(
         token_manager: Arc<TokenManager>,
         monitor: Arc<ProxyMonitor>,
+        monitor_events: Arc<BroadcastEventBus>,
         proxy_config: ProxyConfig,
         axum_server: Arc<AxumServer>,
     ) -> Result<Self> {
         // Create shared Arc references for hot-reload
         let custom_mapping = Arc::new(RwLock::new(proxy_config.custom_mapping.clone()));
+        let router_rules = Arc::new(RwLock::new(proxy_config.router_rules.clone()));
         let security_config = Arc::new(RwLock::new(ProxySecurityConfig::from_proxy_config(
             &proxy_config,
         )));
         let zai_config = Arc::new(RwLock::new(proxy_config.zai.clone()));
+        let providers = Arc::new(RwLock::new(proxy_config.providers.clone()));
         let experimental_config = Arc::new(RwLock::new(proxy_config.experimental.clone()));
+        let retry_policy = Arc::new(RwLock::new(proxy_config.retry.clone()));
 
-        // Initialize AIMD Predictive Rate Limiting System
-        let adaptive_limits = Arc::new(AdaptiveLimitTracker::new(
-            0.85, // safety_margin: 85% of confirmed limit is working threshold
-            antigravity_core::proxy::AIMDController::default(),
-        ));
-        let health_monitor = HealthMonitor::new();
-        let circuit_breaker = Arc::new(CircuitBreakerManager::new());
+        // AIMD / health / circuit breaker live in TokenManager so account selection
+        // and handler feedback share the same state
+        let adaptive_limits = token_manager.adaptive_limits().clone();
+        let health_monitor = token_manager.health_monitor().clone();
+        let circuit_breaker = token_manager.circuit_breaker().clone();
+
+        token_manager.update_hedging_config(&proxy_config.hedging);
 
         // Start health monitor recovery task
         health_monitor.start_recovery_task();
@@ -75,15 +98,20 @@ impl AppState {
             inner: Arc::new(AppStateInner {
                 token_manager,
                 monitor,
+                monitor_events,
                 proxy_config: Arc::new(RwLock::new(proxy_config)),
                 axum_server,
                 custom_mapping,
+                router_rules,
                 security_config,
                 zai_config,
+                providers,
                 experimental_config,
+                retry_policy,
                 adaptive_limits,
                 health_monitor,
                 circuit_breaker,
+                oauth_flows: RwLock::new(HashMap::new()),
             }),
         })
     }
@@ -92,12 +120,15 @@ impl AppState {
         build_proxy_router_with_shared_state(
             self.inner.token_manager.clone(),
             self.inner.custom_mapping.clone(),
+            self.inner.router_rules.clone(),
             // We need to get upstream_proxy, but it's in proxy_config - for now use default
             antigravity_shared::utils::http::UpstreamProxyConfig::default(),
             self.inner.security_config.clone(),
             self.inner.zai_config.clone(),
+            self.inner.providers.clone(),
             self.inner.monitor.clone(),
             self.inner.experimental_config.clone(),
+            self.inner.retry_policy.clone(),
         )
     }
 
@@ -113,6 +144,237 @@ impl AppState {
         account::switch_account(account_id).await
     }
 
+    /// Add (or update) an account from a refresh token, resolving its real email via Google.
+    pub async fn add_account(&self, refresh_token: &str) -> Result<Account, String> {
+        let token_res = oauth::refresh_access_token(refresh_token).await?;
+        let user_info = oauth::get_user_info(&token_res.access_token).await?;
+
+        let token = TokenData::new(
+            token_res.access_token,
+            refresh_token.to_string(),
+            token_res.expires_in,
+            Some(user_info.email.clone()),
+            None,
+            None,
+        );
+
+        let mut account =
+            account::upsert_account(user_info.email.clone(), user_info.get_display_name(), token)?;
+        tracing::info!("➕ Account added: {}", account.email);
+
+        self.activate_new_account(&mut account).await;
+        Ok(account)
+    }
+
+    /// Fetch initial quota for a freshly added account and make it available to the proxy.
+    async fn activate_new_account(&self, account: &mut Account) {
+        if let Ok(quota) = account::fetch_quota_with_retry(account).await {
+            let _ = account::update_account_quota(&account.id, quota.clone());
+            account.update_quota(quota);
+        }
+
+        self.reload_token_pool().await;
+    }
+
+    /// Start a headless OAuth flow. Returns `(state, auth_url)`.
+    pub async fn prepare_oauth(&self, redirect_uri: &str) -> (String, String) {
+        let state = uuid::Uuid::new_v4().simple().to_string();
+        let auth_url = oauth::get_auth_url_with_state(redirect_uri, Some(&state));
+
+        let mut flows = self.inner.oauth_flows.write().await;
+        flows.retain(|_, f| f.created_at.elapsed() < OAUTH_FLOW_TTL);
+        flows.insert(
+            state.clone(),
+            OAuthFlow {
+                redirect_uri: redirect_uri.to_string(),
+                created_at: Instant::now(),
+                outcome: None,
+                exchanging: false,
+            },
+        );
+
+        (state, auth_url)
+    }
+
+    /// Complete the OAuth flow identified by `state` with an authorization code.
+    ///
+    /// A flow completes at most once: calls made while an exchange is running or after
+    /// it succeeded are rejected. A failed exchange leaves the flow open for another code.
+    pub async fn complete_oauth(&self, state: &str, code: &str) -> Result<Account, String> {
+        let redirect_uri = {
+            let mut flows = self.inner.oauth_flows.write().await;
+            match flows.get_mut(state) {
+                Some(f) if f.created_at.elapsed() < OAUTH_FLOW_TTL => {
+                    if f.exchanging || matches!(f.outcome, Some(Ok(_))) {
+                        return Err("OAuth flow already completed or in progress".to_string());
+                    }
+                    f.exchanging = true;
+                    f.redirect_uri.clone()
+                }
+                _ => return Err("No pending OAuth flow (expired or unknown state)".to_string()),
+            }
+        };
+
+        let result = self.exchange_oauth_code(code, &redirect_uri).await;
+
+        if let Some(flow) = self.inner.oauth_flows.write().await.get_mut(state) {
+            flow.exchanging = false;
+            flow.outcome = Some(result.clone());
+        }
+
+        result
+    }
+
+    async fn exchange_oauth_code(&self, code: &str, redirect_uri: &str) -> Result<Account, String> {
+        let token_res = oauth::exchange_code(code, redirect_uri).await?;
+
+        let refresh_token = token_res.refresh_token.ok_or_else(|| {
+            "Google did not return a refresh token. Revoke 'Antigravity Tools' access at \
+             https://myaccount.google.com/permissions and authorize again, or add the account \
+             with a refresh token."
+                .to_string()
+        })?;
+
+        let user_info = oauth::get_user_info(&token_res.access_token).await?;
+        let project_id = project_resolver::fetch_project_id(&token_res.access_token)
+            .await
+            .ok();
+
+        let token = TokenData::new(
+            token_res.access_token,
+            refresh_token,
+            token_res.expires_in,
+            Some(user_info.email.clone()),
+            project_id,
+            None,
+        );
+
+        let mut account =
+            account::upsert_account(user_info.email.clone(), user_info.get_display_name(), token)?;
+        tracing::info!("🔐 OAuth login complete: {}", account.email);
+
+        self.activate_new_account(&mut account).await;
+        Ok(account)
+    }
+
+    /// Look up an OAuth flow: `None` if unknown, `Some(None)` while pending.
+    pub async fn oauth_outcome(&self, state: &str) -> Option<Option<Result<Account, String>>> {
+        self.inner
+            .oauth_flows
+            .read()
+            .await
+            .get(state)
+            .map(|f| f.outcome.clone())
+    }
+
+    /// Cancel a single OAuth flow. Returns `false` if the state is unknown.
+    pub async fn cancel_oauth(&self, state: &str) -> bool {
+        self.inner.oauth_flows.write().await.remove(state).is_some()
+    }
+
+    pub async fn delete_account(&self, account_id: &str) -> Result<(), String> {
+        account::delete_account(account_id)?;
+        tracing::info!("🗑️ Account deleted: {}", account_id);
+        self.reload_token_pool().await;
+        Ok(())
+    }
+
+    pub async fn delete_accounts(&self, account_ids: &[String]) -> Result<(), String> {
+        account::delete_accounts(account_ids)?;
+        tracing::info!("🗑️ {} accounts deleted", account_ids.len());
+        self.reload_token_pool().await;
+        Ok(())
+    }
+
+    pub async fn reorder_accounts(&self, account_ids: &[String]) -> Result<(), String> {
+        account::reorder_accounts(account_ids)?;
+        self.reload_token_pool().await;
+        Ok(())
+    }
+
+    pub async fn set_proxy_enabled(
+        &self,
+        account_id: &str,
+        enable: bool,
+        reason: Option<String>,
+    ) -> Result<Account, String> {
+        let account = account::set_proxy_disabled(account_id, !enable, reason)?;
+        self.reload_token_pool().await;
+        Ok(account)
+    }
+
+    /// Refresh quota for a single account (refreshes the access token if needed).
+    pub async fn refresh_account_quota(&self, account_id: &str) -> Result<QuotaData, String> {
+        let mut account = account::load_account(account_id)?;
+        let quota = account::fetch_quota_with_retry(&mut account)
+            .await
+            .map_err(|e| e.to_string())?;
+        account::update_account_quota(account_id, quota.clone())?;
+        Ok(quota)
+    }
+
+    /// Refresh quota for all enabled, non-forbidden accounts.
+    pub async fn refresh_all_quotas(&self) -> Result<RefreshStats, String> {
+        let accounts: Vec<Account> = account::list_accounts()?
+            .into_iter()
+            .filter(|a| !a.disabled && !a.quota.as_ref().is_some_and(|q| q.is_forbidden))
+            .collect();
+
+        let total = accounts.len();
+        let mut failed = 0;
+        let mut batch = Vec::with_capacity(total);
+
+        for mut acc in accounts {
+            match oauth::ensure_fresh_token(&acc.token).await {
+                Ok(token) => {
+                    if token.access_token != acc.token.access_token {
+                        acc.token = token;
+                        let _ = account::save_account(&acc);
+                    }
+                    batch.push((acc.id, acc.token.access_token));
+                }
+                Err(e) => {
+                    tracing::warn!("Token refresh failed for {}: {}", acc.email, e);
+                    failed += 1;
+                }
+            }
+        }
+
+        let mut success = 0;
+        for (account_id, result) in quota::fetch_all_quotas(batch).await {
+            match result
+                .map_err(|e| e.to_string())
+                .and_then(|q| account::update_account_quota(&account_id, q))
+            {
+                Ok(()) => success += 1,
+                Err(e) => {
+                    tracing::warn!("Quota refresh failed for {}: {}", account_id, e);
+                    failed += 1;
+                }
+            }
+        }
+
+        tracing::info!(
+            "📊 Quota refresh complete: {} succeeded, {} failed",
+            success,
+            failed
+        );
+
+        Ok(RefreshStats {
+            total,
+            success,
+            failed,
+        })
+    }
+
+    /// Reload the proxy token pool so account changes take effect immediately.
+    pub async fn reload_token_pool(&self) {
+        match self.inner.token_manager.load_accounts().await {
+            Ok(count) => tracing::info!("🔄 Token pool reloaded: {} accounts", count),
+            Err(e) => tracing::warn!("⚠️ Failed to reload token pool: {}", e),
+        }
+    }
+
     pub fn get_account_count(&self) -> usize {
         match account::list_accounts() {
             Ok(accounts) => accounts.iter().filter(|a| !a.disabled).count(),
@@ -128,21 +390,47 @@ impl AppState {
         self.inner.monitor.get_stats().await
     }
 
-    pub async fn get_proxy_logs(
+    pub async fn query_proxy_logs(
+        &self,
+        query: &antigravity_core::modules::proxy_db::LogQuery,
+    ) -> Result<(Vec<antigravity_shared::models::ProxyRequestLog>, u64), String> {
+        self.inner.monitor.query_logs(query).await
+    }
+
+    pub async fn usage_rollup(
         &self,
-        limit: Option<usize>,
-    ) -> Vec<antigravity_shared::models::ProxyRequestLog> {
-        self.inner.monitor.get_logs(limit).await
+        query: &antigravity_core::modules::proxy_db::UsageQuery,
+    ) -> Result<Vec<antigravity_shared::models::UsageRollup>, String> {
+        self.inner.monitor.usage_rollup(query).await
     }
 
     pub async fn clear_proxy_logs(&self) {
         self.inner.monitor.clear_logs().await;
     }
 
+    pub fn subscribe_proxy_logs(
+        &self,
+    ) -> tokio::sync::broadcast::Receiver<Arc<antigravity_shared::models::ProxyRequestLog>> {
+        self.inner.monitor_events.subscribe()
+    }
+
     pub fn get_token_manager_count(&self) -> usize {
         self.inner.token_manager.len()
     }
 
+    /// Per-account health / circuit / AIMD view of the token pool
+    pub async fn get_account_availability(&self) -> Vec<AccountAvailability> {
+        self.inner.token_manager.get_account_availability().await
+    }
+
+    pub fn reset_circuit(&self, account_id: &str) -> bool {
+        self.inner.token_manager.reset_circuit(account_id)
+    }
+
+    pub fn clear_rate_limit(&self, account_id: &str) -> bool {
+        self.inner.token_manager.clear_rate_limit(account_id)
+    }
+
     pub async fn hot_reload_proxy_config(&self) {
         match antigravity_core::modules::config::load_config() {
             Ok(app_config) => {
@@ -155,6 +443,11 @@ impl AppState {
                     *mapping = proxy_config.custom_mapping.clone();
                     tracing::debug!("📝 Updated custom_mapping with {} entries", mapping.len());
                 }
+                {
+                    let mut rules = self.inner.router_rules.write().await;
+                    *rules = proxy_config.router_rules.clone();
+                    tracing::debug!("📝 Updated router_rules with {} rules", rules.len());
+                }
                 {
                     let mut security = self.inner.security_config.write().await;
                     *security = ProxySecurityConfig::from_proxy_config(&proxy_config);
@@ -163,10 +456,24 @@ impl AppState {
                     let mut zai = self.inner.zai_config.write().await;
                     *zai = proxy_config.zai.clone();
                 }
+                {
+                    let mut providers = self.inner.providers.write().await;
+                    *providers = proxy_config.providers.clone();
+                }
                 {
                     let mut experimental = self.inner.experimental_config.write().await;
                     *experimental = proxy_config.experimental.clone();
                 }
+                {
+                    let mut retry_policy = self.inner.retry_policy.write().await;
+                    *retry_policy = proxy_config.retry.clone();
+                }
+                self.inner
+                    .token_manager
+                    .update_hedging_config(&proxy_config.hedging);
+                if let Some(store) = self.inner.monitor.store() {
+                    store.set_retention(proxy_config.request_log.clone());
+                }
 
                 // Also update the full proxy_config reference
                 let mut inner_proxy_config = self.inner.proxy_config.write().await;
@@ -180,18 +487,16 @@ impl AppState {
         }
     }
 
-    // AIMD accessors (reserved for future handler integration)
+    // AIMD / health / circuit breaker accessors
     #[allow(dead_code)]
-    pub fn adaptive_limits(&self) -> &Arc<AdaptiveLimitTracker> {
+    pub fn adaptive_limits(&self) -> &Arc<AdaptiveLimitManager> {
         &self.inner.adaptive_limits
     }
 
-    #[allow(dead_code)]
     pub fn health_monitor(&self) -> &Arc<HealthMonitor> {
         &self.inner.health_monitor
     }
 
-    #[allow(dead_code)]
     pub fn circuit_breaker(&self) -> &Arc<CircuitBreakerManager> {
         &self.inner.circuit_breaker
     }
@@ -402,6 +402,35 @@ pub fn update_account_quota(account_id: &str, quota: QuotaData) -> Result<(), St
     save_account(&account)
 }
 
+/// Enable or disable an account for proxy rotation without touching its credentials.
+pub fn set_proxy_disabled(
+    account_id: &str,
+    disabled: bool,
+    reason: Option<String>,
+) -> Result<Account, String> {
+    let mut account = load_account(account_id)?;
+
+    if disabled {
+        account.proxy_disabled = true;
+        account.proxy_disabled_at = Some(chrono::Utc::now().timestamp());
+        account.proxy_disabled_reason =
+            Some(reason.unwrap_or_else(|| "Disabled manually".to_string()));
+    } else {
+        account.proxy_disabled = false;
+        account.proxy_disabled_at = None;
+        account.proxy_disabled_reason = None;
+    }
+
+    save_account(&account)?;
+    logger::log_info(&format!(
+        "Proxy status updated for {}: {}",
+        account.email,
+        if disabled { "disabled" } else { "enabled" }
+    ));
+
+    Ok(account)
+}
+
 /// Switch current account.
 /// This involves refreshing token, stopping/starting process, and injecting token to VSCode DB.
 pub async fn switch_account(account_id: &str) -> Result<(), String> {
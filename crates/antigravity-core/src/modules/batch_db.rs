@@ -0,0 +1,747 @@
+//! Message Batches 持久化 (SQLite `batches.db`)
+//!
+//! 批次与其中的每条请求分表存储；后台 worker 通过 [`BatchStore::claim`] 领取待处理请求，
+//! 完成后写回结果。暂时失败的请求带 `not_before` 放回队列，到期前不会被领取。
+//! 进程重启时将 `running` 状态的请求恢复为 `pending` 重新执行。
+
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+const POOL_SIZE: u32 = 4;
+/// 批次创建后 24 小时内未完成的请求标记为 expired
+pub const BATCH_EXPIRY_MS: i64 = 24 * 60 * 60 * 1000;
+/// 结束 29 天后删除批次与结果
+pub const RESULT_RETENTION_MS: i64 = 29 * 24 * 60 * 60 * 1000;
+
+static DEFAULT_STORE: OnceLock<Arc<BatchStore>> = OnceLock::new();
+
+pub fn get_batch_db_path() -> Result<PathBuf, String> {
+    let data_dir = crate::utils::paths::get_data_dir()?;
+    Ok(data_dir.join("batches.db"))
+}
+
+/// 数据目录下 `batches.db` 的共享存储
+pub fn default_store() -> Result<Arc<BatchStore>, String> {
+    if let Some(store) = DEFAULT_STORE.get() {
+        return Ok(store.clone());
+    }
+    let store = Arc::new(BatchStore::open(&get_batch_db_path()?)?);
+    Ok(DEFAULT_STORE.get_or_init(|| store).clone())
+}
+
+fn init_schema(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS batches (
+            seq INTEGER PRIMARY KEY AUTOINCREMENT,
+            id TEXT NOT NULL UNIQUE,
+            status TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            expires_at INTEGER NOT NULL,
+            ended_at INTEGER,
+            cancel_initiated_at INTEGER,
+            client_key TEXT
+        );
+        CREATE TABLE IF NOT EXISTS batch_requests (
+            batch_id TEXT NOT NULL,
+            position INTEGER NOT NULL,
+            custom_id TEXT NOT NULL,
+            params TEXT NOT NULL,
+            state TEXT NOT NULL,
+            result TEXT,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            not_before INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (batch_id, custom_id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_batch_requests_state ON batch_requests (state);",
+    )
+    .map_err(|e| e.to_string())?;
+    // 旧版本数据库没有 client_key 列
+    let _ = conn.execute("ALTER TABLE batches ADD COLUMN client_key TEXT", []);
+    let _ = conn.execute(
+        "ALTER TABLE batch_requests ADD COLUMN not_before INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+    Ok(())
+}
+
+/// 批次处理状态 (Anthropic `processing_status`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    InProgress,
+    Canceling,
+    Ended,
+}
+
+impl BatchStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::InProgress => "in_progress",
+            Self::Canceling => "canceling",
+            Self::Ended => "ended",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "canceling" => Self::Canceling,
+            "ended" => Self::Ended,
+            _ => Self::InProgress,
+        }
+    }
+}
+
+/// 单条请求的状态；终态与结果 JSONL 中的 `result.type` 一致
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestState {
+    Pending,
+    Running,
+    Succeeded,
+    Errored,
+    Canceled,
+    Expired,
+}
+
+impl RequestState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Running => "running",
+            Self::Succeeded => "succeeded",
+            Self::Errored => "errored",
+            Self::Canceled => "canceled",
+            Self::Expired => "expired",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "running" => Self::Running,
+            "succeeded" => Self::Succeeded,
+            "errored" => Self::Errored,
+            "canceled" => Self::Canceled,
+            "expired" => Self::Expired,
+            _ => Self::Pending,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RequestCounts {
+    /// pending + running
+    pub processing: u64,
+    pub succeeded: u64,
+    pub errored: u64,
+    pub canceled: u64,
+    pub expired: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchRecord {
+    pub id: String,
+    pub status: BatchStatus,
+    pub created_at: i64,
+    pub expires_at: i64,
+    pub ended_at: Option<i64>,
+    pub cancel_initiated_at: Option<i64>,
+    /// 创建批次的客户端 key id (主 key 创建时为 None)，只有同一 key 可以访问
+    pub client_key: Option<String>,
+    pub counts: RequestCounts,
+}
+
+/// worker 领取的待执行请求
+#[derive(Debug, Clone)]
+pub struct ClaimedRequest {
+    pub batch_id: String,
+    pub custom_id: String,
+    pub params: Value,
+    pub attempts: u32,
+    /// 所属批次的客户端 key id
+    pub client_key: Option<String>,
+}
+
+/// 结果 JSONL 中的一行 (result 为 None 时按 state 生成)
+#[derive(Debug, Clone)]
+pub struct RequestResult {
+    pub custom_id: String,
+    pub state: RequestState,
+    pub result: Option<Value>,
+}
+
+pub struct BatchStore {
+    pool: Pool<SqliteConnectionManager>,
+}
+
+impl BatchStore {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+
+        let manager = SqliteConnectionManager::file(path).with_init(|conn| {
+            conn.execute_batch(
+                "PRAGMA journal_mode = WAL;
+                 PRAGMA synchronous = NORMAL;
+                 PRAGMA busy_timeout = 5000;",
+            )
+        });
+        let pool = Pool::builder()
+            .max_size(POOL_SIZE)
+            .build(manager)
+            .map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+        let conn = pool.get().map_err(|e| e.to_string())?;
+        init_schema(&conn)?;
+        // 上次退出时执行中的请求重新排队
+        let recovered = conn
+            .execute(
+                "UPDATE batch_requests SET state = 'pending' WHERE state = 'running'",
+                [],
+            )
+            .map_err(|e| e.to_string())?;
+        if recovered > 0 {
+            tracing::info!("[Batches] Re-queued {} interrupted requests", recovered);
+        }
+        drop(conn);
+
+        Ok(Self { pool })
+    }
+
+    /// 创建批次，`requests` 为 (custom_id, params)，`client_key` 为创建者的客户端 key id
+    pub fn create(
+        &self,
+        requests: &[(String, Value)],
+        client_key: Option<&str>,
+    ) -> Result<BatchRecord, String> {
+        let id = format!("msgbatch_{}", uuid::Uuid::new_v4().simple());
+        let now = chrono::Utc::now().timestamp_millis();
+
+        let mut conn = self.pool.get().map_err(|e| e.to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute(
+            "INSERT INTO batches (id, status, created_at, expires_at, client_key)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                id,
+                BatchStatus::InProgress.as_str(),
+                now,
+                now + BATCH_EXPIRY_MS,
+                client_key
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        {
+            let mut stmt = tx
+                .prepare(
+                    "INSERT INTO batch_requests (batch_id, position, custom_id, params, state)
+                     VALUES (?1, ?2, ?3, ?4, 'pending')",
+                )
+                .map_err(|e| e.to_string())?;
+            for (position, (custom_id, params)) in requests.iter().enumerate() {
+                stmt.execute(params![id, position as i64, custom_id, params.to_string()])
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+
+        self.get(&id)?
+            .ok_or_else(|| "Batch disappeared after insert".to_string())
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<BatchRecord>, String> {
+        let conn = self.pool.get().map_err(|e| e.to_string())?;
+        load_batch(&conn, id)
+    }
+
+    /// 按创建时间倒序分页列出 `client_key` 创建的批次；`after_id` 向更早翻页，
+    /// `before_id` 向更新翻页。返回 (当前页, 是否还有更多)
+    pub fn list(
+        &self,
+        limit: usize,
+        before_id: Option<&str>,
+        after_id: Option<&str>,
+        client_key: Option<&str>,
+    ) -> Result<(Vec<BatchRecord>, bool), String> {
+        let conn = self.pool.get().map_err(|e| e.to_string())?;
+        let cursor = |id: &str| -> Result<i64, String> {
+            conn.query_row(
+                "SELECT seq FROM batches WHERE id = ?1 AND client_key IS ?2",
+                params![id, client_key],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Batch '{}' not found", id))
+        };
+
+        let fetch = limit as i64 + 1;
+        let ids: Vec<String> = if let Some(before) = before_id {
+            let seq = cursor(before)?;
+            let mut ids = query_ids(
+                &conn,
+                "SELECT id FROM batches WHERE seq > ?1 AND client_key IS ?3
+                 ORDER BY seq ASC LIMIT ?2",
+                seq,
+                fetch,
+                client_key,
+            )?;
+            let has_more = ids.len() > limit;
+            ids.truncate(limit);
+            ids.reverse();
+            return Ok((self.load_all(&conn, &ids)?, has_more));
+        } else if let Some(after) = after_id {
+            let seq = cursor(after)?;
+            query_ids(
+                &conn,
+                "SELECT id FROM batches WHERE seq < ?1 AND client_key IS ?3
+                 ORDER BY seq DESC LIMIT ?2",
+                seq,
+                fetch,
+                client_key,
+            )?
+        } else {
+            query_ids(
+                &conn,
+                "SELECT id FROM batches WHERE seq < ?1 AND client_key IS ?3
+                 ORDER BY seq DESC LIMIT ?2",
+                i64::MAX,
+                fetch,
+                client_key,
+            )?
+        };
+
+        let has_more = ids.len() > limit;
+        let ids = &ids[..ids.len().min(limit)];
+        Ok((self.load_all(&conn, ids)?, has_more))
+    }
+
+    fn load_all(&self, conn: &Connection, ids: &[String]) -> Result<Vec<BatchRecord>, String> {
+        let mut batches = Vec::with_capacity(ids.len());
+        for id in ids {
+            if let Some(batch) = load_batch(conn, id)? {
+                batches.push(batch);
+            }
+        }
+        Ok(batches)
+    }
+
+    /// 取消批次：未开始的请求立即标记为 canceled，执行中的请求完成后批次结束
+    pub fn cancel(&self, id: &str) -> Result<Option<BatchRecord>, String> {
+        let mut conn = self.pool.get().map_err(|e| e.to_string())?;
+        let now = chrono::Utc::now().timestamp_millis();
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let updated = tx
+            .execute(
+                "UPDATE batches SET status = 'canceling', cancel_initiated_at = ?2
+                 WHERE id = ?1 AND status = 'in_progress'",
+                params![id, now],
+            )
+            .map_err(|e| e.to_string())?;
+        if updated > 0 {
+            tx.execute(
+                "UPDATE batch_requests SET state = 'canceled'
+                 WHERE batch_id = ?1 AND state = 'pending'",
+                [id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+        drop(conn);
+
+        self.finalize()?;
+        self.get(id)
+    }
+
+    /// 删除已结束的批次；返回 Err 表示批次仍在处理
+    pub fn delete(&self, id: &str) -> Result<bool, String> {
+        let Some(batch) = self.get(id)? else {
+            return Ok(false);
+        };
+        if batch.status != BatchStatus::Ended {
+            return Err(format!(
+                "Batch '{}' is still being processed and cannot be deleted",
+                id
+            ));
+        }
+        let mut conn = self.pool.get().map_err(|e| e.to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute("DELETE FROM batch_requests WHERE batch_id = ?1", [id])
+            .map_err(|e| e.to_string())?;
+        tx.execute("DELETE FROM batches WHERE id = ?1", [id])
+            .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(true)
+    }
+
+    /// 按提交顺序返回全部结果
+    pub fn results(&self, id: &str) -> Result<Vec<RequestResult>, String> {
+        let conn = self.pool.get().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT custom_id, state, result FROM batch_requests
+                 WHERE batch_id = ?1 ORDER BY position",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([id], |row| {
+                Ok(RequestResult {
+                    custom_id: row.get(0)?,
+                    state: RequestState::parse(&row.get::<_, String>(1)?),
+                    result: row
+                        .get::<_, Option<String>>(2)?
+                        .and_then(|r| serde_json::from_str(&r).ok()),
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())
+    }
+
+    /// 领取最多 `limit` 条到期的待处理请求 (按批次创建顺序) 并标记为 running
+    pub fn claim(&self, limit: usize) -> Result<Vec<ClaimedRequest>, String> {
+        let now = chrono::Utc::now().timestamp_millis();
+        let mut conn = self.pool.get().map_err(|e| e.to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let claimed = {
+            let mut stmt = tx
+                .prepare(
+                    "SELECT r.batch_id, r.custom_id, r.params, r.attempts, b.client_key
+                     FROM batch_requests r JOIN batches b ON b.id = r.batch_id
+                     WHERE r.state = 'pending' AND r.not_before <= ?2
+                       AND b.status = 'in_progress'
+                     ORDER BY b.seq, r.position LIMIT ?1",
+                )
+                .map_err(|e| e.to_string())?;
+            let rows = stmt
+                .query_map([limit as i64, now], |row| {
+                    Ok(ClaimedRequest {
+                        batch_id: row.get(0)?,
+                        custom_id: row.get(1)?,
+                        params: serde_json::from_str(&row.get::<_, String>(2)?)
+                            .unwrap_or(Value::Null),
+                        attempts: row.get(3)?,
+                        client_key: row.get(4)?,
+                    })
+                })
+                .map_err(|e| e.to_string())?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.to_string())?
+        };
+        for request in &claimed {
+            tx.execute(
+                "UPDATE batch_requests SET state = 'running'
+                 WHERE batch_id = ?1 AND custom_id = ?2",
+                params![request.batch_id, request.custom_id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(claimed)
+    }
+
+    /// 写入终态结果
+    pub fn complete(
+        &self,
+        batch_id: &str,
+        custom_id: &str,
+        state: RequestState,
+        result: &Value,
+    ) -> Result<(), String> {
+        let conn = self.pool.get().map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE batch_requests SET state = ?3, result = ?4, attempts = attempts + 1
+             WHERE batch_id = ?1 AND custom_id = ?2",
+            params![batch_id, custom_id, state.as_str(), result.to_string()],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// 暂时失败 (限流等)：放回队列，`retry_after` 之后才会再次被领取；
+    /// 批次已在取消中则直接标记为 canceled
+    pub fn release(
+        &self,
+        batch_id: &str,
+        custom_id: &str,
+        retry_after: Duration,
+    ) -> Result<(), String> {
+        let conn = self.pool.get().map_err(|e| e.to_string())?;
+        let not_before = chrono::Utc::now().timestamp_millis() + retry_after.as_millis() as i64;
+        conn.execute(
+            "UPDATE batch_requests SET attempts = attempts + 1, not_before = ?3,
+                state = CASE WHEN (SELECT status FROM batches WHERE id = ?1) = 'in_progress'
+                             THEN 'pending' ELSE 'canceled' END
+             WHERE batch_id = ?1 AND custom_id = ?2",
+            params![batch_id, custom_id, not_before],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// 过期未完成的请求，结束已无待处理请求的批次，清理超出保留期的批次。
+    /// 返回本次结束的批次数
+    pub fn finalize(&self) -> Result<usize, String> {
+        let mut conn = self.pool.get().map_err(|e| e.to_string())?;
+        let now = chrono::Utc::now().timestamp_millis();
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute(
+            "UPDATE batch_requests SET state = 'expired'
+             WHERE state = 'pending'
+               AND batch_id IN (SELECT id FROM batches WHERE status != 'ended' AND expires_at <= ?1)",
+            [now],
+        )
+        .map_err(|e| e.to_string())?;
+        let ended = tx
+            .execute(
+                "UPDATE batches SET status = 'ended', ended_at = ?1
+                 WHERE status != 'ended'
+                   AND NOT EXISTS (
+                       SELECT 1 FROM batch_requests
+                       WHERE batch_id = batches.id AND state IN ('pending', 'running')
+                   )",
+                [now],
+            )
+            .map_err(|e| e.to_string())?;
+        tx.execute(
+            "DELETE FROM batch_requests WHERE batch_id IN
+                (SELECT id FROM batches WHERE status = 'ended' AND ended_at <= ?1)",
+            [now - RESULT_RETENTION_MS],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.execute(
+            "DELETE FROM batches WHERE status = 'ended' AND ended_at <= ?1",
+            [now - RESULT_RETENTION_MS],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(ended)
+    }
+}
+
+fn query_ids(
+    conn: &Connection,
+    sql: &str,
+    seq: i64,
+    limit: i64,
+    client_key: Option<&str>,
+) -> Result<Vec<String>, String> {
+    let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![seq, limit, client_key], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<String>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+fn load_batch(conn: &Connection, id: &str) -> Result<Option<BatchRecord>, String> {
+    let batch = conn
+        .query_row(
+            "SELECT id, status, created_at, expires_at, ended_at, cancel_initiated_at, client_key
+             FROM batches WHERE id = ?1",
+            [id],
+            |row| {
+                Ok(BatchRecord {
+                    id: row.get(0)?,
+                    status: BatchStatus::parse(&row.get::<_, String>(1)?),
+                    created_at: row.get(2)?,
+                    expires_at: row.get(3)?,
+                    ended_at: row.get(4)?,
+                    cancel_initiated_at: row.get(5)?,
+                    client_key: row.get(6)?,
+                    counts: RequestCounts::default(),
+                })
+            },
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let Some(mut batch) = batch else {
+        return Ok(None);
+    };
+
+    let mut stmt = conn
+        .prepare("SELECT state, COUNT(*) FROM batch_requests WHERE batch_id = ?1 GROUP BY state")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as u64))
+        })
+        .map_err(|e| e.to_string())?;
+    for row in rows {
+        let (state, count) = row.map_err(|e| e.to_string())?;
+        let counts = &mut batch.counts;
+        match RequestState::parse(&state) {
+            RequestState::Pending | RequestState::Running => counts.processing += count,
+            RequestState::Succeeded => counts.succeeded += count,
+            RequestState::Errored => counts.errored += count,
+            RequestState::Canceled => counts.canceled += count,
+            RequestState::Expired => counts.expired += count,
+        }
+    }
+    Ok(Some(batch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn temp_store() -> (BatchStore, PathBuf) {
+        let dir = crate::proxy::tests::support::temp_dir("ag-batch-db");
+        let store = BatchStore::open(&dir.join("batches.db")).unwrap();
+        (store, dir)
+    }
+
+    fn requests(n: usize) -> Vec<(String, Value)> {
+        (0..n)
+            .map(|i| (format!("req-{}", i), json!({"model": "claude-sonnet-4-5"})))
+            .collect()
+    }
+
+    #[test]
+    fn test_claim_complete_and_finalize() {
+        let (store, dir) = temp_store();
+        let batch = store.create(&requests(3), None).unwrap();
+        assert_eq!(batch.status, BatchStatus::InProgress);
+        assert_eq!(batch.counts.processing, 3);
+
+        let claimed = store.claim(2).unwrap();
+        assert_eq!(claimed.len(), 2);
+        assert_eq!(claimed[0].custom_id, "req-0");
+        store
+            .complete(&batch.id, "req-0", RequestState::Succeeded, &json!({}))
+            .unwrap();
+        store.release(&batch.id, "req-1", Duration::ZERO).unwrap();
+        assert_eq!(store.finalize().unwrap(), 0);
+
+        let claimed = store.claim(10).unwrap();
+        assert_eq!(claimed.len(), 2);
+        assert_eq!(claimed[0].attempts, 1);
+        for request in claimed {
+            store
+                .complete(
+                    &batch.id,
+                    &request.custom_id,
+                    RequestState::Errored,
+                    &json!({}),
+                )
+                .unwrap();
+        }
+        assert_eq!(store.finalize().unwrap(), 1);
+
+        let batch = store.get(&batch.id).unwrap().unwrap();
+        assert_eq!(batch.status, BatchStatus::Ended);
+        assert_eq!(batch.counts.succeeded, 1);
+        assert_eq!(batch.counts.errored, 2);
+        let results = store.results(&batch.id).unwrap();
+        assert_eq!(results[0].custom_id, "req-0");
+        assert_eq!(results[0].state, RequestState::Succeeded);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_release_defers_only_that_request() {
+        let (store, dir) = temp_store();
+        let batch = store.create(&requests(3), None).unwrap();
+
+        let claimed = store.claim(1).unwrap();
+        assert_eq!(claimed[0].custom_id, "req-0");
+        store
+            .release(&batch.id, "req-0", Duration::from_secs(60))
+            .unwrap();
+
+        // 延后的请求在到期前不会被领取，其余请求照常
+        let ids: Vec<_> = store
+            .claim(10)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.custom_id)
+            .collect();
+        assert_eq!(ids, vec!["req-1", "req-2"]);
+        assert!(store.claim(10).unwrap().is_empty());
+
+        store
+            .release(&batch.id, "req-1", Duration::from_millis(50))
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(60));
+        let claimed = store.claim(10).unwrap();
+        assert_eq!(claimed.len(), 1);
+        assert_eq!(claimed[0].custom_id, "req-1");
+        assert_eq!(claimed[0].attempts, 1);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_cancel_recovery_and_pagination() {
+        let (store, dir) = temp_store();
+        let first = store.create(&requests(2), None).unwrap();
+        let second = store.create(&requests(1), None).unwrap();
+
+        // 执行中的请求在重启后重新排队
+        assert_eq!(store.claim(1).unwrap()[0].batch_id, first.id);
+        drop(store);
+        let store = BatchStore::open(&dir.join("batches.db")).unwrap();
+        assert_eq!(store.claim(10).unwrap().len(), 3);
+
+        let canceled = store.cancel(&second.id).unwrap().unwrap();
+        assert_eq!(canceled.status, BatchStatus::Canceling);
+        store.release(&second.id, "req-0", Duration::ZERO).unwrap();
+        store.finalize().unwrap();
+        let canceled = store.get(&second.id).unwrap().unwrap();
+        assert_eq!(canceled.status, BatchStatus::Ended);
+        assert_eq!(canceled.counts.canceled, 1);
+        assert!(store.delete(&first.id).is_err());
+
+        let (page, has_more) = store.list(1, None, None, None).unwrap();
+        assert_eq!(page[0].id, second.id);
+        assert!(has_more);
+        let (page, has_more) = store.list(1, None, Some(&second.id), None).unwrap();
+        assert_eq!(page[0].id, first.id);
+        assert!(!has_more);
+        let (page, _) = store.list(5, Some(&first.id), None, None).unwrap();
+        assert_eq!(page[0].id, second.id);
+
+        assert!(store.delete(&second.id).unwrap());
+        assert!(store.get(&second.id).unwrap().is_none());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_list_scoped_to_client_key() {
+        let (store, dir) = temp_store();
+        let own = store.create(&requests(1), Some("k1")).unwrap();
+        let other = store.create(&requests(1), Some("k2")).unwrap();
+        let main = store.create(&requests(1), None).unwrap();
+        assert_eq!(own.client_key.as_deref(), Some("k1"));
+
+        let (page, has_more) = store.list(10, None, None, Some("k1")).unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].id, own.id);
+        assert!(!has_more);
+        let (page, _) = store.list(10, None, None, None).unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].id, main.id);
+        // 其他 key 的批次不能作为分页游标
+        assert!(store.list(10, None, Some(&other.id), Some("k1")).is_err());
+
+        let owners: Vec<_> = store
+            .claim(10)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.client_key)
+            .collect();
+        assert_eq!(
+            owners,
+            vec![Some("k1".to_string()), Some("k2".to_string()), None]
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,390 @@
+//! 本地 Files API 存储 (数据目录 `files/`)
+//!
+//! 元数据保存在 `files/files.db`，文件内容按 SHA256 存为 `files/blobs/<hash>`，
+//! 相同内容的多次上传共享同一个 blob。文件过期后不再可见，blob 在无引用时由 GC 删除。
+
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{params, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, OnceLock};
+
+const POOL_SIZE: u32 = 4;
+/// 未指定过期时间时文件保留 7 天
+pub const DEFAULT_FILE_TTL_SECS: i64 = 7 * 24 * 60 * 60;
+/// 过期清理的最小间隔
+const GC_INTERVAL_MS: i64 = 10 * 60 * 1000;
+
+static DEFAULT_STORE: OnceLock<Arc<FileStore>> = OnceLock::new();
+
+pub fn get_files_dir() -> Result<PathBuf, String> {
+    let data_dir = crate::utils::paths::get_data_dir()?;
+    Ok(data_dir.join("files"))
+}
+
+/// 数据目录下的共享文件存储
+pub fn default_store() -> Result<Arc<FileStore>, String> {
+    if let Some(store) = DEFAULT_STORE.get() {
+        return Ok(store.clone());
+    }
+    let store = Arc::new(FileStore::open(&get_files_dir()?)?);
+    Ok(DEFAULT_STORE.get_or_init(|| store).clone())
+}
+
+fn init_schema(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS files (
+            seq INTEGER PRIMARY KEY AUTOINCREMENT,
+            id TEXT NOT NULL UNIQUE,
+            filename TEXT NOT NULL,
+            purpose TEXT NOT NULL,
+            mime_type TEXT NOT NULL,
+            bytes INTEGER NOT NULL,
+            sha256 TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            expires_at INTEGER NOT NULL,
+            client_key TEXT
+        );
+        CREATE INDEX IF NOT EXISTS idx_files_sha256 ON files (sha256);
+        CREATE INDEX IF NOT EXISTS idx_files_expires_at ON files (expires_at);",
+    )
+    .map_err(|e| e.to_string())?;
+    // 旧版本数据库没有 client_key 列
+    let _ = conn.execute("ALTER TABLE files ADD COLUMN client_key TEXT", []);
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileRecord {
+    pub id: String,
+    pub filename: String,
+    pub purpose: String,
+    pub mime_type: String,
+    pub bytes: u64,
+    pub sha256: String,
+    /// 毫秒时间戳
+    pub created_at: i64,
+    pub expires_at: i64,
+    /// 上传文件的客户端 key id (主 key 上传时为 None)，只有同一 key 可以访问
+    pub client_key: Option<String>,
+}
+
+const FILE_COLUMNS: &str =
+    "id, filename, purpose, mime_type, bytes, sha256, created_at, expires_at, client_key";
+
+fn row_to_record(row: &rusqlite::Row) -> rusqlite::Result<FileRecord> {
+    Ok(FileRecord {
+        id: row.get(0)?,
+        filename: row.get(1)?,
+        purpose: row.get(2)?,
+        mime_type: row.get(3)?,
+        bytes: row.get::<_, i64>(4)? as u64,
+        sha256: row.get(5)?,
+        created_at: row.get(6)?,
+        expires_at: row.get(7)?,
+        client_key: row.get(8)?,
+    })
+}
+
+pub struct FileStore {
+    pool: Pool<SqliteConnectionManager>,
+    blob_dir: PathBuf,
+    last_gc: AtomicI64,
+}
+
+impl FileStore {
+    pub fn open(dir: &Path) -> Result<Self, String> {
+        let blob_dir = dir.join("blobs");
+        std::fs::create_dir_all(&blob_dir).map_err(|e| e.to_string())?;
+
+        let manager = SqliteConnectionManager::file(dir.join("files.db")).with_init(|conn| {
+            conn.execute_batch(
+                "PRAGMA journal_mode = WAL;
+                 PRAGMA synchronous = NORMAL;
+                 PRAGMA busy_timeout = 5000;",
+            )
+        });
+        let pool = Pool::builder()
+            .max_size(POOL_SIZE)
+            .build(manager)
+            .map_err(|e| format!("Failed to open {:?}: {}", dir, e))?;
+        init_schema(&*pool.get().map_err(|e| e.to_string())?)?;
+
+        Ok(Self {
+            pool,
+            blob_dir,
+            last_gc: AtomicI64::new(0),
+        })
+    }
+
+    fn blob_path(&self, sha256: &str) -> PathBuf {
+        self.blob_dir.join(sha256)
+    }
+
+    /// 保存文件；内容已存在时复用 blob。`client_key` 为上传者的客户端 key id
+    pub fn put(
+        &self,
+        filename: &str,
+        purpose: &str,
+        mime_type: &str,
+        data: &[u8],
+        ttl_secs: i64,
+        client_key: Option<&str>,
+    ) -> Result<FileRecord, String> {
+        self.maybe_gc();
+
+        let sha256 = format!("{:x}", Sha256::digest(data));
+        let path = self.blob_path(&sha256);
+        if !path.exists() {
+            // 先写临时文件再重命名，避免并发读取到半个文件
+            let tmp =
+                self.blob_dir
+                    .join(format!("{}.{}.tmp", sha256, uuid::Uuid::new_v4().simple()));
+            std::fs::write(&tmp, data).map_err(|e| format!("Failed to write blob: {}", e))?;
+            std::fs::rename(&tmp, &path).map_err(|e| format!("Failed to write blob: {}", e))?;
+        }
+
+        let now = chrono::Utc::now().timestamp_millis();
+        let record = FileRecord {
+            id: format!("file-{}", uuid::Uuid::new_v4().simple()),
+            filename: filename.to_string(),
+            purpose: purpose.to_string(),
+            mime_type: mime_type.to_string(),
+            bytes: data.len() as u64,
+            sha256,
+            created_at: now,
+            expires_at: now + ttl_secs * 1000,
+            client_key: client_key.map(String::from),
+        };
+        let conn = self.pool.get().map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT INTO files
+             (id, filename, purpose, mime_type, bytes, sha256, created_at, expires_at, client_key)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                record.id,
+                record.filename,
+                record.purpose,
+                record.mime_type,
+                record.bytes as i64,
+                record.sha256,
+                record.created_at,
+                record.expires_at,
+                record.client_key
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(record)
+    }
+
+    /// 未过期的文件元数据
+    pub fn get(&self, id: &str) -> Result<Option<FileRecord>, String> {
+        let conn = self.pool.get().map_err(|e| e.to_string())?;
+        conn.query_row(
+            &format!(
+                "SELECT {} FROM files WHERE id = ?1 AND expires_at > ?2",
+                FILE_COLUMNS
+            ),
+            params![id, chrono::Utc::now().timestamp_millis()],
+            row_to_record,
+        )
+        .optional()
+        .map_err(|e| e.to_string())
+    }
+
+    /// 读取文件元数据与内容
+    pub fn read(&self, id: &str) -> Result<Option<(FileRecord, Vec<u8>)>, String> {
+        let Some(record) = self.get(id)? else {
+            return Ok(None);
+        };
+        let data = std::fs::read(self.blob_path(&record.sha256))
+            .map_err(|e| format!("Failed to read file '{}': {}", id, e))?;
+        Ok(Some((record, data)))
+    }
+
+    /// 按上传时间倒序分页列出 `client_key` 上传的文件，`after_id` 为上一页最后一个文件。
+    /// 返回 (当前页, 是否还有更多)
+    pub fn list(
+        &self,
+        purpose: Option<&str>,
+        limit: usize,
+        after_id: Option<&str>,
+        client_key: Option<&str>,
+    ) -> Result<(Vec<FileRecord>, bool), String> {
+        self.maybe_gc();
+
+        let conn = self.pool.get().map_err(|e| e.to_string())?;
+        let cursor = match after_id {
+            Some(id) => conn
+                .query_row(
+                    "SELECT seq FROM files WHERE id = ?1 AND client_key IS ?2",
+                    params![id, client_key],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("File '{}' not found", id))?,
+            None => i64::MAX,
+        };
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM files
+                 WHERE seq < ?1 AND expires_at > ?2 AND (?3 IS NULL OR purpose = ?3)
+                   AND client_key IS ?5
+                 ORDER BY seq DESC LIMIT ?4",
+                FILE_COLUMNS
+            ))
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(
+                params![
+                    cursor,
+                    chrono::Utc::now().timestamp_millis(),
+                    purpose,
+                    limit as i64 + 1,
+                    client_key
+                ],
+                row_to_record,
+            )
+            .map_err(|e| e.to_string())?;
+        let mut files = rows
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        let has_more = files.len() > limit;
+        files.truncate(limit);
+        Ok((files, has_more))
+    }
+
+    pub fn delete(&self, id: &str) -> Result<bool, String> {
+        let conn = self.pool.get().map_err(|e| e.to_string())?;
+        let sha256: Option<String> = conn
+            .query_row("SELECT sha256 FROM files WHERE id = ?1", [id], |row| {
+                row.get(0)
+            })
+            .optional()
+            .map_err(|e| e.to_string())?;
+        let Some(sha256) = sha256 else {
+            return Ok(false);
+        };
+        conn.execute("DELETE FROM files WHERE id = ?1", [id])
+            .map_err(|e| e.to_string())?;
+        self.remove_blob_if_unreferenced(&conn, &sha256)?;
+        Ok(true)
+    }
+
+    fn remove_blob_if_unreferenced(&self, conn: &Connection, sha256: &str) -> Result<(), String> {
+        let referenced: bool = conn
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM files WHERE sha256 = ?1)",
+                [sha256],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        if !referenced {
+            let _ = std::fs::remove_file(self.blob_path(sha256));
+        }
+        Ok(())
+    }
+
+    /// 删除过期文件及无引用的 blob，返回删除的文件数
+    pub fn gc(&self) -> Result<usize, String> {
+        let conn = self.pool.get().map_err(|e| e.to_string())?;
+        let now = chrono::Utc::now().timestamp_millis();
+        self.last_gc.store(now, Ordering::Relaxed);
+
+        let expired: Vec<String> = {
+            let mut stmt = conn
+                .prepare("SELECT DISTINCT sha256 FROM files WHERE expires_at <= ?1")
+                .map_err(|e| e.to_string())?;
+            let rows = stmt
+                .query_map([now], |row| row.get(0))
+                .map_err(|e| e.to_string())?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.to_string())?
+        };
+        let deleted = conn
+            .execute("DELETE FROM files WHERE expires_at <= ?1", [now])
+            .map_err(|e| e.to_string())?;
+        for sha256 in &expired {
+            self.remove_blob_if_unreferenced(&conn, sha256)?;
+        }
+        if deleted > 0 {
+            tracing::info!("[Files] Removed {} expired files", deleted);
+        }
+        Ok(deleted)
+    }
+
+    fn maybe_gc(&self) {
+        let now = chrono::Utc::now().timestamp_millis();
+        if now - self.last_gc.load(Ordering::Relaxed) >= GC_INTERVAL_MS {
+            if let Err(e) = self.gc() {
+                tracing::warn!("[Files] GC failed: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store() -> (FileStore, PathBuf) {
+        let dir = crate::proxy::tests::support::temp_dir("ag-files");
+        (FileStore::open(&dir).unwrap(), dir)
+    }
+
+    #[test]
+    fn test_dedup_and_delete() {
+        let (store, dir) = temp_store();
+        let a = store
+            .put("a.pdf", "user_data", "application/pdf", b"%PDF-1", 60, None)
+            .unwrap();
+        let b = store
+            .put(
+                "b.pdf",
+                "assistants",
+                "application/pdf",
+                b"%PDF-1",
+                60,
+                None,
+            )
+            .unwrap();
+        assert_ne!(a.id, b.id);
+        assert_eq!(a.sha256, b.sha256);
+        assert_eq!(std::fs::read_dir(dir.join("blobs")).unwrap().count(), 1);
+
+        let (files, has_more) = store.list(None, 1, None, None).unwrap();
+        assert_eq!(files[0].id, b.id);
+        assert!(has_more);
+        let (files, _) = store.list(Some("user_data"), 10, None, None).unwrap();
+        assert_eq!(files.len(), 1);
+
+        // 仍有引用时保留 blob
+        assert!(store.delete(&a.id).unwrap());
+        assert_eq!(store.read(&b.id).unwrap().unwrap().1, b"%PDF-1");
+        assert!(store.delete(&b.id).unwrap());
+        assert_eq!(std::fs::read_dir(dir.join("blobs")).unwrap().count(), 0);
+        assert!(!store.delete(&b.id).unwrap());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_expired_files_are_collected() {
+        let (store, dir) = temp_store();
+        let expired = store
+            .put("old.png", "vision", "image/png", b"old", -1, None)
+            .unwrap();
+        let live = store
+            .put("new.png", "vision", "image/png", b"new", 60, None)
+            .unwrap();
+        assert!(store.get(&expired.id).unwrap().is_none());
+        assert_eq!(store.gc().unwrap(), 1);
+        assert!(!dir.join("blobs").join(&expired.sha256).exists());
+        assert!(store.get(&live.id).unwrap().is_some());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
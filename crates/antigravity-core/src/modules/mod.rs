@@ -1,8 +1,11 @@
 pub mod account;
+pub mod batch_db;
 pub mod config;
+pub mod file_store;
 pub mod logger;
 pub mod migration;
 pub mod oauth;
+pub mod pricing;
 pub mod process;
 pub mod proxy_db;
 pub mod quota;
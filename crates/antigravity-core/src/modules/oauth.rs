@@ -49,6 +49,11 @@ impl UserInfo {
 
 /// 生成 OAuth 授权 URL
 pub fn get_auth_url(redirect_uri: &str) -> String {
+    get_auth_url_with_state(redirect_uri, None)
+}
+
+/// 生成带 `state` 参数的 OAuth 授权 URL (用于区分并发的授权流程)
+pub fn get_auth_url_with_state(redirect_uri: &str, state: Option<&str>) -> String {
     let scopes = [
         "https://www.googleapis.com/auth/cloud-platform",
         "https://www.googleapis.com/auth/userinfo.email",
@@ -58,7 +63,7 @@ pub fn get_auth_url(redirect_uri: &str) -> String {
     ]
     .join(" ");
 
-    let params = vec![
+    let mut params = vec![
         ("client_id", CLIENT_ID),
         ("redirect_uri", redirect_uri),
         ("response_type", "code"),
@@ -67,6 +72,9 @@ pub fn get_auth_url(redirect_uri: &str) -> String {
         ("prompt", "consent"),
         ("include_granted_scopes", "true"),
     ];
+    if let Some(state) = state {
+        params.push(("state", state));
+    }
 
     let url = url::Url::parse_with_params(AUTH_URL, &params).expect("无效的 Auth URL");
     url.to_string()
@@ -74,6 +82,14 @@ pub fn get_auth_url(redirect_uri: &str) -> String {
 
 /// 使用 Authorization Code 交换 Token
 pub async fn exchange_code(code: &str, redirect_uri: &str) -> Result<TokenResponse, String> {
+    exchange_code_with_endpoint(TOKEN_URL, code, redirect_uri).await
+}
+
+async fn exchange_code_with_endpoint(
+    token_url: &str,
+    code: &str,
+    redirect_uri: &str,
+) -> Result<TokenResponse, String> {
     let client = crate::utils::http::create_client(15);
 
     let params = [
@@ -85,7 +101,7 @@ pub async fn exchange_code(code: &str, redirect_uri: &str) -> Result<TokenRespon
     ];
 
     let response = client
-        .post(TOKEN_URL)
+        .post(token_url)
         .form(&params)
         .send()
         .await
@@ -210,3 +226,78 @@ pub async fn ensure_fresh_token(
         None,                             // session_id 会在 token_manager 中生成
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::post, Form, Json, Router};
+    use std::collections::HashMap;
+
+    async fn spawn_mock_token_endpoint() -> String {
+        async fn token(Form(form): Form<HashMap<String, String>>) -> axum::response::Response {
+            use axum::response::IntoResponse;
+            if form.get("grant_type").map(String::as_str) != Some("authorization_code") {
+                return (
+                    axum::http::StatusCode::BAD_REQUEST,
+                    "unsupported_grant_type",
+                )
+                    .into_response();
+            }
+            match form.get("code").map(String::as_str) {
+                Some("good-code") if form.contains_key("redirect_uri") => Json(serde_json::json!({
+                    "access_token": "ya29.mock",
+                    "expires_in": 3599,
+                    "token_type": "Bearer",
+                    "refresh_token": "1//mock-refresh"
+                }))
+                .into_response(),
+                _ => (
+                    axum::http::StatusCode::BAD_REQUEST,
+                    r#"{"error":"invalid_grant"}"#,
+                )
+                    .into_response(),
+            }
+        }
+
+        let app = Router::new().route("/token", post(token));
+        format!(
+            "{}/token",
+            crate::proxy::tests::support::spawn_server(app).await
+        )
+    }
+
+    #[test]
+    fn test_auth_url_with_state() {
+        let url = get_auth_url_with_state("http://localhost:8045/api/oauth/callback", Some("abc"));
+        let parsed = url::Url::parse(&url).unwrap();
+        let params: HashMap<_, _> = parsed.query_pairs().into_owned().collect();
+
+        assert_eq!(params.get("state").map(String::as_str), Some("abc"));
+        assert_eq!(
+            params.get("redirect_uri").map(String::as_str),
+            Some("http://localhost:8045/api/oauth/callback")
+        );
+        assert_eq!(
+            params.get("access_type").map(String::as_str),
+            Some("offline")
+        );
+
+        assert!(!get_auth_url("http://localhost:1/cb").contains("state="));
+    }
+
+    #[tokio::test]
+    async fn test_exchange_code_against_mock_endpoint() {
+        let token_url = spawn_mock_token_endpoint().await;
+
+        let ok = exchange_code_with_endpoint(&token_url, "good-code", "http://localhost/cb")
+            .await
+            .unwrap();
+        assert_eq!(ok.access_token, "ya29.mock");
+        assert_eq!(ok.refresh_token.as_deref(), Some("1//mock-refresh"));
+
+        let err = exchange_code_with_endpoint(&token_url, "bad-code", "http://localhost/cb")
+            .await
+            .unwrap_err();
+        assert!(err.contains("invalid_grant"));
+    }
+}
@@ -0,0 +1,72 @@
+//! 模型价格表 (用量分析中的费用估算)
+//!
+//! 价格取自各厂商公开 API 的标准档位 (美元 / 百万 token)，仅用于估算，
+//! 通过 Antigravity 账号调用时并不会产生这些费用。
+
+use crate::proxy::common::model_mapping::wildcard_match;
+
+/// (模型通配符, 输入价格, 输出价格)，按顺序匹配，更具体的模式在前
+const PRICES: &[(&str, f64, f64)] = &[
+    ("gemini-3-pro*", 2.0, 12.0),
+    ("gemini-3-flash*", 0.5, 3.0),
+    ("gemini-2.5-pro*", 1.25, 10.0),
+    ("gemini-2.5-flash-lite*", 0.1, 0.4),
+    ("gemini-2.5-flash-image*", 0.3, 30.0),
+    ("gemini-2.5-flash*", 0.3, 2.5),
+    ("gemini-2.0-flash*", 0.1, 0.4),
+    ("claude-opus-4-5*", 5.0, 25.0),
+    ("claude-opus-*", 15.0, 75.0),
+    ("claude-sonnet-*", 3.0, 15.0),
+    ("claude-3-5-sonnet*", 3.0, 15.0),
+    ("claude-haiku-*", 1.0, 5.0),
+    ("claude-3-5-haiku*", 0.8, 4.0),
+];
+
+/// 缓存命中的输入 token 按输入价格的该比例计费
+const CACHED_INPUT_RATIO: f64 = 0.1;
+
+/// Returns `(input, output)` USD prices per million tokens for `model`.
+pub fn model_price(model: &str) -> Option<(f64, f64)> {
+    let model = model.trim().to_lowercase();
+    let model = model.strip_prefix("models/").unwrap_or(&model);
+    PRICES
+        .iter()
+        .find(|(pattern, _, _)| wildcard_match(pattern, model))
+        .map(|(_, input, output)| (*input, *output))
+}
+
+/// Estimates the USD cost of a request; unknown models cost 0.
+///
+/// Cached tokens are billed at a discount and are not charged again as input.
+pub fn estimate_cost_usd(model: &str, input: u64, output: u64, cached: u64) -> f64 {
+    let Some((input_price, output_price)) = model_price(model) else {
+        return 0.0;
+    };
+    let uncached = input.saturating_sub(cached) as f64;
+    (uncached * input_price
+        + cached as f64 * input_price * CACHED_INPUT_RATIO
+        + output as f64 * output_price)
+        / 1_000_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_model_price_lookup() {
+        assert_eq!(model_price("gemini-2.5-flash-lite"), Some((0.1, 0.4)));
+        assert_eq!(model_price("gemini-2.5-flash"), Some((0.3, 2.5)));
+        assert_eq!(model_price("models/Gemini-2.5-Pro"), Some((1.25, 10.0)));
+        assert_eq!(model_price("claude-opus-4-5-thinking"), Some((5.0, 25.0)));
+        assert_eq!(model_price("unknown-model"), None);
+    }
+
+    #[test]
+    fn test_estimate_cost_discounts_cached_tokens() {
+        let cost = estimate_cost_usd("gemini-2.5-pro", 1_000_000, 100_000, 500_000);
+        // 0.5M * 1.25 + 0.5M * 0.125 + 0.1M * 10
+        assert!((cost - (0.625 + 0.0625 + 1.0)).abs() < 1e-9);
+        assert_eq!(estimate_cost_usd("unknown", 10, 10, 0), 0.0);
+    }
+}
@@ -1,16 +1,54 @@
-use antigravity_shared::models::{ProxyRequestLog, ProxyStats};
-use rusqlite::{params, Connection};
-use std::path::PathBuf;
+//! 请求日志持久化 (SQLite `proxy_logs.db`)
+//!
+//! 写入经由后台线程批量提交 (单事务)，读取使用 r2d2 连接池；
+//! 保留策略按时间与数据库占用大小定期清理最旧的日志。
+
+use crate::modules::pricing;
+use antigravity_shared::models::{
+    ProxyRequestLog, ProxyStats, UsageBucket, UsageGroupBy, UsageRollup,
+};
+use antigravity_shared::proxy::config::RequestLogConfig;
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, params_from_iter, Connection};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+const POOL_SIZE: u32 = 4;
+const WRITE_BATCH_SIZE: usize = 200;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+const RETENTION_INTERVAL: Duration = Duration::from_secs(10 * 60);
+const DEFAULT_PAGE_SIZE: usize = 100;
+const MAX_PAGE_SIZE: usize = 1000;
+
+const LOG_COLUMNS: &str = "id, timestamp, method, url, status, duration, model, error, request_body, response_body, input_tokens, output_tokens, account_email, mapped_model, client_key, cached_tokens";
+
+static DEFAULT_STORE: OnceLock<Arc<ProxyLogStore>> = OnceLock::new();
 
 pub fn get_proxy_db_path() -> Result<PathBuf, String> {
     let data_dir = crate::utils::paths::get_data_dir()?;
     Ok(data_dir.join("proxy_logs.db"))
 }
 
-pub fn init_db() -> Result<(), String> {
-    let db_path = get_proxy_db_path()?;
-    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+/// 数据目录下 `proxy_logs.db` 的共享存储 (同一文件只允许一个批量写入线程)
+pub fn default_store() -> Result<Arc<ProxyLogStore>, String> {
+    if let Some(store) = DEFAULT_STORE.get() {
+        return Ok(store.clone());
+    }
+    let store = Arc::new(ProxyLogStore::open(
+        &get_proxy_db_path()?,
+        RequestLogConfig::default(),
+    )?);
+    Ok(DEFAULT_STORE.get_or_init(|| store).clone())
+}
 
+fn init_schema(conn: &Connection) -> Result<(), String> {
     conn.execute(
         "CREATE TABLE IF NOT EXISTS request_logs (
             id TEXT PRIMARY KEY,
@@ -39,136 +77,831 @@ pub fn init_db() -> Result<(), String> {
     );
     let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN account_email TEXT", []);
     let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN mapped_model TEXT", []);
-
-    conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_timestamp ON request_logs (timestamp DESC)",
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN client_key TEXT", []);
+    let _ = conn.execute(
+        "ALTER TABLE request_logs ADD COLUMN cached_tokens INTEGER",
         [],
+    );
+
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_timestamp ON request_logs (timestamp DESC);
+         CREATE INDEX IF NOT EXISTS idx_model ON request_logs (model);
+         CREATE INDEX IF NOT EXISTS idx_account_email ON request_logs (account_email);
+         CREATE INDEX IF NOT EXISTS idx_client_key ON request_logs (client_key);",
     )
     .map_err(|e| e.to_string())?;
 
     Ok(())
 }
 
-pub fn save_log(log: &ProxyRequestLog) -> Result<(), String> {
-    let db_path = get_proxy_db_path()?;
-    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
-
-    conn.execute(
-        "INSERT INTO request_logs (id, timestamp, method, url, status, duration, model, error, request_body, response_body, input_tokens, output_tokens, account_email, mapped_model)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
-        params![
-            log.id,
-            log.timestamp,
-            log.method,
-            log.url,
-            log.status,
-            log.duration,
-            log.model,
-            log.error,
-            log.request_body,
-            log.response_body,
-            log.input_tokens,
-            log.output_tokens,
-            log.account_email,
-            log.mapped_model,
-        ],
-    ).map_err(|e| e.to_string())?;
+pub fn init_db() -> Result<(), String> {
+    default_store().map(|_| ())
+}
 
+/// 写入日志 (批量异步提交)
+pub fn save_log(log: &ProxyRequestLog) -> Result<(), String> {
+    default_store()?.enqueue(log.clone());
     Ok(())
 }
 
 pub fn get_logs(limit: usize) -> Result<Vec<ProxyRequestLog>, String> {
-    let db_path = get_proxy_db_path()?;
-    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
-
-    let mut stmt = conn.prepare(
-        "SELECT id, timestamp, method, url, status, duration, model, error, request_body, response_body, input_tokens, output_tokens, account_email, mapped_model
-         FROM request_logs
-         ORDER BY timestamp DESC
-         LIMIT ?1"
-    ).map_err(|e| e.to_string())?;
-
-    let logs_iter = stmt
-        .query_map([limit], |row| {
-            Ok(ProxyRequestLog {
-                id: row.get(0)?,
-                timestamp: row.get(1)?,
-                method: row.get(2)?,
-                url: row.get(3)?,
-                status: row.get(4)?,
-                duration: row.get(5)?,
-                model: row.get(6)?,
-                mapped_model: row.get(13).unwrap_or(None),
-                account_email: row.get(12).unwrap_or(None),
-                error: row.get(7)?,
-                request_body: row.get(8).unwrap_or(None),
-                response_body: row.get(9).unwrap_or(None),
-                input_tokens: row.get(10).unwrap_or(None),
-                output_tokens: row.get(11).unwrap_or(None),
-            })
-        })
-        .map_err(|e| e.to_string())?;
+    let query = LogQuery {
+        limit: Some(limit),
+        ..Default::default()
+    };
+    default_store()?.query(&query).map(|(logs, _)| logs)
+}
+
+pub fn get_stats() -> Result<ProxyStats, String> {
+    default_store()?.stats()
+}
 
-    let mut logs = Vec::new();
-    for log in logs_iter {
-        logs.push(log.map_err(|e| e.to_string())?);
+pub fn clear_proxy_logs() -> Result<(), String> {
+    default_store()?.clear()
+}
+
+// ===== 查询 =====
+
+/// 状态码过滤: `429` 精确匹配, `4xx` 按区间, `success` / `error`
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StatusFilter {
+    Between(u16, u16),
+    Outside(u16, u16),
+}
+
+impl StatusFilter {
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_ascii_lowercase();
+        match value.as_str() {
+            "success" | "ok" => Some(Self::Between(200, 399)),
+            "error" | "err" => Some(Self::Outside(200, 399)),
+            _ => {
+                if let Some(class) = value.strip_suffix("xx") {
+                    let digit: u16 = class.parse().ok().filter(|d| (1..=5).contains(d))?;
+                    Some(Self::Between(digit * 100, digit * 100 + 99))
+                } else {
+                    value.parse().ok().map(|code| Self::Between(code, code))
+                }
+            }
+        }
+    }
+
+    fn matches(self, status: u16) -> bool {
+        match self {
+            Self::Between(min, max) => (min..=max).contains(&status),
+            Self::Outside(min, max) => !(min..=max).contains(&status),
+        }
     }
-    Ok(logs)
 }
 
-pub fn get_stats() -> Result<ProxyStats, String> {
-    let db_path = get_proxy_db_path()?;
-    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+/// 请求日志查询条件 (`/api/monitor/requests` 的查询参数)
+///
+/// 文本条件均为大小写不敏感的子串匹配；`since` / `until` 为毫秒时间戳。
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LogQuery {
+    /// 匹配原始模型或映射后的模型
+    pub model: Option<String>,
+    pub account: Option<String>,
+    /// 客户端 key 名称 (精确匹配)
+    pub client_key: Option<String>,
+    pub status: Option<String>,
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    /// 在 URL、模型、账号与错误信息中搜索
+    pub q: Option<String>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
 
-    let total_requests: u64 = conn
-        .query_row("SELECT COUNT(*) FROM request_logs", [], |row| row.get(0))
-        .map_err(|e| e.to_string())?;
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
 
-    let success_count: u64 = conn
-        .query_row(
-            "SELECT COUNT(*) FROM request_logs WHERE status >= 200 AND status < 400",
-            [],
-            |row| row.get(0),
-        )
-        .map_err(|e| e.to_string())?;
+fn contains_ci(haystack: Option<&str>, needle: &str) -> bool {
+    haystack.is_some_and(|h| h.to_lowercase().contains(&needle.to_lowercase()))
+}
 
-    let error_count: u64 = conn
-        .query_row(
-            "SELECT COUNT(*) FROM request_logs WHERE status < 200 OR status >= 400",
-            [],
-            |row| row.get(0),
-        )
-        .map_err(|e| e.to_string())?;
+fn like_pattern(value: &str) -> SqlValue {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    SqlValue::Text(format!("%{}%", escaped))
+}
 
-    let total_input_tokens: u64 = conn
-        .query_row(
-            "SELECT COALESCE(SUM(input_tokens), 0) FROM request_logs",
-            [],
-            |row| row.get(0),
-        )
-        .map_err(|e| e.to_string())?;
+impl LogQuery {
+    pub fn page_size(&self) -> usize {
+        self.limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE)
+    }
+
+    pub fn page_offset(&self) -> usize {
+        self.offset.unwrap_or(0)
+    }
+
+    /// 内存模式下的过滤，与 SQL 条件保持一致
+    pub fn matches(&self, log: &ProxyRequestLog) -> bool {
+        if let Some(model) = non_empty(&self.model) {
+            if !contains_ci(log.model.as_deref(), model)
+                && !contains_ci(log.mapped_model.as_deref(), model)
+            {
+                return false;
+            }
+        }
+        if let Some(account) = non_empty(&self.account) {
+            if !contains_ci(log.account_email.as_deref(), account) {
+                return false;
+            }
+        }
+        if let Some(client_key) = non_empty(&self.client_key) {
+            if log.client_key.as_deref() != Some(client_key) {
+                return false;
+            }
+        }
+        if let Some(filter) = non_empty(&self.status).and_then(StatusFilter::parse) {
+            if !filter.matches(log.status) {
+                return false;
+            }
+        }
+        if self.since.is_some_and(|since| log.timestamp < since)
+            || self.until.is_some_and(|until| log.timestamp > until)
+        {
+            return false;
+        }
+        if let Some(q) = non_empty(&self.q) {
+            let fields = [
+                Some(log.url.as_str()),
+                log.model.as_deref(),
+                log.mapped_model.as_deref(),
+                log.account_email.as_deref(),
+                log.error.as_deref(),
+            ];
+            if !fields.into_iter().any(|f| contains_ci(f, q)) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// 生成 WHERE 子句与参数
+    fn where_clause(&self) -> (String, Vec<SqlValue>) {
+        let mut clauses: Vec<String> = Vec::new();
+        let mut values: Vec<SqlValue> = Vec::new();
+
+        if let Some(model) = non_empty(&self.model) {
+            clauses
+                .push("(model LIKE ? ESCAPE '\\' OR mapped_model LIKE ? ESCAPE '\\')".to_string());
+            values.push(like_pattern(model));
+            values.push(like_pattern(model));
+        }
+        if let Some(account) = non_empty(&self.account) {
+            clauses.push("account_email LIKE ? ESCAPE '\\'".to_string());
+            values.push(like_pattern(account));
+        }
+        if let Some(client_key) = non_empty(&self.client_key) {
+            clauses.push("client_key = ?".to_string());
+            values.push(SqlValue::Text(client_key.to_string()));
+        }
+        match non_empty(&self.status).and_then(StatusFilter::parse) {
+            Some(StatusFilter::Between(min, max)) => {
+                clauses.push("status BETWEEN ? AND ?".to_string());
+                values.push(SqlValue::Integer(min as i64));
+                values.push(SqlValue::Integer(max as i64));
+            }
+            Some(StatusFilter::Outside(min, max)) => {
+                clauses.push("(status < ? OR status > ?)".to_string());
+                values.push(SqlValue::Integer(min as i64));
+                values.push(SqlValue::Integer(max as i64));
+            }
+            None => {}
+        }
+        if let Some(since) = self.since {
+            clauses.push("timestamp >= ?".to_string());
+            values.push(SqlValue::Integer(since));
+        }
+        if let Some(until) = self.until {
+            clauses.push("timestamp <= ?".to_string());
+            values.push(SqlValue::Integer(until));
+        }
+        if let Some(q) = non_empty(&self.q) {
+            let columns = ["url", "model", "mapped_model", "account_email", "error"];
+            let any = columns
+                .iter()
+                .map(|c| format!("{} LIKE ? ESCAPE '\\'", c))
+                .collect::<Vec<_>>()
+                .join(" OR ");
+            clauses.push(format!("({})", any));
+            for _ in columns {
+                values.push(like_pattern(q));
+            }
+        }
+
+        if clauses.is_empty() {
+            (String::new(), values)
+        } else {
+            (format!(" WHERE {}", clauses.join(" AND ")), values)
+        }
+    }
+}
+
+/// 用量汇总查询 (`/api/stats/usage` 的查询参数)
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UsageQuery {
+    /// 起始时间 (毫秒)，默认最近 7 天
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    #[serde(default)]
+    pub bucket: UsageBucket,
+    #[serde(default)]
+    pub group_by: UsageGroupBy,
+}
+
+const DEFAULT_USAGE_RANGE_MS: i64 = 7 * 24 * 60 * 60 * 1000;
+
+/// 去掉映射模型上的路由标签，例如 "gemini-2.5-pro (rule#1 pro)"
+const BASE_MAPPED_MODEL_SQL: &str = "CASE WHEN instr(mapped_model, ' (') > 0 \
+     THEN substr(mapped_model, 1, instr(mapped_model, ' (') - 1) ELSE mapped_model END";
+
+fn group_column_sql(group_by: UsageGroupBy) -> String {
+    match group_by {
+        UsageGroupBy::Model => "model".to_string(),
+        UsageGroupBy::MappedModel => format!("COALESCE({}, model)", BASE_MAPPED_MODEL_SQL),
+        UsageGroupBy::Account => "account_email".to_string(),
+        UsageGroupBy::ClientKey => "client_key".to_string(),
+        UsageGroupBy::None => "NULL".to_string(),
+    }
+}
+
+fn row_to_log(row: &rusqlite::Row) -> rusqlite::Result<ProxyRequestLog> {
+    Ok(ProxyRequestLog {
+        id: row.get(0)?,
+        timestamp: row.get(1)?,
+        method: row.get(2)?,
+        url: row.get(3)?,
+        status: row.get(4)?,
+        duration: row.get(5)?,
+        model: row.get(6)?,
+        mapped_model: row.get(13).unwrap_or(None),
+        account_email: row.get(12).unwrap_or(None),
+        error: row.get(7)?,
+        request_body: row.get(8).unwrap_or(None),
+        response_body: row.get(9).unwrap_or(None),
+        input_tokens: row.get(10).unwrap_or(None),
+        output_tokens: row.get(11).unwrap_or(None),
+        client_key: row.get(14).unwrap_or(None),
+        cached_tokens: row.get(15).unwrap_or(None),
+    })
+}
+
+// ===== 存储 =====
+
+enum WriterMessage {
+    Log(Box<ProxyRequestLog>),
+    Flush(mpsc::Sender<()>),
+}
+
+/// 连接池 + 批量写入的请求日志存储
+pub struct ProxyLogStore {
+    pool: Pool<SqliteConnectionManager>,
+    sender: mpsc::Sender<WriterMessage>,
+    retention: Arc<RwLock<RequestLogConfig>>,
+}
+
+impl ProxyLogStore {
+    pub fn open(path: &Path, retention: RequestLogConfig) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+
+        let manager = SqliteConnectionManager::file(path).with_init(|conn| {
+            conn.execute_batch(
+                "PRAGMA journal_mode = WAL;
+                 PRAGMA synchronous = NORMAL;
+                 PRAGMA busy_timeout = 5000;",
+            )
+        });
+        let pool = Pool::builder()
+            .max_size(POOL_SIZE)
+            .build(manager)
+            .map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+        init_schema(&*pool.get().map_err(|e| e.to_string())?)?;
+
+        let retention = Arc::new(RwLock::new(retention));
+        let (sender, receiver) = mpsc::channel();
+        {
+            let pool = pool.clone();
+            let retention = retention.clone();
+            std::thread::Builder::new()
+                .name("proxy-log-writer".to_string())
+                .spawn(move || run_writer(pool, receiver, retention))
+                .map_err(|e| e.to_string())?;
+        }
+
+        Ok(Self {
+            pool,
+            sender,
+            retention,
+        })
+    }
+
+    pub fn set_retention(&self, retention: RequestLogConfig) {
+        *self.retention.write().unwrap() = retention;
+    }
+
+    /// 加入写入队列 (不阻塞调用方)
+    pub fn enqueue(&self, log: ProxyRequestLog) {
+        if self.sender.send(WriterMessage::Log(Box::new(log))).is_err() {
+            tracing::warn!("Request log writer stopped; log dropped");
+        }
+    }
+
+    /// 阻塞等待队列中已有的日志写入完成
+    pub fn flush(&self) {
+        let (ack_tx, ack_rx) = mpsc::channel();
+        if self.sender.send(WriterMessage::Flush(ack_tx)).is_ok() {
+            let _ = ack_rx.recv_timeout(FLUSH_TIMEOUT);
+        }
+    }
 
-    let total_output_tokens: u64 = conn
-        .query_row(
-            "SELECT COALESCE(SUM(output_tokens), 0) FROM request_logs",
+    /// 按条件分页查询 (按时间倒序)，返回 (当前页, 匹配总数)
+    pub fn query(&self, query: &LogQuery) -> Result<(Vec<ProxyRequestLog>, u64), String> {
+        let conn = self.pool.get().map_err(|e| e.to_string())?;
+        let (where_sql, values) = query.where_clause();
+
+        let total: u64 = conn
+            .query_row(
+                &format!("SELECT COUNT(*) FROM request_logs{}", where_sql),
+                params_from_iter(values.iter()),
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+
+        let mut page_values = values;
+        page_values.push(SqlValue::Integer(query.page_size() as i64));
+        page_values.push(SqlValue::Integer(query.page_offset() as i64));
+
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM request_logs{} ORDER BY timestamp DESC LIMIT ? OFFSET ?",
+                LOG_COLUMNS, where_sql
+            ))
+            .map_err(|e| e.to_string())?;
+        let logs = stmt
+            .query_map(params_from_iter(page_values.iter()), row_to_log)
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+
+        Ok((logs, total))
+    }
+
+    pub fn stats(&self) -> Result<ProxyStats, String> {
+        let conn = self.pool.get().map_err(|e| e.to_string())?;
+        conn.query_row(
+            "SELECT COUNT(*),
+                    COALESCE(SUM(CASE WHEN status < 400 THEN 1 ELSE 0 END), 0),
+                    COALESCE(SUM(input_tokens), 0),
+                    COALESCE(SUM(output_tokens), 0),
+                    COALESCE(SUM(cached_tokens), 0)
+             FROM request_logs",
             [],
-            |row| row.get(0),
+            |row| {
+                let total_requests: u64 = row.get(0)?;
+                let success_count: u64 = row.get(1)?;
+                Ok(ProxyStats {
+                    total_requests,
+                    success_count,
+                    error_count: total_requests - success_count,
+                    total_input_tokens: row.get(2)?,
+                    total_output_tokens: row.get(3)?,
+                    total_cached_tokens: row.get(4)?,
+                })
+            },
         )
-        .map_err(|e| e.to_string())?;
-
-    Ok(ProxyStats {
-        total_requests,
-        success_count,
-        error_count,
-        total_input_tokens,
-        total_output_tokens,
-    })
+        .map_err(|e| e.to_string())
+    }
+
+    /// 按时间段与分组汇总请求数、token 与估算费用 (按时间升序)
+    pub fn usage_rollup(&self, query: &UsageQuery) -> Result<Vec<UsageRollup>, String> {
+        let conn = self.pool.get().map_err(|e| e.to_string())?;
+        let until = query
+            .until
+            .unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
+        let since = query.since.unwrap_or(until - DEFAULT_USAGE_RANGE_MS);
+        let bucket_ms = query.bucket.millis();
+
+        // 额外按实际模型分组，以便逐模型计算费用后再合并
+        let sql = format!(
+            "SELECT (timestamp / ?1) * ?1 AS period, {group} AS grp,
+                    COALESCE({mapped}, model) AS price_model,
+                    COUNT(*),
+                    COALESCE(SUM(CASE WHEN status >= 400 THEN 1 ELSE 0 END), 0),
+                    COALESCE(SUM(input_tokens), 0),
+                    COALESCE(SUM(output_tokens), 0),
+                    COALESCE(SUM(cached_tokens), 0)
+             FROM request_logs
+             WHERE timestamp >= ?2 AND timestamp <= ?3
+             GROUP BY period, grp, price_model",
+            group = group_column_sql(query.group_by),
+            mapped = BASE_MAPPED_MODEL_SQL,
+        );
+        let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![bucket_ms, since, until], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    [
+                        row.get::<_, u64>(3)?,
+                        row.get::<_, u64>(4)?,
+                        row.get::<_, u64>(5)?,
+                        row.get::<_, u64>(6)?,
+                        row.get::<_, u64>(7)?,
+                    ],
+                ))
+            })
+            .map_err(|e| e.to_string())?;
+
+        let mut rollups: BTreeMap<(i64, Option<String>), UsageRollup> = BTreeMap::new();
+        for row in rows {
+            let (period, group, price_model, [requests, errors, input, output, cached]) =
+                row.map_err(|e| e.to_string())?;
+            let entry = rollups
+                .entry((period, group.clone()))
+                .or_insert_with(|| UsageRollup {
+                    period_start: period,
+                    group,
+                    ..Default::default()
+                });
+            entry.requests += requests;
+            entry.errors += errors;
+            entry.input_tokens += input;
+            entry.output_tokens += output;
+            entry.cached_tokens += cached;
+            if let Some(model) = price_model {
+                entry.estimated_cost_usd +=
+                    pricing::estimate_cost_usd(&model, input, output, cached);
+            }
+        }
+        Ok(rollups.into_values().collect())
+    }
+
+    /// 每个客户端 key 自 `since` (毫秒) 以来的 (请求数, token 数)
+    pub fn client_key_usage_since(&self, since: i64) -> Result<Vec<(String, u64, u64)>, String> {
+        let conn = self.pool.get().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT client_key, COUNT(*),
+                        COALESCE(SUM(input_tokens), 0) + COALESCE(SUM(output_tokens), 0)
+                 FROM request_logs
+                 WHERE client_key IS NOT NULL AND timestamp >= ?1
+                 GROUP BY client_key",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([since], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        Ok(rows)
+    }
+
+    pub fn clear(&self) -> Result<(), String> {
+        self.flush();
+        let conn = self.pool.get().map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM request_logs", [])
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// 立即执行一次保留策略，返回删除的日志数
+    pub fn apply_retention(&self) -> Result<usize, String> {
+        let retention = self.retention.read().unwrap().clone();
+        let conn = self.pool.get().map_err(|e| e.to_string())?;
+        apply_retention(&conn, &retention)
+    }
 }
 
-pub fn clear_proxy_logs() -> Result<(), String> {
-    let db_path = get_proxy_db_path()?;
-    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
-    conn.execute("DELETE FROM request_logs", [])
-        .map_err(|e| e.to_string())?;
-    Ok(())
+fn write_batch(pool: &Pool<SqliteConnectionManager>, batch: &mut Vec<ProxyRequestLog>) {
+    if batch.is_empty() {
+        return;
+    }
+    let result = (|| -> Result<(), String> {
+        let mut conn = pool.get().map_err(|e| e.to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        {
+            let mut stmt = tx
+                .prepare_cached(&format!(
+                    "INSERT OR IGNORE INTO request_logs ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+                    LOG_COLUMNS
+                ))
+                .map_err(|e| e.to_string())?;
+            for log in batch.iter() {
+                stmt.execute(params![
+                    log.id,
+                    log.timestamp,
+                    log.method,
+                    log.url,
+                    log.status,
+                    log.duration,
+                    log.model,
+                    log.error,
+                    log.request_body,
+                    log.response_body,
+                    log.input_tokens,
+                    log.output_tokens,
+                    log.account_email,
+                    log.mapped_model,
+                    log.client_key,
+                    log.cached_tokens,
+                ])
+                .map_err(|e| e.to_string())?;
+            }
+        }
+        tx.commit().map_err(|e| e.to_string())
+    })();
+
+    if let Err(e) = result {
+        tracing::error!("Failed to persist {} request logs: {}", batch.len(), e);
+    }
+    batch.clear();
+}
+
+fn apply_retention(conn: &Connection, retention: &RequestLogConfig) -> Result<usize, String> {
+    let mut removed = 0usize;
+
+    if retention.max_age_days > 0 {
+        let cutoff = chrono::Utc::now().timestamp_millis()
+            - retention.max_age_days as i64 * 24 * 60 * 60 * 1000;
+        removed += conn
+            .execute("DELETE FROM request_logs WHERE timestamp < ?1", [cutoff])
+            .map_err(|e| e.to_string())?;
+    }
+
+    if retention.max_size_mb > 0 {
+        let max_bytes = retention.max_size_mb * 1024 * 1024;
+        loop {
+            // 已使用页 (不含空闲页)，删除后空闲页会被后续写入复用
+            let used_bytes: u64 = conn
+                .query_row(
+                    "SELECT (page_count - freelist_count) * page_size
+                     FROM pragma_page_count(), pragma_freelist_count(), pragma_page_size()",
+                    [],
+                    |row| row.get(0),
+                )
+                .map_err(|e| e.to_string())?;
+            if used_bytes <= max_bytes {
+                break;
+            }
+
+            let count: u64 = conn
+                .query_row("SELECT COUNT(*) FROM request_logs", [], |row| row.get(0))
+                .map_err(|e| e.to_string())?;
+            let chunk = (count / 10).max(100);
+            let deleted = conn
+                .execute(
+                    "DELETE FROM request_logs WHERE id IN (
+                        SELECT id FROM request_logs ORDER BY timestamp ASC LIMIT ?1
+                    )",
+                    [chunk],
+                )
+                .map_err(|e| e.to_string())?;
+            if deleted == 0 {
+                break;
+            }
+            removed += deleted;
+        }
+    }
+
+    if removed > 0 {
+        tracing::info!("Request log retention removed {} entries", removed);
+    }
+    Ok(removed)
+}
+
+fn run_writer(
+    pool: Pool<SqliteConnectionManager>,
+    receiver: mpsc::Receiver<WriterMessage>,
+    retention: Arc<RwLock<RequestLogConfig>>,
+) {
+    let mut batch: Vec<ProxyRequestLog> = Vec::with_capacity(WRITE_BATCH_SIZE);
+    let mut batch_started: Option<Instant> = None;
+    let mut last_retention: Option<Instant> = None;
+
+    loop {
+        let timeout = batch_started
+            .map(|t| FLUSH_INTERVAL.saturating_sub(t.elapsed()))
+            .unwrap_or(FLUSH_INTERVAL);
+
+        match receiver.recv_timeout(timeout) {
+            Ok(WriterMessage::Log(log)) => {
+                batch.push(*log);
+                batch_started.get_or_insert_with(Instant::now);
+            }
+            Ok(WriterMessage::Flush(ack)) => {
+                write_batch(&pool, &mut batch);
+                batch_started = None;
+                let _ = ack.send(());
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                write_batch(&pool, &mut batch);
+                break;
+            }
+        }
+
+        if batch.len() >= WRITE_BATCH_SIZE
+            || batch_started.is_some_and(|t| t.elapsed() >= FLUSH_INTERVAL)
+        {
+            write_batch(&pool, &mut batch);
+            batch_started = None;
+        }
+
+        if last_retention.is_none_or(|t| t.elapsed() >= RETENTION_INTERVAL) {
+            last_retention = Some(Instant::now());
+            let retention = retention.read().unwrap().clone();
+            match pool.get() {
+                Ok(conn) => {
+                    if let Err(e) = apply_retention(&conn, &retention) {
+                        tracing::warn!("Request log retention failed: {}", e);
+                    }
+                }
+                Err(e) => tracing::warn!("Request log retention skipped: {}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_log(id: &str, timestamp: i64, status: u16, model: &str) -> ProxyRequestLog {
+        ProxyRequestLog {
+            id: id.to_string(),
+            timestamp,
+            method: "POST".to_string(),
+            url: "/v1/messages".to_string(),
+            status,
+            duration: 10,
+            model: Some(model.to_string()),
+            mapped_model: None,
+            account_email: Some("a@example.com".to_string()),
+            error: None,
+            request_body: None,
+            response_body: None,
+            input_tokens: Some(3),
+            output_tokens: Some(4),
+            client_key: None,
+            cached_tokens: None,
+        }
+    }
+
+    fn temp_store(retention: RequestLogConfig) -> (ProxyLogStore, PathBuf) {
+        let dir = crate::proxy::tests::support::temp_dir("ag-proxy-db");
+        let store = ProxyLogStore::open(&dir.join("proxy_logs.db"), retention).unwrap();
+        (store, dir)
+    }
+
+    #[test]
+    fn test_status_filter_parse() {
+        assert_eq!(
+            StatusFilter::parse("429"),
+            Some(StatusFilter::Between(429, 429))
+        );
+        assert_eq!(
+            StatusFilter::parse("5xx"),
+            Some(StatusFilter::Between(500, 599))
+        );
+        assert!(StatusFilter::parse("error").unwrap().matches(503));
+        assert!(!StatusFilter::parse("error").unwrap().matches(200));
+        assert_eq!(StatusFilter::parse("abc"), None);
+    }
+
+    #[test]
+    fn test_batched_write_and_filtered_query() {
+        let (store, dir) = temp_store(RequestLogConfig::default());
+        let now = chrono::Utc::now().timestamp_millis();
+        store.enqueue(sample_log("1", now - 2000, 200, "claude-sonnet-4-5"));
+        store.enqueue(sample_log("2", now - 1000, 429, "gemini-2.5-pro"));
+        store.enqueue(sample_log("3", now, 200, "gemini-2.5-flash"));
+        store.flush();
+
+        let (logs, total) = store.query(&LogQuery::default()).unwrap();
+        assert_eq!(total, 3);
+        assert_eq!(logs[0].id, "3");
+
+        let query = LogQuery {
+            model: Some("GEMINI".to_string()),
+            status: Some("2xx".to_string()),
+            ..Default::default()
+        };
+        let (logs, total) = store.query(&query).unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(logs[0].id, "3");
+        assert!(query.matches(&logs[0]));
+
+        let page = LogQuery {
+            limit: Some(1),
+            offset: Some(1),
+            ..Default::default()
+        };
+        let (logs, total) = store.query(&page).unwrap();
+        assert_eq!((logs.len(), total), (1, 3));
+        assert_eq!(logs[0].id, "2");
+
+        let stats = store.stats().unwrap();
+        assert_eq!(stats.total_requests, 3);
+        assert_eq!(stats.error_count, 1);
+        assert_eq!(stats.total_input_tokens, 9);
+
+        drop(store);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_retention_by_age() {
+        let (store, dir) = temp_store(RequestLogConfig {
+            max_age_days: 1,
+            max_size_mb: 0,
+        });
+        let now = chrono::Utc::now().timestamp_millis();
+        store.enqueue(sample_log("old", now - 3 * 24 * 60 * 60 * 1000, 200, "m"));
+        store.enqueue(sample_log("new", now, 200, "m"));
+        store.flush();
+
+        assert_eq!(store.apply_retention().unwrap(), 1);
+        let (logs, _) = store.query(&LogQuery::default()).unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].id, "new");
+
+        drop(store);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_client_key_filter_and_usage() {
+        let (store, dir) = temp_store(RequestLogConfig::default());
+        let now = chrono::Utc::now().timestamp_millis();
+        let mut ci = sample_log("1", now, 200, "m");
+        ci.client_key = Some("ci".to_string());
+        store.enqueue(ci);
+        store.enqueue(sample_log("2", now, 200, "m"));
+        store.flush();
+
+        let query = LogQuery {
+            client_key: Some("ci".to_string()),
+            ..Default::default()
+        };
+        let (logs, total) = store.query(&query).unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(logs[0].client_key.as_deref(), Some("ci"));
+
+        let usage = store.client_key_usage_since(now - 1000).unwrap();
+        assert_eq!(usage, vec![("ci".to_string(), 1, 7)]);
+
+        drop(store);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_usage_rollup_groups_by_day_and_model() {
+        let (store, dir) = temp_store(RequestLogConfig::default());
+        let day = UsageBucket::Day.millis();
+        let base = (chrono::Utc::now().timestamp_millis() / day) * day;
+
+        let mut cached = sample_log("1", base - day + 10, 200, "gemini-2.5-pro");
+        cached.mapped_model = Some("gemini-2.5-pro (rule#1 pro)".to_string());
+        cached.cached_tokens = Some(2);
+        store.enqueue(cached);
+        store.enqueue(sample_log("2", base + 10, 200, "gemini-2.5-pro"));
+        store.enqueue(sample_log("3", base + 20, 500, "gemini-2.5-pro"));
+        store.enqueue(sample_log("4", base + 30, 200, "claude-sonnet-4-5"));
+        store.flush();
+
+        let query = UsageQuery {
+            since: Some(base - day),
+            until: Some(base + day),
+            bucket: UsageBucket::Day,
+            group_by: UsageGroupBy::MappedModel,
+        };
+        let rows = store.usage_rollup(&query).unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].period_start, base - day);
+        assert_eq!(rows[0].group.as_deref(), Some("gemini-2.5-pro"));
+        assert_eq!(rows[0].cached_tokens, 2);
+
+        let today_pro = rows
+            .iter()
+            .find(|r| r.period_start == base && r.group.as_deref() == Some("gemini-2.5-pro"))
+            .unwrap();
+        assert_eq!((today_pro.requests, today_pro.errors), (2, 1));
+        assert_eq!(today_pro.input_tokens, 6);
+        assert!(today_pro.estimated_cost_usd > 0.0);
+
+        drop(store);
+        let _ = std::fs::remove_dir_all(dir);
+    }
 }
@@ -230,8 +230,7 @@ pub async fn fetch_quota_inner(
     Err(last_error.unwrap_or_else(|| AppError::Unknown("配额查询失败".to_string())))
 }
 
-/// 批量查询所有账号配额 (备用功能)
-#[allow(dead_code)]
+/// 批量查询所有账号配额
 pub async fn fetch_all_quotas(
     accounts: Vec<(String, String)>,
 ) -> Vec<(String, crate::error::AppResult<QuotaData>)> {
@@ -19,7 +19,8 @@
 //! - On success above threshold: gradually expand limit (+5%)
 
 use dashmap::DashMap;
-use std::sync::atomic::{AtomicU64, Ordering};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::RwLock;
 use std::time::{Duration, Instant};
 
@@ -116,6 +117,8 @@ pub struct AdaptiveLimitTracker {
     minute_started_at: RwLock<Instant>,
     /// When last calibration occurred
     last_calibration: RwLock<Instant>,
+    /// Whether the limit has been learned (429 / expansion / persisted) rather than defaulted
+    calibrated: AtomicBool,
     /// Consecutive successes above threshold (for AIMD reward)
     consecutive_above_threshold: AtomicU64,
     /// Safety margin (e.g., 0.85 = 15% buffer)
@@ -139,6 +142,7 @@ impl AdaptiveLimitTracker {
                     .checked_sub(Duration::from_secs(3600))
                     .unwrap_or_else(Instant::now),
             ),
+            calibrated: AtomicBool::new(false),
             consecutive_above_threshold: AtomicU64::new(0),
             safety_margin,
             aimd,
@@ -175,6 +179,10 @@ impl AdaptiveLimitTracker {
             Ordering::Relaxed,
         );
         tracker.ceiling.store(ceiling, Ordering::Relaxed);
+        if let Some(calibrated_at) = Instant::now().checked_sub(Duration::from_secs(age_seconds)) {
+            *tracker.last_calibration.write().unwrap() = calibrated_at;
+        }
+        tracker.calibrated.store(true, Ordering::Relaxed);
 
         tracing::debug!(
             "Loaded persisted limit: {} (original: {}, age: {}h, confidence: {:.0}%)",
@@ -301,6 +309,7 @@ impl AdaptiveLimitTracker {
         // Ceiling also contracts - limit might have decreased
         self.ceiling.store(actual_limit, Ordering::Relaxed);
         *self.last_calibration.write().unwrap() = Instant::now();
+        self.calibrated.store(true, Ordering::Relaxed);
         self.consecutive_above_threshold.store(0, Ordering::Relaxed);
 
         crate::proxy::prometheus::record_aimd_penalty();
@@ -325,6 +334,7 @@ impl AdaptiveLimitTracker {
             .store(new_threshold, Ordering::Relaxed);
         self.ceiling.fetch_max(new_limit, Ordering::Relaxed);
         *self.last_calibration.write().unwrap() = Instant::now();
+        self.calibrated.store(true, Ordering::Relaxed);
 
         crate::proxy::prometheus::record_aimd_reward();
 
@@ -354,6 +364,34 @@ impl AdaptiveLimitTracker {
     pub fn time_since_calibration(&self) -> Duration {
         self.last_calibration.read().unwrap().elapsed()
     }
+
+    /// Whether the limit was learned rather than the conservative default
+    pub fn is_calibrated(&self) -> bool {
+        self.calibrated.load(Ordering::Relaxed)
+    }
+
+    /// Point-in-time view for the admin API
+    pub fn snapshot(&self) -> AdaptiveLimitSnapshot {
+        AdaptiveLimitSnapshot {
+            confirmed_limit: self.confirmed_limit(),
+            working_threshold: self.working_threshold(),
+            ceiling: self.ceiling(),
+            requests_this_minute: self.requests_this_minute(),
+            usage_ratio: self.usage_ratio(),
+            seconds_since_calibration: self.time_since_calibration().as_secs(),
+        }
+    }
+}
+
+/// Serializable view of an account's adaptive limit
+#[derive(Debug, Clone, Serialize)]
+pub struct AdaptiveLimitSnapshot {
+    pub confirmed_limit: u64,
+    pub working_threshold: u64,
+    pub ceiling: u64,
+    pub requests_this_minute: u64,
+    pub usage_ratio: f64,
+    pub seconds_since_calibration: u64,
 }
 
 /// Manager for all account limit trackers
@@ -439,10 +477,29 @@ impl AdaptiveLimitManager {
         self.get_or_create(account_id).should_allow()
     }
 
-    /// Get all trackers for persistence
+    /// Get snapshot for account
+    pub fn snapshot(&self, account_id: &str) -> AdaptiveLimitSnapshot {
+        self.get_or_create(account_id).snapshot()
+    }
+
+    /// Get snapshot without creating a tracker (for read-only status views)
+    ///
+    /// Accounts without a tracker report the default limit.
+    pub fn peek(&self, account_id: &str) -> AdaptiveLimitSnapshot {
+        match self.get(account_id) {
+            Some(tracker) => tracker.snapshot(),
+            None => AdaptiveLimitTracker::new(self.safety_margin, self.aimd.clone()).snapshot(),
+        }
+    }
+
+    /// Get all calibrated trackers for persistence
+    ///
+    /// Uncalibrated trackers only hold the default limit; persisting them would let
+    /// the age-based decay in `from_persisted` shrink the default after a restart.
     pub fn all_for_persistence(&self) -> Vec<(String, u64, u64, u64)> {
         self.trackers
             .iter()
+            .filter(|entry| entry.value().is_calibrated())
             .map(|entry| {
                 let (confirmed, ceiling, age) = entry.value().to_persisted();
                 (entry.key().clone(), confirmed, ceiling, age)
@@ -574,4 +631,19 @@ mod tests {
         let _ = manager.get_or_create("account2");
         assert_eq!(manager.len(), 2);
     }
+
+    #[test]
+    fn test_manager_peek_does_not_create() {
+        let manager = AdaptiveLimitManager::default();
+        let default = manager.peek("account1");
+        assert!(manager.is_empty());
+
+        manager.record_success("account1");
+        assert_eq!(manager.len(), 1);
+        assert_eq!(manager.peek("account1").requests_this_minute, 1);
+        assert_eq!(
+            manager.peek("account1").working_threshold,
+            default.working_threshold
+        );
+    }
 }
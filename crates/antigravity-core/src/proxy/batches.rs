@@ -0,0 +1,425 @@
+//! Anthropic Message Batches 后台执行
+//!
+//! 批次中的每条请求通过 `handle_messages` 同步管线执行 (账号轮换、粘性会话、限流处理与
+//! 普通请求一致)。被限流的请求放回队列并延后 `RATE_LIMIT_BACKOFF` 再领取，其余请求照常执行。
+//!
+//! 由客户端 key 创建的批次按该 key 逐条校验白名单、限流与每日预算，并经过与 `/v1/messages`
+//! 相同的指标与监控中间件 (token 计入 key 用量，写入请求日志)。
+
+use crate::modules::batch_db::{self, BatchRecord, BatchStatus, ClaimedRequest, RequestState};
+use crate::proxy::client_keys::{self, ClientKeyIdentity, KeyRejection};
+use crate::proxy::middleware::auth::charge_token_budget;
+use crate::proxy::server::AppState;
+use crate::proxy::ProxySecurityConfig;
+use antigravity_shared::proxy::config::Protocol;
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use axum::routing::post;
+use axum::Router;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Notify, RwLock};
+use tokio::task::JoinSet;
+use tower::Service;
+
+/// 同时执行的批次请求数
+const BATCH_CONCURRENCY: usize = 4;
+/// 限流 (429/503/529) 时的最大尝试次数，超过后记为 errored
+const MAX_ATTEMPTS: u32 = 5;
+/// 被限流的请求重新领取前的等待时间
+const RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(30);
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// 单条结果响应体上限
+const MAX_RESPONSE_BYTES: usize = 64 * 1024 * 1024;
+
+static WORKER_STARTED: AtomicBool = AtomicBool::new(false);
+static WAKE: Notify = Notify::const_new();
+
+/// 唤醒 worker (新批次创建后调用)
+pub fn notify() {
+    WAKE.notify_one();
+}
+
+/// 启动后台 worker；进程内只启动一次
+pub fn spawn_worker(state: AppState, security: Arc<RwLock<ProxySecurityConfig>>) {
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+    if WORKER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    runtime.spawn(run_worker(state, security));
+}
+
+fn timestamp(ms: i64) -> Value {
+    chrono::DateTime::from_timestamp_millis(ms)
+        .map(|t| json!(t.to_rfc3339_opts(chrono::SecondsFormat::Millis, true)))
+        .unwrap_or(Value::Null)
+}
+
+/// Anthropic `message_batch` 对象
+pub fn batch_to_json(batch: &BatchRecord) -> Value {
+    json!({
+        "id": batch.id,
+        "type": "message_batch",
+        "processing_status": batch.status,
+        "request_counts": batch.counts,
+        "ended_at": batch.ended_at.map(timestamp),
+        "created_at": timestamp(batch.created_at),
+        "expires_at": timestamp(batch.expires_at),
+        "archived_at": null,
+        "cancel_initiated_at": batch.cancel_initiated_at.map(timestamp),
+        "results_url": (batch.status == BatchStatus::Ended)
+            .then(|| format!("/v1/messages/batches/{}/results", batch.id)),
+    })
+}
+
+/// 结果 JSONL 中的一行
+pub fn result_line(custom_id: &str, state: RequestState, result: Option<Value>) -> Value {
+    json!({
+        "custom_id": custom_id,
+        "result": result.unwrap_or_else(|| json!({"type": state.as_str()})),
+    })
+}
+
+fn errored(error_type: &str, message: &str) -> Value {
+    json!({
+        "type": "errored",
+        "error": {
+            "type": "error",
+            "error": {"type": error_type, "message": message}
+        }
+    })
+}
+
+enum Outcome {
+    Done(RequestState, Value),
+    /// 暂时不可用，放回队列
+    Retry(String),
+}
+
+/// `/v1/messages` 及其指标、监控中间件 (鉴权与预算计费由 [`admit`] / [`execute`] 完成)
+fn messages_service(state: AppState) -> Router {
+    Router::new()
+        .route(
+            "/v1/messages",
+            post(crate::proxy::handlers::claude::handle_messages),
+        )
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            crate::proxy::middleware::monitor::monitor_middleware,
+        ))
+        .layer(axum::middleware::from_fn(
+            crate::proxy::middleware::metrics_middleware,
+        ))
+        .with_state(state)
+}
+
+/// 按创建批次的客户端 key 校验白名单、限流与每日预算，并计入一次请求
+async fn admit(
+    security: &RwLock<ProxySecurityConfig>,
+    key_id: &str,
+    params: &Value,
+) -> Result<ClientKeyIdentity, Outcome> {
+    let security = security.read().await;
+    let Some(key) = security.find_client_key_by_id(key_id) else {
+        return Err(Outcome::Done(
+            RequestState::Errored,
+            errored(
+                "authentication_error",
+                "The API key that created this batch is no longer valid",
+            ),
+        ));
+    };
+    let model = params.get("model").and_then(|m| m.as_str());
+    let admitted = client_keys::check_allowlists(key, Some(Protocol::Anthropic), model)
+        .and_then(|_| client_keys::tracker().admit(key));
+    match admitted {
+        Ok(()) => Ok(ClientKeyIdentity::from(key)),
+        Err(rejection @ KeyRejection::RateLimited { .. }) => {
+            Err(Outcome::Retry(rejection.message()))
+        }
+        Err(rejection) => Err(Outcome::Done(
+            RequestState::Errored,
+            errored(error_type_for(rejection.status()), &rejection.message()),
+        )),
+    }
+}
+
+async fn execute(
+    mut service: Router,
+    security: Arc<RwLock<ProxySecurityConfig>>,
+    request: &ClaimedRequest,
+) -> Outcome {
+    let mut params = request.params.clone();
+    params["stream"] = json!(false);
+
+    let mut http_request = Request::post("/v1/messages")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(params.to_string()))
+        .unwrap();
+    if let Some(key_id) = &request.client_key {
+        match admit(&security, key_id, &params).await {
+            Ok(identity) => {
+                http_request.extensions_mut().insert(identity);
+            }
+            Err(outcome) => return outcome,
+        }
+    }
+
+    // Router 始终 ready，且错误类型为 Infallible
+    let Ok(mut response) = service.call(http_request).await;
+    if let Some(key_id) = &request.client_key {
+        response = charge_token_budget(key_id.clone(), "/v1/messages", response);
+    }
+    let status = response.status();
+    let body = match axum::body::to_bytes(response.into_body(), MAX_RESPONSE_BYTES).await {
+        Ok(body) => body,
+        Err(e) => {
+            return Outcome::Done(
+                RequestState::Errored,
+                errored("api_error", &format!("Failed to read response: {}", e)),
+            )
+        }
+    };
+    let json = serde_json::from_slice::<Value>(&body).ok();
+
+    if status.is_success() {
+        return match json {
+            Some(message) => Outcome::Done(
+                RequestState::Succeeded,
+                json!({"type": "succeeded", "message": message}),
+            ),
+            None => Outcome::Done(
+                RequestState::Errored,
+                errored("api_error", "Upstream returned an invalid message"),
+            ),
+        };
+    }
+
+    let message = json
+        .as_ref()
+        .and_then(|v| v.pointer("/error/message"))
+        .and_then(|m| m.as_str())
+        .map(String::from)
+        .unwrap_or_else(|| String::from_utf8_lossy(&body).to_string());
+    let retryable = matches!(status.as_u16(), 429 | 503 | 529);
+    if retryable && request.attempts + 1 < MAX_ATTEMPTS {
+        return Outcome::Retry(message);
+    }
+
+    // 保留 handle_messages 已经生成的 Claude 错误体
+    let result = match json {
+        Some(error) if error.get("type").and_then(|t| t.as_str()) == Some("error") => {
+            json!({"type": "errored", "error": error})
+        }
+        _ => errored(error_type_for(status), &message),
+    };
+    Outcome::Done(RequestState::Errored, result)
+}
+
+fn error_type_for(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        400 => "invalid_request_error",
+        401 => "authentication_error",
+        403 => "permission_error",
+        404 => "not_found_error",
+        413 => "request_too_large",
+        429 => "rate_limit_error",
+        529 => "overloaded_error",
+        _ => "api_error",
+    }
+}
+
+async fn run_worker(state: AppState, security: Arc<RwLock<ProxySecurityConfig>>) {
+    let store = match batch_db::default_store() {
+        Ok(store) => store,
+        Err(e) => {
+            tracing::error!("[Batches] Worker disabled, failed to open store: {}", e);
+            return;
+        }
+    };
+    tracing::info!("[Batches] Worker started");
+
+    let service = messages_service(state);
+    let mut running = JoinSet::new();
+
+    loop {
+        if let Err(e) = store.finalize() {
+            tracing::warn!("[Batches] Finalize failed: {}", e);
+        }
+
+        let free = BATCH_CONCURRENCY - running.len();
+        if free > 0 {
+            match store.claim(free) {
+                Ok(claimed) => {
+                    for request in claimed {
+                        let service = service.clone();
+                        let security = security.clone();
+                        running.spawn(async move {
+                            let outcome = execute(service, security, &request).await;
+                            (request, outcome)
+                        });
+                    }
+                }
+                Err(e) => tracing::warn!("[Batches] Claim failed: {}", e),
+            }
+        }
+
+        tokio::select! {
+            Some(joined) = running.join_next(), if !running.is_empty() => {
+                let Ok((request, outcome)) = joined else {
+                    continue;
+                };
+                let result = match outcome {
+                    Outcome::Done(request_state, result) => store.complete(
+                        &request.batch_id,
+                        &request.custom_id,
+                        request_state,
+                        &result,
+                    ),
+                    Outcome::Retry(reason) => {
+                        tracing::warn!(
+                            "[Batches] {}/{} deferred ({}), retrying in {}s",
+                            request.batch_id,
+                            request.custom_id,
+                            reason,
+                            RATE_LIMIT_BACKOFF.as_secs()
+                        );
+                        store.release(&request.batch_id, &request.custom_id, RATE_LIMIT_BACKOFF)
+                    }
+                };
+                if let Err(e) = result {
+                    tracing::warn!("[Batches] Failed to record result: {}", e);
+                }
+            }
+            _ = WAKE.notified() => {}
+            _ = tokio::time::sleep(POLL_INTERVAL) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::batch_db::RequestCounts;
+
+    #[test]
+    fn test_batch_to_json() {
+        let batch = BatchRecord {
+            id: "msgbatch_1".to_string(),
+            status: BatchStatus::Ended,
+            created_at: 0,
+            expires_at: batch_db::BATCH_EXPIRY_MS,
+            ended_at: Some(1000),
+            cancel_initiated_at: None,
+            client_key: None,
+            counts: RequestCounts {
+                succeeded: 2,
+                ..Default::default()
+            },
+        };
+        let value = batch_to_json(&batch);
+        assert_eq!(value["processing_status"], "ended");
+        assert_eq!(value["created_at"], "1970-01-01T00:00:00.000Z");
+        assert_eq!(value["ended_at"], "1970-01-01T00:00:01.000Z");
+        assert_eq!(value["request_counts"]["succeeded"], 2);
+        assert_eq!(
+            value["results_url"],
+            "/v1/messages/batches/msgbatch_1/results"
+        );
+        assert_eq!(
+            result_line("a", RequestState::Canceled, None),
+            json!({"custom_id": "a", "result": {"type": "canceled"}})
+        );
+    }
+
+    /// mock 上游：返回带 usageMetadata 的 SSE
+    async fn spawn_sse_upstream() -> String {
+        let app = Router::new().fallback(|| async {
+            let event = json!({"response": {
+                "candidates": [{
+                    "content": {"role": "model", "parts": [{"text": "ok"}]},
+                    "finishReason": "STOP"
+                }],
+                "usageMetadata": {"promptTokenCount": 7, "candidatesTokenCount": 3}
+            }});
+            (
+                [(header::CONTENT_TYPE, "text/event-stream")],
+                format!("data: {}\n\n", event),
+            )
+        });
+        crate::proxy::tests::support::spawn_upstream(app).await
+    }
+
+    fn claimed(key_id: Option<&str>) -> ClaimedRequest {
+        ClaimedRequest {
+            batch_id: "msgbatch_1".to_string(),
+            custom_id: "a".to_string(),
+            params: json!({
+                "model": "claude-sonnet-4-5",
+                "max_tokens": 16,
+                "messages": [{"role": "user", "content": "hi"}]
+            }),
+            attempts: 0,
+            client_key: key_id.map(String::from),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_accounts_requests_to_client_key() {
+        let key = antigravity_shared::proxy::config::ClientApiKey {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: "batch-ci".to_string(),
+            key: "sk-ag-batch-ci".to_string(),
+            enabled: true,
+            allowed_models: vec!["claude-*".to_string()],
+            allowed_protocols: Vec::new(),
+            requests_per_minute: None,
+            daily_token_budget: Some(10),
+            expires_at: None,
+            created_at: 0,
+        };
+        let security = Arc::new(RwLock::new(ProxySecurityConfig {
+            auth_mode: antigravity_shared::proxy::config::ProxyAuthMode::Off,
+            api_key: String::new(),
+            allow_lan_access: false,
+            client_keys: vec![key.clone()],
+        }));
+        let state =
+            crate::proxy::tests::support::app_state(spawn_sse_upstream().await, &["a"]).await;
+        state.monitor.set_enabled(true);
+        let service = messages_service(state.clone());
+
+        let outcome = execute(service.clone(), security.clone(), &claimed(Some(&key.id))).await;
+        assert!(matches!(outcome, Outcome::Done(RequestState::Succeeded, _)));
+        let usage = client_keys::tracker().usage(&key.id);
+        assert_eq!(usage.requests_today, 1);
+        assert_eq!(usage.tokens_today, 10);
+        let logs = state.monitor.get_logs(None).await;
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].url, "/v1/messages");
+        assert_eq!(logs[0].client_key.as_deref(), Some("batch-ci"));
+        assert_eq!(logs[0].input_tokens, Some(7));
+
+        // 预算用尽后不再调用上游
+        let error_type = |outcome: Outcome| match outcome {
+            Outcome::Done(RequestState::Errored, result) => {
+                result["error"]["error"]["type"].as_str().map(String::from)
+            }
+            _ => None,
+        };
+        let outcome = execute(service.clone(), security.clone(), &claimed(Some(&key.id))).await;
+        assert_eq!(error_type(outcome).as_deref(), Some("rate_limit_error"));
+        let outcome = execute(service.clone(), security.clone(), &claimed(Some("deleted"))).await;
+        assert_eq!(error_type(outcome).as_deref(), Some("authentication_error"));
+        assert_eq!(state.monitor.get_logs(None).await.len(), 1);
+
+        security.write().await.client_keys[0].allowed_models = vec!["gemini-*".to_string()];
+        client_keys::tracker().remove(&key.id);
+        let outcome = execute(service, security, &claimed(Some(&key.id))).await;
+        assert_eq!(error_type(outcome).as_deref(), Some("permission_error"));
+    }
+}
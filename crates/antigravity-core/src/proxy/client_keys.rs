@@ -0,0 +1,350 @@
+//! 客户端 API Key：白名单、限流、每日 token 预算与用量统计
+//!
+//! 鉴权中间件在请求进入时调用 [`ClientKeyTracker::admit`]，并在响应结束后
+//! 通过 [`ClientKeyTracker::record_tokens`] 累计 token。用量按 key id 统计，进程内共享。
+
+use crate::modules::proxy_db::ProxyLogStore;
+use crate::proxy::common::model_mapping::wildcard_match;
+use antigravity_shared::proxy::config::{ClientApiKey, Protocol};
+use axum::http::StatusCode;
+use axum::Extension;
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+static TRACKER: Lazy<ClientKeyTracker> = Lazy::new(ClientKeyTracker::default);
+
+/// 进程内共享的用量统计
+pub fn tracker() -> &'static ClientKeyTracker {
+    &TRACKER
+}
+
+/// 生成新的客户端 key secret
+pub fn generate_key_secret() -> String {
+    format!("sk-ag-{}", uuid::Uuid::new_v4().simple())
+}
+
+/// 列表展示用的脱敏 key，例如 `sk-ag-…9f3c`
+pub fn mask_key(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() <= 10 {
+        return "…".to_string();
+    }
+    let prefix: String = chars[..6].iter().collect();
+    let suffix: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}…{}", prefix, suffix)
+}
+
+/// 通过鉴权的客户端 key，写入 request extensions 供下游中间件记录用量
+#[derive(Debug, Clone)]
+pub struct ClientKeyIdentity {
+    pub id: String,
+    pub name: String,
+}
+
+impl From<&ClientApiKey> for ClientKeyIdentity {
+    fn from(key: &ClientApiKey) -> Self {
+        Self {
+            id: key.id.clone(),
+            name: key.name.clone(),
+        }
+    }
+}
+
+/// 调用方的客户端 key id (主 key 为 None)，批次、文件等资源按它划分归属
+pub fn owner_id(identity: &Option<Extension<ClientKeyIdentity>>) -> Option<&str> {
+    identity
+        .as_ref()
+        .map(|Extension(identity)| identity.id.as_str())
+}
+
+/// 请求被拒绝的原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyRejection {
+    ProtocolNotAllowed,
+    ModelNotAllowed(String),
+    /// 设置了模型白名单，但无法从请求中确定模型
+    ModelRequired,
+    /// 请求体读取失败 (超出大小限制或连接中断)
+    InvalidBody(String),
+    RateLimited {
+        retry_after_secs: u64,
+    },
+    BudgetExhausted,
+}
+
+impl KeyRejection {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::ProtocolNotAllowed | Self::ModelNotAllowed(_) => StatusCode::FORBIDDEN,
+            Self::ModelRequired | Self::InvalidBody(_) => StatusCode::BAD_REQUEST,
+            Self::RateLimited { .. } | Self::BudgetExhausted => StatusCode::TOO_MANY_REQUESTS,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            Self::ProtocolNotAllowed => "This API key is not allowed to use this API".to_string(),
+            Self::ModelNotAllowed(model) => {
+                format!("This API key is not allowed to use model '{}'", model)
+            }
+            Self::ModelRequired => {
+                "This API key is restricted to specific models; the request must specify a model"
+                    .to_string()
+            }
+            Self::InvalidBody(e) => format!("Failed to read request body: {}", e),
+            Self::RateLimited { retry_after_secs } => format!(
+                "API key request rate limit exceeded, retry after {}s",
+                retry_after_secs
+            ),
+            Self::BudgetExhausted => "API key daily token budget exhausted".to_string(),
+        }
+    }
+}
+
+/// 根据请求路径判断协议 (非模型 API 返回 None)
+pub fn protocol_for_path(path: &str) -> Option<Protocol> {
+    if path.starts_with("/v1/files") {
+        // 文件上传为 OpenAI / Anthropic 共用，不受协议白名单限制
+        None
+    } else if path.starts_with("/v1/messages") {
+        Some(Protocol::Anthropic)
+    } else if path.starts_with("/v1beta/") {
+        Some(Protocol::Gemini)
+    } else if path.starts_with("/v1/") && !path.contains("event_logging") {
+        Some(Protocol::OpenAI)
+    } else {
+        None
+    }
+}
+
+/// 检查协议与模型白名单 (`model` 为客户端请求的原始模型名)
+pub fn check_allowlists(
+    key: &ClientApiKey,
+    protocol: Option<Protocol>,
+    model: Option<&str>,
+) -> Result<(), KeyRejection> {
+    if let Some(protocol) = protocol {
+        if !key.allowed_protocols.is_empty() && !key.allowed_protocols.contains(&protocol) {
+            return Err(KeyRejection::ProtocolNotAllowed);
+        }
+    }
+    if let Some(model) = model {
+        if !key.allowed_models.is_empty()
+            && !key
+                .allowed_models
+                .iter()
+                .any(|pattern| wildcard_match(pattern, model))
+        {
+            return Err(KeyRejection::ModelNotAllowed(model.to_string()));
+        }
+    }
+    Ok(())
+}
+
+/// 单个 key 的用量
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct ClientKeyUsage {
+    /// 当日统计对应的 UTC 日期 (YYYY-MM-DD)
+    pub day: String,
+    pub requests_today: u64,
+    pub tokens_today: u64,
+    pub total_requests: u64,
+    pub total_tokens: u64,
+    pub last_used_at: Option<i64>,
+}
+
+impl ClientKeyUsage {
+    /// 跨日时重置当日计数
+    fn roll_over(&mut self, today: &str) {
+        if self.day != today {
+            self.day = today.to_string();
+            self.requests_today = 0;
+            self.tokens_today = 0;
+        }
+    }
+}
+
+#[derive(Debug)]
+struct UsageEntry {
+    usage: ClientKeyUsage,
+    window_start: Instant,
+    window_requests: u32,
+}
+
+impl Default for UsageEntry {
+    fn default() -> Self {
+        Self {
+            usage: ClientKeyUsage::default(),
+            window_start: Instant::now(),
+            window_requests: 0,
+        }
+    }
+}
+
+fn today() -> String {
+    chrono::Utc::now().format("%Y-%m-%d").to_string()
+}
+
+#[derive(Debug, Default)]
+pub struct ClientKeyTracker {
+    entries: DashMap<String, UsageEntry>,
+}
+
+impl ClientKeyTracker {
+    /// 检查每分钟请求数与当日预算，通过时计入一次请求
+    pub fn admit(&self, key: &ClientApiKey) -> Result<(), KeyRejection> {
+        let today = today();
+        let mut entry = self.entries.entry(key.id.clone()).or_default();
+        entry.usage.roll_over(&today);
+
+        if let Some(budget) = key.daily_token_budget {
+            if entry.usage.tokens_today >= budget {
+                return Err(KeyRejection::BudgetExhausted);
+            }
+        }
+
+        if let Some(rpm) = key.requests_per_minute {
+            let elapsed = entry.window_start.elapsed();
+            if elapsed >= RATE_WINDOW {
+                entry.window_start = Instant::now();
+                entry.window_requests = 0;
+            } else if entry.window_requests >= rpm {
+                let retry_after_secs = (RATE_WINDOW - elapsed).as_secs().max(1);
+                return Err(KeyRejection::RateLimited { retry_after_secs });
+            }
+            entry.window_requests += 1;
+        }
+
+        entry.usage.requests_today += 1;
+        entry.usage.total_requests += 1;
+        entry.usage.last_used_at = Some(chrono::Utc::now().timestamp());
+        Ok(())
+    }
+
+    pub fn record_tokens(&self, key_id: &str, tokens: u64) {
+        if tokens == 0 {
+            return;
+        }
+        let today = today();
+        let mut entry = self.entries.entry(key_id.to_string()).or_default();
+        entry.usage.roll_over(&today);
+        entry.usage.tokens_today += tokens;
+        entry.usage.total_tokens += tokens;
+    }
+
+    /// 用持久化的请求日志恢复当日用量 (重启后预算不清零)
+    pub fn seed_today(&self, key_id: &str, requests: u64, tokens: u64) {
+        let today = today();
+        let mut entry = self.entries.entry(key_id.to_string()).or_default();
+        entry.usage.roll_over(&today);
+        entry.usage.requests_today = entry.usage.requests_today.max(requests);
+        entry.usage.tokens_today = entry.usage.tokens_today.max(tokens);
+        entry.usage.total_requests = entry.usage.total_requests.max(requests);
+        entry.usage.total_tokens = entry.usage.total_tokens.max(tokens);
+    }
+
+    /// 从请求日志 (按 key 名称记录) 恢复当日用量
+    pub fn seed_from_log_store(&self, store: &ProxyLogStore, keys: &[ClientApiKey]) {
+        let midnight = chrono::Utc::now()
+            .date_naive()
+            .and_hms_opt(0, 0, 0)
+            .map(|t| t.and_utc().timestamp_millis())
+            .unwrap_or_default();
+        match store.client_key_usage_since(midnight) {
+            Ok(rows) => {
+                for (name, requests, tokens) in rows {
+                    if let Some(key) = keys.iter().find(|k| k.name == name) {
+                        self.seed_today(&key.id, requests, tokens);
+                    }
+                }
+            }
+            Err(e) => tracing::warn!("Failed to restore client key usage: {}", e),
+        }
+    }
+
+    pub fn usage(&self, key_id: &str) -> ClientKeyUsage {
+        let today = today();
+        let mut usage = self
+            .entries
+            .get(key_id)
+            .map(|entry| entry.usage.clone())
+            .unwrap_or_default();
+        usage.roll_over(&today);
+        usage
+    }
+
+    pub fn remove(&self, key_id: &str) {
+        self.entries.remove(key_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client_key() -> ClientApiKey {
+        ClientApiKey {
+            id: "k1".to_string(),
+            name: "ci".to_string(),
+            key: "sk-ag-test-key".to_string(),
+            enabled: true,
+            allowed_models: vec!["gemini-*".to_string()],
+            allowed_protocols: vec![Protocol::OpenAI, Protocol::Gemini],
+            requests_per_minute: Some(2),
+            daily_token_budget: Some(100),
+            expires_at: None,
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_allowlists() {
+        let key = client_key();
+        assert!(check_allowlists(&key, Some(Protocol::OpenAI), Some("gemini-2.5-pro")).is_ok());
+        assert_eq!(
+            check_allowlists(&key, Some(Protocol::Anthropic), Some("gemini-2.5-pro")),
+            Err(KeyRejection::ProtocolNotAllowed)
+        );
+        assert_eq!(
+            check_allowlists(&key, Some(Protocol::OpenAI), Some("claude-opus-4-5")),
+            Err(KeyRejection::ModelNotAllowed("claude-opus-4-5".to_string()))
+        );
+        assert!(check_allowlists(&key, None, None).is_ok());
+        assert_eq!(
+            protocol_for_path("/v1/messages/count_tokens"),
+            Some(Protocol::Anthropic)
+        );
+        assert_eq!(protocol_for_path("/metrics"), None);
+        assert_eq!(mask_key("sk-ag-0123456789abcdef"), "sk-ag-…cdef");
+    }
+
+    #[test]
+    fn test_rate_limit_and_budget() {
+        let tracker = ClientKeyTracker::default();
+        let key = client_key();
+        assert!(tracker.admit(&key).is_ok());
+        assert!(tracker.admit(&key).is_ok());
+        assert!(matches!(
+            tracker.admit(&key),
+            Err(KeyRejection::RateLimited { .. })
+        ));
+
+        let unlimited_rate = ClientApiKey {
+            requests_per_minute: None,
+            ..client_key()
+        };
+        tracker.record_tokens("k1", 150);
+        assert_eq!(
+            tracker.admit(&unlimited_rate),
+            Err(KeyRejection::BudgetExhausted)
+        );
+
+        let usage = tracker.usage("k1");
+        assert_eq!(usage.requests_today, 2);
+        assert_eq!(usage.tokens_today, 150);
+    }
+}
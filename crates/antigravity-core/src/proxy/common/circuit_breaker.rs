@@ -10,6 +10,7 @@
 //! - Half-Open: Testing if account has recovered
 
 use parking_lot::RwLock;
+use serde::Serialize;
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{Duration, Instant};
@@ -37,7 +38,8 @@ impl Default for CircuitBreakerConfig {
 }
 
 /// State of the circuit breaker
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum CircuitState {
     /// Normal operation - requests pass through
     Closed,
@@ -312,6 +314,31 @@ impl CircuitBreakerManager {
             .map_or(CircuitState::Closed, |c| c.state)
     }
 
+    /// Get a point-in-time view of an account's circuit (for the admin API)
+    pub fn snapshot(&self, account_id: &str) -> CircuitSnapshot {
+        let circuits = self.circuits.read();
+        let Some(circuit) = circuits.get(account_id) else {
+            return CircuitSnapshot::default();
+        };
+
+        let open_remaining_seconds = match (circuit.state, circuit.opened_at) {
+            (CircuitState::Open, Some(opened_at)) => Some(
+                self.config
+                    .open_duration
+                    .saturating_sub(opened_at.elapsed())
+                    .as_secs(),
+            ),
+            _ => None,
+        };
+
+        CircuitSnapshot {
+            state: circuit.state,
+            consecutive_failures: circuit.consecutive_failures,
+            last_failure_reason: circuit.last_failure_reason.clone(),
+            open_remaining_seconds,
+        }
+    }
+
     /// Get total number of circuit trips (for monitoring)
     pub fn total_trips(&self) -> u64 {
         self.total_trips.load(Ordering::Relaxed)
@@ -367,8 +394,29 @@ impl CircuitBreakerManager {
     }
 }
 
+/// Point-in-time circuit state for a single account
+#[derive(Debug, Clone, Serialize)]
+pub struct CircuitSnapshot {
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    pub last_failure_reason: Option<String>,
+    /// Seconds until the circuit moves to half-open (only while open)
+    pub open_remaining_seconds: Option<u64>,
+}
+
+impl Default for CircuitSnapshot {
+    fn default() -> Self {
+        Self {
+            state: CircuitState::Closed,
+            consecutive_failures: 0,
+            last_failure_reason: None,
+            open_remaining_seconds: None,
+        }
+    }
+}
+
 /// Summary of circuit breaker states across all accounts
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct CircuitBreakerSummary {
     pub closed: usize,
     pub open: usize,
@@ -0,0 +1,547 @@
+// 远程图片 / 文档下载与 /v1/files 引用解析
+// Gemini v1internal 无法直接读取任意 URL，Claude `url` 来源与 OpenAI http(s) `image_url`
+// 在映射前经由上游代理下载并转为 base64 / data URL；`file` 来源从本地文件存储读取。
+// 下载或读取失败时由 handler 返回 400。
+
+use base64::Engine as _;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tokio::time::Duration;
+
+use crate::proxy::mappers::claude::models::{ClaudeRequest, ContentBlock, MessageContent};
+use crate::proxy::mappers::openai::models::{OpenAIContent, OpenAIContentBlock, OpenAIRequest};
+use antigravity_shared::utils::http::UpstreamProxyConfig;
+
+/// 单个文件的大小上限 (与 Gemini inlineData 上限一致)
+pub const MAX_MEDIA_BYTES: usize = 20 * 1024 * 1024;
+const FETCH_TIMEOUT_SECS: u64 = 30;
+
+pub const IMAGE_MIME_TYPES: &[&str] = &[
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+    "image/heic",
+    "image/heif",
+];
+pub const DOCUMENT_MIME_TYPES: &[&str] = &["application/pdf", "text/plain"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchedMedia {
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+impl FetchedMedia {
+    pub fn to_base64(&self) -> String {
+        base64::engine::general_purpose::STANDARD.encode(&self.data)
+    }
+}
+
+const MAX_REDIRECTS: usize = 5;
+
+/// 直连时使用的 DNS 解析器：域名解析到内网地址时拒绝连接
+///
+/// 在建立连接时校验实际使用的地址，首个请求与重定向都会经过这里，DNS rebinding 也无法绕过
+struct PublicOnlyResolver;
+
+impl reqwest::dns::Resolve for PublicOnlyResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        Box::pin(async move {
+            let addrs = resolve_public(name.as_str()).await?;
+            let addrs: reqwest::dns::Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// 解析域名，任一地址为内网地址时拒绝
+async fn resolve_public(host: &str) -> Result<Vec<SocketAddr>, String> {
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, 0))
+        .await
+        .map_err(|e| format!("Failed to resolve media host {}: {}", host, e))?
+        .collect();
+    if let Some(addr) = addrs.iter().find(|addr| is_private_ip(addr.ip())) {
+        return Err(format!(
+            "Media URL host {} resolves to a non-public address {}",
+            host,
+            addr.ip()
+        ));
+    }
+    Ok(addrs)
+}
+
+fn build_client(upstream_proxy: &UpstreamProxyConfig) -> Result<reqwest::Client, String> {
+    // 重定向在 fetch_media 中逐跳校验后手动跟随
+    let mut builder = reqwest::Client::builder()
+        .timeout(Duration::from_secs(FETCH_TIMEOUT_SECS))
+        .redirect(reqwest::redirect::Policy::none());
+
+    if uses_proxy(upstream_proxy) {
+        let proxy = reqwest::Proxy::all(&upstream_proxy.url)
+            .map_err(|e| format!("Invalid upstream proxy url: {}", e))?;
+        builder = builder.proxy(proxy);
+    } else {
+        builder = builder.dns_resolver(Arc::new(PublicOnlyResolver));
+    }
+
+    builder
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {}", e))
+}
+
+/// reqwest 的错误信息不含底层原因 (如解析器拒绝)，逐级拼接
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(e) = source {
+        message.push_str(": ");
+        message.push_str(&e.to_string());
+        source = e.source();
+    }
+    message
+}
+
+fn uses_proxy(upstream_proxy: &UpstreamProxyConfig) -> bool {
+    upstream_proxy.enabled && !upstream_proxy.url.is_empty()
+}
+
+/// 仅允许公网 http(s) 地址，拒绝本机与内网字面量地址 (域名在连接前另行解析校验)
+fn validate_url(raw: &str) -> Result<url::Url, String> {
+    let url = url::Url::parse(raw).map_err(|e| format!("Invalid media URL '{}': {}", raw, e))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("Unsupported media URL scheme '{}'", url.scheme()));
+    }
+    let private = match url.host() {
+        Some(url::Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            domain == "localhost" || domain.ends_with(".localhost")
+        }
+        Some(url::Host::Ipv4(ip)) => is_private_ip(IpAddr::V4(ip)),
+        Some(url::Host::Ipv6(ip)) => is_private_ip(IpAddr::V6(ip)),
+        None => true,
+    };
+    if private {
+        return Err(format!("Media URL host is not allowed: {}", raw));
+    }
+    Ok(url)
+}
+
+fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || (a == 100 && (b & 0xc0) == 64) // 100.64.0.0/10 carrier-grade NAT
+                || (a == 198 && (b & 0xfe) == 18) // 198.18.0.0/15 benchmarking
+        }
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            ip.is_loopback()
+                || ip.is_unspecified()
+                || (segments[0] & 0xfe00) == 0xfc00 // unique local
+                || (segments[0] & 0xffc0) == 0xfe80 // link local
+                || segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] // 64:ff9b::/96 NAT64
+                || segments[..2] == [0x2001, 0xdb8] // 2001:db8::/32 documentation
+                || ip.to_ipv4_mapped().is_some_and(|v4| is_private_ip(IpAddr::V4(v4)))
+        }
+    }
+}
+
+/// Content-Type 缺失或为通用类型时按文件头识别
+fn sniff_mime(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if data.starts_with(b"\xff\xd8\xff") {
+        Some("image/jpeg")
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else if data.starts_with(b"%PDF-") {
+        Some("application/pdf")
+    } else {
+        None
+    }
+}
+
+pub(crate) fn resolve_mime(content_type: Option<&str>, data: &[u8]) -> Option<String> {
+    let declared = content_type
+        .and_then(|ct| ct.split(';').next())
+        .map(|ct| ct.trim().to_ascii_lowercase())
+        .filter(|ct| !ct.is_empty() && ct != "application/octet-stream");
+    declared.or_else(|| sniff_mime(data).map(String::from))
+}
+
+/// 下载 URL，校验大小与 MIME 类型
+pub async fn fetch_media(
+    raw_url: &str,
+    upstream_proxy: &UpstreamProxyConfig,
+    allowed_mime_types: &[&str],
+) -> Result<FetchedMedia, String> {
+    let mut url = validate_url(raw_url)?;
+    let client = build_client(upstream_proxy)?;
+    let proxied = uses_proxy(upstream_proxy);
+    let mut redirects = 0;
+    let mut response = loop {
+        // 经代理时由代理解析域名，这里先在本地解析校验
+        if proxied {
+            if let Some(url::Host::Domain(domain)) = url.host() {
+                resolve_public(domain).await?;
+            }
+        }
+        let response = client
+            .get(url.clone())
+            .send()
+            .await
+            .map_err(|e| format!("Failed to fetch {}: {}", raw_url, error_chain(&e)))?;
+        if !response.status().is_redirection() {
+            break response;
+        }
+        let Some(location) = response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|v| v.to_str().ok())
+        else {
+            break response;
+        };
+        redirects += 1;
+        if redirects > MAX_REDIRECTS {
+            return Err(format!("Failed to fetch {}: too many redirects", raw_url));
+        }
+        let next = url
+            .join(location)
+            .map_err(|e| format!("Invalid redirect from {}: {}", raw_url, e))?;
+        url = validate_url(next.as_str())?;
+    };
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "Failed to fetch {}: HTTP {}",
+            raw_url,
+            response.status().as_u16()
+        ));
+    }
+    if response
+        .content_length()
+        .is_some_and(|len| len as usize > MAX_MEDIA_BYTES)
+    {
+        return Err(format!(
+            "Media at {} exceeds the {} MB limit",
+            raw_url,
+            MAX_MEDIA_BYTES / 1024 / 1024
+        ));
+    }
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+
+    let mut data = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", raw_url, e))?
+    {
+        if data.len() + chunk.len() > MAX_MEDIA_BYTES {
+            return Err(format!(
+                "Media at {} exceeds the {} MB limit",
+                raw_url,
+                MAX_MEDIA_BYTES / 1024 / 1024
+            ));
+        }
+        data.extend_from_slice(&chunk);
+    }
+
+    let mime_type = resolve_mime(content_type.as_deref(), &data)
+        .ok_or_else(|| format!("Could not determine media type of {}", raw_url))?;
+    if !allowed_mime_types.contains(&mime_type.as_str()) {
+        return Err(format!(
+            "Unsupported media type '{}' at {} (allowed: {})",
+            mime_type,
+            raw_url,
+            allowed_mime_types.join(", ")
+        ));
+    }
+
+    tracing::debug!(
+        "[MediaFetch] Fetched {} ({}, {} bytes)",
+        raw_url,
+        mime_type,
+        data.len()
+    );
+    Ok(FetchedMedia { mime_type, data })
+}
+
+/// data URL (`data:<mime>;base64,...`) 中声明的 MIME 类型，缺失或格式不对时返回 None
+pub(crate) fn data_url_mime(data_url: &str) -> Option<&str> {
+    let (meta, _) = data_url.strip_prefix("data:")?.split_once(',')?;
+    let mime = meta.split(';').next()?.trim();
+    mime.contains('/').then_some(mime)
+}
+
+/// 读取 `client_key` 通过 /v1/files 上传的文件，校验大小与 MIME 类型
+pub fn load_file(
+    file_id: &str,
+    client_key: Option<&str>,
+    allowed_mime_types: &[&str],
+) -> Result<FetchedMedia, String> {
+    let (file, data) = crate::modules::file_store::default_store()?
+        .read(file_id)?
+        .filter(|(file, _)| file.client_key.as_deref() == client_key)
+        .ok_or_else(|| format!("File '{}' not found or expired", file_id))?;
+    if data.len() > MAX_MEDIA_BYTES {
+        return Err(format!(
+            "File '{}' exceeds the {} MB inline limit",
+            file_id,
+            MAX_MEDIA_BYTES / 1024 / 1024
+        ));
+    }
+    if !allowed_mime_types.contains(&file.mime_type.as_str()) {
+        return Err(format!(
+            "Unsupported media type '{}' for file '{}' (allowed: {})",
+            file.mime_type,
+            file_id,
+            allowed_mime_types.join(", ")
+        ));
+    }
+    Ok(FetchedMedia {
+        mime_type: file.mime_type,
+        data,
+    })
+}
+
+/// `url` / `file` 来源对应的内容，其他来源返回 None
+async fn load_source(
+    kind: &str,
+    source_type: &str,
+    url: Option<&str>,
+    file_id: Option<&str>,
+    upstream_proxy: &UpstreamProxyConfig,
+    client_key: Option<&str>,
+    allowed_mime_types: &[&str],
+) -> Result<Option<FetchedMedia>, String> {
+    match source_type {
+        "url" => {
+            let url = url.ok_or_else(|| format!("{}.source.url: Field required", kind))?;
+            fetch_media(url, upstream_proxy, allowed_mime_types)
+                .await
+                .map(Some)
+        }
+        "file" => {
+            let file_id =
+                file_id.ok_or_else(|| format!("{}.source.file_id: Field required", kind))?;
+            load_file(file_id, client_key, allowed_mime_types).map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// 将 Claude 请求中 `url` / `file` 来源的图片与文档替换为 base64 (纯文本文档转为 `text` 来源)
+///
+/// `file` 来源只能引用 `client_key` 上传的文件。
+pub async fn resolve_claude_media(
+    request: &mut ClaudeRequest,
+    upstream_proxy: &UpstreamProxyConfig,
+    client_key: Option<&str>,
+) -> Result<(), String> {
+    for message in &mut request.messages {
+        let MessageContent::Array(blocks) = &mut message.content else {
+            continue;
+        };
+        for block in blocks {
+            match block {
+                ContentBlock::Image { source, .. } => {
+                    let media = load_source(
+                        "image",
+                        &source.source_type,
+                        source.url.as_deref(),
+                        source.file_id.as_deref(),
+                        upstream_proxy,
+                        client_key,
+                        IMAGE_MIME_TYPES,
+                    )
+                    .await?;
+                    if let Some(media) = media {
+                        source.data = media.to_base64();
+                        source.media_type = media.mime_type;
+                        source.source_type = "base64".to_string();
+                        source.url = None;
+                        source.file_id = None;
+                    } else if source.source_type != "base64" {
+                        return Err(format!(
+                            "Unsupported image source type '{}'",
+                            source.source_type
+                        ));
+                    }
+                }
+                ContentBlock::Document { source, .. } => {
+                    let media = load_source(
+                        "document",
+                        &source.source_type,
+                        source.url.as_deref(),
+                        source.file_id.as_deref(),
+                        upstream_proxy,
+                        client_key,
+                        DOCUMENT_MIME_TYPES,
+                    )
+                    .await?;
+                    if let Some(media) = media {
+                        if media.mime_type == "text/plain" {
+                            source.data = String::from_utf8_lossy(&media.data).into_owned();
+                            source.source_type = "text".to_string();
+                        } else {
+                            source.data = media.to_base64();
+                            source.source_type = "base64".to_string();
+                        }
+                        source.media_type = media.mime_type;
+                        source.url = None;
+                        source.file_id = None;
+                    } else if !matches!(source.source_type.as_str(), "base64" | "text") {
+                        return Err(format!(
+                            "Unsupported document source type '{}'",
+                            source.source_type
+                        ));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 将 OpenAI 请求中的 http(s) `image_url` 与 `file.file_id` 替换为 data URL
+///
+/// `file_id` 只能引用 `client_key` 上传的文件。
+pub async fn resolve_openai_media(
+    request: &mut OpenAIRequest,
+    upstream_proxy: &UpstreamProxyConfig,
+    client_key: Option<&str>,
+) -> Result<(), String> {
+    for message in &mut request.messages {
+        let Some(OpenAIContent::Array(blocks)) = &mut message.content else {
+            continue;
+        };
+        for block in blocks {
+            match block {
+                OpenAIContentBlock::ImageUrl { image_url }
+                    if image_url.url.starts_with("http://")
+                        || image_url.url.starts_with("https://") =>
+                {
+                    let media =
+                        fetch_media(&image_url.url, upstream_proxy, IMAGE_MIME_TYPES).await?;
+                    image_url.url =
+                        format!("data:{};base64,{}", media.mime_type, media.to_base64());
+                }
+                OpenAIContentBlock::File { file }
+                    if file.file_data.as_deref().and_then(data_url_mime).is_none() =>
+                {
+                    // 缺少 file_data 或其中没有 MIME 类型时，以存储文件的内容与类型为准
+                    let file_id = file.file_id.as_deref().ok_or(if file.file_data.is_some() {
+                        "file.file_data must be a data URL with a MIME type"
+                    } else {
+                        "file.file_id or file.file_data is required"
+                    })?;
+                    let allowed: Vec<&str> = IMAGE_MIME_TYPES
+                        .iter()
+                        .chain(DOCUMENT_MIME_TYPES)
+                        .copied()
+                        .collect();
+                    let media = load_file(file_id, client_key, &allowed)?;
+                    file.file_data = Some(format!(
+                        "data:{};base64,{}",
+                        media.mime_type,
+                        media.to_base64()
+                    ));
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_url_rejects_local_hosts() {
+        assert!(validate_url("https://example.com/a.png").is_ok());
+        assert!(validate_url("http://127.0.0.1/a.png").is_err());
+        assert!(validate_url("http://10.0.0.8/a.png").is_err());
+        assert!(validate_url("http://[::1]/a.png").is_err());
+        assert!(validate_url("http://localhost:8045/a.png").is_err());
+        assert!(validate_url("file:///etc/passwd").is_err());
+        assert!(validate_url("http://100.100.100.200/latest/meta-data").is_err());
+        assert!(validate_url("http://198.18.0.1/a.png").is_err());
+        assert!(validate_url("http://[64:ff9b::a00:1]/a.png").is_err());
+        assert!(validate_url("http://[2001:db8::1]/a.png").is_err());
+        assert!(validate_url("http://100.128.0.1/a.png").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_hostnames_resolving_to_private_addresses_rejected() {
+        assert!(resolve_public("localhost").await.is_err());
+        assert!(validate_url("http://LOCALHOST./a.png").is_err());
+
+        // 绕过字面量检查的域名在连接时由解析器拒绝
+        let client = build_client(&UpstreamProxyConfig::default()).unwrap();
+        let err = client
+            .get("http://localhost/a.png")
+            .send()
+            .await
+            .unwrap_err();
+        assert!(error_chain(&err).contains("non-public address"), "{}", err);
+    }
+
+    #[test]
+    fn test_resolve_mime() {
+        assert_eq!(
+            resolve_mime(Some("image/PNG; charset=binary"), b"").as_deref(),
+            Some("image/png")
+        );
+        assert_eq!(
+            resolve_mime(Some("application/octet-stream"), b"%PDF-1.7").as_deref(),
+            Some("application/pdf")
+        );
+        assert_eq!(
+            resolve_mime(None, b"\x89PNG\r\n\x1a\n....").as_deref(),
+            Some("image/png")
+        );
+        assert_eq!(resolve_mime(None, b"hello"), None);
+    }
+
+    #[test]
+    fn test_data_url_mime() {
+        assert_eq!(
+            data_url_mime("data:application/pdf;base64,JVBE"),
+            Some("application/pdf")
+        );
+        assert_eq!(data_url_mime("data:text/plain,hello"), Some("text/plain"));
+        assert_eq!(data_url_mime("data:;base64,JVBE"), None);
+        assert_eq!(data_url_mime("data:base64,JVBE"), None);
+        assert_eq!(data_url_mime("JVBERi0xLjc="), None);
+    }
+
+    #[tokio::test]
+    async fn test_file_data_without_mime_rejected_without_file_id() {
+        let mut request: OpenAIRequest = serde_json::from_value(serde_json::json!({
+            "model": "gemini-2.5-flash",
+            "messages": [{"role": "user", "content": [
+                {"type": "file", "file": {"file_data": "data:;base64,JVBE"}}
+            ]}]
+        }))
+        .unwrap();
+        let err = resolve_openai_media(&mut request, &UpstreamProxyConfig::default(), None)
+            .await
+            .unwrap_err();
+        assert!(err.contains("MIME type"), "{}", err);
+    }
+}
@@ -2,5 +2,7 @@
 
 pub mod circuit_breaker;
 pub mod json_schema;
+pub mod media_fetch;
 pub mod model_mapping;
+pub mod token_counter;
 pub mod utils;
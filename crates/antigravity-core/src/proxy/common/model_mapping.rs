@@ -1,5 +1,8 @@
 // 模型名称映射
+use antigravity_shared::proxy::config::{ModelRouteRule, Protocol, RouteMatchKind};
+use dashmap::DashMap;
 use once_cell::sync::Lazy;
+use regex::Regex;
 use std::collections::HashMap;
 
 static CLAUDE_TO_GEMINI: Lazy<HashMap<&'static str, &'static str>> = Lazy::new(|| {
@@ -54,6 +57,17 @@ static CLAUDE_TO_GEMINI: Lazy<HashMap<&'static str, &'static str>> = Lazy::new(|
     m
 });
 
+/// Embedding 模型映射表 (与聊天映射分离，聊天路由不会解析到 embedding 模型)
+static EMBEDDING_MODELS: Lazy<HashMap<&'static str, &'static str>> = Lazy::new(|| {
+    let mut m = HashMap::new();
+    m.insert("text-embedding-3-small", "gemini-embedding-001");
+    m.insert("text-embedding-3-large", "gemini-embedding-001");
+    m.insert("text-embedding-ada-002", "gemini-embedding-001");
+    m.insert("text-embedding-004", "text-embedding-004");
+    m.insert("gemini-embedding-001", "gemini-embedding-001");
+    m
+});
+
 pub fn map_claude_model_to_gemini(input: &str) -> String {
     // 1. Check exact match in map
     if let Some(mapped) = CLAUDE_TO_GEMINI.get(input) {
@@ -71,7 +85,11 @@ pub fn map_claude_model_to_gemini(input: &str) -> String {
 
 /// 获取所有内置支持的模型列表关键字
 pub fn get_supported_models() -> Vec<String> {
-    CLAUDE_TO_GEMINI.keys().map(|s| s.to_string()).collect()
+    CLAUDE_TO_GEMINI
+        .keys()
+        .chain(EMBEDDING_MODELS.keys())
+        .map(|s| s.to_string())
+        .collect()
 }
 
 /// 动态获取所有可用模型列表 (包含内置与用户自定义)
@@ -124,24 +142,172 @@ pub async fn get_all_dynamic_models(
 }
 
 /// 通配符匹配辅助函数
-/// 支持简单的 * 通配符匹配
+/// 支持任意数量的 `*` (匹配任意串) 与 `?` (匹配单个字符)
 ///
 /// # 示例
 /// - `gpt-4*` 匹配 `gpt-4`, `gpt-4-turbo`, `gpt-4-0613` 等
 /// - `claude-3-5-sonnet-*` 匹配所有 3.5 sonnet 版本
 /// - `*-thinking` 匹配所有以 `-thinking` 结尾的模型
-fn wildcard_match(pattern: &str, text: &str) -> bool {
-    if let Some(star_pos) = pattern.find('*') {
-        let prefix = &pattern[..star_pos];
-        let suffix = &pattern[star_pos + 1..];
-        text.starts_with(prefix) && text.ends_with(suffix)
-    } else {
-        pattern == text
+/// - `claude-*-4-*` 匹配 `claude-sonnet-4-5`, `claude-opus-4-1` 等
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    let (mut pi, mut ti) = (0, 0);
+    // 最近一个 `*` 的位置及其当时对应的 text 位置 (用于回溯)
+    let mut star: Option<(usize, usize)> = None;
+
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((star_pi, star_ti)) = star {
+            pi = star_pi + 1;
+            ti = star_ti + 1;
+            star = Some((star_pi, star_ti + 1));
+        } else {
+            return false;
+        }
+    }
+
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+/// 通配符规则的具体程度：字面字符越多越具体
+fn wildcard_specificity(pattern: &str) -> usize {
+    pattern.chars().filter(|c| *c != '*' && *c != '?').count()
+}
+
+/// 在映射表中查找模型：精确匹配优先，其次最具体的通配符规则。返回 (规则, 目标)
+pub fn lookup_mapping<'a>(
+    mapping: &'a std::collections::HashMap<String, String>,
+    model: &str,
+) -> Option<(&'a String, &'a String)> {
+    if let Some(entry) = mapping.get_key_value(model) {
+        return Some(entry);
+    }
+    // HashMap 无序，按具体程度与字典序排序保证结果稳定
+    mapping
+        .iter()
+        .filter(|(pattern, _)| pattern.contains('*') && wildcard_match(pattern, model))
+        .max_by(|(a, _), (b, _)| {
+            wildcard_specificity(a)
+                .cmp(&wildcard_specificity(b))
+                .then_with(|| b.cmp(a))
+        })
+}
+
+/// 正则缓存 (规则热更新后新 pattern 会按需编译，非法正则缓存为 None)
+static REGEX_CACHE: Lazy<DashMap<String, Option<Regex>>> = Lazy::new(DashMap::new);
+
+fn regex_match(pattern: &str, text: &str) -> bool {
+    let entry =
+        REGEX_CACHE
+            .entry(pattern.to_string())
+            .or_insert_with(|| match Regex::new(pattern) {
+                Ok(re) => Some(re),
+                Err(e) => {
+                    tracing::warn!("[Router] 非法正则规则 '{}': {}", pattern, e);
+                    None
+                }
+            });
+    entry.as_ref().is_some_and(|re| re.is_match(text))
+}
+
+/// 判断单条路由规则是否命中
+pub fn rule_matches(rule: &ModelRouteRule, model: &str, protocol: Protocol) -> bool {
+    if !rule.enabled {
+        return false;
+    }
+    if !rule.protocols.is_empty() && !rule.protocols.contains(&protocol) {
+        return false;
+    }
+    match rule.match_kind {
+        RouteMatchKind::Exact => rule.pattern == model,
+        RouteMatchKind::Glob => wildcard_match(&rule.pattern, model),
+        RouteMatchKind::Regex => regex_match(&rule.pattern, model),
+    }
+}
+
+/// 路由解析结果：目标模型 + 回退链 + 命中的规则
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteResolution {
+    /// 依次尝试的模型 (首个为主模型)
+    candidates: Vec<String>,
+    /// 当前正在使用的候选下标
+    current: usize,
+    /// 命中的规则描述 (未命中有序规则时为 None)
+    pub rule: Option<String>,
+}
+
+impl RouteResolution {
+    fn new(target: String, fallbacks: &[String], rule: Option<String>) -> Self {
+        let mut candidates = vec![target];
+        for fallback in fallbacks {
+            if !fallback.is_empty() && !candidates.contains(fallback) {
+                candidates.push(fallback.clone());
+            }
+        }
+        Self {
+            candidates,
+            current: 0,
+            rule,
+        }
+    }
+
+    /// 当前应使用的模型
+    pub fn model(&self) -> &str {
+        &self.candidates[self.current]
+    }
+
+    /// 当前是否为回退模型
+    pub fn is_fallback(&self) -> bool {
+        self.current > 0
+    }
+
+    /// 切换到下一个回退模型，回退链耗尽时返回 false
+    pub fn advance(&mut self, reason: &str) -> bool {
+        if self.current + 1 >= self.candidates.len() {
+            return false;
+        }
+        let previous = self.model().to_string();
+        self.current += 1;
+        tracing::warn!(
+            "[Router] {} 配额在所有账号上耗尽，回退到 {} ({}/{}): {}",
+            previous,
+            self.model(),
+            self.current,
+            self.candidates.len() - 1,
+            reason
+        );
+        true
+    }
+
+    /// 用于 X-Mapped-Model / 监控日志的描述，例如 `gemini-2.5-flash (rule#1 gpt-*, fallback 1)`
+    pub fn label(&self) -> String {
+        let label = match (&self.rule, self.current) {
+            (None, _) => return self.model().to_string(),
+            (Some(rule), 0) => format!("{} ({})", self.model(), rule),
+            (Some(rule), n) => format!("{} ({}, fallback {})", self.model(), rule, n),
+        };
+        // HTTP 头只允许可见 ASCII
+        label
+            .chars()
+            .map(|c| {
+                if c.is_ascii_graphic() || c == ' ' {
+                    c
+                } else {
+                    '?'
+                }
+            })
+            .collect()
     }
 }
 
 /// 核心模型路由解析引擎
-/// 优先级：精确匹配 > 通配符匹配 > 系统默认映射
+/// 优先级：精确匹配 > 通配符匹配 (越具体越优先) > 系统默认映射
 ///
 /// # 参数
 /// - `original_model`: 原始模型名称
@@ -153,23 +319,19 @@ pub fn resolve_model_route(
     original_model: &str,
     custom_mapping: &std::collections::HashMap<String, String>,
 ) -> String {
-    // 1. 精确匹配 (最高优先级)
-    if let Some(target) = custom_mapping.get(original_model) {
-        tracing::info!("[Router] 精确映射: {} -> {}", original_model, target);
-        return target.clone();
-    }
-
-    // 2. 通配符匹配
-    for (pattern, target) in custom_mapping.iter() {
-        if pattern.contains('*') && wildcard_match(pattern, original_model) {
+    // 1. 精确匹配 (最高优先级) / 2. 通配符匹配
+    if let Some((pattern, target)) = lookup_mapping(custom_mapping, original_model) {
+        if pattern == original_model {
+            tracing::info!("[Router] 精确映射: {} -> {}", original_model, target);
+        } else {
             tracing::info!(
                 "[Router] 通配符映射: {} -> {} (规则: {})",
                 original_model,
                 target,
                 pattern
             );
-            return target.clone();
         }
+        return target.clone();
     }
 
     // 3. 系统默认映射
@@ -180,6 +342,60 @@ pub fn resolve_model_route(
     result
 }
 
+/// 带有序规则的路由解析
+///
+/// 有序规则按配置顺序匹配，首条命中生效；均未命中时回退到 `resolve_model_route`
+pub fn resolve_route_with_rules(
+    original_model: &str,
+    protocol: Protocol,
+    rules: &[ModelRouteRule],
+    custom_mapping: &std::collections::HashMap<String, String>,
+) -> RouteResolution {
+    for (idx, rule) in rules.iter().enumerate() {
+        if rule_matches(rule, original_model, protocol) {
+            let rule_desc = match &rule.name {
+                Some(name) if !name.is_empty() => format!("rule#{} {}", idx + 1, name),
+                _ => format!("rule#{} {}", idx + 1, rule.pattern),
+            };
+            tracing::info!(
+                "[Router] 规则映射: {} -> {} ({}, 回退链: {:?})",
+                original_model,
+                rule.target,
+                rule_desc,
+                rule.fallbacks
+            );
+            return RouteResolution::new(rule.target.clone(), &rule.fallbacks, Some(rule_desc));
+        }
+    }
+
+    RouteResolution::new(
+        resolve_model_route(original_model, custom_mapping),
+        &[],
+        None,
+    )
+}
+
+/// 解析 embedding 请求的上游模型，不是 embedding 模型时返回 None
+///
+/// 有序规则 / 自定义映射的目标若为 embedding 模型 (或其别名) 则优先使用，
+/// 否则按内置 embedding 映射表解析原始模型名 (聊天用的通配规则不影响 embedding)。
+pub fn resolve_embedding_model(
+    original_model: &str,
+    protocol: Protocol,
+    rules: &[ModelRouteRule],
+    custom_mapping: &std::collections::HashMap<String, String>,
+) -> Option<String> {
+    let routed = rules
+        .iter()
+        .find(|rule| rule_matches(rule, original_model, protocol))
+        .map(|rule| rule.target.as_str())
+        .or_else(|| lookup_mapping(custom_mapping, original_model).map(|(_, t)| t.as_str()));
+    routed
+        .and_then(|target| EMBEDDING_MODELS.get(target))
+        .or_else(|| EMBEDDING_MODELS.get(original_model))
+        .map(|model| model.to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -203,5 +419,155 @@ mod tests {
             map_claude_model_to_gemini("unknown-model"),
             "claude-sonnet-4-5"
         );
+        // 聊天映射不会解析到 embedding 模型
+        assert_eq!(
+            map_claude_model_to_gemini("text-embedding-3-small"),
+            "claude-sonnet-4-5"
+        );
+    }
+
+    #[test]
+    fn test_resolve_embedding_model() {
+        let mut mapping = std::collections::HashMap::new();
+        mapping.insert(
+            "my-embedder".to_string(),
+            "text-embedding-3-small".to_string(),
+        );
+        let rules = vec![rule(RouteMatchKind::Glob, "*", "gemini-2.5-flash")];
+
+        let resolve = |model: &str| resolve_embedding_model(model, Protocol::OpenAI, &[], &mapping);
+        assert_eq!(
+            resolve("text-embedding-3-small").as_deref(),
+            Some("gemini-embedding-001")
+        );
+        assert_eq!(
+            resolve("my-embedder").as_deref(),
+            Some("gemini-embedding-001")
+        );
+        assert_eq!(resolve("gemini-2.5-flash"), None);
+
+        // 指向聊天模型的通配规则不影响 embedding 别名
+        assert_eq!(
+            resolve_embedding_model("text-embedding-004", Protocol::OpenAI, &rules, &mapping)
+                .as_deref(),
+            Some("text-embedding-004")
+        );
+    }
+
+    fn rule(kind: RouteMatchKind, pattern: &str, target: &str) -> ModelRouteRule {
+        ModelRouteRule {
+            name: None,
+            enabled: true,
+            match_kind: kind,
+            pattern: pattern.to_string(),
+            protocols: Vec::new(),
+            target: target.to_string(),
+            fallbacks: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_wildcard_match_multiple_stars() {
+        assert!(wildcard_match("gpt-4*", "gpt-4-turbo"));
+        assert!(wildcard_match("*-thinking", "claude-opus-4-5-thinking"));
+        assert!(wildcard_match("claude-*-4-*", "claude-sonnet-4-5"));
+        assert!(wildcard_match("gemini-?-pro", "gemini-3-pro"));
+        assert!(!wildcard_match("claude-*-4-*", "claude-3-5-sonnet"));
+        assert!(!wildcard_match("gpt-4", "gpt-4o"));
+    }
+
+    #[test]
+    fn test_wildcard_mapping_is_deterministic() {
+        let mut mapping = HashMap::new();
+        mapping.insert("gpt-*".to_string(), "gemini-2.5-flash".to_string());
+        mapping.insert("gpt-4o*".to_string(), "gemini-2.5-pro".to_string());
+        mapping.insert("*".to_string(), "gemini-3-flash".to_string());
+
+        // 最具体的规则胜出，与 HashMap 迭代顺序无关
+        for _ in 0..20 {
+            assert_eq!(
+                resolve_model_route("gpt-4o-mini", &mapping),
+                "gemini-2.5-pro"
+            );
+            assert_eq!(
+                resolve_model_route("gpt-3.5-turbo", &mapping),
+                "gemini-2.5-flash"
+            );
+        }
+    }
+
+    #[test]
+    fn test_ordered_rules_first_match_wins() {
+        let rules = vec![
+            rule(RouteMatchKind::Exact, "gpt-4o", "gemini-3-pro-high"),
+            rule(
+                RouteMatchKind::Regex,
+                "^gpt-4o(-mini)?$",
+                "gemini-2.5-flash",
+            ),
+            rule(RouteMatchKind::Glob, "gpt-*", "gemini-2.5-pro"),
+        ];
+        let mapping = HashMap::new();
+
+        let r = resolve_route_with_rules("gpt-4o", Protocol::OpenAI, &rules, &mapping);
+        assert_eq!(r.model(), "gemini-3-pro-high");
+        assert_eq!(r.rule.as_deref(), Some("rule#1 gpt-4o"));
+
+        let r = resolve_route_with_rules("gpt-4o-mini", Protocol::OpenAI, &rules, &mapping);
+        assert_eq!(r.model(), "gemini-2.5-flash");
+
+        let r = resolve_route_with_rules("gpt-3.5-turbo", Protocol::OpenAI, &rules, &mapping);
+        assert_eq!(r.model(), "gemini-2.5-pro");
+
+        // 未命中规则时回退到 custom_mapping / 系统默认映射
+        let r = resolve_route_with_rules("claude-opus-4", Protocol::Anthropic, &rules, &mapping);
+        assert_eq!(r.model(), "claude-opus-4-5-thinking");
+        assert!(r.rule.is_none());
+    }
+
+    #[test]
+    fn test_rule_protocol_condition_and_disabled() {
+        let mut anthropic_only = rule(RouteMatchKind::Glob, "claude-*", "gemini-3-pro-high");
+        anthropic_only.protocols = vec![Protocol::Anthropic];
+        let mut disabled = rule(RouteMatchKind::Glob, "*", "gemini-2.5-flash");
+        disabled.enabled = false;
+        let rules = vec![anthropic_only, disabled];
+        let mapping = HashMap::new();
+
+        let r = resolve_route_with_rules("claude-opus-4", Protocol::Anthropic, &rules, &mapping);
+        assert_eq!(r.model(), "gemini-3-pro-high");
+
+        let r = resolve_route_with_rules("claude-opus-4", Protocol::OpenAI, &rules, &mapping);
+        assert_eq!(r.model(), "claude-opus-4-5-thinking");
+    }
+
+    #[test]
+    fn test_fallback_chain_and_label() {
+        let mut r = rule(
+            RouteMatchKind::Glob,
+            "claude-opus-*",
+            "claude-opus-4-5-thinking",
+        );
+        r.name = Some("opus".to_string());
+        r.fallbacks = vec![
+            "claude-sonnet-4-5-thinking".to_string(),
+            "claude-opus-4-5-thinking".to_string(), // 与主模型重复，忽略
+            "gemini-3-pro-high".to_string(),
+        ];
+        let mut route =
+            resolve_route_with_rules("claude-opus-4", Protocol::Anthropic, &[r], &HashMap::new());
+
+        assert_eq!(route.label(), "claude-opus-4-5-thinking (rule#1 opus)");
+        assert!(route.advance("quota"));
+        assert!(route.is_fallback());
+        assert_eq!(route.model(), "claude-sonnet-4-5-thinking");
+        assert_eq!(
+            route.label(),
+            "claude-sonnet-4-5-thinking (rule#1 opus, fallback 1)"
+        );
+        assert!(route.advance("quota"));
+        assert_eq!(route.model(), "gemini-3-pro-high");
+        assert!(!route.advance("quota"));
+        assert_eq!(route.model(), "gemini-3-pro-high");
     }
 }
@@ -0,0 +1,386 @@
+// 本地 Token 估算 (用于 count_tokens / countTokens 端点)
+//
+// Claude / Gemini 的分词器均未公开，这里使用 cl100k_base BPE 作为近似，
+// 图片按 Anthropic 公布的 `width * height / 750` 公式估算，PDF 按页估算。
+
+use base64::Engine as _;
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use tiktoken_rs::CoreBPE;
+
+use crate::proxy::mappers::claude::models::{
+    ClaudeRequest, ContentBlock, DocumentSource, ImageSource, MessageContent, SystemPrompt,
+};
+
+static BPE: Lazy<Option<CoreBPE>> = Lazy::new(|| match tiktoken_rs::cl100k_base() {
+    Ok(bpe) => Some(bpe),
+    Err(e) => {
+        tracing::warn!(
+            "[TokenCounter] Failed to load cl100k_base, using char heuristic: {}",
+            e
+        );
+        None
+    }
+});
+
+/// 每条消息的角色/分隔符开销
+const MESSAGE_OVERHEAD: u32 = 4;
+/// 每个工具定义的结构开销
+const TOOL_OVERHEAD: u32 = 8;
+/// 无法解析尺寸时的图片估算值 (≈ 1.15MP 上限)
+const IMAGE_FALLBACK_TOKENS: u32 = 1600;
+/// 图片长边超过该值会被上游缩放
+const IMAGE_MAX_EDGE: f64 = 1568.0;
+/// 每页 PDF 的估算值 (文本 + 页面图像)
+const PDF_PAGE_TOKENS: u32 = 1500;
+
+/// 统计纯文本 token 数
+pub fn count_text(text: &str) -> u32 {
+    if text.is_empty() {
+        return 0;
+    }
+    match BPE.as_ref() {
+        Some(bpe) => bpe.encode_ordinary(text).len() as u32,
+        None => (text.chars().count() as u32).div_ceil(4),
+    }
+}
+
+/// 统计任意 JSON 值 (工具参数、tool_result 等) 序列化后的 token 数
+fn count_json(value: &Value) -> u32 {
+    match value {
+        Value::Null => 0,
+        Value::String(s) => count_text(s),
+        other => count_text(&other.to_string()),
+    }
+}
+
+/// 根据 base64 图片尺寸估算 token 数
+pub fn estimate_image_tokens(base64_data: &str) -> u32 {
+    let dims = base64::engine::general_purpose::STANDARD
+        .decode(base64_data.trim())
+        .ok()
+        .and_then(|bytes| {
+            image::ImageReader::new(std::io::Cursor::new(bytes))
+                .with_guessed_format()
+                .ok()?
+                .into_dimensions()
+                .ok()
+        });
+
+    let Some((width, height)) = dims else {
+        return IMAGE_FALLBACK_TOKENS;
+    };
+
+    let (mut w, mut h) = (width as f64, height as f64);
+    let long_edge = w.max(h);
+    if long_edge > IMAGE_MAX_EDGE {
+        let scale = IMAGE_MAX_EDGE / long_edge;
+        w *= scale;
+        h *= scale;
+    }
+
+    ((w * h / 750.0).ceil() as u32).clamp(1, IMAGE_FALLBACK_TOKENS)
+}
+
+/// 估算 PDF 页数 (统计 `/Type /Page` 对象，排除 `/Pages`)
+fn count_pdf_pages(bytes: &[u8]) -> u32 {
+    let needle = b"/Type";
+    let mut pages = 0;
+    let mut i = 0;
+    while i + needle.len() <= bytes.len() {
+        if &bytes[i..i + needle.len()] == needle {
+            let mut j = i + needle.len();
+            while j < bytes.len() && bytes[j].is_ascii_whitespace() {
+                j += 1;
+            }
+            if bytes[j..].starts_with(b"/Page") && !bytes[j..].starts_with(b"/Pages") {
+                pages += 1;
+            }
+            i = j;
+        } else {
+            i += 1;
+        }
+    }
+    pages
+}
+
+/// 估算文档 (PDF / 纯文本) token 数
+fn estimate_document(source_type: &str, media_type: &str, data: &str) -> u32 {
+    if source_type == "text" || media_type.starts_with("text/") {
+        let text = if source_type == "base64" {
+            base64::engine::general_purpose::STANDARD
+                .decode(data.trim())
+                .map(|b| String::from_utf8_lossy(&b).into_owned())
+                .unwrap_or_default()
+        } else {
+            data.to_string()
+        };
+        return count_text(&text);
+    }
+
+    let pages = base64::engine::general_purpose::STANDARD
+        .decode(data.trim())
+        .map(|bytes| count_pdf_pages(&bytes))
+        .unwrap_or(0);
+    pages.max(1) * PDF_PAGE_TOKENS
+}
+
+fn estimate_claude_image(source: &ImageSource) -> u32 {
+    if source.source_type == "base64" {
+        estimate_image_tokens(&source.data)
+    } else {
+        IMAGE_FALLBACK_TOKENS
+    }
+}
+
+fn estimate_claude_document(source: &DocumentSource) -> u32 {
+    estimate_document(&source.source_type, &source.media_type, &source.data)
+}
+
+/// tool_result.content 可以是字符串或内容块数组 (含图片)
+fn count_tool_result_content(content: &Value) -> u32 {
+    match content {
+        Value::Array(items) => items
+            .iter()
+            .map(|item| match item.get("type").and_then(|t| t.as_str()) {
+                Some("text") => count_json(item.get("text").unwrap_or(&Value::Null)),
+                Some("image") => {
+                    match serde_json::from_value::<ImageSource>(item["source"].clone()) {
+                        Ok(source) => estimate_claude_image(&source),
+                        Err(_) => IMAGE_FALLBACK_TOKENS,
+                    }
+                }
+                _ => count_json(item),
+            })
+            .sum(),
+        other => count_json(other),
+    }
+}
+
+fn count_claude_block(block: &ContentBlock) -> u32 {
+    match block {
+        ContentBlock::Text { text, .. } => count_text(text),
+        ContentBlock::Thinking { thinking, .. } => count_text(thinking),
+        ContentBlock::RedactedThinking { data } => count_text(data),
+        ContentBlock::Image { source, .. } => estimate_claude_image(source),
+        ContentBlock::Document { source, .. } => estimate_claude_document(source),
+        ContentBlock::ToolUse { name, input, .. }
+        | ContentBlock::ServerToolUse { name, input, .. } => count_text(name) + count_json(input),
+        ContentBlock::ToolResult { content, .. } => count_tool_result_content(content),
+        ContentBlock::WebSearchToolResult { content, .. } => count_json(content),
+    }
+}
+
+/// 估算 system prompt 的 token 数
+pub fn count_claude_system(system: &Option<SystemPrompt>) -> u32 {
+    match system {
+        Some(SystemPrompt::String(s)) => count_text(s),
+        Some(SystemPrompt::Array(blocks)) => blocks.iter().map(|b| count_text(&b.text)).sum(),
+        None => 0,
+    }
+}
+
+/// 估算工具定义的 token 数
+pub fn count_claude_tools(req: &ClaudeRequest) -> u32 {
+    req.tools
+        .as_ref()
+        .map(|tools| {
+            tools
+                .iter()
+                .map(|tool| {
+                    TOOL_OVERHEAD
+                        + tool.name.as_deref().map(count_text).unwrap_or(0)
+                        + tool.description.as_deref().map(count_text).unwrap_or(0)
+                        + tool.input_schema.as_ref().map(count_json).unwrap_or(0)
+                })
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// 估算 Claude 请求的 input_tokens (system + messages + tools)
+pub fn count_claude_request(req: &ClaudeRequest) -> u32 {
+    let messages: u32 = req
+        .messages
+        .iter()
+        .map(|msg| {
+            MESSAGE_OVERHEAD
+                + match &msg.content {
+                    MessageContent::String(s) => count_text(s),
+                    MessageContent::Array(blocks) => blocks.iter().map(count_claude_block).sum(),
+                }
+        })
+        .sum();
+
+    count_claude_system(&req.system) + messages + count_claude_tools(req)
+}
+
+/// 估算 Gemini part 的 token 数
+fn count_gemini_part(part: &Value) -> u32 {
+    if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+        return count_text(text);
+    }
+    if let Some(inline) = part.get("inlineData") {
+        let mime = inline
+            .get("mimeType")
+            .and_then(|m| m.as_str())
+            .unwrap_or("");
+        let data = inline.get("data").and_then(|d| d.as_str()).unwrap_or("");
+        return if mime.starts_with("image/") {
+            estimate_image_tokens(data)
+        } else {
+            estimate_document("base64", mime, data)
+        };
+    }
+    if let Some(call) = part.get("functionCall") {
+        return count_json(call);
+    }
+    if let Some(resp) = part.get("functionResponse") {
+        return count_json(resp);
+    }
+    if part.get("fileData").is_some() {
+        return IMAGE_FALLBACK_TOKENS;
+    }
+    0
+}
+
+fn count_gemini_content(content: &Value) -> u32 {
+    content
+        .get("parts")
+        .and_then(|p| p.as_array())
+        .map(|parts| parts.iter().map(count_gemini_part).sum())
+        .unwrap_or(0)
+}
+
+/// Gemini 请求体 (兼容 countTokens 的 `generateContentRequest` 包装形式)
+fn gemini_inner(body: &Value) -> &Value {
+    body.get("generateContentRequest").unwrap_or(body)
+}
+
+/// 估算 Gemini contents 的 token 数
+pub fn count_gemini_contents(body: &Value) -> u32 {
+    gemini_inner(body)
+        .get("contents")
+        .and_then(|c| c.as_array())
+        .map(|arr| {
+            arr.iter()
+                .map(|c| MESSAGE_OVERHEAD + count_gemini_content(c))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// 估算 Gemini systemInstruction + tools 的 token 数
+pub fn count_gemini_system_and_tools(body: &Value) -> u32 {
+    let body = gemini_inner(body);
+
+    let system = body
+        .get("systemInstruction")
+        .map(count_gemini_content)
+        .unwrap_or(0);
+
+    let tools: u32 = body
+        .get("tools")
+        .and_then(|t| t.as_array())
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|tool| tool.get("functionDeclarations").and_then(|d| d.as_array()))
+                .flatten()
+                .map(|decl| TOOL_OVERHEAD + count_json(decl))
+                .sum()
+        })
+        .unwrap_or(0);
+
+    system + tools
+}
+
+/// 估算 Gemini 原生请求 (contents + systemInstruction + tools) 的 token 数
+pub fn count_gemini_request(body: &Value) -> u32 {
+    count_gemini_contents(body) + count_gemini_system_and_tools(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // 1x1 PNG
+    const TINY_PNG: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mP8z8BQDwAEhQGAhKmMIQAAAABJRU5ErkJggg==";
+
+    #[test]
+    fn test_count_text_non_zero() {
+        assert_eq!(count_text(""), 0);
+        assert!(count_text("Hello, world!") > 0);
+        assert!(count_text(&"lorem ipsum ".repeat(100)) > count_text("lorem ipsum"));
+    }
+
+    #[test]
+    fn test_image_tokens_use_dimensions() {
+        assert_eq!(estimate_image_tokens(TINY_PNG), 1);
+        assert_eq!(estimate_image_tokens("not-base64!"), IMAGE_FALLBACK_TOKENS);
+    }
+
+    #[test]
+    fn test_pdf_page_count() {
+        let pdf = b"%PDF-1.4\n1 0 obj << /Type /Pages /Count 2 >>\n2 0 obj << /Type /Page >>\n3 0 obj << /Type/Page >>";
+        assert_eq!(count_pdf_pages(pdf), 2);
+    }
+
+    #[test]
+    fn test_claude_request_counts_all_sections() {
+        let base: ClaudeRequest = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{"role": "user", "content": "Summarize the attached files"}]
+        }))
+        .unwrap();
+        let base_count = count_claude_request(&base);
+        assert!(base_count > MESSAGE_OVERHEAD);
+
+        let full: ClaudeRequest = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "system": "You are a careful reviewer.",
+            "tools": [{
+                "name": "read_file",
+                "description": "Read a file from disk",
+                "input_schema": {"type": "object", "properties": {"path": {"type": "string"}}}
+            }],
+            "messages": [{
+                "role": "user",
+                "content": [
+                    {"type": "text", "text": "Summarize the attached files"},
+                    {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": TINY_PNG}},
+                    {"type": "document", "source": {"type": "text", "media_type": "text/plain", "data": "Quarterly numbers are up."}}
+                ]
+            }]
+        }))
+        .unwrap();
+
+        let system = count_claude_system(&full.system);
+        let tools = count_claude_tools(&full);
+        assert!(system > 0);
+        assert!(tools > TOOL_OVERHEAD);
+        assert_eq!(
+            count_claude_request(&full),
+            base_count + system + tools + 1 + count_text("Quarterly numbers are up.")
+        );
+    }
+
+    #[test]
+    fn test_gemini_request_counts_contents_system_and_tools() {
+        let body = json!({
+            "systemInstruction": {"parts": [{"text": "Be brief."}]},
+            "contents": [
+                {"role": "user", "parts": [{"text": "What's the weather?"}]},
+                {"role": "model", "parts": [{"functionCall": {"name": "weather", "args": {"city": "Paris"}}}]}
+            ],
+            "tools": [{"functionDeclarations": [{"name": "weather", "parameters": {"type": "object"}}]}]
+        });
+
+        let total = count_gemini_request(&body);
+        assert!(total > 2 * MESSAGE_OVERHEAD + TOOL_OVERHEAD);
+
+        // countTokens 的 generateContentRequest 包装形式结果一致
+        let wrapped = json!({"generateContentRequest": body});
+        assert_eq!(count_gemini_request(&wrapped), total);
+    }
+}
@@ -98,7 +98,7 @@ pub async fn handle_audio_transcription(
     // 6. 获取 Token 和上游客户端
     let token_manager = state.token_manager;
     let (access_token, project_id, email) = token_manager
-        .get_token("text", false, None)
+        .get_token_for_model("text", false, None, Some(&model))
         .await
         .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e))?;
 
@@ -122,16 +122,22 @@ pub async fn handle_audio_transcription(
         .map_err(|e| (StatusCode::BAD_GATEWAY, format!("上游请求失败: {}", e)))?;
 
     if !response.status().is_success() {
+        let status_code = response.status().as_u16();
         let error_text = response
             .text()
             .await
             .unwrap_or_else(|_| "Unknown error".to_string());
+        token_manager
+            .report_upstream_error(&email, status_code, &error_text)
+            .await;
         return Err((
             StatusCode::BAD_GATEWAY,
             format!("Gemini API 错误: {}", error_text),
         ));
     }
 
+    token_manager.mark_account_success(&email);
+
     let result: Value = response
         .json()
         .await
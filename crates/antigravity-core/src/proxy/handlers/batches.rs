@@ -0,0 +1,327 @@
+// Anthropic Message Batches API Handler (/v1/messages/batches)
+use axum::{
+    body::Body,
+    extract::{Extension, Json, Path, Query},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+use crate::modules::batch_db::{self, BatchRecord, BatchStatus, BatchStore};
+use crate::proxy::batches::{batch_to_json, notify, result_line};
+use crate::proxy::client_keys::{owner_id, ClientKeyIdentity};
+use crate::proxy::mappers::claude::ClaudeRequest;
+
+const MAX_BATCH_REQUESTS: usize = 100_000;
+const DEFAULT_LIST_LIMIT: usize = 20;
+const MAX_LIST_LIMIT: usize = 1000;
+
+fn claude_error(status: StatusCode, error_type: &str, message: &str) -> Response {
+    (
+        status,
+        Json(json!({
+            "type": "error",
+            "error": {"type": error_type, "message": message}
+        })),
+    )
+        .into_response()
+}
+
+fn invalid_request(message: &str) -> Response {
+    claude_error(StatusCode::BAD_REQUEST, "invalid_request_error", message)
+}
+
+fn not_found(batch_id: &str) -> Response {
+    claude_error(
+        StatusCode::NOT_FOUND,
+        "not_found_error",
+        &format!("Batch '{}' not found", batch_id),
+    )
+}
+
+fn internal_error(e: String) -> Response {
+    claude_error(StatusCode::INTERNAL_SERVER_ERROR, "api_error", &e)
+}
+
+/// 读取批次；不属于 `owner` 的批次按不存在处理
+fn load_owned(
+    store: &BatchStore,
+    batch_id: &str,
+    owner: Option<&str>,
+) -> Result<Option<BatchRecord>, String> {
+    Ok(store
+        .get(batch_id)?
+        .filter(|batch| batch.client_key.as_deref() == owner))
+}
+
+/// 校验 `requests` 数组，返回 (custom_id, params)
+fn parse_requests(body: &Value) -> Result<Vec<(String, Value)>, String> {
+    let requests = body
+        .get("requests")
+        .and_then(|r| r.as_array())
+        .ok_or("requests: Field required")?;
+    if requests.is_empty() {
+        return Err("requests: must contain at least one request".to_string());
+    }
+    if requests.len() > MAX_BATCH_REQUESTS {
+        return Err(format!(
+            "requests: a batch may contain at most {} requests",
+            MAX_BATCH_REQUESTS
+        ));
+    }
+
+    let mut seen = HashSet::new();
+    let mut parsed = Vec::with_capacity(requests.len());
+    for (i, request) in requests.iter().enumerate() {
+        let custom_id = request
+            .get("custom_id")
+            .and_then(|c| c.as_str())
+            .ok_or_else(|| format!("requests.{}.custom_id: Field required", i))?;
+        let valid_id = (1..=64).contains(&custom_id.len())
+            && custom_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid_id {
+            return Err(format!(
+                "requests.{}.custom_id: must be 1-64 characters of [a-zA-Z0-9_-]",
+                i
+            ));
+        }
+        if !seen.insert(custom_id) {
+            return Err(format!(
+                "requests.{}.custom_id: duplicate custom_id '{}'",
+                i, custom_id
+            ));
+        }
+
+        let params = request
+            .get("params")
+            .cloned()
+            .ok_or_else(|| format!("requests.{}.params: Field required", i))?;
+        if params.get("stream").and_then(|s| s.as_bool()) == Some(true) {
+            return Err(format!(
+                "requests.{}.params.stream: streaming is not supported in batches",
+                i
+            ));
+        }
+        serde_json::from_value::<ClaudeRequest>(params.clone())
+            .map_err(|e| format!("requests.{}.params: {}", i, e))?;
+        parsed.push((custom_id.to_string(), params));
+    }
+    Ok(parsed)
+}
+
+/// POST /v1/messages/batches
+pub async fn handle_create_batch(
+    identity: Option<Extension<ClientKeyIdentity>>,
+    Json(body): Json<Value>,
+) -> Response {
+    let requests = match parse_requests(&body) {
+        Ok(requests) => requests,
+        Err(e) => return invalid_request(&e),
+    };
+    let store = match batch_db::default_store() {
+        Ok(store) => store,
+        Err(e) => return internal_error(e),
+    };
+    match store.create(&requests, owner_id(&identity)) {
+        Ok(batch) => {
+            tracing::info!(
+                "[Batches] Created {} with {} requests",
+                batch.id,
+                requests.len()
+            );
+            notify();
+            Json(batch_to_json(&batch)).into_response()
+        }
+        Err(e) => internal_error(e),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListBatchesQuery {
+    limit: Option<usize>,
+    before_id: Option<String>,
+    after_id: Option<String>,
+}
+
+/// GET /v1/messages/batches
+pub async fn handle_list_batches(
+    identity: Option<Extension<ClientKeyIdentity>>,
+    Query(query): Query<ListBatchesQuery>,
+) -> Response {
+    let store = match batch_db::default_store() {
+        Ok(store) => store,
+        Err(e) => return internal_error(e),
+    };
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_LIST_LIMIT)
+        .clamp(1, MAX_LIST_LIMIT);
+    match store.list(
+        limit,
+        query.before_id.as_deref(),
+        query.after_id.as_deref(),
+        owner_id(&identity),
+    ) {
+        Ok((batches, has_more)) => Json(json!({
+            "data": batches.iter().map(batch_to_json).collect::<Vec<_>>(),
+            "has_more": has_more,
+            "first_id": batches.first().map(|b| b.id.clone()),
+            "last_id": batches.last().map(|b| b.id.clone()),
+        }))
+        .into_response(),
+        Err(e) => invalid_request(&e),
+    }
+}
+
+/// GET /v1/messages/batches/:batch_id
+pub async fn handle_get_batch(
+    identity: Option<Extension<ClientKeyIdentity>>,
+    Path(batch_id): Path<String>,
+) -> Response {
+    let store = match batch_db::default_store() {
+        Ok(store) => store,
+        Err(e) => return internal_error(e),
+    };
+    match load_owned(&store, &batch_id, owner_id(&identity)) {
+        Ok(Some(batch)) => Json(batch_to_json(&batch)).into_response(),
+        Ok(None) => not_found(&batch_id),
+        Err(e) => internal_error(e),
+    }
+}
+
+/// POST /v1/messages/batches/:batch_id/cancel
+pub async fn handle_cancel_batch(
+    identity: Option<Extension<ClientKeyIdentity>>,
+    Path(batch_id): Path<String>,
+) -> Response {
+    let store = match batch_db::default_store() {
+        Ok(store) => store,
+        Err(e) => return internal_error(e),
+    };
+    match load_owned(&store, &batch_id, owner_id(&identity)) {
+        Ok(Some(_)) => {}
+        Ok(None) => return not_found(&batch_id),
+        Err(e) => return internal_error(e),
+    }
+    match store.cancel(&batch_id) {
+        Ok(Some(batch)) => Json(batch_to_json(&batch)).into_response(),
+        Ok(None) => not_found(&batch_id),
+        Err(e) => internal_error(e),
+    }
+}
+
+/// DELETE /v1/messages/batches/:batch_id
+pub async fn handle_delete_batch(
+    identity: Option<Extension<ClientKeyIdentity>>,
+    Path(batch_id): Path<String>,
+) -> Response {
+    let store = match batch_db::default_store() {
+        Ok(store) => store,
+        Err(e) => return internal_error(e),
+    };
+    match load_owned(&store, &batch_id, owner_id(&identity)) {
+        Ok(Some(_)) => {}
+        Ok(None) => return not_found(&batch_id),
+        Err(e) => return internal_error(e),
+    }
+    match store.delete(&batch_id) {
+        Ok(true) => Json(json!({"id": batch_id, "type": "message_batch_deleted"})).into_response(),
+        Ok(false) => not_found(&batch_id),
+        Err(e) => invalid_request(&e),
+    }
+}
+
+/// GET /v1/messages/batches/:batch_id/results (JSONL，按提交顺序)
+pub async fn handle_batch_results(
+    identity: Option<Extension<ClientKeyIdentity>>,
+    Path(batch_id): Path<String>,
+) -> Response {
+    let store = match batch_db::default_store() {
+        Ok(store) => store,
+        Err(e) => return internal_error(e),
+    };
+    match load_owned(&store, &batch_id, owner_id(&identity)) {
+        Ok(Some(batch)) if batch.status == BatchStatus::Ended => {}
+        Ok(Some(_)) => {
+            return invalid_request(&format!(
+                "Batch '{}' is still processing; results are available once it has ended",
+                batch_id
+            ))
+        }
+        Ok(None) => return not_found(&batch_id),
+        Err(e) => return internal_error(e),
+    }
+
+    let results = match store.results(&batch_id) {
+        Ok(results) => results,
+        Err(e) => return internal_error(e),
+    };
+    let mut body = String::new();
+    for result in results {
+        body.push_str(&result_line(&result.custom_id, result.state, result.result).to_string());
+        body.push('\n');
+    }
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/x-jsonl")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_requests_validation() {
+        let params = json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 16,
+            "messages": [{"role": "user", "content": "hi"}]
+        });
+        let ok = json!({"requests": [
+            {"custom_id": "a-1", "params": params},
+            {"custom_id": "b_2", "params": params}
+        ]});
+        assert_eq!(parse_requests(&ok).unwrap().len(), 2);
+
+        let duplicate = json!({"requests": [
+            {"custom_id": "a", "params": params},
+            {"custom_id": "a", "params": params}
+        ]});
+        assert!(parse_requests(&duplicate)
+            .unwrap_err()
+            .contains("duplicate"));
+
+        let mut streaming = params.clone();
+        streaming["stream"] = json!(true);
+        let streaming = json!({"requests": [{"custom_id": "a", "params": streaming}]});
+        assert!(parse_requests(&streaming).unwrap_err().contains("stream"));
+
+        let bad_id = json!({"requests": [{"custom_id": "a b", "params": params}]});
+        assert!(parse_requests(&bad_id).is_err());
+        assert!(parse_requests(&json!({"requests": []})).is_err());
+    }
+
+    #[test]
+    fn test_batches_scoped_to_creating_key() {
+        let dir = crate::proxy::tests::support::temp_dir("ag-batch-owner");
+        let store = BatchStore::open(&dir.join("batches.db")).unwrap();
+        let requests = vec![("a".to_string(), json!({"model": "claude-sonnet-4-5"}))];
+        let own = store.create(&requests, Some("k1")).unwrap();
+        let main = store.create(&requests, None).unwrap();
+
+        let owned = |id: &str, owner: Option<&str>| load_owned(&store, id, owner).unwrap();
+        assert_eq!(owned(&own.id, Some("k1")).unwrap().id, own.id);
+        assert!(owned(&own.id, Some("k2")).is_none());
+        assert!(owned(&own.id, None).is_none());
+        assert!(owned(&main.id, Some("k1")).is_none());
+        assert!(owned(&main.id, None).is_some());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
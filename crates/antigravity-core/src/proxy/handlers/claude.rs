@@ -2,7 +2,7 @@
 
 use axum::{
     body::Body,
-    extract::{Json, State},
+    extract::{Extension, Json, State},
     http::{header, StatusCode},
     response::{IntoResponse, Response},
 };
@@ -12,26 +12,30 @@ use serde_json::{json, Value};
 use tokio::time::{sleep, Duration};
 use tracing::{debug, error, info};
 
+use crate::proxy::client_keys::{owner_id, ClientKeyIdentity};
+use crate::proxy::common::token_counter;
 use crate::proxy::mappers::claude::{
-    close_tool_loop_for_thinking, create_claude_sse_stream, transform_claude_request_in,
-    transform_response, ClaudeRequest,
+    close_tool_loop_for_thinking, collect_stream_to_json, create_claude_sse_stream,
+    create_claude_sse_stream_from_openai, transform_claude_request_in,
+    transform_claude_request_to_openai, transform_response, ClaudeRequest,
 };
+use crate::proxy::prompt_cache::{self, PromptCacheRegistry};
+use crate::proxy::providers::{passthrough, registry};
 use crate::proxy::server::AppState;
+use crate::proxy::upstream::retry::{RetryDecision, RetryState};
+use antigravity_shared::proxy::config::{Protocol, ProviderProtocol, UpstreamProvider};
 use axum::http::HeaderMap;
-use std::sync::atomic::Ordering;
 
-const MAX_RETRY_ATTEMPTS: usize = 10;
-const MAX_529_RETRY_ATTEMPTS: usize = 3600; // 529 = 1 hour of retries (1 attempt per second)
 const MIN_SIGNATURE_LENGTH: usize = 10; // 最小有效签名长度
+const SIGNATURE_RETRY_DELAY: Duration = Duration::from_millis(200); // 移除 thinking 后重试前的等待
 
 // ===== Model Constants for Background Tasks =====
 // These can be adjusted for performance/cost optimization
 const BACKGROUND_MODEL_LITE: &str = "gemini-2.5-flash-lite"; // For simple/lightweight tasks
 const BACKGROUND_MODEL_STANDARD: &str = "gemini-2.5-flash"; // For complex background tasks
 
-// ===== Jitter Configuration (REMOVED) =====
-// Jitter was causing connection instability, reverted to fixed delays
-// const JITTER_FACTOR: f64 = 0.2;
+// ===== Retry Policy =====
+// 重试次数、退避与抖动由 ProxyConfig.retry 配置 (见 upstream::retry)，抖动默认关闭
 
 // ===== Thinking 块处理辅助函数 =====
 
@@ -119,6 +123,7 @@ fn filter_invalid_thinking_blocks(messages: &mut [Message]) {
                                 );
                                 new_blocks.push(ContentBlock::Text {
                                     text: thinking.clone(),
+                                    cache_control: None,
                                 });
                             } else {
                                 tracing::debug!(
@@ -140,6 +145,7 @@ fn filter_invalid_thinking_blocks(messages: &mut [Message]) {
             if blocks.is_empty() {
                 blocks.push(ContentBlock::Text {
                     text: String::new(),
+                    cache_control: None,
                 });
             }
         }
@@ -255,157 +261,12 @@ fn create_claude_context_length_error(error_text: &str) -> Response {
     (StatusCode::BAD_REQUEST, Json(claude_error)).into_response()
 }
 
-// ===== 统一退避策略模块 =====
-
-// [REMOVED] apply_jitter function
-// Jitter logic removed to restore stability (v3.3.16 fix)
-
-/// 重试策略枚举
-#[derive(Debug, Clone)]
-enum RetryStrategy {
-    /// 不重试，直接返回错误
-    NoRetry,
-    /// 固定延迟
-    FixedDelay(Duration),
-    /// 线性退避：base_ms * (attempt + 1)
-    LinearBackoff { base_ms: u64 },
-    /// 指数退避：base_ms * 2^attempt，上限 max_ms
-    ExponentialBackoff { base_ms: u64, max_ms: u64 },
-}
-
-/// 根据错误状态码和错误信息确定重试策略
-fn determine_retry_strategy(
-    status_code: u16,
-    error_text: &str,
-    retried_without_thinking: bool,
-) -> RetryStrategy {
-    match status_code {
-        // 400 错误：Thinking 签名失败
-        400 if !retried_without_thinking
-            && (error_text.contains("Invalid `signature`")
-                || error_text.contains("thinking.signature")
-                || error_text.contains("thinking.thinking")) =>
-        {
-            // 固定 200ms 延迟后重试
-            RetryStrategy::FixedDelay(Duration::from_millis(200))
-        }
-
-        // 429 限流错误
-        429 => {
-            // 优先使用服务端返回的 Retry-After
-            if let Some(delay_ms) = crate::proxy::upstream::retry::parse_retry_delay(error_text) {
-                let actual_delay = delay_ms.saturating_add(200).min(10_000);
-                RetryStrategy::FixedDelay(Duration::from_millis(actual_delay))
-            } else {
-                // 否则使用线性退避：1s, 2s, 3s
-                RetryStrategy::LinearBackoff { base_ms: 1000 }
-            }
-        }
-
-        // 529 服务器过载 - 固定 1 秒重试
-        529 => RetryStrategy::FixedDelay(Duration::from_secs(1)),
-
-        // 503 服务不可用 - 指数退避
-        503 => RetryStrategy::ExponentialBackoff {
-            base_ms: 1000,
-            max_ms: 8000,
-        },
-
-        // 500 服务器内部错误
-        500 => {
-            // 线性退避：500ms, 1s, 1.5s
-            RetryStrategy::LinearBackoff { base_ms: 500 }
-        }
-
-        // 401/403 认证/权限错误：可重试（轮换账号）
-        401 | 403 => RetryStrategy::FixedDelay(Duration::from_millis(100)),
-
-        // 其他错误：不重试
-        _ => RetryStrategy::NoRetry,
-    }
-}
-
-/// 执行退避策略并返回是否应该继续重试
-async fn apply_retry_strategy(
-    strategy: RetryStrategy,
-    attempt: usize,
-    status_code: u16,
-    trace_id: &str,
-) -> bool {
-    match strategy {
-        RetryStrategy::NoRetry => {
-            debug!(
-                "[{}] Non-retryable error {}, stopping",
-                trace_id, status_code
-            );
-            false
-        }
-
-        RetryStrategy::FixedDelay(duration) => {
-            let base_ms = duration.as_millis() as u64;
-            info!(
-                "[{}] ⏱️  Retry with fixed delay: status={}, attempt={}/{}, base={}ms",
-                trace_id,
-                status_code,
-                attempt + 1,
-                MAX_RETRY_ATTEMPTS,
-                base_ms
-            );
-            sleep(duration).await;
-            true
-        }
-
-        RetryStrategy::LinearBackoff { base_ms } => {
-            let calculated_ms = base_ms * (attempt as u64 + 1);
-            info!(
-                "[{}] ⏱️  Retry with linear backoff: status={}, attempt={}/{}, base={}ms",
-                trace_id,
-                status_code,
-                attempt + 1,
-                MAX_RETRY_ATTEMPTS,
-                calculated_ms
-            );
-            sleep(Duration::from_millis(calculated_ms)).await;
-            true
-        }
-
-        RetryStrategy::ExponentialBackoff { base_ms, max_ms } => {
-            let calculated_ms = (base_ms * 2_u64.pow(attempt as u32)).min(max_ms);
-            info!(
-                "[{}] ⏱️  Retry with exponential backoff: status={}, attempt={}/{}, base={}ms",
-                trace_id,
-                status_code,
-                attempt + 1,
-                MAX_RETRY_ATTEMPTS,
-                calculated_ms
-            );
-            sleep(Duration::from_millis(calculated_ms)).await;
-            true
-        }
-    }
-}
-
-/// 判断是否应该轮换账号
-/// 529/503 是服务端过载，轮换账号无意义
-/// 429/500/401/403 是账号级别问题，需要轮换
-fn should_rotate_account(status_code: u16) -> bool {
-    match status_code {
-        // 账号级别错误 → 轮换
-        429 | 401 | 403 | 500 => true,
-        // 服务端级别错误 → 不轮换（等待 backoff 后重试同一账号）
-        503 | 529 => false,
-        // 400 等客户端错误 → 不轮换（重试无意义）
-        _ => false,
-    }
-}
-
-// ===== 退避策略模块结束 =====
-
 /// 处理 Claude messages 请求
 ///
 /// 处理 Chat 消息请求流程
 pub async fn handle_messages(
     State(state): State<AppState>,
+    identity: Option<Extension<ClientKeyIdentity>>,
     headers: HeaderMap,
     Json(body): Json<Value>,
 ) -> Response {
@@ -422,30 +283,17 @@ pub async fn handle_messages(
             .collect::<String>()
             .to_lowercase();
 
-    // Decide whether this request should be handled by z.ai (Anthropic passthrough) or the existing Google flow.
-    let zai: antigravity_shared::proxy::config::ZaiConfig = state.zai.read().await.clone();
-    let zai_enabled =
-        zai.enabled && !matches!(zai.dispatch_mode, crate::proxy::ZaiDispatchMode::Off);
-    let google_accounts = state.token_manager.len();
-
-    let use_zai = if !zai_enabled {
-        false
-    } else {
-        match zai.dispatch_mode {
-            crate::proxy::ZaiDispatchMode::Off => false,
-            crate::proxy::ZaiDispatchMode::Exclusive => true,
-            crate::proxy::ZaiDispatchMode::Fallback => google_accounts == 0,
-            crate::proxy::ZaiDispatchMode::Pooled => {
-                // Treat z.ai as exactly one extra slot in the pool.
-                // No strict guarantees: it may get 0 requests if selection never hits.
-                let total = google_accounts.saturating_add(1).max(1);
-                let slot = state.provider_rr.fetch_add(1, Ordering::Relaxed) % total;
-                slot == 0
-            }
-        }
-    };
-
-    // [CRITICAL REFACTOR] 优先解析并过滤 Thinking 块，确保 z.ai 也是用修复后的 Body
+    // Decide whether this request should be handled by an Anthropic-compatible provider
+    // (z.ai or `providers`, passthrough) or the existing Google flow.
+    let requested_model = body
+        .get("model")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string();
+    let provider =
+        registry::select_provider(&state, ProviderProtocol::Anthropic, &requested_model).await;
+
+    // [CRITICAL REFACTOR] 优先解析并过滤 Thinking 块，确保 provider 也是用修复后的 Body
     let mut request: crate::proxy::mappers::claude::models::ClaudeRequest =
         match serde_json::from_value(body) {
             Ok(r) => r,
@@ -473,18 +321,23 @@ pub async fn handle_messages(
         close_tool_loop_for_thinking(&mut request.messages);
     }
 
-    if use_zai {
+    if let Some(provider) = provider {
         // 重新序列化修复后的请求体
         let new_body = match serde_json::to_value(&request) {
             Ok(v) => v,
             Err(e) => {
-                tracing::error!("Failed to serialize fixed request for z.ai: {}", e);
+                tracing::error!(
+                    "Failed to serialize fixed request for provider {}: {}",
+                    provider.id,
+                    e
+                );
                 return StatusCode::INTERNAL_SERVER_ERROR.into_response();
             }
         };
 
-        return crate::proxy::providers::zai_anthropic::forward_anthropic_json(
+        return passthrough::forward_json(
             &state,
+            &provider,
             axum::http::Method::POST,
             "/v1/messages",
             &headers,
@@ -496,6 +349,35 @@ pub async fn handle_messages(
     // Google Flow 继续使用 request 对象
     // (后续代码不需要再次 filter_invalid_thinking_blocks)
 
+    // url 来源的图片 / 文档经由上游代理下载为 base64，失败时返回 400 而不是丢弃内容
+    let upstream_proxy = state.upstream_proxy.read().await.clone();
+    if let Err(e) = crate::proxy::common::media_fetch::resolve_claude_media(
+        &mut request,
+        &upstream_proxy,
+        owner_id(&identity),
+    )
+    .await
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "type": "error",
+                "error": {
+                    "type": "invalid_request_error",
+                    "message": e
+                }
+            })),
+        )
+            .into_response();
+    }
+
+    // 没有 Anthropic 兼容 provider 时，经协议转换交给 OpenAI 兼容 provider
+    if let Some(provider) =
+        registry::select_provider(&state, ProviderProtocol::OpenAI, &requested_model).await
+    {
+        return forward_to_openai_provider(&state, &provider, &headers, &request, &trace_id).await;
+    }
+
     // 获取最新一条“有意义”的消息内容（用于日志记录和后台任务检测）
     // 策略：反向遍历，首先筛选出所有角色为 "user" 的消息，然后从中找到第一条非 "Warmup" 且非空的文本消息
     // 获取最新一条“有意义”的消息内容（用于日志记录和后台任务检测）
@@ -513,9 +395,10 @@ pub async fn handle_messages(
                     // 对于数组，提取所有 Text 块并拼接，忽略 ToolResult
                     arr.iter()
                         .filter_map(|block| match block {
-                            crate::proxy::mappers::claude::models::ContentBlock::Text { text } => {
-                                Some(text.as_str())
-                            }
+                            crate::proxy::mappers::claude::models::ContentBlock::Text {
+                                text,
+                                ..
+                            } => Some(text.as_str()),
                             _ => None,
                         })
                         .collect::<Vec<_>>()
@@ -613,40 +496,40 @@ pub async fn handle_messages(
     let _session_id: Option<&str> = None;
 
     // 2. 获取 UpstreamClient
-    let upstream = state.upstream.clone();
 
     // 3. 准备闭包
     let mut request_for_body = request.clone();
-    let token_manager = state.token_manager;
+    let token_manager = state.token_manager.clone();
 
-    let pool_size = token_manager.len();
-    let max_attempts = if pool_size == 0 {
-        1
-    } else {
-        MAX_RETRY_ATTEMPTS
-    };
+    let retry_policy = state.retry_policy.read().await.clone();
+    let mut retry = RetryState::new(retry_policy, token_manager.len());
 
     let mut last_error = String::new();
     let mut retried_without_thinking = false;
-    let mut skip_rotation = false; // 529/503 时跳过账号轮换
-    let mut attempt = 0_usize;
-    let mut retry_529_count = 0_usize;
+    let mut quota_exhausted = false; // 本轮是否因 429 耗尽所有尝试
+
+    // 模型路由解析 (有序规则 + 回退链)
+    let mut routed_model = request_for_body.model.clone();
+    let mut route = super::common::resolve_route(&state, &routed_model, Protocol::Anthropic).await;
 
     loop {
-        // 检查是否超过最大尝试次数
-        if attempt >= max_attempts && retry_529_count == 0 {
-            break;
-        }
-        // 如果正在 529 重试中，检查 529 重试限制
-        if retry_529_count >= MAX_529_RETRY_ATTEMPTS {
+        // 检查是否超过最大尝试次数 / 总时限
+        if retry.is_exhausted() {
+            // 所有尝试均因配额耗尽失败时切换到回退模型
+            if quota_exhausted && route.advance(&last_error) {
+                retry.reset();
+                quota_exhausted = false;
+                continue;
+            }
             break;
         }
 
-        // 2. 模型路由解析
-        let mut mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
-            &request_for_body.model,
-            &*state.custom_mapping.read().await,
-        );
+        // 2. 当前路由模型 (签名错误重试会改写 request_for_body.model，需要重新解析)
+        if request_for_body.model != routed_model && !route.is_fallback() {
+            routed_model = request_for_body.model.clone();
+            route = super::common::resolve_route(&state, &routed_model, Protocol::Anthropic).await;
+        }
+        let mut mapped_model = route.model().to_string();
 
         // 将 Claude 工具转为 Value 数组以便探测联网
         let tools_val: Option<Vec<Value>> = request_for_body.tools.as_ref().map(|list| {
@@ -667,15 +550,30 @@ pub async fn handle_messages(
             crate::proxy::session_manager::SessionManager::extract_session_id(&request_for_body);
         let session_id = Some(session_id_str.as_str());
 
-        // 关键修复: 529/503 时不轮换账号（服务端问题，轮换无意义）
-        let force_rotate_token = attempt > 0 && !skip_rotation;
-        skip_rotation = false; // 重置标志
+        // 是否换账号由重试策略决定 (529/503 等服务端问题不轮换)
+        let force_rotate_token = retry.take_rotation();
+
+        // Prompt cache 属于创建它的账号，首次尝试固定到该账号
+        if !force_rotate_token {
+            if let Some(owner) = PromptCacheRegistry::global().session_owner(&session_id_str) {
+                token_manager.pin_session(&session_id_str, &owner);
+            }
+        }
         let (access_token, project_id, email) = match token_manager
-            .get_token(&config.request_type, force_rotate_token, session_id)
+            .get_token_for_model(
+                &config.request_type,
+                force_rotate_token,
+                session_id,
+                Some(&mapped_model),
+            )
             .await
         {
             Ok(t) => t,
             Err(e) => {
+                if route.advance(&e) {
+                    retry.reset();
+                    continue;
+                }
                 let safe_message = if e.contains("invalid_grant") {
                     "OAuth refresh failed (invalid_grant): refresh_token likely revoked/expired; reauthorize account(s) to restore service.".to_string()
                 } else {
@@ -756,12 +654,18 @@ pub async fn handle_messages(
             }
         }
 
+        // 后台任务降级时不再展示路由规则
+        let mapped_label = if mapped_model == route.model() {
+            route.label()
+        } else {
+            mapped_model.clone()
+        };
         request_with_mapped.model = mapped_model;
 
         // 生成 Trace ID (简单用时间戳后缀)
         // let _trace_id = format!("req_{}", chrono::Utc::now().timestamp_subsec_millis());
 
-        let gemini_body = match transform_claude_request_in(&request_with_mapped, &project_id) {
+        let mut gemini_body = match transform_claude_request_in(&request_with_mapped, &project_id) {
             Ok(b) => {
                 debug!(
                     "[{}] Transformed Gemini Body: {}",
@@ -785,6 +689,23 @@ pub async fn handle_messages(
             }
         };
 
+        // cache_control 断点: 命中或创建上游 cachedContent
+        let cache_use = match prompt_cache::plan(&request_with_mapped, &gemini_body) {
+            Some(plan) => {
+                prompt_cache::prepare(
+                    &state.upstream,
+                    &plan,
+                    &mut gemini_body,
+                    &access_token,
+                    &email,
+                    &session_id_str,
+                )
+                .await
+            }
+            None => None,
+        };
+        let cache_created = cache_use.as_ref().is_some_and(|c| c.created);
+
         // 4. 上游调用 - 自动转换逻辑
         let client_wants_stream = request.stream;
         // [AUTO-CONVERSION] 非 Stream 请求自动转换为 Stream 以享受更宽松的配额
@@ -805,19 +726,27 @@ pub async fn handle_messages(
         };
         let query = if actual_stream { Some("alt=sse") } else { None };
 
-        let response = match upstream
-            .call_v1_internal(method, &access_token, gemini_body, query)
-            .await
-        {
+        let (email, response) = super::common::call_upstream(
+            &state,
+            method,
+            query,
+            gemini_body,
+            &access_token,
+            email,
+            &request_with_mapped.model,
+        )
+        .await;
+        let response = match response {
             Ok(r) => r,
             Err(e) => {
                 last_error = e.clone();
                 debug!(
                     "Request failed on attempt {}/{}: {}",
-                    attempt + 1,
-                    max_attempts,
+                    retry.attempt(),
+                    retry.max_attempts(),
                     e
                 );
+                retry.on_network_error();
                 continue;
             }
         };
@@ -833,8 +762,12 @@ pub async fn handle_messages(
             if actual_stream {
                 let stream = response.bytes_stream();
                 let gemini_stream = Box::pin(stream);
-                let claude_stream =
-                    create_claude_sse_stream(gemini_stream, trace_id.clone(), email.clone());
+                let claude_stream = create_claude_sse_stream(
+                    gemini_stream,
+                    trace_id.clone(),
+                    email.clone(),
+                    cache_created,
+                );
 
                 // 转换为 Bytes stream
                 let sse_stream = claude_stream.map(|result| -> Result<Bytes, std::io::Error> {
@@ -853,13 +786,11 @@ pub async fn handle_messages(
                         .header(header::CACHE_CONTROL, "no-cache")
                         .header(header::CONNECTION, "keep-alive")
                         .header("X-Account-Email", &email)
-                        .header("X-Mapped-Model", &request_with_mapped.model)
+                        .header("X-Mapped-Model", &mapped_label)
                         .body(Body::from_stream(sse_stream))
                         .unwrap();
                 } else {
                     // 客户端要非 Stream，需要收集完整响应并转换为 JSON
-                    use crate::proxy::mappers::claude::collect_stream_to_json;
-
                     match collect_stream_to_json(sse_stream).await {
                         Ok(full_response) => {
                             info!("[{}] ✓ Stream collected and converted to JSON", trace_id);
@@ -867,7 +798,7 @@ pub async fn handle_messages(
                                 .status(StatusCode::OK)
                                 .header(header::CONTENT_TYPE, "application/json")
                                 .header("X-Account-Email", &email)
-                                .header("X-Mapped-Model", &request_with_mapped.model)
+                                .header("X-Mapped-Model", &mapped_label)
                                 .body(Body::from(serde_json::to_string(&full_response).unwrap()))
                                 .unwrap();
                         }
@@ -955,7 +886,7 @@ pub async fn handle_messages(
                     StatusCode::OK,
                     [
                         ("X-Account-Email", email.as_str()),
-                        ("X-Mapped-Model", request_with_mapped.model.as_str()),
+                        ("X-Mapped-Model", mapped_label.as_str()),
                     ],
                     Json(claude_response),
                 )
@@ -979,6 +910,18 @@ pub async fn handle_messages(
         last_error = format!("HTTP {}: {}", status_code, error_text);
         debug!("[{}] Upstream Error Response: {}", trace_id, error_text);
 
+        // 缓存已过期或被删除: 移除记录，下次重试发送完整请求
+        if let Some(cache_use) = &cache_use {
+            if error_text.contains("CachedContent") || error_text.contains("cachedContent") {
+                PromptCacheRegistry::global().invalidate(&cache_use.account, &cache_use.hash);
+            }
+        }
+
+        // 反馈给熔断器 / 健康监控 / AIMD
+        token_manager
+            .report_upstream_error(&email, status_code, &error_text)
+            .await;
+
         // 3. 标记限流状态(用于 UI 显示) - 使用异步版本以支持实时配额刷新
         // 🆕 传入实际使用的模型,实现模型级别限流,避免不同模型配额互相影响
         if status_code == 429 || status_code == 529 || status_code == 503 || status_code == 500 {
@@ -992,6 +935,7 @@ pub async fn handle_messages(
                 )
                 .await;
         }
+        quota_exhausted = status_code == 429;
 
         // Handle context length exceeded error (400) - non-retryable, return Claude-compatible format
         // This ensures clients like opencode receive proper error messages instead of raw JSON
@@ -1058,62 +1002,33 @@ pub async fn handle_messages(
                 request_for_body.model = m;
             }
 
-            // 使用统一退避策略
-            let strategy =
-                determine_retry_strategy(status_code, &error_text, retried_without_thinking);
-            if apply_retry_strategy(strategy, attempt, status_code, &trace_id).await {
-                continue;
-            }
-        }
-
-        // 5. 统一处理所有可重试错误
-        // [REMOVED] 不再特殊处理 QUOTA_EXHAUSTED,允许账号轮换
-        // 原逻辑会在第一个账号配额耗尽时直接返回,导致"平衡"模式无法切换账号
-
-        // 确定重试策略
-        let strategy = determine_retry_strategy(status_code, &error_text, retried_without_thinking);
-
-        // 529 特殊处理：无限重试（最多 MAX_529_RETRY_ATTEMPTS = 3600 次）
-        if status_code == 529 {
-            retry_529_count += 1;
-            if retry_529_count >= MAX_529_RETRY_ATTEMPTS {
-                error!(
-                    "[{}] 529 retry limit reached ({} attempts)",
-                    trace_id, retry_529_count
-                );
-                return (status, error_text).into_response();
-            }
-            info!(
-                "[{}] ⏱️  529 retry {}/{}, waiting 1s (same account)",
-                trace_id, retry_529_count, MAX_529_RETRY_ATTEMPTS
-            );
-            sleep(Duration::from_secs(1)).await;
-            skip_rotation = true;
+            sleep(SIGNATURE_RETRY_DELAY).await;
             continue;
         }
 
-        // 其他错误：增加 attempt 计数
-        retry_529_count = 0; // 重置 529 计数
-        attempt += 1;
-
-        // 执行退避
-        if apply_retry_strategy(strategy, attempt, status_code, &trace_id).await {
-            // 关键修复: 判断是否需要轮换账号
-            if !should_rotate_account(status_code) {
+        // 5. 统一处理所有可重试错误 (按 ProxyConfig.retry 的状态码规则)
+        match retry.on_status(status_code, retry_after.as_deref(), &error_text) {
+            RetryDecision::Retry { delay, rotate } => {
                 info!(
-                    "[{}] ⏸️  Keeping same account for status {} (server-side issue, rotation pointless)",
-                    trace_id, status_code
+                    "[{}] ⏱️  Retry after {}ms: status={}, attempt={}/{}, rotate={}",
+                    trace_id,
+                    delay.as_millis(),
+                    status_code,
+                    retry.attempt(),
+                    retry.max_attempts(),
+                    rotate
                 );
-                skip_rotation = true; // 下次迭代不轮换
+                sleep(delay).await;
+            }
+            RetryDecision::Exhausted => {}
+            RetryDecision::FailFast => {
+                // 不可重试的错误，直接返回
+                error!(
+                    "[{}] Non-retryable error {}: {}",
+                    trace_id, status_code, error_text
+                );
+                return (status, error_text).into_response();
             }
-            continue;
-        } else {
-            // 不可重试的错误，直接返回
-            error!(
-                "[{}] Non-retryable error {}: {}",
-                trace_id, status_code, error_text
-            );
-            return (status, error_text).into_response();
         }
     }
 
@@ -1121,11 +1036,105 @@ pub async fn handle_messages(
         "type": "error",
         "error": {
             "type": "overloaded_error",
-            "message": format!("All {} attempts failed. Last error: {}", max_attempts, last_error)
+            "message": format!("All {} attempts failed. Last error: {}", retry.max_attempts(), last_error)
         }
     }))).into_response()
 }
 
+/// 将 Claude 请求转换为 OpenAI Chat Completions 交给 provider，响应转换回 Claude 格式
+///
+/// 上游始终以流式调用，客户端要非 Stream 时收集为 JSON
+async fn forward_to_openai_provider(
+    state: &AppState,
+    provider: &UpstreamProvider,
+    headers: &HeaderMap,
+    request: &ClaudeRequest,
+    trace_id: &str,
+) -> Response {
+    let mapped_model = passthrough::map_model(provider, &request.model);
+    info!(
+        "[{}] Claude Request → OpenAI provider {} | Model: {} -> {} | Stream: {}",
+        trace_id, provider.id, request.model, mapped_model, request.stream
+    );
+
+    // 模型名由 send_json 按 provider 映射
+    let body = transform_claude_request_to_openai(request, &request.model);
+    // 需要解析上游 SSE，不透传压缩协商
+    let mut headers = headers.clone();
+    headers.remove(header::ACCEPT_ENCODING);
+
+    let response = match passthrough::send_json(
+        state,
+        provider,
+        axum::http::Method::POST,
+        "/v1/chat/completions",
+        &headers,
+        body,
+    )
+    .await
+    {
+        Ok(r) => r,
+        Err(response) => return response,
+    };
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        error!(
+            "[{}] Provider {} returned {}: {}",
+            trace_id, provider.id, status, error_text
+        );
+        return (
+            StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::BAD_GATEWAY),
+            Json(json!({
+                "type": "error",
+                "error": {
+                    "type": "api_error",
+                    "message": error_text
+                }
+            })),
+        )
+            .into_response();
+    }
+
+    let claude_stream = create_claude_sse_stream_from_openai(
+        Box::pin(response.bytes_stream()),
+        trace_id.to_string(),
+        provider.id.clone(),
+    );
+    let sse_stream = claude_stream.map(|result| -> Result<Bytes, std::io::Error> {
+        match result {
+            Ok(bytes) => Ok(bytes),
+            Err(e) => Ok(Bytes::from(format!("data: {{\"error\":\"{}\"}}\n\n", e))),
+        }
+    });
+
+    if request.stream {
+        return Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "text/event-stream")
+            .header(header::CACHE_CONTROL, "no-cache")
+            .header(header::CONNECTION, "keep-alive")
+            .header("X-Mapped-Model", &mapped_model)
+            .body(Body::from_stream(sse_stream))
+            .unwrap();
+    }
+
+    match collect_stream_to_json(sse_stream).await {
+        Ok(full_response) => Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header("X-Mapped-Model", &mapped_model)
+            .body(Body::from(serde_json::to_string(&full_response).unwrap()))
+            .unwrap(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Stream collection error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
 /// 列出可用模型
 pub async fn handle_list_models(State(state): State<AppState>) -> impl IntoResponse {
     use crate::proxy::common::model_mapping::get_all_dynamic_models;
@@ -1150,19 +1159,29 @@ pub async fn handle_list_models(State(state): State<AppState>) -> impl IntoRespo
     }))
 }
 
-/// 计算 tokens (占位符)
+/// 计算 tokens
+///
+/// 默认使用本地估算 (system + messages + tools + 图片/文档)；
+/// 开启 `experimental.upstream_count_tokens` 时由上游 countTokens 统计 messages，失败回退本地估算
 pub async fn handle_count_tokens(
     State(state): State<AppState>,
     headers: HeaderMap,
     Json(body): Json<Value>,
 ) -> Response {
-    let zai = state.zai.read().await.clone();
-    let zai_enabled =
-        zai.enabled && !matches!(zai.dispatch_mode, crate::proxy::ZaiDispatchMode::Off);
-
-    if zai_enabled {
-        return crate::proxy::providers::zai_anthropic::forward_anthropic_json(
+    // 启用了处理该模型的 Anthropic 兼容 provider 时由 provider 计数
+    let model = body
+        .get("model")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+    let providers = registry::all_providers(&state).await;
+    let provider = registry::candidates(&providers, ProviderProtocol::Anthropic, model)
+        .into_iter()
+        .next()
+        .cloned();
+    if let Some(provider) = provider {
+        return passthrough::forward_json(
             &state,
+            &provider,
             axum::http::Method::POST,
             "/v1/messages/count_tokens",
             &headers,
@@ -1171,11 +1190,64 @@ pub async fn handle_count_tokens(
         .await;
     }
 
-    Json(json!({
-        "input_tokens": 0,
-        "output_tokens": 0
-    }))
-    .into_response()
+    let request: ClaudeRequest = match serde_json::from_value(body) {
+        Ok(r) => r,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "type": "error",
+                    "error": {
+                        "type": "invalid_request_error",
+                        "message": format!("Invalid request body: {}", e)
+                    }
+                })),
+            )
+                .into_response();
+        }
+    };
+
+    let local_tokens = token_counter::count_claude_request(&request);
+
+    let input_tokens = if state.experimental.read().await.upstream_count_tokens {
+        let upstream_result = super::common::count_tokens_upstream(
+            &state,
+            &request.model,
+            Protocol::Anthropic,
+            |mapped_model, project_id| {
+                let mut mapped_request = request.clone();
+                mapped_request.model = mapped_model.to_string();
+                let gemini_body = transform_claude_request_in(&mapped_request, project_id)?;
+                Ok(gemini_body["request"]["contents"].clone())
+            },
+        )
+        .await;
+
+        match upstream_result {
+            // countTokens 只统计 contents，system/tools 仍使用本地估算
+            Ok(contents_tokens) => {
+                contents_tokens
+                    + token_counter::count_claude_system(&request.system)
+                    + token_counter::count_claude_tools(&request)
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "[CountTokens] Upstream countTokens failed, using local estimate: {}",
+                    e
+                );
+                local_tokens
+            }
+        }
+    } else {
+        local_tokens
+    };
+
+    debug!(
+        "[CountTokens] model={} input_tokens={} (local={})",
+        request.model, input_tokens, local_tokens
+    );
+
+    Json(json!({ "input_tokens": input_tokens })).into_response()
 }
 
 // 移除已失效的简单单元测试，后续将补全完整的集成测试
@@ -1311,9 +1383,9 @@ fn extract_last_user_message_for_detection(request: &ClaudeRequest) -> Option<St
                 crate::proxy::mappers::claude::models::MessageContent::Array(arr) => arr
                     .iter()
                     .filter_map(|block| match block {
-                        crate::proxy::mappers::claude::models::ContentBlock::Text { text } => {
-                            Some(text.as_str())
-                        }
+                        crate::proxy::mappers::claude::models::ContentBlock::Text {
+                            text, ..
+                        } => Some(text.as_str()),
                         _ => None,
                     })
                     .collect::<Vec<_>>()
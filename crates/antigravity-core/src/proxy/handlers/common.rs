@@ -1,6 +1,193 @@
+use crate::proxy::adaptive_limit::ProbeStrategy;
+use crate::proxy::common::model_mapping::{resolve_route_with_rules, RouteResolution};
 use crate::proxy::server::AppState;
+use crate::proxy::upstream::client::UpstreamClient;
+use antigravity_shared::proxy::config::Protocol;
 use axum::{extract::Json, extract::State, http::StatusCode, response::IntoResponse};
+use futures::future::BoxFuture;
 use serde_json::{json, Value};
+use std::sync::Arc;
+
+/// Resolves the target model (and fallback chain) for `model` using the
+/// ordered router rules first, then `custom_mapping` and built-in defaults.
+pub async fn resolve_route(state: &AppState, model: &str, protocol: Protocol) -> RouteResolution {
+    let rules = state.router_rules.read().await;
+    let mapping = state.custom_mapping.read().await;
+    resolve_route_with_rules(model, protocol, &rules, &mapping)
+}
+
+/// 单次上游调用的失败结果 (非 2xx 响应或网络错误)，携带所用账号
+struct FailedAttempt {
+    email: String,
+    result: Result<reqwest::Response, String>,
+}
+
+type AttemptResult = Result<(String, reqwest::Response), FailedAttempt>;
+
+/// 构造一次可在独立任务中执行的上游调用 (主请求与对冲请求共用同一闭包类型)
+fn upstream_attempt(
+    upstream: Arc<UpstreamClient>,
+    method: &'static str,
+    query: Option<&'static str>,
+    access_token: String,
+    email: String,
+    body: Value,
+) -> impl FnOnce() -> BoxFuture<'static, AttemptResult> + Send + 'static {
+    move || {
+        Box::pin(async move {
+            match upstream
+                .call_v1_internal(method, &access_token, body, query)
+                .await
+            {
+                Ok(response) if response.status().is_success() => Ok((email, response)),
+                result => Err(FailedAttempt { email, result }),
+            }
+        })
+    }
+}
+
+/// 对冲中未被返回的失败结果 (通常是对冲账号的 429)，按其所用账号上报
+async fn report_discarded_attempt(state: &AppState, failed: FailedAttempt) {
+    match failed.result {
+        Ok(response) => {
+            let status = response.status().as_u16();
+            let error_text = response.text().await.unwrap_or_default();
+            tracing::debug!(
+                "Hedge: discarded attempt on {} failed with HTTP {}",
+                failed.email,
+                status
+            );
+            state
+                .token_manager
+                .report_upstream_error(&failed.email, status, &error_text)
+                .await;
+        }
+        Err(e) => tracing::debug!("Hedge: discarded attempt on {} failed: {}", failed.email, e),
+    }
+}
+
+/// 廉价探测请求体：保留 project/model 等包装字段，只请求 1 个 token
+fn cheap_probe_body(body: &Value) -> Value {
+    let mut probe = body.clone();
+    probe["request"] = json!({
+        "contents": [{ "role": "user", "parts": [{ "text": "ping" }] }],
+        "generationConfig": { "maxOutputTokens": 1 }
+    });
+    probe
+}
+
+/// Calls the upstream v1internal `method`, optionally through the SmartProber.
+///
+/// When hedging is enabled and the account is close to its AIMD threshold the
+/// request is hedged on a second account (or followed by a cheap probe).
+/// Returns the email of the account whose response is returned; on failure this
+/// is always the primary account, so callers keep their usual error handling.
+/// Failures of the other hedged account are reported here against that account.
+pub async fn call_upstream(
+    state: &AppState,
+    method: &'static str,
+    query: Option<&'static str>,
+    body: Value,
+    access_token: &str,
+    email: String,
+    model: &str,
+) -> (String, Result<reqwest::Response, String>) {
+    let token_manager = &state.token_manager;
+    let prober = token_manager.smart_prober();
+    let strategy = if prober.is_enabled() {
+        token_manager.probe_strategy(&email)
+    } else {
+        ProbeStrategy::None
+    };
+    // cachedContent 只属于当前账号，不能对冲到其他账号
+    let strategy = match strategy {
+        ProbeStrategy::DelayedHedge | ProbeStrategy::ImmediateHedge
+            if body.pointer("/request/cachedContent").is_some() =>
+        {
+            ProbeStrategy::None
+        }
+        other => other,
+    };
+
+    let primary = |body: Value| {
+        upstream_attempt(
+            state.upstream.clone(),
+            method,
+            query,
+            access_token.to_string(),
+            email.clone(),
+            body,
+        )
+    };
+
+    let result = match strategy {
+        ProbeStrategy::None => primary(body)().await,
+        ProbeStrategy::CheapProbe => {
+            let probe = upstream_attempt(
+                state.upstream.clone(),
+                "generateContent",
+                None,
+                access_token.to_string(),
+                email.clone(),
+                cheap_probe_body(&body),
+            );
+            prober
+                .execute_with_cheap_probe(&email, primary(body), move || async move {
+                    probe().await.map(|_| ())
+                })
+                .await
+        }
+        ProbeStrategy::DelayedHedge | ProbeStrategy::ImmediateHedge => {
+            match token_manager.get_hedge_token(&email, Some(model)) {
+                None => {
+                    tracing::debug!("Hedge skipped for {}: no secondary account", email);
+                    primary(body)().await
+                }
+                Some((hedge_token, hedge_project, hedge_email)) => {
+                    let mut hedge_body = body.clone();
+                    hedge_body["project"] = json!(hedge_project);
+                    let secondary = upstream_attempt(
+                        state.upstream.clone(),
+                        method,
+                        query,
+                        hedge_token,
+                        hedge_email.clone(),
+                        hedge_body,
+                    );
+
+                    let hedged = if strategy == ProbeStrategy::ImmediateHedge {
+                        prober
+                            .execute_with_immediate_hedge(
+                                &email,
+                                &hedge_email,
+                                primary(body),
+                                secondary,
+                            )
+                            .await
+                    } else {
+                        prober
+                            .execute_with_delayed_hedge(
+                                &email,
+                                &hedge_email,
+                                primary(body),
+                                secondary,
+                            )
+                            .await
+                    };
+                    if let Some(discarded) = hedged.discarded_error {
+                        report_discarded_attempt(state, discarded).await;
+                    }
+                    hedged.result.map(|r| r.into_inner())
+                }
+            }
+        }
+    };
+
+    match result {
+        Ok((email, response)) => (email, Ok(response)),
+        Err(failed) => (failed.email, failed.result),
+    }
+}
 
 /// Detects model capabilities and configuration
 /// POST /v1/models/detect
@@ -14,11 +201,14 @@ pub async fn handle_detect_model(
         return (StatusCode::BAD_REQUEST, "Missing 'model' field").into_response();
     }
 
-    // 1. Resolve mapping
-    let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
-        model_name,
-        &*state.custom_mapping.read().await,
-    );
+    // 1. Resolve mapping (router rules may be scoped to a protocol, default OpenAI)
+    let protocol = match body.get("protocol").and_then(|v| v.as_str()) {
+        Some("anthropic") | Some("claude") => Protocol::Anthropic,
+        Some("gemini") => Protocol::Gemini,
+        _ => Protocol::OpenAI,
+    };
+    let route = resolve_route(&state, model_name, protocol).await;
+    let mapped_model = route.model().to_string();
 
     // 2. Resolve capabilities
     let config = crate::proxy::mappers::common_utils::resolve_request_config(
@@ -31,6 +221,7 @@ pub async fn handle_detect_model(
     let mut response = json!({
         "model": model_name,
         "mapped_model": mapped_model,
+        "route_rule": route.rule,
         "type": config.request_type,
         "features": {
             "has_web_search": config.inject_google_search,
@@ -46,3 +237,65 @@ pub async fn handle_detect_model(
 
     Json(response).into_response()
 }
+
+/// Forwards `contents` to the upstream v1internal `countTokens` method.
+///
+/// `build_contents` receives the mapped model and project id and returns the
+/// Gemini `contents` array. Returns upstream `totalTokens`.
+pub async fn count_tokens_upstream<F>(
+    state: &AppState,
+    model: &str,
+    protocol: Protocol,
+    build_contents: F,
+) -> Result<u32, String>
+where
+    F: FnOnce(&str, &str) -> Result<Value, String>,
+{
+    let route = resolve_route(state, model, protocol).await;
+    let mapped_model = route.model();
+    let config =
+        crate::proxy::mappers::common_utils::resolve_request_config(model, mapped_model, &None);
+
+    let (access_token, project_id, email) = state
+        .token_manager
+        .get_token_for_model(&config.request_type, false, None, Some(mapped_model))
+        .await?;
+
+    let contents = build_contents(mapped_model, &project_id)?;
+    let body = json!({
+        "request": {
+            "model": format!("models/{}", config.final_model),
+            "contents": contents,
+        }
+    });
+
+    let response = state
+        .upstream
+        .call_v1_internal("countTokens", &access_token, body, None)
+        .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        state
+            .token_manager
+            .report_upstream_error(&email, status.as_u16(), &error_text)
+            .await;
+        return Err(format!(
+            "Upstream countTokens HTTP {}: {}",
+            status, error_text
+        ));
+    }
+
+    let result: Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Invalid countTokens response: {}", e))?;
+    state.token_manager.mark_account_success(&email);
+
+    result
+        .get("totalTokens")
+        .and_then(|v| v.as_u64())
+        .map(|v| v as u32)
+        .ok_or_else(|| format!("countTokens response missing totalTokens: {}", result))
+}
@@ -0,0 +1,535 @@
+// Embeddings 处理器
+// OpenAI /v1/embeddings 与 Gemini 原生 embedContent / batchEmbedContents
+
+use axum::{
+    extract::{Json, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use base64::Engine as _;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::{debug, info};
+
+use crate::proxy::common::{model_mapping, token_counter};
+use crate::proxy::mappers::gemini::unwrap_response;
+use crate::proxy::server::AppState;
+use crate::proxy::upstream::retry::{RetryDecision, RetryState};
+use antigravity_shared::proxy::config::Protocol;
+
+/// Embedding 请求独立的配额组 (无状态，不参与 60s 账号锁定)
+pub const EMBEDDING_QUOTA_GROUP: &str = "embedding";
+
+/// 上游支持的 embedding 模型 (映射后的名称)
+const SUPPORTED_EMBEDDING_MODELS: &[&str] = &["gemini-embedding-001", "text-embedding-004"];
+
+/// 不是 embedding 模型时返回 400 model_not_found，而不是把请求发给聊天模型
+fn model_not_found(requested: &str) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(json!({
+            "error": {
+                "message": format!(
+                    "The model '{}' does not exist or does not support embeddings. Supported: {}",
+                    requested,
+                    SUPPORTED_EMBEDDING_MODELS.join(", ")
+                ),
+                "type": "invalid_request_error",
+                "param": "model",
+                "code": "model_not_found"
+            }
+        })),
+    )
+        .into_response()
+}
+
+/// 按路由规则、自定义映射与内置 embedding 映射表解析上游模型
+async fn resolve_embedding_model(
+    state: &AppState,
+    model: &str,
+    protocol: Protocol,
+) -> Option<String> {
+    let rules = state.router_rules.read().await;
+    let mapping = state.custom_mapping.read().await;
+    model_mapping::resolve_embedding_model(model, protocol, &rules, &mapping)
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum EmbeddingInput {
+    Single(String),
+    Batch(Vec<String>),
+}
+
+impl EmbeddingInput {
+    fn into_texts(self) -> Vec<String> {
+        match self {
+            EmbeddingInput::Single(s) => vec![s],
+            EmbeddingInput::Batch(v) => v,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EmbeddingRequest {
+    pub model: String,
+    pub input: EmbeddingInput,
+    #[serde(default)]
+    pub dimensions: Option<u32>,
+    /// "float" (默认) 或 "base64"
+    #[serde(default)]
+    pub encoding_format: Option<String>,
+}
+
+/// OpenAI 输入 -> Gemini batchEmbedContents 请求体
+fn build_batch_request(model: &str, texts: &[String], dimensions: Option<u32>) -> Value {
+    let requests: Vec<Value> = texts
+        .iter()
+        .map(|text| {
+            let mut req = json!({
+                "model": format!("models/{}", model),
+                "content": {"parts": [{"text": text}]}
+            });
+            if let Some(dim) = dimensions {
+                req["outputDimensionality"] = json!(dim);
+            }
+            req
+        })
+        .collect();
+
+    json!({ "requests": requests })
+}
+
+/// 原生请求中的 model 字段统一改写为映射后的模型
+fn rewrite_native_models(method: &str, body: &mut Value, model: &str) {
+    let model_path = json!(format!("models/{}", model));
+    if method == "batchEmbedContents" {
+        if let Some(requests) = body.get_mut("requests").and_then(|r| r.as_array_mut()) {
+            for req in requests {
+                req["model"] = model_path.clone();
+            }
+        }
+    } else if let Some(obj) = body.as_object_mut() {
+        obj.insert("model".to_string(), model_path);
+    }
+}
+
+/// f32 小端序 base64 编码 (与 OpenAI encoding_format=base64 一致)
+fn encode_embedding_base64(values: &[Value]) -> String {
+    let bytes: Vec<u8> = values
+        .iter()
+        .flat_map(|v| (v.as_f64().unwrap_or(0.0) as f32).to_le_bytes())
+        .collect();
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+/// Gemini batchEmbedContents 响应 -> OpenAI embeddings 响应
+fn build_openai_response(
+    gemini_resp: &Value,
+    model: &str,
+    texts: &[String],
+    base64_encoding: bool,
+) -> Result<Value, String> {
+    let embeddings = gemini_resp
+        .get("embeddings")
+        .and_then(|e| e.as_array())
+        .ok_or_else(|| format!("Upstream response missing embeddings: {}", gemini_resp))?;
+
+    if embeddings.len() != texts.len() {
+        return Err(format!(
+            "Upstream returned {} embeddings for {} inputs",
+            embeddings.len(),
+            texts.len()
+        ));
+    }
+
+    let data: Vec<Value> = embeddings
+        .iter()
+        .enumerate()
+        .map(|(index, emb)| {
+            let values = emb
+                .get("values")
+                .and_then(|v| v.as_array())
+                .cloned()
+                .unwrap_or_default();
+            let embedding = if base64_encoding {
+                json!(encode_embedding_base64(&values))
+            } else {
+                Value::Array(values)
+            };
+            json!({
+                "object": "embedding",
+                "index": index,
+                "embedding": embedding
+            })
+        })
+        .collect();
+
+    let prompt_tokens: u32 = texts.iter().map(|t| token_counter::count_text(t)).sum();
+
+    Ok(json!({
+        "object": "list",
+        "data": data,
+        "model": model,
+        "usage": {
+            "prompt_tokens": prompt_tokens,
+            "total_tokens": prompt_tokens
+        }
+    }))
+}
+
+/// 调用上游 embedding 方法，带账号轮换
+async fn call_embedding_upstream(
+    state: &AppState,
+    method: &str,
+    mapped_model: &str,
+    request: Value,
+) -> Result<Value, (StatusCode, String)> {
+    let token_manager = &state.token_manager;
+    let retry_policy = state.retry_policy.read().await.clone();
+    let mut retry = RetryState::new(retry_policy, token_manager.len());
+    let mut last_error = String::new();
+
+    while !retry.is_exhausted() {
+        let force_rotate = retry.take_rotation();
+        let (access_token, project_id, email) = token_manager
+            .get_token_for_model(
+                EMBEDDING_QUOTA_GROUP,
+                force_rotate,
+                None,
+                Some(mapped_model),
+            )
+            .await
+            .map_err(|e| {
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    format!("Token error: {}", e),
+                )
+            })?;
+
+        info!(
+            "✓ Using account: {} for {} (model: {})",
+            email, method, mapped_model
+        );
+
+        let wrapped = json!({
+            "project": project_id,
+            "requestId": format!("emb-{}", uuid::Uuid::new_v4()),
+            "model": mapped_model,
+            "userAgent": "antigravity",
+            "request": request.clone()
+        });
+
+        let response = match state
+            .upstream
+            .call_v1_internal(method, &access_token, wrapped, None)
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                debug!(
+                    "Embedding request failed on attempt {}/{}: {}",
+                    retry.attempt(),
+                    retry.max_attempts(),
+                    e
+                );
+                last_error = e;
+                retry.on_network_error();
+                continue;
+            }
+        };
+
+        let status = response.status();
+        if status.is_success() {
+            token_manager.mark_account_success(&email);
+            let json: Value = response.json().await.map_err(|e| {
+                (
+                    StatusCode::BAD_GATEWAY,
+                    format!("Parse upstream response failed: {}", e),
+                )
+            })?;
+            return Ok(unwrap_response(&json));
+        }
+
+        let status_code = status.as_u16();
+        let retry_after = response
+            .headers()
+            .get("Retry-After")
+            .and_then(|h| h.to_str().ok())
+            .map(|s| s.to_string());
+        let error_text = response.text().await.unwrap_or_default();
+        token_manager
+            .report_upstream_error(&email, status_code, &error_text)
+            .await;
+        last_error = format!("HTTP {}: {}", status_code, error_text);
+
+        // 按 ProxyConfig.retry 的状态码规则重试，其余 (如 400) 直接返回
+        match retry.on_status(status_code, retry_after.as_deref(), &error_text) {
+            RetryDecision::Retry { delay, rotate } => {
+                tracing::warn!(
+                    "Embedding upstream {} on account {}, attempt {}/{}, waiting {}ms (rotate: {})",
+                    status_code,
+                    email,
+                    retry.attempt(),
+                    retry.max_attempts(),
+                    delay.as_millis(),
+                    rotate
+                );
+                tokio::time::sleep(delay).await;
+            }
+            RetryDecision::Exhausted => {}
+            RetryDecision::FailFast => {
+                return Err((
+                    StatusCode::from_u16(status_code).unwrap_or(StatusCode::BAD_GATEWAY),
+                    error_text,
+                ));
+            }
+        }
+    }
+
+    Err((
+        StatusCode::TOO_MANY_REQUESTS,
+        format!("All accounts exhausted. Last error: {}", last_error),
+    ))
+}
+
+/// OpenAI Embeddings API
+/// POST /v1/embeddings
+pub async fn handle_embeddings(
+    State(state): State<AppState>,
+    Json(body): Json<Value>,
+) -> Result<Response, (StatusCode, String)> {
+    let request: EmbeddingRequest = serde_json::from_value(body)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)))?;
+
+    let base64_encoding = match request.encoding_format.as_deref() {
+        None | Some("float") => false,
+        Some("base64") => true,
+        Some(other) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Unsupported encoding_format: {}", other),
+            ))
+        }
+    };
+
+    let texts = request.input.into_texts();
+    if texts.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "'input' must not be empty".to_string(),
+        ));
+    }
+
+    let Some(mapped_model) =
+        resolve_embedding_model(&state, &request.model, Protocol::OpenAI).await
+    else {
+        return Ok(model_not_found(&request.model));
+    };
+
+    info!(
+        "[Embeddings] model={} -> {}, inputs={}, dimensions={:?}",
+        request.model,
+        mapped_model,
+        texts.len(),
+        request.dimensions
+    );
+
+    let batch = build_batch_request(&mapped_model, &texts, request.dimensions);
+    let gemini_resp =
+        call_embedding_upstream(&state, "batchEmbedContents", &mapped_model, batch).await?;
+
+    let response = build_openai_response(&gemini_resp, &request.model, &texts, base64_encoding)
+        .map_err(|e| (StatusCode::BAD_GATEWAY, e))?;
+
+    Ok(Json(response).into_response())
+}
+
+/// Gemini 原生 embedContent / batchEmbedContents (由 handle_generate 按 method 分发)
+pub async fn handle_gemini_embed(
+    state: &AppState,
+    model_name: &str,
+    method: &str,
+    mut body: Value,
+) -> Result<Response, (StatusCode, String)> {
+    let Some(mapped_model) = resolve_embedding_model(state, model_name, Protocol::Gemini).await
+    else {
+        return Ok(model_not_found(model_name));
+    };
+
+    info!(
+        "[Embeddings] Gemini {} model={} -> {}",
+        method, model_name, mapped_model
+    );
+
+    rewrite_native_models(method, &mut body, &mapped_model);
+    let gemini_resp = call_embedding_upstream(state, method, &mapped_model, body).await?;
+
+    Ok(Json(gemini_resp).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::tests::support;
+    use axum::body::to_bytes;
+    use axum::extract::Request;
+    use std::sync::{Arc, Mutex};
+
+    type Captured = Arc<Mutex<Vec<(String, Value)>>>;
+
+    /// 本地 mock 上游：记录请求并按输入数量返回假向量
+    async fn spawn_mock_upstream(captured: Captured) -> String {
+        let app = axum::Router::new().fallback(move |req: Request| {
+            let captured = captured.clone();
+            async move {
+                let path = req.uri().path().to_string();
+                let bytes = to_bytes(req.into_body(), usize::MAX).await.unwrap();
+                let body: Value = serde_json::from_slice(&bytes).unwrap();
+                captured.lock().unwrap().push((path.clone(), body.clone()));
+
+                let response = if path.ends_with(":batchEmbedContents") {
+                    let count = body["request"]["requests"].as_array().unwrap().len();
+                    let embeddings: Vec<Value> = (0..count)
+                        .map(|i| json!({"values": [i as f64, 0.5, -1.0]}))
+                        .collect();
+                    json!({"response": {"embeddings": embeddings}})
+                } else {
+                    json!({"response": {"embedding": {"values": [0.25, 0.75]}}})
+                };
+                Json(response)
+            }
+        });
+
+        support::spawn_upstream(app).await
+    }
+
+    async fn test_state(base_url: String) -> AppState {
+        support::app_state(base_url, &["acc"]).await
+    }
+
+    async fn response_json(response: Response) -> Value {
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[test]
+    fn test_build_batch_request_with_dimensions() {
+        let texts = vec!["a".to_string(), "b".to_string()];
+        let req = build_batch_request("gemini-embedding-001", &texts, Some(256));
+        let requests = req["requests"].as_array().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1]["model"], "models/gemini-embedding-001");
+        assert_eq!(requests[1]["content"]["parts"][0]["text"], "b");
+        assert_eq!(requests[0]["outputDimensionality"], 256);
+    }
+
+    #[test]
+    fn test_base64_encoding_is_f32_le() {
+        let encoded = encode_embedding_base64(&[json!(1.0), json!(-2.5)]);
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .unwrap();
+        assert_eq!(bytes.len(), 8);
+        assert_eq!(f32::from_le_bytes(bytes[4..8].try_into().unwrap()), -2.5);
+    }
+
+    #[tokio::test]
+    async fn test_openai_embeddings_via_mock_upstream() {
+        let captured: Captured = Arc::new(Mutex::new(Vec::new()));
+        let state = test_state(spawn_mock_upstream(captured.clone()).await).await;
+
+        let body = json!({
+            "model": "text-embedding-3-small",
+            "input": ["hello", "world"],
+            "dimensions": 3
+        });
+        let response = handle_embeddings(State(state.clone()), Json(body))
+            .await
+            .unwrap()
+            .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let json = response_json(response).await;
+        assert_eq!(json["object"], "list");
+        assert_eq!(json["model"], "text-embedding-3-small");
+        assert_eq!(json["data"].as_array().unwrap().len(), 2);
+        assert_eq!(json["data"][1]["index"], 1);
+        assert_eq!(json["data"][1]["embedding"], json!([1.0, 0.5, -1.0]));
+        assert!(json["usage"]["prompt_tokens"].as_u64().unwrap() > 0);
+
+        let calls = captured.lock().unwrap();
+        assert_eq!(calls.len(), 1);
+        let (path, upstream_body) = &calls[0];
+        assert!(path.ends_with(":batchEmbedContents"));
+        assert_eq!(upstream_body["project"], "test-project");
+        assert_eq!(upstream_body["model"], "gemini-embedding-001");
+        assert_eq!(
+            upstream_body["request"]["requests"][0]["outputDimensionality"],
+            3
+        );
+    }
+
+    #[tokio::test]
+    async fn test_openai_embeddings_single_string_input() {
+        let captured: Captured = Arc::new(Mutex::new(Vec::new()));
+        let state = test_state(spawn_mock_upstream(captured.clone()).await).await;
+
+        let body = json!({"model": "text-embedding-004", "input": "just one"});
+        let response = handle_embeddings(State(state), Json(body))
+            .await
+            .unwrap()
+            .into_response();
+        let json = response_json(response).await;
+        assert_eq!(json["data"].as_array().unwrap().len(), 1);
+
+        let calls = captured.lock().unwrap();
+        assert_eq!(
+            calls[0].1["request"]["requests"][0]["content"]["parts"][0]["text"],
+            "just one"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unknown_embedding_model_rejected() {
+        let captured: Captured = Arc::new(Mutex::new(Vec::new()));
+        let state = test_state(spawn_mock_upstream(captured.clone()).await).await;
+
+        let body = json!({"model": "my-embedder", "input": "hi"});
+        let response = handle_embeddings(State(state.clone()), Json(body))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            response.headers()[axum::http::header::CONTENT_TYPE],
+            "application/json"
+        );
+        let error = response_json(response).await;
+        assert_eq!(error["error"]["code"], "model_not_found");
+
+        let body = json!({"content": {"parts": [{"text": "hi"}]}});
+        let response = handle_gemini_embed(&state, "gemini-2.5-flash", "embedContent", body)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(captured.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_gemini_native_embed_content() {
+        let captured: Captured = Arc::new(Mutex::new(Vec::new()));
+        let state = test_state(spawn_mock_upstream(captured.clone()).await).await;
+
+        let body = json!({"content": {"parts": [{"text": "hi"}]}, "taskType": "RETRIEVAL_QUERY"});
+        let response = handle_gemini_embed(&state, "text-embedding-004", "embedContent", body)
+            .await
+            .unwrap();
+        let json = response_json(response).await;
+        assert_eq!(json["embedding"]["values"], json!([0.25, 0.75]));
+
+        let calls = captured.lock().unwrap();
+        assert!(calls[0].0.ends_with(":embedContent"));
+        assert_eq!(calls[0].1["request"]["model"], "models/text-embedding-004");
+        assert_eq!(calls[0].1["request"]["taskType"], "RETRIEVAL_QUERY");
+    }
+}
@@ -0,0 +1,427 @@
+// Files API Handler (/v1/files)
+// OpenAI 与 Anthropic 共用同一路径，按请求头区分响应格式：
+// 带 `anthropic-version` / `anthropic-beta` 的请求返回 Anthropic 文件对象，其余按 OpenAI 格式。
+use axum::{
+    body::Body,
+    extract::{Extension, Json, Multipart, Path, Query},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::modules::file_store::{self, FileRecord, FileStore, DEFAULT_FILE_TTL_SECS};
+use crate::proxy::client_keys::{owner_id, ClientKeyIdentity};
+use crate::proxy::common::media_fetch;
+
+/// 单个上传文件的大小上限
+const MAX_UPLOAD_BYTES: usize = 100 * 1024 * 1024;
+const DEFAULT_LIST_LIMIT: usize = 20;
+const MAX_LIST_LIMIT: usize = 10_000;
+/// OpenAI `expires_after[seconds]` 的允许范围 (1 小时 - 30 天)
+const MIN_TTL_SECS: i64 = 60 * 60;
+const MAX_TTL_SECS: i64 = 30 * 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Flavor {
+    OpenAI,
+    Anthropic,
+}
+
+impl Flavor {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        if headers.contains_key("anthropic-version") || headers.contains_key("anthropic-beta") {
+            Self::Anthropic
+        } else {
+            Self::OpenAI
+        }
+    }
+
+    fn error(self, status: StatusCode, message: &str) -> Response {
+        let body = match self {
+            Self::Anthropic => json!({
+                "type": "error",
+                "error": {
+                    "type": match status {
+                        StatusCode::NOT_FOUND => "not_found_error",
+                        StatusCode::PAYLOAD_TOO_LARGE => "request_too_large",
+                        s if s.is_server_error() => "api_error",
+                        _ => "invalid_request_error",
+                    },
+                    "message": message
+                }
+            }),
+            Self::OpenAI => json!({
+                "error": {
+                    "message": message,
+                    "type": if status.is_server_error() { "server_error" } else { "invalid_request_error" },
+                    "param": null,
+                    "code": null
+                }
+            }),
+        };
+        (status, Json(body)).into_response()
+    }
+
+    fn not_found(self, file_id: &str) -> Response {
+        self.error(
+            StatusCode::NOT_FOUND,
+            &format!("No such File object: {}", file_id),
+        )
+    }
+
+    fn file_object(self, file: &FileRecord) -> Value {
+        match self {
+            Self::Anthropic => json!({
+                "id": file.id,
+                "type": "file",
+                "filename": file.filename,
+                "mime_type": file.mime_type,
+                "size_bytes": file.bytes,
+                "created_at": chrono::DateTime::from_timestamp_millis(file.created_at)
+                    .map(|t| t.to_rfc3339_opts(chrono::SecondsFormat::Millis, true)),
+                "downloadable": true,
+            }),
+            Self::OpenAI => json!({
+                "id": file.id,
+                "object": "file",
+                "bytes": file.bytes,
+                "created_at": file.created_at / 1000,
+                "expires_at": file.expires_at / 1000,
+                "filename": file.filename,
+                "purpose": file.purpose,
+                "status": "processed",
+            }),
+        }
+    }
+}
+
+/// 读取文件元数据；不属于 `owner` 的文件按不存在处理
+fn load_owned(
+    store: &FileStore,
+    file_id: &str,
+    owner: Option<&str>,
+) -> Result<Option<FileRecord>, String> {
+    Ok(store
+        .get(file_id)?
+        .filter(|file| file.client_key.as_deref() == owner))
+}
+
+/// 上传文件的 MIME：优先使用表单声明的类型，否则按文件头 / 扩展名识别
+fn upload_mime_type(declared: Option<&str>, filename: &str, data: &[u8]) -> String {
+    if let Some(mime) = media_fetch::resolve_mime(declared, data) {
+        return mime;
+    }
+    let lower = filename.to_ascii_lowercase();
+    if [".txt", ".md", ".csv", ".log"]
+        .iter()
+        .any(|ext| lower.ends_with(ext))
+    {
+        "text/plain".to_string()
+    } else {
+        "application/octet-stream".to_string()
+    }
+}
+
+/// POST /v1/files (multipart: file, purpose, expires_after[seconds])
+pub async fn handle_upload_file(
+    identity: Option<Extension<ClientKeyIdentity>>,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> Response {
+    let flavor = Flavor::from_headers(&headers);
+    let mut upload: Option<(String, Option<String>, Vec<u8>)> = None;
+    let mut purpose = None;
+    let mut ttl_secs = DEFAULT_FILE_TTL_SECS;
+
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => {
+                return flavor.error(
+                    StatusCode::BAD_REQUEST,
+                    &format!("Invalid multipart body: {}", e),
+                )
+            }
+        };
+        match field.name().unwrap_or("") {
+            "file" => {
+                let filename = field.file_name().unwrap_or("upload").to_string();
+                let content_type = field.content_type().map(String::from);
+                let data = match field.bytes().await {
+                    Ok(data) => data,
+                    Err(e) => {
+                        return flavor.error(
+                            StatusCode::BAD_REQUEST,
+                            &format!("Failed to read file: {}", e),
+                        )
+                    }
+                };
+                upload = Some((filename, content_type, data.to_vec()));
+            }
+            "purpose" => purpose = field.text().await.ok(),
+            "expires_after[seconds]" => {
+                let seconds = field.text().await.ok().and_then(|s| s.trim().parse().ok());
+                match seconds {
+                    Some(s) if (MIN_TTL_SECS..=MAX_TTL_SECS).contains(&s) => ttl_secs = s,
+                    _ => {
+                        return flavor.error(
+                            StatusCode::BAD_REQUEST,
+                            &format!(
+                                "expires_after[seconds] must be between {} and {}",
+                                MIN_TTL_SECS, MAX_TTL_SECS
+                            ),
+                        )
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let Some((filename, content_type, data)) = upload else {
+        return flavor.error(StatusCode::BAD_REQUEST, "Missing required field 'file'");
+    };
+    if data.is_empty() {
+        return flavor.error(StatusCode::BAD_REQUEST, "File is empty");
+    }
+    if data.len() > MAX_UPLOAD_BYTES {
+        return flavor.error(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            &format!(
+                "File exceeds the {} MB limit",
+                MAX_UPLOAD_BYTES / 1024 / 1024
+            ),
+        );
+    }
+    // Anthropic 上传无 purpose 字段
+    let purpose = match (purpose, flavor) {
+        (Some(purpose), _) => purpose,
+        (None, Flavor::Anthropic) => "user_data".to_string(),
+        (None, Flavor::OpenAI) => {
+            return flavor.error(StatusCode::BAD_REQUEST, "Missing required field 'purpose'")
+        }
+    };
+
+    let mime_type = upload_mime_type(content_type.as_deref(), &filename, &data);
+    let store = match file_store::default_store() {
+        Ok(store) => store,
+        Err(e) => return flavor.error(StatusCode::INTERNAL_SERVER_ERROR, &e),
+    };
+    match store.put(
+        &filename,
+        &purpose,
+        &mime_type,
+        &data,
+        ttl_secs,
+        owner_id(&identity),
+    ) {
+        Ok(file) => {
+            tracing::info!(
+                "[Files] Stored {} ({}, {} bytes)",
+                file.id,
+                file.mime_type,
+                file.bytes
+            );
+            Json(flavor.file_object(&file)).into_response()
+        }
+        Err(e) => flavor.error(StatusCode::INTERNAL_SERVER_ERROR, &e),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListFilesQuery {
+    purpose: Option<String>,
+    limit: Option<usize>,
+    /// OpenAI 游标
+    after: Option<String>,
+    /// Anthropic 游标
+    after_id: Option<String>,
+}
+
+/// GET /v1/files
+pub async fn handle_list_files(
+    identity: Option<Extension<ClientKeyIdentity>>,
+    headers: HeaderMap,
+    Query(query): Query<ListFilesQuery>,
+) -> Response {
+    let flavor = Flavor::from_headers(&headers);
+    let store = match file_store::default_store() {
+        Ok(store) => store,
+        Err(e) => return flavor.error(StatusCode::INTERNAL_SERVER_ERROR, &e),
+    };
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_LIST_LIMIT)
+        .clamp(1, MAX_LIST_LIMIT);
+    let after = query.after.as_deref().or(query.after_id.as_deref());
+    let (files, has_more) =
+        match store.list(query.purpose.as_deref(), limit, after, owner_id(&identity)) {
+            Ok(page) => page,
+            Err(e) => return flavor.error(StatusCode::BAD_REQUEST, &e),
+        };
+
+    let mut body = json!({
+        "data": files.iter().map(|f| flavor.file_object(f)).collect::<Vec<_>>(),
+        "first_id": files.first().map(|f| f.id.clone()),
+        "last_id": files.last().map(|f| f.id.clone()),
+        "has_more": has_more,
+    });
+    if flavor == Flavor::OpenAI {
+        body["object"] = json!("list");
+    }
+    Json(body).into_response()
+}
+
+/// GET /v1/files/:file_id
+pub async fn handle_get_file(
+    identity: Option<Extension<ClientKeyIdentity>>,
+    headers: HeaderMap,
+    Path(file_id): Path<String>,
+) -> Response {
+    let flavor = Flavor::from_headers(&headers);
+    let store = match file_store::default_store() {
+        Ok(store) => store,
+        Err(e) => return flavor.error(StatusCode::INTERNAL_SERVER_ERROR, &e),
+    };
+    match load_owned(&store, &file_id, owner_id(&identity)) {
+        Ok(Some(file)) => Json(flavor.file_object(&file)).into_response(),
+        Ok(None) => flavor.not_found(&file_id),
+        Err(e) => flavor.error(StatusCode::INTERNAL_SERVER_ERROR, &e),
+    }
+}
+
+/// DELETE /v1/files/:file_id
+pub async fn handle_delete_file(
+    identity: Option<Extension<ClientKeyIdentity>>,
+    headers: HeaderMap,
+    Path(file_id): Path<String>,
+) -> Response {
+    let flavor = Flavor::from_headers(&headers);
+    let store = match file_store::default_store() {
+        Ok(store) => store,
+        Err(e) => return flavor.error(StatusCode::INTERNAL_SERVER_ERROR, &e),
+    };
+    match load_owned(&store, &file_id, owner_id(&identity)) {
+        Ok(Some(_)) => {}
+        Ok(None) => return flavor.not_found(&file_id),
+        Err(e) => return flavor.error(StatusCode::INTERNAL_SERVER_ERROR, &e),
+    }
+    match store.delete(&file_id) {
+        Ok(true) => Json(match flavor {
+            Flavor::Anthropic => json!({"id": file_id, "type": "file_deleted"}),
+            Flavor::OpenAI => json!({"id": file_id, "object": "file", "deleted": true}),
+        })
+        .into_response(),
+        Ok(false) => flavor.not_found(&file_id),
+        Err(e) => flavor.error(StatusCode::INTERNAL_SERVER_ERROR, &e),
+    }
+}
+
+/// GET /v1/files/:file_id/content
+pub async fn handle_file_content(
+    identity: Option<Extension<ClientKeyIdentity>>,
+    headers: HeaderMap,
+    Path(file_id): Path<String>,
+) -> Response {
+    let flavor = Flavor::from_headers(&headers);
+    let store = match file_store::default_store() {
+        Ok(store) => store,
+        Err(e) => return flavor.error(StatusCode::INTERNAL_SERVER_ERROR, &e),
+    };
+    match load_owned(&store, &file_id, owner_id(&identity)) {
+        Ok(Some(_)) => {}
+        Ok(None) => return flavor.not_found(&file_id),
+        Err(e) => return flavor.error(StatusCode::INTERNAL_SERVER_ERROR, &e),
+    }
+    match store.read(&file_id) {
+        Ok(Some((file, data))) => Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, file.mime_type)
+            .header(
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"{}\"",
+                    file.filename.replace('"', "")
+                ),
+            )
+            .body(Body::from(data))
+            .unwrap(),
+        Ok(None) => flavor.not_found(&file_id),
+        Err(e) => flavor.error(StatusCode::INTERNAL_SERVER_ERROR, &e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flavor_and_file_objects() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(Flavor::from_headers(&headers), Flavor::OpenAI);
+        headers.insert("anthropic-version", "2023-06-01".parse().unwrap());
+        assert_eq!(Flavor::from_headers(&headers), Flavor::Anthropic);
+
+        let file = FileRecord {
+            id: "file-1".to_string(),
+            filename: "a.pdf".to_string(),
+            purpose: "user_data".to_string(),
+            mime_type: "application/pdf".to_string(),
+            bytes: 6,
+            sha256: "x".to_string(),
+            created_at: 1_000,
+            expires_at: 61_000,
+            client_key: None,
+        };
+        let openai = Flavor::OpenAI.file_object(&file);
+        assert_eq!(openai["object"], "file");
+        assert_eq!(openai["created_at"], 1);
+        assert_eq!(openai["expires_at"], 61);
+        let anthropic = Flavor::Anthropic.file_object(&file);
+        assert_eq!(anthropic["type"], "file");
+        assert_eq!(anthropic["size_bytes"], 6);
+        assert_eq!(anthropic["created_at"], "1970-01-01T00:00:01.000Z");
+
+        assert_eq!(
+            upload_mime_type(None, "notes.md", b"# hi"),
+            "text/plain".to_string()
+        );
+        assert_eq!(
+            upload_mime_type(Some("application/octet-stream"), "x", b"%PDF-1.4"),
+            "application/pdf".to_string()
+        );
+    }
+
+    #[test]
+    fn test_files_scoped_to_uploading_key() {
+        let dir = crate::proxy::tests::support::temp_dir("ag-files-owner");
+        let store = FileStore::open(&dir).unwrap();
+        let a = store
+            .put("a.txt", "user_data", "text/plain", b"a", 60, Some("key-a"))
+            .unwrap();
+        let b = store
+            .put("b.txt", "user_data", "text/plain", b"b", 60, Some("key-b"))
+            .unwrap();
+
+        assert_eq!(
+            load_owned(&store, &a.id, Some("key-a"))
+                .unwrap()
+                .unwrap()
+                .id,
+            a.id
+        );
+        assert!(load_owned(&store, &b.id, Some("key-a")).unwrap().is_none());
+        assert!(load_owned(&store, &b.id, None).unwrap().is_none());
+        let (files, has_more) = store.list(None, 10, None, Some("key-a")).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].id, a.id);
+        assert!(!has_more);
+        // 其他 key 的文件不能作为分页游标
+        assert!(store.list(None, 10, Some(&b.id), Some("key-a")).is_err());
+        assert!(store.list(None, 10, None, None).unwrap().0.is_empty());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
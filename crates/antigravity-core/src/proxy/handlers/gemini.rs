@@ -1,27 +1,59 @@
 // Gemini Handler
 use axum::{
+    body::Body,
     extract::State,
-    extract::{Json, Path},
+    extract::{Json, Path, Query},
     http::StatusCode,
-    response::IntoResponse,
+    response::{IntoResponse, Response},
 };
+use bytes::Bytes;
+use futures::StreamExt;
 use serde_json::{json, Value};
+use std::collections::HashMap;
 use tracing::{debug, error, info};
 
+use crate::proxy::common::token_counter;
+use crate::proxy::mappers::gemini::errors::google_error_response;
+use crate::proxy::mappers::gemini::streaming::{GeminiStreamFormat, GeminiStreamTransformer};
 use crate::proxy::mappers::gemini::{unwrap_response, wrap_request};
 use crate::proxy::server::AppState;
 use crate::proxy::session_manager::SessionManager;
-
-const MAX_RETRY_ATTEMPTS: usize = 10;
-const MAX_529_RETRY_ATTEMPTS: usize = 3600; // 529 = 1 hour of retries (1 attempt per second)
+use crate::proxy::upstream::retry::{RetryDecision, RetryState};
+use antigravity_shared::proxy::config::Protocol;
 
 /// 处理 generateContent 和 streamGenerateContent
 /// 路径参数: model_name, method (e.g. "gemini-pro", "generateContent")
+/// 错误统一返回 Google 风格错误体，与官方 SDK 的错误解析保持一致
 pub async fn handle_generate(
     State(state): State<AppState>,
     Path(model_action): Path<String>,
-    Json(body): Json<Value>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    Query(params): Query<HashMap<String, String>>,
+    body: Bytes,
+) -> Response {
+    let body: Value = match serde_json::from_slice(&body) {
+        Ok(body) => body,
+        Err(e) => {
+            return google_error_response(
+                StatusCode::BAD_REQUEST,
+                &format!("Invalid JSON payload received. {}", e),
+            )
+        }
+    };
+    // streamGenerateContent: ?alt=sse 输出 SSE，否则输出 JSON 数组
+    let stream_format = GeminiStreamFormat::from_alt(params.get("alt").map(String::as_str));
+
+    match generate(state, model_action, stream_format, body).await {
+        Ok(response) => response,
+        Err((status, message)) => google_error_response(status, &message),
+    }
+}
+
+async fn generate(
+    state: AppState,
+    model_action: String,
+    stream_format: GeminiStreamFormat,
+    body: Value,
+) -> Result<Response, (StatusCode, String)> {
     // 解析 model:method
     let (model_name, method) = if let Some((m, action)) = model_action.rsplit_once(':') {
         (m.to_string(), action.to_string())
@@ -31,6 +63,16 @@ pub async fn handle_generate(
 
     tracing::info!("Received Gemini request: {}/{}", model_name, method);
 
+    // models/{model}:countTokens 与 /countTokens 路由共用同一实现
+    if method == "countTokens" {
+        let total_tokens = count_tokens(&state, &model_name, &body).await;
+        return Ok(Json(json!({ "totalTokens": total_tokens })).into_response());
+    }
+
+    if method == "embedContent" || method == "batchEmbedContents" {
+        return super::embeddings::handle_gemini_embed(&state, &model_name, &method, body).await;
+    }
+
     // 1. 验证方法
     if method != "generateContent" && method != "streamGenerateContent" {
         return Err((
@@ -40,26 +82,31 @@ pub async fn handle_generate(
     }
     let is_stream = method == "streamGenerateContent";
 
-    // 2. 获取 UpstreamClient 和 TokenManager
-    let upstream = state.upstream.clone();
-    let token_manager = state.token_manager;
-    let pool_size = token_manager.len();
-    // max_attempts НЕ ограничен pool_size: при 529/503 мы не ротируем аккаунт
-    let max_attempts = if pool_size == 0 {
-        1
-    } else {
-        MAX_RETRY_ATTEMPTS
-    };
+    // 2. 获取 TokenManager 与重试策略
+    // 最大尝试次数不受账号池大小限制: 529/503 时不轮换账号
+    let token_manager = state.token_manager.clone();
+    let retry_policy = state.retry_policy.read().await.clone();
+    let mut retry = RetryState::new(retry_policy, token_manager.len());
 
     let mut last_error = String::new();
-    let mut skip_rotation = false; // 529/503 时跳过账号轮换
+    let mut quota_exhausted = false; // 本轮是否因 429 耗尽所有尝试
 
-    for attempt in 0..max_attempts {
-        // 3. 模型路由解析
-        let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
-            &model_name,
-            &*state.custom_mapping.read().await,
-        );
+    // 3. 模型路由解析 (有序规则 + 回退链)
+    let mut route = super::common::resolve_route(&state, &model_name, Protocol::Gemini).await;
+
+    loop {
+        if retry.is_exhausted() {
+            // 所有尝试均因配额耗尽失败时切换到回退模型
+            if quota_exhausted && route.advance(&last_error) {
+                retry.reset();
+                quota_exhausted = false;
+                continue;
+            }
+            break;
+        }
+
+        let mapped_model = route.model().to_string();
+        let mapped_label = route.label();
         // 提取 tools 列表以进行联网探测 (Gemini 风格可能是嵌套的)
         let tools_val: Option<Vec<Value>> =
             body.get("tools").and_then(|t| t.as_array()).map(|arr| {
@@ -87,15 +134,23 @@ pub async fn handle_generate(
         // 提取 SessionId (粘性指纹)
         let session_id = SessionManager::extract_gemini_session_id(&body, &model_name);
 
-        // 关键修复: 529/503 时不轮换账号（服务端问题，轮换无意义）
-        let force_rotate = attempt > 0 && !skip_rotation;
-        skip_rotation = false; // 重置标志
+        // 是否换账号由重试策略决定 (529/503 等服务端问题不轮换)
+        let force_rotate = retry.take_rotation();
         let (access_token, project_id, email) = match token_manager
-            .get_token(&config.request_type, force_rotate, Some(&session_id))
+            .get_token_for_model(
+                &config.request_type,
+                force_rotate,
+                Some(&session_id),
+                Some(&mapped_model),
+            )
             .await
         {
             Ok(t) => t,
             Err(e) => {
+                if route.advance(&e) {
+                    retry.reset();
+                    continue;
+                }
                 return Err((
                     StatusCode::SERVICE_UNAVAILABLE,
                     format!("Token error: {}", e),
@@ -116,103 +171,43 @@ pub async fn handle_generate(
             "generateContent"
         };
 
-        let response = match upstream
-            .call_v1_internal(
-                upstream_method,
-                &access_token,
-                wrapped_body.clone(),
-                query_string,
-            )
-            .await
-        {
+        let (email, response) = super::common::call_upstream(
+            &state,
+            upstream_method,
+            query_string,
+            wrapped_body,
+            &access_token,
+            email,
+            &mapped_model,
+        )
+        .await;
+        let response = match response {
             Ok(r) => r,
             Err(e) => {
                 last_error = e.clone();
                 debug!(
                     "Gemini Request failed on attempt {}/{}: {}",
-                    attempt + 1,
-                    max_attempts,
+                    retry.attempt(),
+                    retry.max_attempts(),
                     e
                 );
+                retry.on_network_error();
                 continue;
             }
         };
 
         let status = response.status();
         if status.is_success() {
+            token_manager.mark_account_success(&email);
+
             // 6. 响应处理
             if is_stream {
-                use axum::body::Body;
-                use axum::response::Response;
-                use bytes::{Bytes, BytesMut};
-                use futures::StreamExt;
-
-                let mut response_stream = response.bytes_stream();
-                let mut buffer = BytesMut::new();
-
-                let stream = async_stream::stream! {
-                    while let Some(item) = response_stream.next().await {
-                        match item {
-                            Ok(bytes) => {
-                                debug!("[Gemini-SSE] Received chunk: {} bytes", bytes.len());
-                                buffer.extend_from_slice(&bytes);
-                                while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
-                                    let line_raw = buffer.split_to(pos + 1);
-                                    if let Ok(line_str) = std::str::from_utf8(&line_raw) {
-                                        let line = line_str.trim();
-                                        if line.is_empty() { continue; }
-
-                                        if line.starts_with("data: ") {
-                                            let json_part = line.trim_start_matches("data: ").trim();
-                                            if json_part == "[DONE]" {
-                                                yield Ok::<Bytes, String>(Bytes::from("data: [DONE]\n\n"));
-                                                continue;
-                                            }
-
-                                            match serde_json::from_str::<Value>(json_part) {
-                                                Ok(mut json) => {
-                                                    // Unwrap v1internal response wrapper
-                                                    if let Some(inner) = json.get_mut("response").map(|v| v.take()) {
-                                                        let new_line = format!("data: {}\n\n", serde_json::to_string(&inner).unwrap_or_default());
-                                                        yield Ok::<Bytes, String>(Bytes::from(new_line));
-                                                    } else {
-                                                        yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&json).unwrap_or_default())));
-                                                    }
-                                                }
-                                                Err(e) => {
-                                                    debug!("[Gemini-SSE] JSON parse error: {}, passing raw line", e);
-                                                    yield Ok::<Bytes, String>(Bytes::from(format!("{}\n\n", line)));
-                                                }
-                                            }
-                                        } else {
-                                            // Non-data lines (comments, etc.)
-                                            yield Ok::<Bytes, String>(Bytes::from(format!("{}\n\n", line)));
-                                        }
-                                    } else {
-                                        // Non-UTF8 data? Just pass it through or skip
-                                        debug!("[Gemini-SSE] Non-UTF8 line encountered");
-                                        yield Ok::<Bytes, String>(line_raw.freeze());
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                error!("[Gemini-SSE] Connection error: {}", e);
-                                yield Err(format!("Stream error: {}", e));
-                            }
-                        }
-                    }
-                };
-
-                let body = Body::from_stream(stream);
-                return Ok(Response::builder()
-                    .header("Content-Type", "text/event-stream")
-                    .header("Cache-Control", "no-cache")
-                    .header("Connection", "keep-alive")
-                    .header("X-Account-Email", &email)
-                    .header("X-Mapped-Model", &mapped_model)
-                    .body(body)
-                    .unwrap()
-                    .into_response());
+                return Ok(stream_response(
+                    response,
+                    stream_format,
+                    &email,
+                    &mapped_label,
+                ));
             }
 
             let gemini_resp: Value = response
@@ -225,7 +220,7 @@ pub async fn handle_generate(
                 StatusCode::OK,
                 [
                     ("X-Account-Email", email.as_str()),
-                    ("X-Mapped-Model", mapped_model.as_str()),
+                    ("X-Mapped-Model", mapped_label.as_str()),
                 ],
                 Json(unwrapped),
             )
@@ -245,188 +240,110 @@ pub async fn handle_generate(
             .unwrap_or_else(|_| format!("HTTP {}", status_code));
         last_error = format!("HTTP {}: {}", status_code, error_text);
 
-        // 处理可重试错误
-        if status_code == 429
-            || status_code == 529
-            || status_code == 503
-            || status_code == 500
-            || status_code == 403
-            || status_code == 401
-        {
-            // 记录限流信息 (全局同步)
+        // 反馈给熔断器 / 健康监控 / AIMD
+        token_manager
+            .report_upstream_error(&email, status_code, &error_text)
+            .await;
+
+        // 记录限流信息 (全局同步)
+        if matches!(status_code, 429 | 529 | 503 | 500 | 403 | 401) {
             token_manager.mark_rate_limited(
                 &email,
                 status_code,
                 retry_after.as_deref(),
                 &error_text,
+                Some(&mapped_model),
             );
+        }
+        quota_exhausted = status_code == 429;
 
-            // 只有明确包含 "QUOTA_EXHAUSTED" 才停止
-            if status_code == 429 && error_text.contains("QUOTA_EXHAUSTED") {
-                error!(
-                    "Gemini Quota exhausted (429) on account {} attempt {}/{}, stopping to protect pool.",
-                    email,
-                    attempt + 1,
-                    max_attempts
-                );
-                return Err((status, error_text));
-            }
-
-            // 529 服务器过载 - 内部循环，最多重试 MAX_529_RETRY_ATTEMPTS 次
-            if status_code == 529 {
-                for retry_529 in 0..MAX_529_RETRY_ATTEMPTS {
-                    tracing::warn!(
-                        "Gemini Upstream 529 (server overload) on {} attempt {}/{}, waiting 1s",
-                        email,
-                        retry_529 + 1,
-                        MAX_529_RETRY_ATTEMPTS
-                    );
-                    tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-
-                    // 重新发送请求
-                    let retry_response = match upstream
-                        .call_v1_internal(
-                            upstream_method,
-                            &access_token,
-                            wrapped_body.clone(),
-                            query_string,
-                        )
-                        .await
-                    {
-                        Ok(r) => r,
-                        Err(e) => {
-                            tracing::debug!("529 retry {} failed: {}", retry_529 + 1, e);
-                            continue;
-                        }
-                    };
-
-                    let retry_status = retry_response.status();
-                    if retry_status.is_success() {
-                        // 成功！返回结果
-                        if is_stream {
-                            use axum::body::Body;
-                            use axum::response::Response;
-                            use bytes::{Bytes, BytesMut};
-                            use futures::StreamExt;
-
-                            let mut response_stream = retry_response.bytes_stream();
-                            let mut buffer = BytesMut::new();
-
-                            let stream = async_stream::stream! {
-                                while let Some(item) = response_stream.next().await {
-                                    match item {
-                                        Ok(bytes) => {
-                                            buffer.extend_from_slice(&bytes);
-                                            while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
-                                                let line_raw = buffer.split_to(pos + 1);
-                                                if let Ok(line_str) = std::str::from_utf8(&line_raw) {
-                                                    let line = line_str.trim();
-                                                    if line.is_empty() { continue; }
-                                                    if line.starts_with("data: ") {
-                                                        let json_part = line.trim_start_matches("data: ").trim();
-                                                        if json_part == "[DONE]" {
-                                                            yield Ok::<Bytes, String>(Bytes::from("data: [DONE]\n\n"));
-                                                            continue;
-                                                        }
-                                                        match serde_json::from_str::<Value>(json_part) {
-                                                            Ok(mut json) => {
-                                                                if let Some(inner) = json.get_mut("response").map(|v| v.take()) {
-                                                                    yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&inner).unwrap_or_default())));
-                                                                } else {
-                                                                    yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&json).unwrap_or_default())));
-                                                                }
-                                                            }
-                                                            Err(_) => {
-                                                                yield Ok::<Bytes, String>(Bytes::from(format!("{}\n\n", line)));
-                                                            }
-                                                        }
-                                                    } else {
-                                                        yield Ok::<Bytes, String>(Bytes::from(format!("{}\n\n", line)));
-                                                    }
-                                                }
-                                            }
-                                        }
-                                        Err(e) => {
-                                            yield Err(format!("Stream error: {}", e));
-                                        }
-                                    }
-                                }
-                            };
-
-                            let body = Body::from_stream(stream);
-                            return Ok(Response::builder()
-                                .header("Content-Type", "text/event-stream")
-                                .header("Cache-Control", "no-cache")
-                                .header("Connection", "keep-alive")
-                                .header("X-Account-Email", &email)
-                                .header("X-Mapped-Model", &mapped_model)
-                                .body(body)
-                                .unwrap()
-                                .into_response());
-                        }
-
-                        let gemini_resp: Value = retry_response.json().await.map_err(|e| {
-                            (StatusCode::BAD_GATEWAY, format!("Parse error: {}", e))
-                        })?;
-                        let unwrapped = unwrap_response(&gemini_resp);
-                        return Ok((
-                            StatusCode::OK,
-                            [
-                                ("X-Account-Email", email.as_str()),
-                                ("X-Mapped-Model", mapped_model.as_str()),
-                            ],
-                            Json(unwrapped),
-                        )
-                            .into_response());
-                    }
-
-                    // 529 继续重试，其他错误退出内部循环
-                    if retry_response.status().as_u16() != 529 {
-                        break;
-                    }
-                }
-                // 内部循环耗尽，继续外部循环（尝试下一个账号）
+        // 只有明确包含 "QUOTA_EXHAUSTED" 才停止 (存在回退模型时改用回退模型)
+        if status_code == 429 && error_text.contains("QUOTA_EXHAUSTED") {
+            if route.advance(&error_text) {
+                retry.reset();
+                quota_exhausted = false;
                 continue;
             }
+            error!(
+                "Gemini Quota exhausted (429) on account {} attempt {}/{}, stopping to protect pool.",
+                email,
+                retry.attempt(),
+                retry.max_attempts()
+            );
+            return Err((status, error_text));
+        }
 
-            // 503 服务不可用 - exponential backoff
-            if status_code == 503 {
-                let backoff_ms = 1000_u64 * 2_u64.pow(attempt as u32).min(8000);
+        // 按 ProxyConfig.retry 的状态码规则重试
+        match retry.on_status(status_code, retry_after.as_deref(), &error_text) {
+            RetryDecision::Retry { delay, rotate } => {
                 tracing::warn!(
-                    "Gemini Upstream 503 (service unavailable) on {} attempt {}/{}, waiting {}ms (no rotation)",
+                    "Gemini Upstream {} on {} attempt {}/{}, waiting {}ms (rotate: {})",
+                    status_code,
                     email,
-                    attempt + 1,
-                    max_attempts,
-                    backoff_ms
+                    retry.attempt(),
+                    retry.max_attempts(),
+                    delay.as_millis(),
+                    rotate
                 );
-                tokio::time::sleep(tokio::time::Duration::from_millis(backoff_ms)).await;
-                skip_rotation = true;
-                continue;
+                tokio::time::sleep(delay).await;
+            }
+            RetryDecision::Exhausted => {}
+            RetryDecision::FailFast => {
+                // 404 等由于模型配置或路径错误的 HTTP 异常，直接报错，不进行无效轮换
+                error!(
+                    "Gemini Upstream non-retryable error {}: {}",
+                    status_code, error_text
+                );
+                return Err((status, error_text));
             }
-
-            tracing::warn!(
-                "Gemini Upstream {} on account {} attempt {}/{}, rotating account",
-                status_code,
-                email,
-                attempt + 1,
-                max_attempts
-            );
-            continue;
         }
-
-        // 404 等由于模型配置或路径错误的 HTTP 异常，直接报错，不进行无效轮换
-        error!(
-            "Gemini Upstream non-retryable error {}: {}",
-            status_code, error_text
-        );
-        return Err((status, error_text));
     }
 
-    Ok((
+    Err((
         StatusCode::TOO_MANY_REQUESTS,
         format!("All accounts exhausted. Last error: {}", last_error),
-    )
-        .into_response())
+    ))
+}
+
+/// 将上游 SSE 转换为客户端请求的流式格式 (SSE 或 JSON 数组)
+fn stream_response(
+    response: reqwest::Response,
+    format: GeminiStreamFormat,
+    email: &str,
+    mapped_label: &str,
+) -> Response {
+    let mut response_stream = response.bytes_stream();
+    let mut transformer = GeminiStreamTransformer::new(format);
+
+    let stream = async_stream::stream! {
+        while let Some(item) = response_stream.next().await {
+            match item {
+                Ok(bytes) => {
+                    debug!("[Gemini-SSE] Received chunk: {} bytes", bytes.len());
+                    for chunk in transformer.push(&bytes) {
+                        yield Ok::<Bytes, String>(chunk);
+                    }
+                }
+                Err(e) => {
+                    error!("[Gemini-SSE] Connection error: {}", e);
+                    yield Err(format!("Stream error: {}", e));
+                    return;
+                }
+            }
+        }
+        for chunk in transformer.finish() {
+            yield Ok::<Bytes, String>(chunk);
+        }
+    };
+
+    Response::builder()
+        .header("Content-Type", format.content_type())
+        .header("Cache-Control", "no-cache")
+        .header("Connection", "keep-alive")
+        .header("X-Account-Email", email)
+        .header("X-Mapped-Model", mapped_label)
+        .body(Body::from_stream(stream))
+        .unwrap()
 }
 
 pub async fn handle_list_models(
@@ -468,20 +385,155 @@ pub async fn handle_get_model(Path(model_name): Path<String>) -> impl IntoRespon
 
 pub async fn handle_count_tokens(
     State(state): State<AppState>,
-    Path(_model_name): Path<String>,
-    Json(_body): Json<Value>,
+    Path(model_name): Path<String>,
+    Json(body): Json<Value>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let model_group = "gemini";
-    let (_access_token, _project_id, _) = state
-        .token_manager
-        .get_token(model_group, false, None)
+    let total_tokens = count_tokens(&state, &model_name, &body).await;
+    Ok(Json(json!({ "totalTokens": total_tokens })))
+}
+
+/// countTokens: 默认本地估算，开启 `experimental.upstream_count_tokens` 时转发上游
+async fn count_tokens(state: &AppState, model_name: &str, body: &Value) -> u32 {
+    let local_tokens = token_counter::count_gemini_request(body);
+
+    let total_tokens = if state.experimental.read().await.upstream_count_tokens {
+        let contents = body
+            .get("generateContentRequest")
+            .unwrap_or(body)
+            .get("contents")
+            .cloned()
+            .unwrap_or_else(|| json!([]));
+
+        match super::common::count_tokens_upstream(state, model_name, Protocol::Gemini, |_, _| {
+            Ok(contents)
+        })
         .await
-        .map_err(|e| {
-            (
-                StatusCode::SERVICE_UNAVAILABLE,
-                format!("Token error: {}", e),
-            )
-        })?;
+        {
+            // countTokens 只统计 contents，systemInstruction/tools 仍使用本地估算
+            Ok(contents_tokens) => {
+                contents_tokens + token_counter::count_gemini_system_and_tools(body)
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "[CountTokens] Upstream countTokens failed, using local estimate: {}",
+                    e
+                );
+                local_tokens
+            }
+        }
+    } else {
+        local_tokens
+    };
 
-    Ok(Json(json!({"totalTokens": 0})))
+    debug!(
+        "[CountTokens] model={} totalTokens={} (local={})",
+        model_name, total_tokens, local_tokens
+    );
+    total_tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::tests::support;
+    use antigravity_shared::proxy::config::{
+        RetryAction, RetryBackoff, RetryPolicyConfig, RetryStatusRule,
+    };
+    use axum::body::to_bytes;
+    use axum::extract::Request;
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+
+    /// 本地 mock 上游：按脚本依次返回状态码，记录每次调用的 access token
+    async fn spawn_scripted_upstream(script: Vec<u16>, tokens: Arc<Mutex<Vec<String>>>) -> String {
+        let script = Arc::new(Mutex::new(VecDeque::from(script)));
+        let app = axum::Router::new().fallback(move |req: Request| {
+            let script = script.clone();
+            let tokens = tokens.clone();
+            async move {
+                let token = req
+                    .headers()
+                    .get("authorization")
+                    .and_then(|v| v.to_str().ok())
+                    .unwrap_or_default()
+                    .trim_start_matches("Bearer ")
+                    .to_string();
+                tokens.lock().unwrap().push(token);
+                let status = script.lock().unwrap().pop_front().unwrap_or(200);
+                let body = if status == 200 {
+                    json!({"response": {"candidates": [{"content": {"role": "model", "parts": [{"text": "ok"}]}}]}})
+                } else {
+                    json!({"error": {"code": status, "message": "scripted failure"}})
+                };
+                (StatusCode::from_u16(status).unwrap(), Json(body))
+            }
+        });
+
+        support::spawn_upstream(app).await
+    }
+
+    /// 两个账号 + 无等待的重试规则 (额外: 502 同账号重试)
+    async fn test_state(base_url: String) -> AppState {
+        let state = support::app_state(base_url, &["a", "b"]).await;
+
+        let mut retry_policy = RetryPolicyConfig::default();
+        retry_policy.rules.push(RetryStatusRule {
+            status: 502,
+            action: RetryAction::SameAccount,
+            backoff: RetryBackoff::Fixed,
+            base_delay_ms: 0,
+            max_delay_ms: 0,
+            counts_toward_limit: true,
+        });
+        for rule in &mut retry_policy.rules {
+            rule.base_delay_ms = 0;
+        }
+        retry_policy.honor_retry_after = false;
+
+        *state.retry_policy.write().await = retry_policy;
+        state
+    }
+
+    async fn run(state: AppState) -> Response {
+        let body = json!({"contents": [{"role": "user", "parts": [{"text": "hi"}]}]});
+        match generate(
+            state,
+            "gemini-2.5-flash:generateContent".to_string(),
+            GeminiStreamFormat::from_alt(None),
+            body,
+        )
+        .await
+        {
+            Ok(response) => response,
+            Err((status, message)) => google_error_response(status, &message),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_policy_same_account_then_rotate() {
+        let tokens = Arc::new(Mutex::new(Vec::new()));
+        let base_url = spawn_scripted_upstream(vec![502, 502, 429], tokens.clone()).await;
+        let response = run(test_state(base_url).await).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let tokens = tokens.lock().unwrap().clone();
+        assert_eq!(tokens.len(), 4);
+        // 502 按配置用同一账号重试，429 换账号
+        assert_eq!(tokens[0], tokens[1]);
+        assert_eq!(tokens[1], tokens[2]);
+        assert_ne!(tokens[2], tokens[3]);
+    }
+
+    #[tokio::test]
+    async fn test_retry_policy_fails_fast_on_unlisted_status() {
+        let tokens = Arc::new(Mutex::new(Vec::new()));
+        let base_url = spawn_scripted_upstream(vec![404], tokens.clone()).await;
+        let response = run(test_state(base_url).await).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["error"]["code"], 404);
+        assert_eq!(tokens.lock().unwrap().len(), 1);
+    }
 }
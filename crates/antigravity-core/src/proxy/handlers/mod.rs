@@ -2,8 +2,12 @@
 // 核心端点处理器模块
 
 pub mod audio;
+pub mod batches;
 pub mod claude;
 pub mod common;
+pub mod embeddings;
+pub mod files;
 pub mod gemini;
 pub mod mcp;
 pub mod openai;
+pub mod responses;
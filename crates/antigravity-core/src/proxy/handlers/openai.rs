@@ -1,27 +1,66 @@
 // OpenAI Handler
-use axum::{extract::Json, extract::State, http::StatusCode, response::IntoResponse};
+use axum::{
+    extract::Json,
+    extract::State,
+    http::{HeaderMap, Method, StatusCode},
+    response::IntoResponse,
+    Extension,
+};
 use base64::Engine as _;
 use bytes::Bytes;
 use serde_json::{json, Value};
 use tracing::{debug, error, info}; // Import Engine trait for encode method
 
+use crate::proxy::client_keys::{owner_id, ClientKeyIdentity};
 use crate::proxy::mappers::openai::{
     transform_openai_request, transform_openai_response, OpenAIRequest,
 };
 // use crate::proxy::upstream::client::UpstreamClient; // 通过 state 获取
+use crate::proxy::providers::{passthrough, registry};
 use crate::proxy::server::AppState;
+use crate::proxy::upstream::retry::{RetryDecision, RetryState};
+use antigravity_shared::proxy::config::{Protocol, ProviderProtocol};
 
-const MAX_RETRY_ATTEMPTS: usize = 10;
-const MAX_529_RETRY_ATTEMPTS: usize = 3600; // 529 = 1 hour of retries (1 attempt per second)
 use crate::proxy::session_manager::SessionManager;
 
 pub async fn handle_chat_completions(
     State(state): State<AppState>,
+    identity: Option<Extension<ClientKeyIdentity>>,
+    headers: HeaderMap,
     Json(body): Json<Value>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
+    // 命中 OpenAI 兼容 provider (vLLM / Ollama 等) 时原样透传
+    let requested_model = body
+        .get("model")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+    if let Some(provider) =
+        registry::select_provider(&state, ProviderProtocol::OpenAI, requested_model).await
+    {
+        return Ok(passthrough::forward_json(
+            &state,
+            &provider,
+            Method::POST,
+            "/v1/chat/completions",
+            &headers,
+            body,
+        )
+        .await);
+    }
+
     let mut openai_req: OpenAIRequest = serde_json::from_value(body)
         .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)))?;
 
+    // http(s) 图片 URL 经由上游代理下载为 data URL
+    let upstream_proxy = state.upstream_proxy.read().await.clone();
+    crate::proxy::common::media_fetch::resolve_openai_media(
+        &mut openai_req,
+        &upstream_proxy,
+        owner_id(&identity),
+    )
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
     // Safety: Ensure messages is not empty
     if openai_req.messages.is_empty() {
         debug!("Received request with empty messages, injecting fallback...");
@@ -42,34 +81,30 @@ pub async fn handle_chat_completions(
     debug!("Received OpenAI request for model: {}", openai_req.model);
 
     // 1. 获取 UpstreamClient (Clone handle)
-    let upstream = state.upstream.clone();
-    let token_manager = state.token_manager;
-    let pool_size = token_manager.len();
-    let max_attempts = if pool_size == 0 {
-        1
-    } else {
-        MAX_RETRY_ATTEMPTS
-    };
+    let token_manager = state.token_manager.clone();
+    let retry_policy = state.retry_policy.read().await.clone();
+    let mut retry = RetryState::new(retry_policy, token_manager.len());
 
     let mut last_error = String::new();
-    let mut skip_rotation = false;
-    let mut attempt = 0_usize;
-    let mut retry_529_count = 0_usize;
+    let mut quota_exhausted = false; // 本轮是否因 429 耗尽所有尝试
+
+    // 2. 模型路由解析 (有序规则 + 回退链)
+    let mut route = super::common::resolve_route(&state, &openai_req.model, Protocol::OpenAI).await;
 
     loop {
-        // 检查是否超过最大尝试次数
-        if attempt >= max_attempts && retry_529_count == 0 {
-            break;
-        }
-        if retry_529_count >= MAX_529_RETRY_ATTEMPTS {
+        // 检查是否超过最大尝试次数 / 总时限
+        if retry.is_exhausted() {
+            // 所有尝试均因配额耗尽失败时切换到回退模型
+            if quota_exhausted && route.advance(&last_error) {
+                retry.reset();
+                quota_exhausted = false;
+                continue;
+            }
             break;
         }
 
-        // 2. 模型路由解析
-        let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
-            &openai_req.model,
-            &*state.custom_mapping.read().await,
-        );
+        let mapped_model = route.model().to_string();
+        let mapped_label = route.label();
         // 将 OpenAI 工具转为 Value 数组以便探测联网
         let tools_val: Option<Vec<Value>> = openai_req.tools.as_ref().map(|list| list.to_vec());
         let config = crate::proxy::mappers::common_utils::resolve_request_config(
@@ -82,15 +117,23 @@ pub async fn handle_chat_completions(
         let session_id = SessionManager::extract_openai_session_id(&openai_req);
 
         // 4. 获取 Token (使用准确的 request_type)
-        // 关键修复: 529/503 时不轮换账号（服务端问题，轮换无意义）
-        let force_rotate = attempt > 0 && !skip_rotation;
-        skip_rotation = false; // 重置标志
+        // 是否换账号由重试策略决定 (529/503 等服务端问题不轮换)
+        let force_rotate = retry.take_rotation();
         let (access_token, project_id, email) = match token_manager
-            .get_token(&config.request_type, force_rotate, Some(&session_id))
+            .get_token_for_model(
+                &config.request_type,
+                force_rotate,
+                Some(&session_id),
+                Some(&mapped_model),
+            )
             .await
         {
             Ok(t) => t,
             Err(e) => {
+                if route.advance(&e) {
+                    retry.reset();
+                    continue;
+                }
                 return Err((
                     StatusCode::SERVICE_UNAVAILABLE,
                     format!("Token error: {}", e),
@@ -125,25 +168,35 @@ pub async fn handle_chat_completions(
         };
         let query_string = if actual_stream { Some("alt=sse") } else { None };
 
-        let response = match upstream
-            .call_v1_internal(method, &access_token, gemini_body, query_string)
-            .await
-        {
+        let (email, response) = super::common::call_upstream(
+            &state,
+            method,
+            query_string,
+            gemini_body,
+            &access_token,
+            email,
+            &mapped_model,
+        )
+        .await;
+        let response = match response {
             Ok(r) => r,
             Err(e) => {
                 last_error = e.clone();
                 debug!(
                     "OpenAI Request failed on attempt {}/{}: {}",
-                    attempt + 1,
-                    max_attempts,
+                    retry.attempt(),
+                    retry.max_attempts(),
                     e
                 );
+                retry.on_network_error();
                 continue;
             }
         };
 
         let status = response.status();
         if status.is_success() {
+            token_manager.mark_account_success(&email);
+
             // 5. 处理流式 vs 非流式
             if actual_stream {
                 use crate::proxy::mappers::openai::streaming::create_openai_sse_stream;
@@ -151,8 +204,17 @@ pub async fn handle_chat_completions(
                 use axum::response::Response;
 
                 let gemini_stream = response.bytes_stream();
-                let openai_stream =
-                    create_openai_sse_stream(Box::pin(gemini_stream), openai_req.model.clone());
+                // 内部转流时始终带 usage，供收集器写入 response.usage
+                let include_usage = !client_wants_stream
+                    || openai_req
+                        .stream_options
+                        .as_ref()
+                        .is_some_and(|o| o.include_usage);
+                let openai_stream = create_openai_sse_stream(
+                    Box::pin(gemini_stream),
+                    openai_req.model.clone(),
+                    include_usage,
+                );
 
                 // 判断客户端期望的格式
                 if client_wants_stream {
@@ -163,7 +225,7 @@ pub async fn handle_chat_completions(
                         .header("Cache-Control", "no-cache")
                         .header("Connection", "keep-alive")
                         .header("X-Account-Email", &email)
-                        .header("X-Mapped-Model", &mapped_model)
+                        .header("X-Mapped-Model", &mapped_label)
                         .body(body)
                         .unwrap()
                         .into_response());
@@ -187,7 +249,7 @@ pub async fn handle_chat_completions(
                                 StatusCode::OK,
                                 [
                                     ("X-Account-Email", email.as_str()),
-                                    ("X-Mapped-Model", mapped_model.as_str()),
+                                    ("X-Mapped-Model", mapped_label.as_str()),
                                 ],
                                 Json(full_response),
                             )
@@ -213,7 +275,7 @@ pub async fn handle_chat_completions(
                 StatusCode::OK,
                 [
                     ("X-Account-Email", email.as_str()),
-                    ("X-Mapped-Model", mapped_model.as_str()),
+                    ("X-Mapped-Model", mapped_label.as_str()),
                 ],
                 Json(openai_response),
             )
@@ -240,111 +302,63 @@ pub async fn handle_chat_completions(
             error_text
         );
 
-        // 429/529/503/500 智能处理
+        // 反馈给熔断器 / 健康监控 / AIMD
+        token_manager
+            .report_upstream_error(&email, status_code, &error_text)
+            .await;
+
+        // 记录限流信息 (全局同步)
         if status_code == 429 || status_code == 529 || status_code == 503 || status_code == 500 {
-            // 记录限流信息 (全局同步)
             token_manager.mark_rate_limited(
                 &email,
                 status_code,
                 retry_after.as_deref(),
                 &error_text,
+                Some(&mapped_model),
             );
+        }
+        quota_exhausted = status_code == 429;
 
-            // 1. 优先尝试解析 RetryInfo (由 Google Cloud 直接下发)
-            if let Some(delay_ms) = crate::proxy::upstream::retry::parse_retry_delay(&error_text) {
-                let actual_delay = delay_ms.saturating_add(200).min(10_000);
+        // 只有明确包含 "QUOTA_EXHAUSTED" 才停止 (存在回退模型时改用回退模型)
+        if status_code == 429 && error_text.contains("QUOTA_EXHAUSTED") {
+            if route.advance(&error_text) {
+                retry.reset();
+                quota_exhausted = false;
+                continue;
+            }
+            error!(
+                "OpenAI Quota exhausted (429) on account {} attempt {}/{}, stopping to protect pool.",
+                email,
+                retry.attempt(),
+                retry.max_attempts()
+            );
+            return Err((status, error_text));
+        }
+
+        // 按 ProxyConfig.retry 的状态码规则重试
+        match retry.on_status(status_code, retry_after.as_deref(), &error_text) {
+            RetryDecision::Retry { delay, rotate } => {
                 tracing::warn!(
-                    "OpenAI Upstream {} on {} attempt {}/{}, waiting {}ms then retrying",
+                    "OpenAI Upstream {} on {} attempt {}/{}, waiting {}ms (rotate: {})",
                     status_code,
                     email,
-                    attempt + 1,
-                    max_attempts,
-                    actual_delay
+                    retry.attempt(),
+                    retry.max_attempts(),
+                    delay.as_millis(),
+                    rotate
                 );
-                tokio::time::sleep(tokio::time::Duration::from_millis(actual_delay)).await;
-                continue;
+                tokio::time::sleep(delay).await;
             }
-
-            // 2. 只有明确包含 "QUOTA_EXHAUSTED" 才停止
-            if error_text.contains("QUOTA_EXHAUSTED") {
+            RetryDecision::Exhausted => {}
+            RetryDecision::FailFast => {
+                // 404 等由于模型配置或路径错误的 HTTP 异常，直接报错，不进行无效轮换
                 error!(
-                    "OpenAI Quota exhausted (429) on account {} attempt {}/{}, stopping to protect pool.",
-                    email,
-                    attempt + 1,
-                    max_attempts
+                    "OpenAI Upstream non-retryable error {} on account {}: {}",
+                    status_code, email, error_text
                 );
                 return Err((status, error_text));
             }
-
-            // 529 服务器过载 - 无限重试（最多 MAX_529_RETRY_ATTEMPTS 次）
-            if status_code == 529 {
-                retry_529_count += 1;
-                if retry_529_count >= MAX_529_RETRY_ATTEMPTS {
-                    error!(
-                        "OpenAI 529 retry limit reached ({} attempts)",
-                        retry_529_count
-                    );
-                    return Err((status, error_text));
-                }
-                tracing::info!(
-                    "OpenAI 529 retry {}/{}, waiting 1s (same account: {})",
-                    retry_529_count,
-                    MAX_529_RETRY_ATTEMPTS,
-                    email
-                );
-                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-                skip_rotation = true;
-                continue;
-            }
-
-            // 其他错误：增加 attempt 计数, 重置 529 计数
-            retry_529_count = 0;
-            attempt += 1;
-
-            // 503 服务不可用 - exponential backoff
-            if status_code == 503 {
-                let backoff_ms = 1000_u64 * 2_u64.pow(attempt as u32).min(8000);
-                tracing::warn!(
-                    "OpenAI Upstream 503 (service unavailable) on {} attempt {}/{}, waiting {}ms (no rotation)",
-                    email,
-                    attempt,
-                    max_attempts,
-                    backoff_ms
-                );
-                tokio::time::sleep(tokio::time::Duration::from_millis(backoff_ms)).await;
-                skip_rotation = true;
-                continue;
-            }
-
-            // 4. 429/500 — 轮换账号
-            tracing::warn!(
-                "OpenAI Upstream {} on {} attempt {}/{}, rotating account",
-                status_code,
-                email,
-                attempt,
-                max_attempts
-            );
-            continue;
-        }
-
-        // 只有 403 (权限/地区限制) 和 401 (认证失效) 触发账号轮换
-        if status_code == 403 || status_code == 401 {
-            tracing::warn!(
-                "OpenAI Upstream {} on account {} attempt {}/{}, rotating account",
-                status_code,
-                email,
-                attempt + 1,
-                max_attempts
-            );
-            continue;
         }
-
-        // 404 等由于模型配置或路径错误的 HTTP 异常，直接报错，不进行无效轮换
-        error!(
-            "OpenAI Upstream non-retryable error {} on account {}: {}",
-            status_code, email, error_text
-        );
-        return Err((status, error_text));
     }
 
     // 所有尝试均失败
@@ -360,239 +374,10 @@ pub async fn handle_completions(
     State(state): State<AppState>,
     Json(mut body): Json<Value>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
-    info!(
-        "Received /v1/completions or /v1/responses payload: {:?}",
-        body
-    );
-
-    let is_codex_style = body.get("input").is_some() && body.get("instructions").is_some();
+    info!("Received /v1/completions payload: {:?}", body);
 
     // 1. Convert Payload to Messages (Shared Chat Format)
-    if is_codex_style {
-        let instructions = body
-            .get("instructions")
-            .and_then(|v| v.as_str())
-            .unwrap_or_default();
-        let input_items = body.get("input").and_then(|v| v.as_array());
-
-        let mut messages = Vec::new();
-
-        // System Instructions
-        if !instructions.is_empty() {
-            messages.push(json!({ "role": "system", "content": instructions }));
-        }
-
-        let mut call_id_to_name = std::collections::HashMap::new();
-
-        // Pass 1: Build Call ID to Name Map
-        if let Some(items) = input_items {
-            for item in items {
-                let item_type = item.get("type").and_then(|v| v.as_str()).unwrap_or("");
-                match item_type {
-                    "function_call" | "local_shell_call" | "web_search_call" => {
-                        let call_id = item
-                            .get("call_id")
-                            .and_then(|v| v.as_str())
-                            .or_else(|| item.get("id").and_then(|v| v.as_str()))
-                            .unwrap_or("unknown");
-
-                        let name = if item_type == "local_shell_call" {
-                            "shell"
-                        } else if item_type == "web_search_call" {
-                            "google_search"
-                        } else {
-                            item.get("name")
-                                .and_then(|v| v.as_str())
-                                .unwrap_or("unknown")
-                        };
-
-                        call_id_to_name.insert(call_id.to_string(), name.to_string());
-                        tracing::debug!("Mapped call_id {} to name {}", call_id, name);
-                    }
-                    _ => {}
-                }
-            }
-        }
-
-        // Pass 2: Map Input Items to Messages
-        if let Some(items) = input_items {
-            for item in items {
-                let item_type = item.get("type").and_then(|v| v.as_str()).unwrap_or("");
-                match item_type {
-                    "message" => {
-                        let role = item.get("role").and_then(|v| v.as_str()).unwrap_or("user");
-                        let content = item.get("content").and_then(|v| v.as_array());
-                        let mut text_parts = Vec::new();
-                        let mut image_parts: Vec<Value> = Vec::new();
-
-                        if let Some(parts) = content {
-                            for part in parts {
-                                // 处理文本块
-                                if let Some(text) = part.get("text").and_then(|v| v.as_str()) {
-                                    text_parts.push(text.to_string());
-                                }
-                                // [NEW] 处理图像块 (Codex input_image 格式)
-                                else if part.get("type").and_then(|v| v.as_str())
-                                    == Some("input_image")
-                                {
-                                    if let Some(image_url) =
-                                        part.get("image_url").and_then(|v| v.as_str())
-                                    {
-                                        image_parts.push(json!({
-                                            "type": "image_url",
-                                            "image_url": { "url": image_url }
-                                        }));
-                                        debug!("[Codex] Found input_image: {}", image_url);
-                                    }
-                                }
-                                // [NEW] 兼容标准 OpenAI image_url 格式
-                                else if part.get("type").and_then(|v| v.as_str())
-                                    == Some("image_url")
-                                {
-                                    if let Some(url_obj) = part.get("image_url") {
-                                        image_parts.push(json!({
-                                            "type": "image_url",
-                                            "image_url": url_obj.clone()
-                                        }));
-                                    }
-                                }
-                            }
-                        }
-
-                        // 构造消息内容：如果有图像则使用数组格式
-                        if image_parts.is_empty() {
-                            messages.push(json!({
-                                "role": role,
-                                "content": text_parts.join("\n")
-                            }));
-                        } else {
-                            let mut content_blocks: Vec<Value> = Vec::new();
-                            if !text_parts.is_empty() {
-                                content_blocks.push(json!({
-                                    "type": "text",
-                                    "text": text_parts.join("\n")
-                                }));
-                            }
-                            content_blocks.extend(image_parts);
-                            messages.push(json!({
-                                "role": role,
-                                "content": content_blocks
-                            }));
-                        }
-                    }
-                    "function_call" | "local_shell_call" | "web_search_call" => {
-                        let mut name = item
-                            .get("name")
-                            .and_then(|v| v.as_str())
-                            .unwrap_or("unknown");
-                        let mut args_str = item
-                            .get("arguments")
-                            .and_then(|v| v.as_str())
-                            .unwrap_or("{}")
-                            .to_string();
-                        let call_id = item
-                            .get("call_id")
-                            .and_then(|v| v.as_str())
-                            .or_else(|| item.get("id").and_then(|v| v.as_str()))
-                            .unwrap_or("unknown");
-
-                        // Handle native shell calls
-                        if item_type == "local_shell_call" {
-                            name = "shell";
-                            if let Some(action) = item.get("action") {
-                                if let Some(exec) = action.get("exec") {
-                                    // Map to ShellCommandToolCallParams (string command) or ShellToolCallParams (array command)
-                                    // Most LLMs prefer a single string for shell
-                                    let mut args_obj = serde_json::Map::new();
-                                    if let Some(cmd) = exec.get("command") {
-                                        // CRITICAL FIX: The 'shell' tool schema defines 'command' as an ARRAY of strings.
-                                        // We MUST pass it as an array, not a joined string, otherwise Gemini rejects with 400 INVALID_ARGUMENT.
-                                        let cmd_val = if cmd.is_string() {
-                                            json!([cmd]) // Wrap in array
-                                        } else {
-                                            cmd.clone() // Assume already array
-                                        };
-                                        args_obj.insert("command".to_string(), cmd_val);
-                                    }
-                                    if let Some(wd) =
-                                        exec.get("working_directory").or(exec.get("workdir"))
-                                    {
-                                        args_obj.insert("workdir".to_string(), wd.clone());
-                                    }
-                                    args_str = serde_json::to_string(&args_obj)
-                                        .unwrap_or("{}".to_string());
-                                }
-                            }
-                        } else if item_type == "web_search_call" {
-                            name = "google_search";
-                            if let Some(action) = item.get("action") {
-                                let mut args_obj = serde_json::Map::new();
-                                if let Some(q) = action.get("query") {
-                                    args_obj.insert("query".to_string(), q.clone());
-                                }
-                                args_str =
-                                    serde_json::to_string(&args_obj).unwrap_or("{}".to_string());
-                            }
-                        }
-
-                        messages.push(json!({
-                            "role": "assistant",
-                            "tool_calls": [
-                                {
-                                    "id": call_id,
-                                    "type": "function",
-                                    "function": {
-                                        "name": name,
-                                        "arguments": args_str
-                                    }
-                                }
-                            ]
-                        }));
-                    }
-                    "function_call_output" | "custom_tool_call_output" => {
-                        let call_id = item
-                            .get("call_id")
-                            .and_then(|v| v.as_str())
-                            .unwrap_or("unknown");
-                        let output = item.get("output");
-                        let output_str = if let Some(o) = output {
-                            if o.is_string() {
-                                o.as_str().unwrap().to_string()
-                            } else if let Some(content) = o.get("content").and_then(|v| v.as_str())
-                            {
-                                content.to_string()
-                            } else {
-                                o.to_string()
-                            }
-                        } else {
-                            "".to_string()
-                        };
-
-                        let name = call_id_to_name.get(call_id).cloned().unwrap_or_else(|| {
-                            // Fallback: if unknown and we see function_call_output, it's likely "shell" in this context
-                            tracing::warn!(
-                                "Unknown tool name for call_id {}, defaulting to 'shell'",
-                                call_id
-                            );
-                            "shell".to_string()
-                        });
-
-                        messages.push(json!({
-                            "role": "tool",
-                            "tool_call_id": call_id,
-                            "name": name,
-                            "content": output_str
-                        }));
-                    }
-                    _ => {}
-                }
-            }
-        }
-
-        if let Some(obj) = body.as_object_mut() {
-            obj.insert("messages".to_string(), json!(messages));
-        }
-    } else if let Some(prompt_val) = body.get("prompt") {
+    if let Some(prompt_val) = body.get("prompt") {
         // Legacy OpenAI Style: prompt -> Chat
         let prompt_str = match prompt_val {
             Value::String(s) => s.clone(),
@@ -610,9 +395,7 @@ pub async fn handle_completions(
         }
     }
 
-    // 2. Reuse handle_chat_completions logic (wrapping with custom handler or direct call)
-    // Actually, due to SSE handling differences (Codex uses different event format), we replicate the loop here or abstract it.
-    // For now, let's replicate the core loop but with Codex specific SSE mapping.
+    // 2. Legacy Completions 的 SSE 格式与 Chat 不同，这里复制核心循环
 
     let mut openai_req: OpenAIRequest = serde_json::from_value(body.clone())
         .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)))?;
@@ -634,18 +417,17 @@ pub async fn handle_completions(
     }
 
     let upstream = state.upstream.clone();
-    let token_manager = state.token_manager;
-    let pool_size = token_manager.len();
-    let max_attempts = MAX_RETRY_ATTEMPTS.min(pool_size).max(1);
+    let token_manager = state.token_manager.clone();
+    let retry_policy = state.retry_policy.read().await.clone();
+    let mut retry = RetryState::new(retry_policy, token_manager.len());
 
     let mut last_error = String::new();
 
-    for _attempt in 0..max_attempts {
+    while !retry.is_exhausted() {
         // 1. 模型路由解析
-        let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
-            &openai_req.model,
-            &*state.custom_mapping.read().await,
-        );
+        let route = super::common::resolve_route(&state, &openai_req.model, Protocol::OpenAI).await;
+        let mapped_model = route.model().to_string();
+        let mapped_label = route.label();
         // 将 OpenAI 工具转为 Value 数组以便探测联网
         let tools_val: Option<Vec<Value>> = openai_req.tools.as_ref().map(|list| list.to_vec());
         let config = crate::proxy::mappers::common_utils::resolve_request_config(
@@ -654,8 +436,14 @@ pub async fn handle_completions(
             &tools_val,
         );
 
+        let force_rotate = retry.take_rotation();
         let (access_token, project_id, email) = match token_manager
-            .get_token(&config.request_type, false, None)
+            .get_token_for_model(
+                &config.request_type,
+                force_rotate,
+                None,
+                Some(&mapped_model),
+            )
             .await
         {
             Ok(t) => t,
@@ -671,9 +459,8 @@ pub async fn handle_completions(
 
         let gemini_body = transform_openai_request(&openai_req, &project_id, &mapped_model);
 
-        // [New] 打印转换后的报文 (Gemini Body) 供调试 (Codex 路径)
         if let Ok(body_json) = serde_json::to_string_pretty(&gemini_body) {
-            debug!("[Codex-Request] Transformed Gemini Body:\n{}", body_json);
+            debug!("[Completions] Transformed Gemini Body:\n{}", body_json);
         }
 
         let list_response = openai_req.stream;
@@ -691,35 +478,30 @@ pub async fn handle_completions(
             Ok(r) => r,
             Err(e) => {
                 last_error = e.clone();
+                retry.on_network_error();
                 continue;
             }
         };
 
         let status = response.status();
         if status.is_success() {
+            token_manager.mark_account_success(&email);
+
             if list_response {
                 use axum::body::Body;
                 use axum::response::Response;
 
                 let gemini_stream = response.bytes_stream();
-                let body = if is_codex_style {
-                    use crate::proxy::mappers::openai::streaming::create_codex_sse_stream;
-                    let s =
-                        create_codex_sse_stream(Box::pin(gemini_stream), openai_req.model.clone());
-                    Body::from_stream(s)
-                } else {
-                    use crate::proxy::mappers::openai::streaming::create_legacy_sse_stream;
-                    let s =
-                        create_legacy_sse_stream(Box::pin(gemini_stream), openai_req.model.clone());
-                    Body::from_stream(s)
-                };
+                use crate::proxy::mappers::openai::streaming::create_legacy_sse_stream;
+                let s = create_legacy_sse_stream(Box::pin(gemini_stream), openai_req.model.clone());
+                let body = Body::from_stream(s);
 
                 return Ok(Response::builder()
                     .header("Content-Type", "text/event-stream")
                     .header("Cache-Control", "no-cache")
                     .header("Connection", "keep-alive")
                     .header("X-Account-Email", &email)
-                    .header("X-Mapped-Model", &mapped_model)
+                    .header("X-Mapped-Model", &mapped_label)
                     .body(body)
                     .unwrap()
                     .into_response());
@@ -758,13 +540,34 @@ pub async fn handle_completions(
 
         // Handle errors and retry
         let status_code = status.as_u16();
+        let retry_after = response
+            .headers()
+            .get("Retry-After")
+            .and_then(|h| h.to_str().ok())
+            .map(|s| s.to_string());
         let error_text = response.text().await.unwrap_or_default();
         last_error = format!("HTTP {}: {}", status_code, error_text);
 
-        if status_code == 429 || status_code == 403 || status_code == 401 {
-            continue;
+        token_manager
+            .report_upstream_error(&email, status_code, &error_text)
+            .await;
+
+        match retry.on_status(status_code, retry_after.as_deref(), &error_text) {
+            RetryDecision::Retry { delay, rotate } => {
+                tracing::warn!(
+                    "[Completions] Upstream {} on {} attempt {}/{}, waiting {}ms (rotate: {})",
+                    status_code,
+                    email,
+                    retry.attempt(),
+                    retry.max_attempts(),
+                    delay.as_millis(),
+                    rotate
+                );
+                tokio::time::sleep(delay).await;
+            }
+            RetryDecision::Exhausted => {}
+            RetryDecision::FailFast => return Err((status, error_text)),
         }
-        return Err((status, error_text));
     }
 
     Err((
@@ -864,16 +667,18 @@ pub async fn handle_images_generations(
     let upstream = state.upstream.clone();
     let token_manager = state.token_manager;
 
-    let (access_token, project_id, email) =
-        match token_manager.get_token("image_gen", false, None).await {
-            Ok(t) => t,
-            Err(e) => {
-                return Err((
-                    StatusCode::SERVICE_UNAVAILABLE,
-                    format!("Token error: {}", e),
-                ));
-            }
-        };
+    let (access_token, project_id, email) = match token_manager
+        .get_token_for_model("image_gen", false, None, Some("gemini-3-pro-image"))
+        .await
+    {
+        Ok(t) => t,
+        Err(e) => {
+            return Err((
+                StatusCode::SERVICE_UNAVAILABLE,
+                format!("Token error: {}", e),
+            ));
+        }
+    };
 
     info!("✓ Using account: {} for image generation", email);
 
@@ -1114,16 +919,18 @@ pub async fn handle_images_edits(
     let upstream = state.upstream.clone();
     let token_manager = state.token_manager;
     // Fix: Proper get_token call with correct signature and unwrap (using image_gen quota)
-    let (access_token, project_id, _email) =
-        match token_manager.get_token("image_gen", false, None).await {
-            Ok(t) => t,
-            Err(e) => {
-                return Err((
-                    StatusCode::SERVICE_UNAVAILABLE,
-                    format!("Token error: {}", e),
-                ));
-            }
-        };
+    let (access_token, project_id, _email) = match token_manager
+        .get_token_for_model("image_gen", false, None, Some(&model))
+        .await
+    {
+        Ok(t) => t,
+        Err(e) => {
+            return Err((
+                StatusCode::SERVICE_UNAVAILABLE,
+                format!("Token error: {}", e),
+            ));
+        }
+    };
 
     // 2. 映射配置
     let mut contents_parts = Vec::new();
@@ -0,0 +1,387 @@
+// OpenAI Responses API Handler (/v1/responses)
+use axum::{
+    body::Body,
+    extract::{Extension, Json, Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::{json, Value};
+use tracing::{debug, info};
+
+use crate::proxy::client_keys::{owner_id, ClientKeyIdentity};
+use crate::proxy::mappers::openai::responses::{
+    build_chat_request, build_responses_output, create_responses_sse_stream,
+    output_items_to_messages, responses_input_to_messages, ResponsesContext,
+};
+use crate::proxy::mappers::openai::{
+    transform_openai_request, transform_openai_response, OpenAIRequest,
+};
+use crate::proxy::response_store::ResponseStore;
+use crate::proxy::server::AppState;
+use crate::proxy::upstream::retry::{RetryDecision, RetryState};
+use antigravity_shared::proxy::config::Protocol;
+
+fn not_found(response_id: &str) -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({
+            "error": {
+                "message": format!("Response with id '{}' not found.", response_id),
+                "type": "invalid_request_error",
+                "param": "response_id",
+                "code": "not_found"
+            }
+        })),
+    )
+        .into_response()
+}
+
+/// 将完成的响应写入本地存储 (历史 + 本次输出)
+fn store_response(
+    store: &ResponseStore,
+    mut conversation: Vec<Value>,
+    response: Value,
+    client_key: Option<&str>,
+) {
+    let Some(id) = response
+        .get("id")
+        .and_then(|v| v.as_str())
+        .map(String::from)
+    else {
+        return;
+    };
+    if let Some(output) = response.get("output").and_then(|o| o.as_array()) {
+        conversation.extend(output_items_to_messages(output));
+    }
+    store.insert(id, response, conversation, client_key);
+}
+
+/// 处理 Responses API 请求
+///
+/// 支持字符串/数组 `input`、`instructions`、`previous_response_id` 链式对话与 `store`
+pub async fn handle_responses(
+    State(state): State<AppState>,
+    identity: Option<Extension<ClientKeyIdentity>>,
+    Json(body): Json<Value>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    debug!("Received /v1/responses payload: {:?}", body);
+
+    // 1. 还原对话历史 (previous_response_id)
+    let history = match body.get("previous_response_id").and_then(|v| v.as_str()) {
+        Some(prev_id) => match state.response_store.get(prev_id, owner_id(&identity)) {
+            Some(stored) => stored.messages,
+            None => return Ok(not_found(prev_id)),
+        },
+        None => Vec::new(),
+    };
+
+    // 2. 输入项 -> Chat 消息 (instructions 不随 previous_response_id 继承)
+    let mut conversation = history.clone();
+    conversation.extend(responses_input_to_messages(body.get("input"), &history));
+    if conversation.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Invalid request: 'input' is required".to_string(),
+        ));
+    }
+
+    let mut messages = Vec::with_capacity(conversation.len() + 1);
+    if let Some(instructions) = body
+        .get("instructions")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+    {
+        messages.push(json!({ "role": "system", "content": instructions }));
+    }
+    messages.extend(conversation.iter().cloned());
+
+    let chat_body = build_chat_request(&body, messages);
+    let mut openai_req: OpenAIRequest = serde_json::from_value(chat_body)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)))?;
+    let upstream_proxy = state.upstream_proxy.read().await.clone();
+    crate::proxy::common::media_fetch::resolve_openai_media(
+        &mut openai_req,
+        &upstream_proxy,
+        owner_id(&identity),
+    )
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let ctx = ResponsesContext::from_request(&body);
+    let should_store = ctx.store();
+
+    let token_manager = state.token_manager.clone();
+    let retry_policy = state.retry_policy.read().await.clone();
+    let mut retry = RetryState::new(retry_policy, token_manager.len());
+
+    let mut last_error = String::new();
+    let mut quota_exhausted = false;
+
+    let mut route = super::common::resolve_route(&state, &openai_req.model, Protocol::OpenAI).await;
+
+    loop {
+        if retry.is_exhausted() {
+            // 所有尝试均因配额耗尽失败时切换到回退模型
+            if quota_exhausted && route.advance(&last_error) {
+                retry.reset();
+                quota_exhausted = false;
+                continue;
+            }
+            break;
+        }
+
+        let mapped_model = route.model().to_string();
+        let mapped_label = route.label();
+        let tools_val: Option<Vec<Value>> = openai_req.tools.as_ref().map(|list| list.to_vec());
+        let config = crate::proxy::mappers::common_utils::resolve_request_config(
+            &openai_req.model,
+            &mapped_model,
+            &tools_val,
+        );
+
+        let force_rotate = retry.take_rotation();
+        let (access_token, project_id, email) = match token_manager
+            .get_token_for_model(
+                &config.request_type,
+                force_rotate,
+                None,
+                Some(&mapped_model),
+            )
+            .await
+        {
+            Ok(t) => t,
+            Err(e) => {
+                if route.advance(&e) {
+                    retry.reset();
+                    continue;
+                }
+                return Err((
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    format!("Token error: {}", e),
+                ));
+            }
+        };
+
+        info!(
+            "✓ [Responses] Using account: {} (type: {})",
+            email, config.request_type
+        );
+
+        let gemini_body = transform_openai_request(&openai_req, &project_id, &mapped_model);
+
+        let method = if openai_req.stream {
+            "streamGenerateContent"
+        } else {
+            "generateContent"
+        };
+        let query_string = if openai_req.stream {
+            Some("alt=sse")
+        } else {
+            None
+        };
+
+        let (email, response) = super::common::call_upstream(
+            &state,
+            method,
+            query_string,
+            gemini_body,
+            &access_token,
+            email,
+            &mapped_model,
+        )
+        .await;
+        let response = match response {
+            Ok(r) => r,
+            Err(e) => {
+                last_error = e.clone();
+                retry.on_network_error();
+                continue;
+            }
+        };
+
+        let status = response.status();
+        if status.is_success() {
+            token_manager.mark_account_success(&email);
+
+            if openai_req.stream {
+                let store = state.response_store.clone();
+                let conversation = conversation.clone();
+                let owner = owner_id(&identity).map(String::from);
+                let on_complete: Box<dyn FnOnce(Value) + Send> = Box::new(move |resp| {
+                    if should_store {
+                        store_response(&store, conversation, resp, owner.as_deref());
+                    }
+                });
+
+                let s = create_responses_sse_stream(
+                    Box::pin(response.bytes_stream()),
+                    ctx.clone(),
+                    on_complete,
+                );
+
+                return Ok(Response::builder()
+                    .header("Content-Type", "text/event-stream")
+                    .header("Cache-Control", "no-cache")
+                    .header("Connection", "keep-alive")
+                    .header("X-Account-Email", &email)
+                    .header("X-Mapped-Model", &mapped_label)
+                    .body(Body::from_stream(s))
+                    .unwrap()
+                    .into_response());
+            }
+
+            let gemini_resp: Value = response
+                .json()
+                .await
+                .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Parse error: {}", e)))?;
+
+            let chat_resp = transform_openai_response(&gemini_resp);
+            let resp = build_responses_output(&ctx, &chat_resp, &gemini_resp);
+
+            if should_store {
+                store_response(
+                    &state.response_store,
+                    conversation,
+                    resp.clone(),
+                    owner_id(&identity),
+                );
+            }
+
+            return Ok(Response::builder()
+                .header("Content-Type", "application/json")
+                .header("X-Account-Email", &email)
+                .header("X-Mapped-Model", &mapped_label)
+                .body(Body::from(resp.to_string()))
+                .unwrap()
+                .into_response());
+        }
+
+        // 错误处理与轮换
+        let status_code = status.as_u16();
+        let retry_after = response
+            .headers()
+            .get("Retry-After")
+            .and_then(|h| h.to_str().ok())
+            .map(|s| s.to_string());
+        let error_text = response.text().await.unwrap_or_default();
+        last_error = format!("HTTP {}: {}", status_code, error_text);
+        quota_exhausted = status_code == 429;
+
+        token_manager
+            .report_upstream_error(&email, status_code, &error_text)
+            .await;
+
+        match retry.on_status(status_code, retry_after.as_deref(), &error_text) {
+            RetryDecision::Retry { delay, rotate } => {
+                tracing::warn!(
+                    "[Responses] Upstream {} on {} attempt {}/{}, waiting {}ms (rotate: {})",
+                    status_code,
+                    email,
+                    retry.attempt(),
+                    retry.max_attempts(),
+                    delay.as_millis(),
+                    rotate
+                );
+                tokio::time::sleep(delay).await;
+            }
+            RetryDecision::Exhausted => {}
+            RetryDecision::FailFast => return Err((status, error_text)),
+        }
+    }
+
+    Err((
+        StatusCode::TOO_MANY_REQUESTS,
+        format!("All attempts failed. Last error: {}", last_error),
+    ))
+}
+
+/// GET /v1/responses/:response_id
+pub async fn handle_get_response(
+    State(state): State<AppState>,
+    identity: Option<Extension<ClientKeyIdentity>>,
+    Path(response_id): Path<String>,
+) -> Response {
+    match state.response_store.get(&response_id, owner_id(&identity)) {
+        Some(stored) => Json(stored.response).into_response(),
+        None => not_found(&response_id),
+    }
+}
+
+/// DELETE /v1/responses/:response_id
+pub async fn handle_delete_response(
+    State(state): State<AppState>,
+    identity: Option<Extension<ClientKeyIdentity>>,
+    Path(response_id): Path<String>,
+) -> Response {
+    if state
+        .response_store
+        .remove(&response_id, owner_id(&identity))
+    {
+        Json(json!({
+            "id": response_id,
+            "object": "response.deleted",
+            "deleted": true
+        }))
+        .into_response()
+    } else {
+        not_found(&response_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::tests::support;
+
+    fn identity(id: &str) -> Option<Extension<ClientKeyIdentity>> {
+        Some(Extension(ClientKeyIdentity {
+            id: id.to_string(),
+            name: id.to_string(),
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_stored_responses_scoped_to_client_key() {
+        let state = support::app_state("http://127.0.0.1:9/v1internal".to_string(), &[]).await;
+        state.response_store.insert(
+            "resp_a".to_string(),
+            json!({"id": "resp_a"}),
+            vec![],
+            Some("key-a"),
+        );
+
+        let response = handle_get_response(
+            State(state.clone()),
+            identity("key-b"),
+            Path("resp_a".to_string()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            response.headers()[axum::http::header::CONTENT_TYPE],
+            "application/json"
+        );
+        let response =
+            handle_delete_response(State(state.clone()), None, Path("resp_a".to_string())).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // 其他 key 不能通过 previous_response_id 续写
+        let response = handle_responses(
+            State(state.clone()),
+            identity("key-b"),
+            Json(json!({"model": "gemini-2.5-flash", "input": "hi", "previous_response_id": "resp_a"})),
+        )
+        .await
+        .map(IntoResponse::into_response)
+        .unwrap_or_else(IntoResponse::into_response);
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = handle_get_response(
+            State(state.clone()),
+            identity("key-a"),
+            Path("resp_a".to_string()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
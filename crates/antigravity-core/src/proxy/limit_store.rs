@@ -0,0 +1,102 @@
+//! 限流状态持久化
+//!
+//! 将 AIMD 自适应限额与仍在生效的限流锁定 (包括 `QUOTA_EXHAUSTED` 精确锁定) 写入
+//! 数据目录下的 `proxy_limits.json`，重启后由 `TokenManager::load_accounts` 恢复，
+//! 避免重启后反复请求已知配额耗尽的账号。
+
+use crate::proxy::rate_limit::RateLimitReason;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+pub const LIMITS_FILE: &str = "proxy_limits.json";
+
+/// 单个账号的 AIMD 限额
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PersistedAdaptiveLimit {
+    pub account_id: String,
+    pub confirmed_limit: u64,
+    pub ceiling: u64,
+    /// 保存时距离上次校准的秒数
+    pub calibration_age_seconds: u64,
+}
+
+/// 单条限流锁定 (key 可能是 account_id 或 email)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PersistedRateLimit {
+    pub key: String,
+    /// 解锁时间 (Unix 秒)
+    pub reset_at: u64,
+    pub reason: RateLimitReason,
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct LimitSnapshot {
+    /// 保存时间 (Unix 秒)
+    pub saved_at: u64,
+    #[serde(default)]
+    pub adaptive_limits: Vec<PersistedAdaptiveLimit>,
+    #[serde(default)]
+    pub rate_limits: Vec<PersistedRateLimit>,
+}
+
+/// 读取快照，文件不存在时返回 `None`
+pub fn load(data_dir: &Path) -> Result<Option<LimitSnapshot>, String> {
+    let path = data_dir.join(LIMITS_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(&path).map_err(|e| format!("读取限流快照失败: {}", e))?;
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| format!("解析限流快照失败: {}", e))
+}
+
+/// 原子写入快照
+pub fn save(data_dir: &Path, snapshot: &LimitSnapshot) -> Result<(), String> {
+    let path = data_dir.join(LIMITS_FILE);
+    let temp_path = data_dir.join(format!("{}.tmp", LIMITS_FILE));
+
+    let content =
+        serde_json::to_string_pretty(snapshot).map_err(|e| format!("序列化限流快照失败: {}", e))?;
+
+    fs::write(&temp_path, content).map_err(|e| format!("写入临时限流快照失败: {}", e))?;
+    fs::rename(&temp_path, &path).map_err(|e| format!("保存限流快照失败: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let dir = crate::proxy::tests::support::temp_dir("ag-limit-store");
+
+        assert_eq!(load(&dir).unwrap(), None);
+
+        let snapshot = LimitSnapshot {
+            saved_at: 1_700_000_000,
+            adaptive_limits: vec![PersistedAdaptiveLimit {
+                account_id: "a".to_string(),
+                confirmed_limit: 42,
+                ceiling: 60,
+                calibration_age_seconds: 120,
+            }],
+            rate_limits: vec![PersistedRateLimit {
+                key: "a@example.com".to_string(),
+                reset_at: 1_700_003_600,
+                reason: RateLimitReason::QuotaExhausted,
+                model: Some("claude-sonnet-4-5".to_string()),
+            }],
+        };
+        save(&dir, &snapshot).unwrap();
+
+        assert_eq!(load(&dir).unwrap(), Some(snapshot));
+        assert!(!dir.join(format!("{}.tmp", LIMITS_FILE)).exists());
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
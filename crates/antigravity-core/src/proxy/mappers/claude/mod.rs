@@ -1,8 +1,10 @@
 // Claude mapper 模块
-// 负责 Claude ↔ Gemini 协议转换
+// 负责 Claude ↔ Gemini 协议转换，以及 Claude → OpenAI 兼容 provider 的转换
 
 pub mod collector;
 pub mod models;
+pub mod openai_request;
+pub mod openai_streaming;
 pub mod request;
 pub mod response;
 pub mod streaming;
@@ -11,6 +13,8 @@ pub mod utils;
 
 pub use collector::collect_stream_to_json;
 pub use models::*;
+pub use openai_request::transform_claude_request_to_openai;
+pub use openai_streaming::create_claude_sse_stream_from_openai;
 pub use request::transform_claude_request_in;
 pub use response::transform_response;
 pub use streaming::{PartProcessor, StreamingState};
@@ -25,6 +29,7 @@ pub fn create_claude_sse_stream(
     mut gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
     trace_id: String,
     email: String,
+    cache_created: bool,
 ) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
     use async_stream::stream;
     use bytes::BytesMut;
@@ -32,6 +37,7 @@ pub fn create_claude_sse_stream(
 
     Box::pin(stream! {
         let mut state = StreamingState::new();
+        state.cache_created = cache_created;
         let mut buffer = BytesMut::new();
 
         while let Some(chunk_result) = gemini_stream.next().await {
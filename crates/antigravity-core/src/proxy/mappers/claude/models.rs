@@ -53,6 +53,9 @@ pub struct SystemBlock {
     #[serde(rename = "type")]
     pub block_type: String,
     pub text: String,
+    /// prompt caching 断点 (`{"type": "ephemeral", "ttl": "1h"}`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<serde_json::Value>,
 }
 
 /// Message
@@ -74,7 +77,11 @@ pub enum MessageContent {
 #[serde(tag = "type")]
 pub enum ContentBlock {
     #[serde(rename = "text")]
-    Text { text: String },
+    Text {
+        text: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cache_control: Option<serde_json::Value>,
+    },
 
     #[serde(rename = "thinking")]
     Thinking {
@@ -95,6 +102,8 @@ pub enum ContentBlock {
     #[serde(rename = "document")]
     Document {
         source: DocumentSource,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
         #[serde(skip_serializing_if = "Option::is_none")]
         cache_control: Option<serde_json::Value>,
     },
@@ -119,6 +128,8 @@ pub enum ContentBlock {
         content: serde_json::Value, // Changed from String to Value to support Array of Blocks
         #[serde(skip_serializing_if = "Option::is_none")]
         is_error: Option<bool>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cache_control: Option<serde_json::Value>,
     },
 
     #[serde(rename = "server_tool_use")]
@@ -138,17 +149,29 @@ pub enum ContentBlock {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImageSource {
     #[serde(rename = "type")]
-    pub source_type: String,
+    pub source_type: String, // "base64" | "url" | "file"
+    #[serde(default)]
     pub media_type: String,
+    #[serde(default)]
     pub data: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DocumentSource {
     #[serde(rename = "type")]
-    pub source_type: String, // "base64"
-    pub media_type: String, // e.g. "application/pdf"
-    pub data: String,       // base64 data
+    pub source_type: String, // "base64" | "text" | "url" | "file"
+    #[serde(default)]
+    pub media_type: String, // e.g. "application/pdf", "text/plain"
+    #[serde(default)]
+    pub data: String, // base64 data, 或 text 类型的纯文本
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_id: Option<String>,
 }
 
 /// Tool - supports both client tools (with input_schema) and server tools (like web_search)
@@ -209,12 +232,24 @@ pub struct Metadata {
 }
 
 /// Output Configuration (Claude API v2.0.67+)
-/// Controls effort level for model reasoning
+/// Controls effort level for model reasoning and structured output format
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OutputConfig {
     /// Effort level: "high", "medium", "low"
     #[serde(skip_serializing_if = "Option::is_none")]
     pub effort: Option<String>,
+    /// Structured output: `{"type": "json_schema", "schema": {...}}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<OutputFormat>,
+}
+
+/// Structured output format
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputFormat {
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<serde_json::Value>,
 }
 
 /// Claude API 响应
@@ -0,0 +1,343 @@
+// Claude 请求转换 (Claude → OpenAI Chat Completions)
+// 用于将 /v1/messages 请求交给 OpenAI 兼容的 provider
+
+use super::models::*;
+use crate::proxy::mappers::openai::models::{
+    OpenAIContent, OpenAIContentBlock, OpenAIFile, OpenAIImageUrl, OpenAIMessage, ToolCall,
+    ToolFunction,
+};
+use serde_json::{json, Value};
+
+/// 将 Claude 请求转换为 OpenAI Chat Completions 请求体 (始终为流式，附带 usage)
+///
+/// - system → system 消息
+/// - thinking → assistant `reasoning_content`，tool_use → `tool_calls`
+/// - tool_result → 紧跟其后的 `tool` 消息
+/// - web_search 等服务端工具没有对应物，直接忽略
+pub fn transform_claude_request_to_openai(request: &ClaudeRequest, model: &str) -> Value {
+    let mut messages = Vec::new();
+
+    let system_text = match &request.system {
+        Some(SystemPrompt::String(s)) => s.clone(),
+        Some(SystemPrompt::Array(blocks)) => blocks
+            .iter()
+            .map(|b| b.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        None => String::new(),
+    };
+    if !system_text.trim().is_empty() {
+        messages.push(text_message("system", system_text));
+    }
+
+    for message in &request.messages {
+        match &message.content {
+            MessageContent::String(s) => messages.push(text_message(&message.role, s.clone())),
+            MessageContent::Array(blocks) if message.role == "assistant" => {
+                messages.push(convert_assistant_blocks(blocks));
+            }
+            MessageContent::Array(blocks) => {
+                messages.extend(convert_user_blocks(&message.role, blocks));
+            }
+        }
+    }
+
+    let mut body = json!({
+        "model": model,
+        "messages": messages,
+        "stream": true,
+        "stream_options": { "include_usage": true },
+    });
+
+    if let Some(max_tokens) = request.max_tokens {
+        body["max_tokens"] = json!(max_tokens);
+    }
+    if let Some(temperature) = request.temperature {
+        body["temperature"] = json!(temperature);
+    }
+    if let Some(top_p) = request.top_p {
+        body["top_p"] = json!(top_p);
+    }
+    if let Some(user) = request.metadata.as_ref().and_then(|m| m.user_id.as_ref()) {
+        body["user"] = json!(user);
+    }
+    if let Some(effort) = reasoning_effort(request) {
+        body["reasoning_effort"] = json!(effort);
+    }
+
+    let tools: Vec<Value> = request
+        .tools
+        .iter()
+        .flatten()
+        .filter(|t| !t.is_web_search())
+        .filter_map(|t| {
+            let name = t.name.as_ref()?;
+            Some(json!({
+                "type": "function",
+                "function": {
+                    "name": name,
+                    "description": t.description.clone().unwrap_or_default(),
+                    "parameters": t.input_schema.clone().unwrap_or_else(|| json!({"type": "object", "properties": {}})),
+                }
+            }))
+        })
+        .collect();
+    if !tools.is_empty() {
+        body["tools"] = Value::Array(tools);
+    }
+
+    body
+}
+
+/// output_config.effort 优先，否则按 thinking budget 估算
+fn reasoning_effort(request: &ClaudeRequest) -> Option<String> {
+    if let Some(effort) = request
+        .output_config
+        .as_ref()
+        .and_then(|c| c.effort.as_ref())
+    {
+        return Some(effort.to_lowercase());
+    }
+    let thinking = request.thinking.as_ref()?;
+    if thinking.type_ != "enabled" {
+        return None;
+    }
+    let effort = match thinking.budget_tokens.unwrap_or(0) {
+        0..=4095 => "low",
+        4096..=16383 => "medium",
+        _ => "high",
+    };
+    Some(effort.to_string())
+}
+
+fn text_message(role: &str, text: String) -> OpenAIMessage {
+    OpenAIMessage {
+        role: role.to_string(),
+        content: Some(OpenAIContent::String(text)),
+        reasoning_content: None,
+        tool_calls: None,
+        tool_call_id: None,
+        name: None,
+    }
+}
+
+fn convert_assistant_blocks(blocks: &[ContentBlock]) -> OpenAIMessage {
+    let mut text = String::new();
+    let mut reasoning = String::new();
+    let mut tool_calls = Vec::new();
+
+    for block in blocks {
+        match block {
+            ContentBlock::Text { text: t, .. } => text.push_str(t),
+            ContentBlock::Thinking { thinking, .. } => reasoning.push_str(thinking),
+            ContentBlock::ToolUse {
+                id, name, input, ..
+            } => tool_calls.push(ToolCall {
+                id: id.clone(),
+                r#type: "function".to_string(),
+                function: ToolFunction {
+                    name: name.clone(),
+                    arguments: input.to_string(),
+                },
+            }),
+            _ => {}
+        }
+    }
+
+    OpenAIMessage {
+        role: "assistant".to_string(),
+        // 仅有 tool_calls 时 content 为 null
+        content: (!text.is_empty() || tool_calls.is_empty()).then_some(OpenAIContent::String(text)),
+        reasoning_content: (!reasoning.is_empty()).then_some(reasoning),
+        tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
+        tool_call_id: None,
+        name: None,
+    }
+}
+
+/// tool_result 拆分为独立的 tool 消息 (放在前面)，其余内容合并为一条消息
+fn convert_user_blocks(role: &str, blocks: &[ContentBlock]) -> Vec<OpenAIMessage> {
+    let mut out = Vec::new();
+    let mut parts = Vec::new();
+
+    for block in blocks {
+        match block {
+            ContentBlock::Text { text, .. } => {
+                parts.push(OpenAIContentBlock::Text { text: text.clone() })
+            }
+            ContentBlock::Image { source, .. } => {
+                let url = match &source.url {
+                    Some(url) if source.source_type == "url" => url.clone(),
+                    _ => format!("data:{};base64,{}", source.media_type, source.data),
+                };
+                parts.push(OpenAIContentBlock::ImageUrl {
+                    image_url: OpenAIImageUrl { url, detail: None },
+                });
+            }
+            ContentBlock::Document { source, title, .. } => {
+                if source.source_type == "text" {
+                    parts.push(OpenAIContentBlock::Text {
+                        text: source.data.clone(),
+                    });
+                } else {
+                    parts.push(OpenAIContentBlock::File {
+                        file: OpenAIFile {
+                            file_id: None,
+                            file_data: Some(format!(
+                                "data:{};base64,{}",
+                                source.media_type, source.data
+                            )),
+                            filename: title.clone(),
+                        },
+                    });
+                }
+            }
+            ContentBlock::ToolResult {
+                tool_use_id,
+                content,
+                ..
+            } => out.push(OpenAIMessage {
+                role: "tool".to_string(),
+                content: Some(OpenAIContent::String(tool_result_text(content))),
+                reasoning_content: None,
+                tool_calls: None,
+                tool_call_id: Some(tool_use_id.clone()),
+                name: None,
+            }),
+            _ => {}
+        }
+    }
+
+    if !parts.is_empty() {
+        // 纯文本内容使用字符串形式，兼容不支持 content 数组的后端
+        let content = if parts
+            .iter()
+            .all(|p| matches!(p, OpenAIContentBlock::Text { .. }))
+        {
+            OpenAIContent::String(
+                parts
+                    .iter()
+                    .filter_map(|p| match p {
+                        OpenAIContentBlock::Text { text } => Some(text.as_str()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            )
+        } else {
+            OpenAIContent::Array(parts)
+        };
+        out.push(OpenAIMessage {
+            role: role.to_string(),
+            content: Some(content),
+            reasoning_content: None,
+            tool_calls: None,
+            tool_call_id: None,
+            name: None,
+        });
+    }
+
+    out
+}
+
+/// tool_result 的 content 可能是字符串或内容块数组，只保留文本
+fn tool_result_text(content: &Value) -> String {
+    match content {
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items
+            .iter()
+            .filter_map(|item| item.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transform_tool_loop_and_thinking() {
+        let request: ClaudeRequest = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 1024,
+            "system": [{"type": "text", "text": "You are helpful."}],
+            "thinking": {"type": "enabled", "budget_tokens": 8000},
+            "tools": [
+                {"name": "Read", "description": "Read a file", "input_schema": {"type": "object", "properties": {"file_path": {"type": "string"}}}},
+                {"type": "web_search_20250305", "name": "web_search"}
+            ],
+            "messages": [
+                {"role": "user", "content": "Open main.rs"},
+                {"role": "assistant", "content": [
+                    {"type": "thinking", "thinking": "Need to read it.", "signature": "sig"},
+                    {"type": "tool_use", "id": "toolu_1", "name": "Read", "input": {"file_path": "main.rs"}}
+                ]},
+                {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "toolu_1", "content": [{"type": "text", "text": "fn main() {}"}]},
+                    {"type": "text", "text": "Explain it"}
+                ]}
+            ]
+        }))
+        .unwrap();
+
+        let body = transform_claude_request_to_openai(&request, "qwen3-coder");
+        assert_eq!(body["model"], "qwen3-coder");
+        assert_eq!(body["stream"], true);
+        assert_eq!(body["max_tokens"], 1024);
+        assert_eq!(body["reasoning_effort"], "medium");
+
+        let tools = body["tools"].as_array().unwrap();
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0]["function"]["name"], "Read");
+
+        let messages = body["messages"].as_array().unwrap();
+        let roles: Vec<&str> = messages
+            .iter()
+            .map(|m| m["role"].as_str().unwrap())
+            .collect();
+        assert_eq!(roles, vec!["system", "user", "assistant", "tool", "user"]);
+
+        let assistant = &messages[2];
+        assert!(assistant.get("content").is_none());
+        assert_eq!(assistant["reasoning_content"], "Need to read it.");
+        assert_eq!(assistant["tool_calls"][0]["id"], "toolu_1");
+        let args: Value = serde_json::from_str(
+            assistant["tool_calls"][0]["function"]["arguments"]
+                .as_str()
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(args["file_path"], "main.rs");
+
+        assert_eq!(messages[3]["tool_call_id"], "toolu_1");
+        assert_eq!(messages[3]["content"], "fn main() {}");
+        assert_eq!(messages[4]["content"], "Explain it");
+    }
+
+    #[test]
+    fn test_transform_images_and_documents() {
+        let request: ClaudeRequest = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{"role": "user", "content": [
+                {"type": "text", "text": "Compare"},
+                {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "AAAA"}},
+                {"type": "document", "title": "spec.pdf", "source": {"type": "base64", "media_type": "application/pdf", "data": "JVBE"}}
+            ]}]
+        }))
+        .unwrap();
+
+        let body = transform_claude_request_to_openai(&request, "gpt-4o");
+        let content = body["messages"][0]["content"].as_array().unwrap();
+        assert_eq!(content[1]["image_url"]["url"], "data:image/png;base64,AAAA");
+        assert_eq!(
+            content[2]["file"]["file_data"],
+            "data:application/pdf;base64,JVBE"
+        );
+        assert_eq!(content[2]["file"]["filename"], "spec.pdf");
+        assert!(body.get("reasoning_effort").is_none());
+        assert!(body.get("tools").is_none());
+    }
+}
@@ -0,0 +1,370 @@
+// Claude 流式响应转换 (OpenAI Chat Completions SSE → Claude SSE)
+// 复用 StreamingState 负责块的开始 / 结束与 message_stop
+
+use super::models::UsageMetadata;
+use super::streaming::{BlockType, StreamingState};
+use bytes::Bytes;
+use futures::Stream;
+use serde_json::{json, Value};
+use std::pin::Pin;
+
+/// OpenAI chunk 处理器
+///
+/// finish_reason 与 usage 可能分布在不同的 chunk 中 (`stream_options.include_usage`)，
+/// 因此结束事件推迟到 `[DONE]` 或流结束时发送。
+pub struct OpenAIChunkProcessor {
+    pub state: StreamingState,
+    tool_index: Option<u64>,
+    finish_reason: Option<String>,
+    usage: Option<UsageMetadata>,
+}
+
+impl Default for OpenAIChunkProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OpenAIChunkProcessor {
+    pub fn new() -> Self {
+        Self {
+            state: StreamingState::new(),
+            tool_index: None,
+            finish_reason: None,
+            usage: None,
+        }
+    }
+
+    /// 处理单行 SSE 数据
+    pub fn process_line(&mut self, line: &str) -> Vec<Bytes> {
+        let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+            return vec![];
+        };
+        if data.is_empty() {
+            return vec![];
+        }
+        if data == "[DONE]" {
+            return self.finish();
+        }
+        match serde_json::from_str::<Value>(data) {
+            Ok(chunk) => self.process_chunk(&chunk),
+            Err(_) => vec![],
+        }
+    }
+
+    /// 处理一个 `chat.completion.chunk`
+    pub fn process_chunk(&mut self, chunk: &Value) -> Vec<Bytes> {
+        let mut out = Vec::new();
+
+        if let Some(error) = chunk.get("error") {
+            out.extend(self.state.end_block());
+            let message = error
+                .get("message")
+                .and_then(|m| m.as_str())
+                .map(str::to_string)
+                .unwrap_or_else(|| error.to_string());
+            out.push(self.state.emit(
+                "error",
+                json!({
+                    "type": "error",
+                    "error": { "type": "api_error", "message": message }
+                }),
+            ));
+            return out;
+        }
+
+        if !self.state.message_start_sent {
+            out.push(self.state.emit_message_start(&json!({
+                "responseId": chunk.get("id").and_then(|v| v.as_str()).unwrap_or("msg_unknown"),
+                "modelVersion": chunk.get("model").and_then(|v| v.as_str()).unwrap_or(""),
+            })));
+        }
+
+        if let Some(usage) = chunk.get("usage").filter(|u| !u.is_null()) {
+            self.usage = Some(to_usage_metadata(usage));
+        }
+
+        let Some(choice) = chunk
+            .get("choices")
+            .and_then(|c| c.as_array())
+            .and_then(|c| c.first())
+        else {
+            return out;
+        };
+
+        if let Some(delta) = choice.get("delta") {
+            // DeepSeek / vLLM 使用 reasoning_content，OpenRouter / Ollama 使用 reasoning
+            let reasoning = delta
+                .get("reasoning_content")
+                .or_else(|| delta.get("reasoning"))
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.is_empty());
+            if let Some(thinking) = reasoning {
+                if self.state.current_block_type() != BlockType::Thinking {
+                    out.extend(self.state.start_block(
+                        BlockType::Thinking,
+                        json!({ "type": "thinking", "thinking": "" }),
+                    ));
+                }
+                out.push(
+                    self.state
+                        .emit_delta("thinking_delta", json!({ "thinking": thinking })),
+                );
+            }
+
+            if let Some(text) = delta
+                .get("content")
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.is_empty())
+            {
+                if self.state.current_block_type() != BlockType::Text {
+                    out.extend(
+                        self.state
+                            .start_block(BlockType::Text, json!({ "type": "text", "text": "" })),
+                    );
+                }
+                out.push(self.state.emit_delta("text_delta", json!({ "text": text })));
+            }
+
+            for call in delta
+                .get("tool_calls")
+                .and_then(|v| v.as_array())
+                .into_iter()
+                .flatten()
+            {
+                out.extend(self.process_tool_call(call));
+            }
+        }
+
+        if let Some(reason) = choice.get("finish_reason").and_then(|v| v.as_str()) {
+            self.finish_reason = Some(reason.to_string());
+        }
+
+        out
+    }
+
+    /// 同一 index 的后续 chunk 只携带 arguments 片段
+    fn process_tool_call(&mut self, call: &Value) -> Vec<Bytes> {
+        let mut out = Vec::new();
+        let index = call.get("index").and_then(|v| v.as_u64()).unwrap_or(0);
+        let function = call.get("function");
+
+        let is_new = self.tool_index != Some(index)
+            || self.state.current_block_type() != BlockType::Function;
+        if is_new {
+            let id = call
+                .get("id")
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+                .unwrap_or_else(|| {
+                    format!(
+                        "toolu_{}",
+                        crate::proxy::common::utils::generate_random_id()
+                    )
+                });
+            let name = function
+                .and_then(|f| f.get("name"))
+                .and_then(|v| v.as_str())
+                .unwrap_or_default();
+            out.extend(self.state.start_block(
+                BlockType::Function,
+                json!({ "type": "tool_use", "id": id, "name": name, "input": {} }),
+            ));
+            self.state.mark_tool_used();
+            self.tool_index = Some(index);
+        }
+
+        if let Some(arguments) = function
+            .and_then(|f| f.get("arguments"))
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+        {
+            out.push(
+                self.state
+                    .emit_delta("input_json_delta", json!({ "partial_json": arguments })),
+            );
+        }
+
+        out
+    }
+
+    /// 发送 message_delta / message_stop (仅一次)
+    pub fn finish(&mut self) -> Vec<Bytes> {
+        if self.state.message_stop_sent {
+            return vec![];
+        }
+        let mut out = Vec::new();
+        if !self.state.message_start_sent {
+            out.push(self.state.emit_message_start(&json!({})));
+        }
+        let reason = match self.finish_reason.as_deref() {
+            Some("length") => Some("MAX_TOKENS"),
+            _ => None,
+        };
+        out.extend(self.state.emit_finish(reason, self.usage.as_ref()));
+        out
+    }
+}
+
+/// OpenAI usage → Gemini UsageMetadata，以便复用 Claude usage 转换
+fn to_usage_metadata(usage: &Value) -> UsageMetadata {
+    let get = |v: Option<&Value>| v.and_then(|v| v.as_u64()).map(|n| n as u32);
+    UsageMetadata {
+        prompt_token_count: get(usage.get("prompt_tokens")),
+        candidates_token_count: get(usage.get("completion_tokens")),
+        total_token_count: get(usage.get("total_tokens")),
+        cached_content_token_count: get(usage.pointer("/prompt_tokens_details/cached_tokens")),
+    }
+}
+
+/// 创建从 OpenAI SSE 流到 Claude SSE 流的转换
+pub fn create_claude_sse_stream_from_openai(
+    mut openai_stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
+    trace_id: String,
+    provider_id: String,
+) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
+    use async_stream::stream;
+    use bytes::BytesMut;
+    use futures::StreamExt;
+
+    Box::pin(stream! {
+        let mut processor = OpenAIChunkProcessor::new();
+        let mut buffer = BytesMut::new();
+
+        while let Some(chunk_result) = openai_stream.next().await {
+            match chunk_result {
+                Ok(chunk) => {
+                    buffer.extend_from_slice(&chunk);
+
+                    while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                        let line_raw = buffer.split_to(pos + 1);
+                        if let Ok(line_str) = std::str::from_utf8(&line_raw) {
+                            let line = line_str.trim();
+                            if line.is_empty() { continue; }
+                            for sse_chunk in processor.process_line(line) {
+                                yield Ok(sse_chunk);
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    yield Err(format!("Stream error: {}", e));
+                    break;
+                }
+            }
+        }
+
+        if let Some(usage) = &processor.usage {
+            tracing::info!(
+                "[{}] ✓ Stream completed | Provider: {} | In: {} tokens | Out: {} tokens",
+                trace_id,
+                provider_id,
+                usage.prompt_token_count.unwrap_or(0),
+                usage.candidates_token_count.unwrap_or(0)
+            );
+        }
+
+        // 上游未发送 [DONE] 时补发结束事件
+        for chunk in processor.finish() {
+            yield Ok(chunk);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(lines: &[&str]) -> Vec<Value> {
+        let mut processor = OpenAIChunkProcessor::new();
+        let text: String = lines
+            .iter()
+            .flat_map(|line| processor.process_line(line))
+            .map(|b| String::from_utf8(b.to_vec()).unwrap())
+            .collect();
+        text.lines()
+            .filter_map(|l| l.strip_prefix("data: "))
+            .map(|d| serde_json::from_str(d).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_reasoning_text_and_tool_calls() {
+        let events = run(&[
+            r#"data: {"id":"chatcmpl-1","model":"qwen3","choices":[{"index":0,"delta":{"role":"assistant","reasoning_content":"Let me look"}}]}"#,
+            r#"data: {"id":"chatcmpl-1","model":"qwen3","choices":[{"index":0,"delta":{"content":"Reading"}}]}"#,
+            r#"data: {"id":"chatcmpl-1","model":"qwen3","choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"id":"call_a","type":"function","function":{"name":"Read","arguments":""}}]}}]}"#,
+            r#"data: {"id":"chatcmpl-1","model":"qwen3","choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"file_path\":"}}]}}]}"#,
+            r#"data: {"id":"chatcmpl-1","model":"qwen3","choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":"\"main.rs\"}"}}]}}]}"#,
+            r#"data: {"id":"chatcmpl-1","model":"qwen3","choices":[{"index":0,"delta":{},"finish_reason":"tool_calls"}]}"#,
+            r#"data: {"id":"chatcmpl-1","model":"qwen3","choices":[],"usage":{"prompt_tokens":120,"completion_tokens":30,"total_tokens":150,"prompt_tokens_details":{"cached_tokens":20}}}"#,
+            "data: [DONE]",
+        ]);
+
+        let types: Vec<&str> = events.iter().map(|e| e["type"].as_str().unwrap()).collect();
+        assert_eq!(
+            types,
+            vec![
+                "message_start",
+                "content_block_start",
+                "content_block_delta",
+                "content_block_stop",
+                "content_block_start",
+                "content_block_delta",
+                "content_block_stop",
+                "content_block_start",
+                "content_block_delta",
+                "content_block_delta",
+                "content_block_stop",
+                "message_delta",
+                "message_stop",
+            ]
+        );
+        assert_eq!(events[0]["message"]["model"], "qwen3");
+        assert_eq!(events[2]["delta"]["thinking"], "Let me look");
+        assert_eq!(events[5]["delta"]["text"], "Reading");
+        assert_eq!(events[7]["index"], 2);
+        assert_eq!(events[7]["content_block"]["id"], "call_a");
+        assert_eq!(events[7]["content_block"]["name"], "Read");
+        assert_eq!(events[9]["delta"]["partial_json"], "\"main.rs\"}");
+        assert_eq!(events[11]["delta"]["stop_reason"], "tool_use");
+        assert_eq!(events[11]["usage"]["input_tokens"], 100);
+        assert_eq!(events[11]["usage"]["output_tokens"], 30);
+        assert_eq!(events[11]["usage"]["cache_read_input_tokens"], 20);
+    }
+
+    #[test]
+    fn test_length_finish_without_done_and_parallel_tools() {
+        let mut processor = OpenAIChunkProcessor::new();
+        let mut events = Vec::new();
+        for line in [
+            r#"data: {"id":"c","model":"m","choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"id":"a","function":{"name":"Glob","arguments":"{}"}},{"index":1,"id":"b","function":{"name":"Grep","arguments":"{}"}}]}}]}"#,
+            r#"data: {"id":"c","model":"m","choices":[{"index":0,"delta":{},"finish_reason":"length"}]}"#,
+        ] {
+            events.extend(processor.process_line(line));
+        }
+        events.extend(processor.finish());
+        assert!(processor.finish().is_empty());
+
+        let text: String = events
+            .iter()
+            .map(|b| String::from_utf8(b.to_vec()).unwrap())
+            .collect();
+        assert_eq!(text.matches("\"type\":\"tool_use\"").count(), 2);
+        assert!(text.contains("\"stop_reason\":\"tool_use\""));
+        assert_eq!(text.matches("message_stop").count(), 2); // event 行 + data 行
+    }
+
+    #[test]
+    fn test_max_tokens_stop_reason() {
+        let events = run(&[
+            r#"data: {"id":"c","model":"m","choices":[{"index":0,"delta":{"content":"Hi"},"finish_reason":"length"}]}"#,
+            "data: [DONE]",
+        ]);
+        let delta = events
+            .iter()
+            .find(|e| e["type"] == "message_delta")
+            .unwrap();
+        assert_eq!(delta["delta"]["stop_reason"], "max_tokens");
+    }
+}
@@ -320,22 +320,7 @@ pub fn transform_claude_request_in(
 
     // Inject imageConfig if present (for image generation models)
     if let Some(image_config) = config.image_config {
-        if let Some(obj) = inner_request.as_object_mut() {
-            // 1. Remove tools (image generation does not support tools)
-            obj.remove("tools");
-
-            // 2. Remove systemInstruction (image generation does not support system prompts)
-            obj.remove("systemInstruction");
-
-            // 3. Clean generationConfig (remove thinkingConfig, responseMimeType, responseModalities etc.)
-            let gen_config = obj.entry("generationConfig").or_insert_with(|| json!({}));
-            if let Some(gen_obj) = gen_config.as_object_mut() {
-                gen_obj.remove("thinkingConfig");
-                gen_obj.remove("responseMimeType");
-                gen_obj.remove("responseModalities");
-                gen_obj.insert("imageConfig".to_string(), image_config);
-            }
-        }
+        crate::proxy::mappers::common_utils::apply_image_config(&mut inner_request, image_config);
     }
 
     // 生成 requestId
@@ -549,7 +534,7 @@ fn build_contents(
             MessageContent::Array(blocks) => {
                 for item in blocks {
                     match item {
-                        ContentBlock::Text { text } => {
+                        ContentBlock::Text { text, .. } => {
                             if text != "(no content)" {
                                 parts.push(json!({"text": text}));
                             }
@@ -653,23 +638,41 @@ fn build_contents(
                             continue;
                         }
                         ContentBlock::Image { source, .. } => {
-                            if source.source_type == "base64" {
-                                parts.push(json!({
-                                    "inlineData": {
-                                        "mimeType": source.media_type,
-                                        "data": source.data
-                                    }
-                                }));
+                            // url 来源已由 handler 下载为 base64 (media_fetch)
+                            if source.source_type != "base64" {
+                                return Err(format!(
+                                    "Unsupported image source type '{}'",
+                                    source.source_type
+                                ));
                             }
+                            parts.push(json!({
+                                "inlineData": {
+                                    "mimeType": source.media_type,
+                                    "data": source.data
+                                }
+                            }));
                         }
-                        ContentBlock::Document { source, .. } => {
-                            if source.source_type == "base64" {
-                                parts.push(json!({
+                        ContentBlock::Document { source, title, .. } => {
+                            match source.source_type.as_str() {
+                                "base64" => parts.push(json!({
                                     "inlineData": {
                                         "mimeType": source.media_type,
                                         "data": source.data
                                     }
-                                }));
+                                })),
+                                // 纯文本文档直接作为文本发送
+                                "text" => parts.push(json!({
+                                    "text": match title {
+                                        Some(title) => format!("{}\n\n{}", title, source.data),
+                                        None => source.data.clone(),
+                                    }
+                                })),
+                                other => {
+                                    return Err(format!(
+                                        "Unsupported document source type '{}'",
+                                        other
+                                    ))
+                                }
                             }
                         }
                         ContentBlock::ToolUse {
@@ -1028,6 +1031,16 @@ fn build_generation_config(
                 config["effortLevel"]
             );
         }
+
+        // Structured output: output_config.format -> responseMimeType / responseSchema
+        if let Some(format) = &output_config.format {
+            if format.type_ == "json_schema" {
+                crate::proxy::mappers::common_utils::apply_structured_output(
+                    &mut config,
+                    format.schema.as_ref(),
+                );
+            }
+        }
     }
 
     // web_search 强制 candidateCount=1
@@ -1207,6 +1220,7 @@ mod tests {
                             {"type": "text", "text": "file2.txt"}
                         ]),
                         is_error: Some(false),
+                        cache_control: None,
                     }]),
                 },
             ],
@@ -1263,6 +1277,7 @@ mod tests {
                         },
                         ContentBlock::Text {
                             text: "Here is my response".to_string(),
+                            cache_control: None,
                         },
                     ]),
                 },
@@ -1273,6 +1288,8 @@ mod tests {
                             source_type: "base64".to_string(),
                             media_type: "image/png".to_string(),
                             data: "iVBORw0KGgo=".to_string(),
+                            url: None,
+                            file_id: None,
                         },
                         cache_control: Some(json!({"type": "ephemeral"})), // 这个也应该被清理
                     }]),
@@ -1319,6 +1336,7 @@ mod tests {
                     content: MessageContent::Array(vec![
                         ContentBlock::Text {
                             text: "Checking...".to_string(),
+                            cache_control: None,
                         },
                         ContentBlock::ToolUse {
                             id: "tool_1".to_string(),
@@ -1336,6 +1354,7 @@ mod tests {
                         tool_use_id: "tool_1".to_string(),
                         content: serde_json::Value::String("file1.txt\nfile2.txt".to_string()),
                         is_error: Some(false),
+                        cache_control: None,
                         // cache_control: None, // removed
                     }]),
                 },
@@ -1394,6 +1413,7 @@ mod tests {
                     role: "assistant".to_string(),
                     content: MessageContent::Array(vec![ContentBlock::Text {
                         text: "Response".to_string(),
+                        cache_control: None,
                     }]),
                 },
             ],
@@ -1444,6 +1464,7 @@ mod tests {
                     },
                     ContentBlock::Text {
                         text: "Hi".to_string(),
+                        cache_control: None,
                     },
                 ]),
             }],
@@ -1493,6 +1514,7 @@ mod tests {
                     },
                     ContentBlock::Text {
                         text: "Hi".to_string(),
+                        cache_control: None,
                     },
                 ]),
             }],
@@ -1521,4 +1543,77 @@ mod tests {
             "Redacted thinking should NOT have thought: true"
         );
     }
+
+    #[test]
+    fn test_output_config_format_maps_to_response_schema() {
+        let req: ClaudeRequest = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{"role": "user", "content": "Extract the order"}],
+            "output_config": {
+                "format": {
+                    "type": "json_schema",
+                    "schema": {
+                        "type": "object",
+                        "definitions": {
+                            "Item": {
+                                "type": "object",
+                                "properties": {
+                                    "sku": {"type": "string", "pattern": "^[A-Z]+$"},
+                                    "qty": {"type": "integer", "minimum": 1}
+                                }
+                            }
+                        },
+                        "properties": {
+                            "order": {
+                                "type": "object",
+                                "properties": {
+                                    "items": {"type": "array", "items": {"$ref": "#/definitions/Item"}}
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }))
+        .unwrap();
+
+        let body = transform_claude_request_in(&req, "test-project").unwrap();
+        let gen_config = &body["request"]["generationConfig"];
+        assert_eq!(gen_config["responseMimeType"], "application/json");
+
+        let schema = &gen_config["responseSchema"];
+        assert!(schema.get("definitions").is_none());
+        let item = &schema["properties"]["order"]["properties"]["items"]["items"];
+        assert!(item.get("$ref").is_none());
+        assert_eq!(item["properties"]["qty"]["type"], "integer");
+        assert!(item["properties"]["qty"].get("minimum").is_none());
+        assert!(item["properties"]["sku"].get("pattern").is_none());
+    }
+
+    #[test]
+    fn test_text_document_and_unresolved_url_source() {
+        let req: ClaudeRequest = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{"role": "user", "content": [
+                {"type": "document", "title": "notes.txt", "source": {"type": "text", "media_type": "text/plain", "data": "hello"}},
+                {"type": "text", "text": "Summarize"}
+            ]}]
+        }))
+        .unwrap();
+        let body = transform_claude_request_in(&req, "test-project").unwrap();
+        let parts = &body["request"]["contents"][0]["parts"];
+        assert_eq!(parts[0]["text"], "notes.txt\n\nhello");
+
+        // url 来源必须先由 handler 下载，不能被静默丢弃
+        let req: ClaudeRequest = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{"role": "user", "content": [
+                {"type": "image", "source": {"type": "url", "url": "https://example.com/a.png"}}
+            ]}]
+        }))
+        .unwrap();
+        assert!(transform_claude_request_in(&req, "test-project")
+            .unwrap_err()
+            .contains("url"));
+    }
 }
@@ -337,6 +337,7 @@ impl NonStreamingProcessor {
 
         self.content_blocks.push(ContentBlock::Text {
             text: self.text_builder.clone(),
+            cache_control: None,
         });
         self.text_builder.clear();
     }
@@ -450,7 +451,7 @@ mod tests {
         assert_eq!(claude_resp.content.len(), 1);
 
         match &claude_resp.content[0] {
-            ContentBlock::Text { text } => {
+            ContentBlock::Text { text, .. } => {
                 assert_eq!(text, "Hello, world!");
             }
             _ => panic!("Expected Text block"),
@@ -510,7 +511,7 @@ mod tests {
         }
 
         match &claude_resp.content[1] {
-            ContentBlock::Text { text } => {
+            ContentBlock::Text { text, .. } => {
                 assert_eq!(text, "The answer is 42");
             }
             _ => panic!("Expected Text block"),
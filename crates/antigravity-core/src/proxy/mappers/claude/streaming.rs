@@ -2,7 +2,7 @@
 // 对应 StreamingState + PartProcessor
 
 use super::models::*;
-use super::utils::to_claude_usage;
+use super::utils::to_claude_usage_with_cache;
 // use crate::proxy::mappers::signature_store::store_thought_signature; // Deprecated
 use crate::proxy::SignatureCache;
 use bytes::Bytes;
@@ -174,6 +174,8 @@ pub struct StreamingState {
     last_valid_state: Option<BlockType>,
     // [NEW] Model tracking for signature cache
     pub model_name: Option<String>,
+    /// 本次请求创建了 prompt cache，cachedContentTokenCount 计为 cache_creation
+    pub cache_created: bool,
 }
 
 impl Default for StreamingState {
@@ -198,6 +200,7 @@ impl StreamingState {
             parse_error_count: 0,
             last_valid_state: None,
             model_name: None,
+            cache_created: false,
         }
     }
 
@@ -220,7 +223,7 @@ impl StreamingState {
         let usage = raw_json
             .get("usageMetadata")
             .and_then(|u| serde_json::from_value::<UsageMetadata>(u.clone()).ok())
-            .map(|u| to_claude_usage(&u));
+            .map(|u| to_claude_usage_with_cache(&u, self.cache_created));
 
         let mut message = json!({
             "id": raw_json.get("responseId")
@@ -422,13 +425,15 @@ impl StreamingState {
             "end_turn"
         };
 
-        let usage = usage_metadata.map(to_claude_usage).unwrap_or(Usage {
-            input_tokens: 0,
-            output_tokens: 0,
-            cache_read_input_tokens: None,
-            cache_creation_input_tokens: None,
-            server_tool_use: None,
-        });
+        let usage = usage_metadata
+            .map(|u| to_claude_usage_with_cache(u, self.cache_created))
+            .unwrap_or(Usage {
+                input_tokens: 0,
+                output_tokens: 0,
+                cache_read_input_tokens: None,
+                cache_creation_input_tokens: None,
+                server_tool_use: None,
+            });
 
         chunks.push(self.emit(
             "message_delta",
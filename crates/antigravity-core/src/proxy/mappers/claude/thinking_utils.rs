@@ -102,12 +102,14 @@ pub fn close_tool_loop_for_thinking(messages: &mut Vec<Message>) {
             role: "assistant".to_string(),
             content: MessageContent::Array(vec![ContentBlock::Text {
                 text: "[Tool execution completed. Please proceed.]".to_string(),
+                cache_control: None,
             }]),
         });
         messages.push(Message {
             role: "user".to_string(),
             content: MessageContent::Array(vec![ContentBlock::Text {
                 text: "Proceed.".to_string(),
+                cache_control: None,
             }]),
         });
     }
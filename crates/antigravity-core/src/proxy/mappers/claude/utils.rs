@@ -4,20 +4,33 @@
 
 /// 从 Gemini UsageMetadata 转换为 Claude Usage
 pub fn to_claude_usage(usage_metadata: &super::models::UsageMetadata) -> super::models::Usage {
+    to_claude_usage_with_cache(usage_metadata, false)
+}
+
+/// `cache_created` 表示本次请求刚创建了上游缓存 (prompt_cache)：
+/// 此时 cachedContentTokenCount 计为 cache_creation_input_tokens，而非命中
+pub fn to_claude_usage_with_cache(
+    usage_metadata: &super::models::UsageMetadata,
+    cache_created: bool,
+) -> super::models::Usage {
     let prompt_tokens = usage_metadata.prompt_token_count.unwrap_or(0);
     let cached_tokens = usage_metadata.cached_content_token_count.unwrap_or(0);
 
+    let (cache_read, cache_creation) = if cache_created {
+        (None, cached_tokens)
+    } else if cached_tokens > 0 {
+        (Some(cached_tokens), 0)
+    } else {
+        (None, 0)
+    };
+
     super::models::Usage {
         // input_tokens 应该排除缓存的部分
         input_tokens: prompt_tokens.saturating_sub(cached_tokens),
         output_tokens: usage_metadata.candidates_token_count.unwrap_or(0),
         // 缓存统计
-        cache_read_input_tokens: if cached_tokens > 0 {
-            Some(cached_tokens)
-        } else {
-            None
-        },
-        cache_creation_input_tokens: Some(0), // Gemini 不提供此字段,设为 0
+        cache_read_input_tokens: cache_read,
+        cache_creation_input_tokens: Some(cache_creation),
         server_tool_use: None,
     }
 }
@@ -44,4 +57,26 @@ mod tests {
         assert_eq!(claude_usage.input_tokens, 100);
         assert_eq!(claude_usage.output_tokens, 50);
     }
+
+    #[test]
+    fn test_to_claude_usage_with_cache() {
+        use super::super::models::UsageMetadata;
+
+        let usage = UsageMetadata {
+            prompt_token_count: Some(5000),
+            candidates_token_count: Some(20),
+            total_token_count: Some(5020),
+            cached_content_token_count: Some(4000),
+        };
+
+        let read = to_claude_usage_with_cache(&usage, false);
+        assert_eq!(read.input_tokens, 1000);
+        assert_eq!(read.cache_read_input_tokens, Some(4000));
+        assert_eq!(read.cache_creation_input_tokens, Some(0));
+
+        let created = to_claude_usage_with_cache(&usage, true);
+        assert_eq!(created.input_tokens, 1000);
+        assert_eq!(created.cache_read_input_tokens, None);
+        assert_eq!(created.cache_creation_input_tokens, Some(4000));
+    }
 }
@@ -188,6 +188,39 @@ pub fn deep_clean_undefined(value: &mut Value) {
     }
 }
 
+/// 结构化输出：写入 `responseMimeType: application/json`，并在提供 schema 时
+/// 经 `clean_json_schema` 清洗 ($ref 展开、移除不支持字段) 后写入 `responseSchema`
+pub fn apply_structured_output(gen_config: &mut Value, schema: Option<&Value>) {
+    gen_config["responseMimeType"] = json!("application/json");
+
+    if let Some(schema) = schema.filter(|s| s.is_object()) {
+        let mut cleaned = schema.clone();
+        crate::proxy::common::json_schema::clean_json_schema(&mut cleaned);
+        gen_config["responseSchema"] = cleaned;
+    }
+}
+
+/// 图像生成模型：移除不支持的 tools / systemInstruction，清理 generationConfig
+/// (thinkingConfig、结构化输出、responseModalities) 后写入 `imageConfig`
+///
+/// Claude / OpenAI / Gemini 三条入口共用，保证图像请求体一致。
+pub fn apply_image_config(inner_request: &mut Value, image_config: Value) {
+    let Some(obj) = inner_request.as_object_mut() else {
+        return;
+    };
+    obj.remove("tools");
+    obj.remove("systemInstruction");
+    let gen_config = obj.entry("generationConfig").or_insert_with(|| json!({}));
+    if let Some(gen_obj) = gen_config.as_object_mut() {
+        gen_obj.remove("thinkingConfig");
+        gen_obj.remove("responseMimeType");
+        gen_obj.remove("responseSchema");
+        // Cherry Studio 会发送 responseModalities，与 imageConfig 冲突
+        gen_obj.remove("responseModalities");
+        gen_obj.insert("imageConfig".to_string(), image_config);
+    }
+}
+
 /// Detects if the tool list contains a request for networking/web search.
 /// Supported keywords: "web_search", "google_search", "web_search_20250305"
 pub fn detects_networking_tool(tools: &Option<Vec<Value>>) -> bool {
@@ -375,4 +408,52 @@ mod tests {
         assert_eq!(config_4k_wide["imageSize"], "4K");
         assert_eq!(config_4k_wide["aspectRatio"], "21:9");
     }
+
+    #[test]
+    fn test_image_gen_config_consistent_across_protocols() {
+        let model = "gemini-3-pro-image-2k";
+        let schema = json!({"type": "object", "properties": {"a": {"type": "string"}}});
+
+        let claude: crate::proxy::mappers::claude::models::ClaudeRequest =
+            serde_json::from_value(json!({
+                "model": model,
+                "max_tokens": 1024,
+                "system": "Be creative",
+                "messages": [{"role": "user", "content": "Draw a cat"}],
+                "output_config": {"format": {"type": "json_schema", "schema": schema}}
+            }))
+            .unwrap();
+        let openai: crate::proxy::mappers::openai::OpenAIRequest = serde_json::from_value(json!({
+            "model": model,
+            "messages": [
+                {"role": "system", "content": "Be creative"},
+                {"role": "user", "content": "Draw a cat"}
+            ],
+            "response_format": {"type": "json_schema", "json_schema": {"name": "a", "schema": schema}}
+        }))
+        .unwrap();
+        let gemini = json!({
+            "model": model,
+            "systemInstruction": {"parts": [{"text": "Be creative"}]},
+            "contents": [{"role": "user", "parts": [{"text": "Draw a cat"}]}],
+            "generationConfig": {"responseMimeType": "application/json", "responseSchema": schema}
+        });
+
+        let bodies = [
+            crate::proxy::mappers::claude::request::transform_claude_request_in(&claude, "p")
+                .unwrap(),
+            crate::proxy::mappers::openai::transform_openai_request(&openai, "p", model),
+            crate::proxy::mappers::gemini::wrapper::wrap_request(&gemini, "p", model),
+        ];
+        for body in &bodies {
+            let request = &body["request"];
+            assert!(request.get("tools").is_none(), "{}", body);
+            assert!(request.get("systemInstruction").is_none(), "{}", body);
+            let gen_config = &request["generationConfig"];
+            for key in ["responseMimeType", "responseSchema", "thinkingConfig"] {
+                assert!(gen_config.get(key).is_none(), "{} kept {}", body, key);
+            }
+            assert_eq!(gen_config["imageConfig"], parse_image_config(model).0);
+        }
+    }
 }
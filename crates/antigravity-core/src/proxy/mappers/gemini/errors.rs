@@ -0,0 +1,69 @@
+// Google 风格错误响应
+// Generative Language API 的错误体: {"error":{"code":400,"message":"...","status":"INVALID_ARGUMENT"}}
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::{json, Value};
+
+/// HTTP 状态码对应的 google.rpc.Code 名称
+pub fn google_status(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        400 => "INVALID_ARGUMENT",
+        401 => "UNAUTHENTICATED",
+        403 => "PERMISSION_DENIED",
+        404 => "NOT_FOUND",
+        409 => "ABORTED",
+        412 => "FAILED_PRECONDITION",
+        429 => "RESOURCE_EXHAUSTED",
+        499 => "CANCELLED",
+        501 => "UNIMPLEMENTED",
+        503 | 529 => "UNAVAILABLE",
+        504 => "DEADLINE_EXCEEDED",
+        _ => "INTERNAL",
+    }
+}
+
+/// 构造错误体；上游已是 Google 错误体时原样透传
+pub fn google_error_body(status: StatusCode, message: &str) -> Value {
+    if let Ok(upstream) = serde_json::from_str::<Value>(message) {
+        if upstream.get("error").is_some_and(Value::is_object) {
+            return upstream;
+        }
+    }
+    json!({
+        "error": {
+            "code": status.as_u16(),
+            "message": message,
+            "status": google_status(status),
+        }
+    })
+}
+
+pub fn google_error_response(status: StatusCode, message: &str) -> Response {
+    (status, Json(google_error_body(status, message))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_google_error_body() {
+        assert_eq!(
+            google_error_body(StatusCode::TOO_MANY_REQUESTS, "quota"),
+            json!({"error": {"code": 429, "message": "quota", "status": "RESOURCE_EXHAUSTED"}})
+        );
+        let upstream = r#"{"error":{"code":404,"message":"not found","status":"NOT_FOUND"}}"#;
+        assert_eq!(
+            google_error_body(StatusCode::NOT_FOUND, upstream),
+            serde_json::from_str::<Value>(upstream).unwrap()
+        );
+        assert_eq!(
+            google_status(StatusCode::from_u16(529).unwrap()),
+            "UNAVAILABLE"
+        );
+    }
+}
@@ -1,7 +1,9 @@
 // Gemini mapper 模块
 // 负责 v1internal 包装/解包
 
+pub mod errors;
 pub mod models;
+pub mod streaming;
 pub mod wrapper;
 
 // No public exports needed here if unused
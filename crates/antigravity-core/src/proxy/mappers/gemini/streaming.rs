@@ -0,0 +1,188 @@
+// Gemini 原生流式响应转换
+// 上游 v1internal 始终以 SSE 返回 (包装在 `response` 字段中)，
+// 这里按客户端请求的形式输出，与公开 Generative Language API 保持一致：
+// - `?alt=sse`: `data: {...}\r\n\r\n`，无 `[DONE]` 结束标记
+// - 默认:       JSON 数组 `[{...}\n,\r\n{...}\n]`
+
+use bytes::{Bytes, BytesMut};
+use serde_json::Value;
+
+/// 客户端请求的流式输出形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeminiStreamFormat {
+    Sse,
+    JsonArray,
+}
+
+impl GeminiStreamFormat {
+    /// `alt` 查询参数为 `sse` 时输出 SSE，否则输出 JSON 数组
+    pub fn from_alt(alt: Option<&str>) -> Self {
+        if alt.is_some_and(|alt| alt.eq_ignore_ascii_case("sse")) {
+            Self::Sse
+        } else {
+            Self::JsonArray
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Sse => "text/event-stream",
+            Self::JsonArray => "application/json; charset=UTF-8",
+        }
+    }
+}
+
+/// JSON 数组形式的流式响应 Content-Type 为 application/json，
+/// 中间件需按路径识别，避免整体缓冲响应体
+pub fn is_stream_path(path: &str) -> bool {
+    path.contains(":streamGenerateContent")
+}
+
+/// 从 JSON 数组流的尾部解析最后一个元素 (用于提取 usageMetadata)
+pub fn parse_json_array_tail(tail: &str) -> Option<Value> {
+    let body = tail.trim().strip_suffix(']')?;
+    let last = match body.rfind("\n,\r\n") {
+        Some(pos) => &body[pos + 4..],
+        None => body.strip_prefix('[')?,
+    };
+    serde_json::from_str(last.trim()).ok()
+}
+
+/// 将上游 SSE 分块增量转换为客户端格式
+#[derive(Debug)]
+pub struct GeminiStreamTransformer {
+    format: GeminiStreamFormat,
+    buffer: BytesMut,
+    chunks_emitted: usize,
+}
+
+impl GeminiStreamTransformer {
+    pub fn new(format: GeminiStreamFormat) -> Self {
+        Self {
+            format,
+            buffer: BytesMut::new(),
+            chunks_emitted: 0,
+        }
+    }
+
+    /// 输入一段上游字节，返回可立即发送给客户端的数据
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<Bytes> {
+        self.buffer.extend_from_slice(bytes);
+        let mut out = Vec::new();
+        while let Some(pos) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line = self.buffer.split_to(pos + 1);
+            if let Some(chunk) = self.transform_line(&line) {
+                out.push(chunk);
+            }
+        }
+        out
+    }
+
+    /// 上游结束：处理残留的最后一行并补全 JSON 数组
+    pub fn finish(&mut self) -> Vec<Bytes> {
+        let mut out = Vec::new();
+        if !self.buffer.is_empty() {
+            let line = self.buffer.split();
+            if let Some(chunk) = self.transform_line(&line) {
+                out.push(chunk);
+            }
+        }
+        if self.format == GeminiStreamFormat::JsonArray {
+            out.push(Bytes::from_static(if self.chunks_emitted == 0 {
+                b"[]"
+            } else {
+                b"]"
+            }));
+        }
+        out
+    }
+
+    fn transform_line(&mut self, raw: &[u8]) -> Option<Bytes> {
+        let line = std::str::from_utf8(raw).ok()?.trim();
+        let data = line.strip_prefix("data:")?.trim();
+        if data.is_empty() || data == "[DONE]" {
+            return None;
+        }
+
+        let mut json: Value = match serde_json::from_str(data) {
+            Ok(json) => json,
+            Err(e) => {
+                tracing::debug!("[Gemini-SSE] Skipping unparsable chunk: {}", e);
+                return None;
+            }
+        };
+        // 解开 v1internal 的 response 包装
+        if let Some(inner) = json.get_mut("response").map(Value::take) {
+            json = inner;
+        }
+
+        let chunk = match self.format {
+            GeminiStreamFormat::Sse => format!(
+                "data: {}\r\n\r\n",
+                serde_json::to_string(&json).unwrap_or_default()
+            ),
+            GeminiStreamFormat::JsonArray => format!(
+                "{}{}\n",
+                if self.chunks_emitted == 0 {
+                    "["
+                } else {
+                    ",\r\n"
+                },
+                serde_json::to_string_pretty(&json).unwrap_or_default()
+            ),
+        };
+        self.chunks_emitted += 1;
+        Some(Bytes::from(chunk))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect(format: GeminiStreamFormat, chunks: &[&[u8]]) -> String {
+        let mut transformer = GeminiStreamTransformer::new(format);
+        let mut out = Vec::new();
+        for chunk in chunks {
+            out.extend(transformer.push(chunk));
+        }
+        out.extend(transformer.finish());
+        out.iter()
+            .map(|b| String::from_utf8_lossy(b).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_sse_unwraps_and_drops_done() {
+        let upstream: &[&[u8]] = &[
+            b"data: {\"response\":{\"candidates\":[]}}\n\ndata: [DO",
+            b"NE]\n\n",
+        ];
+        assert_eq!(
+            collect(GeminiStreamFormat::Sse, upstream),
+            "data: {\"candidates\":[]}\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_json_array_framing() {
+        let upstream: &[&[u8]] = &[b"data: {\"a\":1}\n\ndata: {\"b\":2}"];
+        assert_eq!(
+            collect(GeminiStreamFormat::JsonArray, upstream),
+            "[{\n  \"a\": 1\n}\n,\r\n{\n  \"b\": 2\n}\n]"
+        );
+        assert_eq!(collect(GeminiStreamFormat::JsonArray, &[]), "[]");
+        assert_eq!(
+            parse_json_array_tail(&collect(GeminiStreamFormat::JsonArray, upstream)),
+            Some(serde_json::json!({"b": 2}))
+        );
+        assert_eq!(
+            GeminiStreamFormat::from_alt(Some("SSE")),
+            GeminiStreamFormat::Sse
+        );
+        assert_eq!(
+            GeminiStreamFormat::from_alt(None),
+            GeminiStreamFormat::JsonArray
+        );
+    }
+}
@@ -67,6 +67,14 @@ pub fn wrap_request(body: &Value, project_id: &str, mapped_model: &str) -> Value
         }
     }
 
+    // 结构化输出：保留客户端的 responseMimeType/responseSchema，仅清洗 Schema
+    if let Some(schema) = inner_request
+        .get_mut("generationConfig")
+        .and_then(|g| g.get_mut("responseSchema"))
+    {
+        crate::proxy::common::json_schema::clean_json_schema(schema);
+    }
+
     tracing::debug!(
         "[Debug] Gemini Wrap: original='{}', mapped='{}', final='{}', type='{}'",
         original_model,
@@ -82,22 +90,7 @@ pub fn wrap_request(body: &Value, project_id: &str, mapped_model: &str) -> Value
 
     // Inject imageConfig if present (for image generation models)
     if let Some(image_config) = config.image_config {
-        if let Some(obj) = inner_request.as_object_mut() {
-            // 1. Remove tools (image generation does not support tools)
-            obj.remove("tools");
-
-            // 2. Remove systemInstruction (image generation does not support system prompts)
-            obj.remove("systemInstruction");
-
-            // 3. Clean generationConfig (remove thinkingConfig, responseMimeType, responseModalities etc.)
-            let gen_config = obj.entry("generationConfig").or_insert_with(|| json!({}));
-            if let Some(gen_obj) = gen_config.as_object_mut() {
-                gen_obj.remove("thinkingConfig");
-                gen_obj.remove("responseMimeType");
-                gen_obj.remove("responseModalities"); // Cherry Studio sends this, might conflict
-                gen_obj.insert("imageConfig".to_string(), image_config);
-            }
-        }
+        crate::proxy::mappers::common_utils::apply_image_config(&mut inner_request, image_config);
     } else {
         // [NEW] 只在非图像生成模式下注入 Antigravity 身份 (原始简化版)
         let antigravity_identity = "You are Antigravity, a powerful agentic AI coding assistant designed by the Google Deepmind team working on Advanced Agentic Coding.\n\
@@ -265,4 +258,35 @@ mod tests {
         // Should NOT inject duplicate, so only 1 part remains
         assert_eq!(parts.len(), 1);
     }
+
+    #[test]
+    fn test_response_schema_preserved_and_cleaned() {
+        let body = json!({
+            "model": "gemini-2.5-flash",
+            "contents": [{"role": "user", "parts": [{"text": "Hi"}]}],
+            "generationConfig": {
+                "responseMimeType": "application/json",
+                "responseSchema": {
+                    "type": "object",
+                    "$defs": {
+                        "Point": {
+                            "type": "object",
+                            "properties": {"x": {"type": "number", "exclusiveMinimum": 0}}
+                        }
+                    },
+                    "properties": {"origin": {"$ref": "#/$defs/Point"}}
+                }
+            }
+        });
+
+        let result = wrap_request(&body, "test-proj", "gemini-2.5-flash");
+        let gen_config = &result["request"]["generationConfig"];
+        assert_eq!(gen_config["responseMimeType"], "application/json");
+
+        let origin = &gen_config["responseSchema"]["properties"]["origin"];
+        assert!(origin.get("$ref").is_none());
+        assert_eq!(origin["properties"]["x"]["type"], "number");
+        assert!(origin["properties"]["x"].get("exclusiveMinimum").is_none());
+        assert!(gen_config["responseSchema"].get("$defs").is_none());
+    }
 }
@@ -5,6 +5,7 @@ use super::models::*;
 use bytes::Bytes;
 use futures::StreamExt;
 use serde_json::Value;
+use std::collections::BTreeMap;
 use std::io;
 
 /// SSE 事件类型
@@ -64,11 +65,11 @@ where
         created: chrono::Utc::now().timestamp() as u64,
         model: String::new(),
         choices: vec![],
+        usage: None,
     };
 
-    let mut content = String::new();
-    let mut tool_calls: Vec<ToolCall> = Vec::new();
-    let mut finish_reason: Option<String> = None;
+    // n > 1 时按 choice.index 分别累积
+    let mut choices: BTreeMap<u32, ChoiceAccumulator> = BTreeMap::new();
 
     for event in chunks {
         // 提取基本信息
@@ -82,13 +83,21 @@ where
             response.created = created;
         }
 
+        // include_usage 的最后一个 chunk
+        if let Some(usage) = event.data.get("usage").filter(|u| u.is_object()) {
+            response.usage = serde_json::from_value(usage.clone()).ok();
+        }
+
         // 处理 choices
         if let Some(choices_arr) = event.data.get("choices").and_then(|v| v.as_array()) {
             for choice in choices_arr {
+                let index = choice.get("index").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+                let acc = choices.entry(index).or_default();
+
                 if let Some(delta) = choice.get("delta") {
                     // 累积 content
                     if let Some(text) = delta.get("content").and_then(|v| v.as_str()) {
-                        content.push_str(text);
+                        acc.content.push_str(text);
                     }
 
                     // 累积 tool_calls
@@ -98,8 +107,8 @@ where
                                 tc.get("index").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
 
                             // 确保 tool_calls 有足够的空间
-                            while tool_calls.len() <= index {
-                                tool_calls.push(ToolCall {
+                            while acc.tool_calls.len() <= index {
+                                acc.tool_calls.push(ToolCall {
                                     id: String::new(),
                                     r#type: "function".to_string(),
                                     function: ToolFunction {
@@ -110,62 +119,94 @@ where
                             }
 
                             if let Some(id) = tc.get("id").and_then(|v| v.as_str()) {
-                                tool_calls[index].id = id.to_string();
+                                acc.tool_calls[index].id = id.to_string();
                             }
                             if let Some(func) = tc.get("function") {
                                 if let Some(name) = func.get("name").and_then(|v| v.as_str()) {
-                                    tool_calls[index].function.name = name.to_string();
+                                    acc.tool_calls[index].function.name = name.to_string();
                                 }
                                 if let Some(args) = func.get("arguments").and_then(|v| v.as_str()) {
-                                    tool_calls[index].function.arguments.push_str(args);
+                                    acc.tool_calls[index].function.arguments.push_str(args);
                                 }
                             }
                         }
                     }
                 }
 
+                // 累积 logprobs
+                if let Some(content) = choice
+                    .get("logprobs")
+                    .and_then(|l| l.get("content"))
+                    .and_then(|c| serde_json::from_value::<Vec<TokenLogprob>>(c.clone()).ok())
+                {
+                    acc.logprobs
+                        .get_or_insert_with(ChoiceLogprobs::default)
+                        .content
+                        .extend(content);
+                }
+
                 // 获取 finish_reason
                 if let Some(reason) = choice.get("finish_reason").and_then(|v| v.as_str()) {
-                    finish_reason = Some(reason.to_string());
+                    acc.finish_reason = Some(reason.to_string());
                 }
             }
         }
+    }
 
-        // OpenAIResponse 没有 usage 字段，跳过
+    // 3. 构建最终的 choices (至少一个)
+    if choices.is_empty() {
+        choices.insert(0, ChoiceAccumulator::default());
     }
+    response.choices = choices
+        .into_iter()
+        .map(|(index, acc)| acc.into_choice(index))
+        .collect();
 
-    // 3. 构建最终的 choice
-    let message = if !tool_calls.is_empty() {
-        OpenAIMessage {
-            role: "assistant".to_string(),
-            content: if content.is_empty() {
-                None
-            } else {
-                Some(OpenAIContent::String(content))
-            },
-            tool_calls: Some(tool_calls),
-            reasoning_content: None,
-            tool_call_id: None,
-            name: None,
-        }
-    } else {
-        OpenAIMessage {
-            role: "assistant".to_string(),
-            content: Some(OpenAIContent::String(content)),
-            tool_calls: None,
-            reasoning_content: None,
-            tool_call_id: None,
-            name: None,
-        }
-    };
+    Ok(response)
+}
 
-    response.choices.push(Choice {
-        index: 0,
-        message,
-        finish_reason,
-    });
+/// 单个 choice 的累积状态
+#[derive(Debug, Default)]
+struct ChoiceAccumulator {
+    content: String,
+    tool_calls: Vec<ToolCall>,
+    logprobs: Option<ChoiceLogprobs>,
+    finish_reason: Option<String>,
+}
 
-    Ok(response)
+impl ChoiceAccumulator {
+    fn into_choice(self, index: u32) -> Choice {
+        let message = if !self.tool_calls.is_empty() {
+            OpenAIMessage {
+                role: "assistant".to_string(),
+                content: if self.content.is_empty() {
+                    None
+                } else {
+                    Some(OpenAIContent::String(self.content))
+                },
+                tool_calls: Some(self.tool_calls),
+                reasoning_content: None,
+                tool_call_id: None,
+                name: None,
+            }
+        } else {
+            OpenAIMessage {
+                role: "assistant".to_string(),
+                content: Some(OpenAIContent::String(self.content)),
+                tool_calls: None,
+                reasoning_content: None,
+                tool_call_id: None,
+                name: None,
+            }
+        };
+
+        Choice {
+            index,
+            message,
+            logprobs: self.logprobs,
+            finish_reason: self.finish_reason,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -202,4 +243,30 @@ mod tests {
             panic!("Expected String content");
         }
     }
+
+    #[tokio::test]
+    async fn test_collect_multiple_choices_with_logprobs_and_usage() {
+        let sse_data = vec![
+            "data: {\"id\":\"chatcmpl-1\",\"model\":\"gemini\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"A\"},\"logprobs\":{\"content\":[{\"token\":\"A\",\"logprob\":-0.5,\"bytes\":[65],\"top_logprobs\":[]}]},\"finish_reason\":null}]}\n\n",
+            "data: {\"id\":\"chatcmpl-1\",\"model\":\"gemini\",\"choices\":[{\"index\":1,\"delta\":{\"role\":\"assistant\",\"content\":\"B\"},\"logprobs\":null,\"finish_reason\":\"stop\"}]}\n\n",
+            "data: {\"id\":\"chatcmpl-1\",\"model\":\"gemini\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"\"},\"logprobs\":null,\"finish_reason\":\"length\"}]}\n\n",
+            "data: {\"id\":\"chatcmpl-1\",\"model\":\"gemini\",\"choices\":[],\"usage\":{\"prompt_tokens\":4,\"completion_tokens\":2,\"total_tokens\":6}}\n\n",
+            "data: [DONE]\n\n",
+        ];
+
+        let byte_stream = stream::iter(
+            sse_data
+                .into_iter()
+                .map(|s| Ok::<Bytes, io::Error>(Bytes::from(s))),
+        );
+
+        let response = collect_openai_stream_to_json(byte_stream).await.unwrap();
+        assert_eq!(response.choices.len(), 2);
+        assert_eq!(response.choices[0].finish_reason.as_deref(), Some("length"));
+        assert_eq!(response.choices[1].finish_reason.as_deref(), Some("stop"));
+        let logprobs = response.choices[0].logprobs.as_ref().unwrap();
+        assert_eq!(logprobs.content[0].token, "A");
+        assert!(response.choices[1].logprobs.is_none());
+        assert_eq!(response.usage.unwrap().total_tokens, 6);
+    }
 }
@@ -5,6 +5,7 @@ pub mod collector;
 pub mod models;
 pub mod request;
 pub mod response;
+pub mod responses;
 pub mod streaming;
 
 pub use collector::collect_openai_stream_to_json;
@@ -153,6 +153,24 @@ pub fn transform_openai_request(
                                         }
                                     }
                                 }
+                                OpenAIContentBlock::File { file } => {
+                                    // file_id 已由 handler 从 /v1/files 解析为 data URL (media_fetch)
+                                    let inline = file.file_data.as_deref().and_then(|d| {
+                                        let mime_type = crate::proxy::common::media_fetch::data_url_mime(d)?;
+                                        Some((mime_type, d.split_once(',')?.1))
+                                    });
+                                    match inline {
+                                        Some((mime_type, data)) => {
+                                            parts.push(json!({
+                                                "inlineData": { "mimeType": mime_type, "data": data }
+                                            }));
+                                        }
+                                        None => tracing::warn!(
+                                            "[OpenAI-Request] Skipping file block without a typed data URL: {:?}",
+                                            file.file_id
+                                        ),
+                                    }
+                                }
                                 OpenAIContentBlock::AudioUrl { audio_url: _ } => {
                                     // [PR #311 部分合并] 暂时跳过 audio_url 处理
                                     // 完整实现需要下载音频文件并转换为 Gemini inlineData 格式
@@ -244,7 +262,10 @@ pub fn transform_openai_request(
             || mapped_model.contains("-pro"));
 
     let mut gen_config = json!({
-        "maxOutputTokens": request.max_tokens.unwrap_or(64000),
+        "maxOutputTokens": request
+            .max_completion_tokens
+            .or(request.max_tokens)
+            .unwrap_or(64000),
         "temperature": request.temperature.unwrap_or(1.0),
         "topP": request.top_p.unwrap_or(1.0),
     });
@@ -254,6 +275,23 @@ pub fn transform_openai_request(
         gen_config["candidateCount"] = json!(n);
     }
 
+    // 采样参数透传 (评测场景需要可复现的 seed 与 logprobs)
+    if let Some(seed) = request.seed {
+        gen_config["seed"] = json!(seed);
+    }
+    if let Some(penalty) = request.presence_penalty {
+        gen_config["presencePenalty"] = json!(penalty);
+    }
+    if let Some(penalty) = request.frequency_penalty {
+        gen_config["frequencyPenalty"] = json!(penalty);
+    }
+    if request.logprobs == Some(true) {
+        gen_config["responseLogprobs"] = json!(true);
+        if let Some(top) = request.top_logprobs {
+            gen_config["logprobs"] = json!(top);
+        }
+    }
+
     // [FIX PR #368] 为 Gemini 3 Pro 注入 thinkingConfig (使用 thinkingBudget 而非 thinkingLevel)
     if is_gemini_3_thinking {
         gen_config["thinkingConfig"] = json!({
@@ -273,9 +311,17 @@ pub fn transform_openai_request(
         }
     }
 
+    // response_format: json_object / json_schema -> responseMimeType / responseSchema
     if let Some(fmt) = &request.response_format {
-        if fmt.r#type == "json_object" {
-            gen_config["responseMimeType"] = json!("application/json");
+        match fmt.r#type.as_str() {
+            "json_object" => {
+                crate::proxy::mappers::common_utils::apply_structured_output(&mut gen_config, None)
+            }
+            "json_schema" => crate::proxy::mappers::common_utils::apply_structured_output(
+                &mut gen_config,
+                fmt.json_schema.as_ref().and_then(|s| s.schema.as_ref()),
+            ),
+            _ => {}
         }
     }
 
@@ -389,17 +435,7 @@ pub fn transform_openai_request(
     }
 
     if let Some(image_config) = config.image_config {
-        if let Some(obj) = inner_request.as_object_mut() {
-            obj.remove("tools");
-            obj.remove("systemInstruction");
-            let gen_config = obj.entry("generationConfig").or_insert_with(|| json!({}));
-            if let Some(gen_obj) = gen_config.as_object_mut() {
-                gen_obj.remove("thinkingConfig");
-                gen_obj.remove("responseMimeType");
-                gen_obj.remove("responseModalities");
-                gen_obj.insert("imageConfig".to_string(), image_config);
-            }
-        }
+        crate::proxy::mappers::common_utils::apply_image_config(&mut inner_request, image_config);
     }
 
     json!({
@@ -447,6 +483,11 @@ mod tests {
                     OpenAIContentBlock::ImageUrl { image_url: OpenAIImageUrl {
                         url: "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mP8z8BQDwAEhQGAhKmMIQAAAABJRU5ErkJggg==".to_string(),
                         detail: None
+                    } },
+                    OpenAIContentBlock::File { file: OpenAIFile {
+                        file_id: Some("file-1".to_string()),
+                        file_data: Some("data:application/pdf;base64,JVBERi0=".to_string()),
+                        filename: None,
                     } }
                 ])),
                 reasoning_content: None,
@@ -457,9 +498,17 @@ mod tests {
             stream: false,
             n: None,
             max_tokens: None,
+            max_completion_tokens: None,
             temperature: None,
             top_p: None,
             stop: None,
+            seed: None,
+            presence_penalty: None,
+            frequency_penalty: None,
+            logprobs: None,
+            top_logprobs: None,
+            stream_options: None,
+            user: None,
             response_format: None,
             tools: None,
             tool_choice: None,
@@ -471,11 +520,103 @@ mod tests {
 
         let result = transform_openai_request(&req, "test-v", "gemini-1.5-flash");
         let parts = &result["request"]["contents"][0]["parts"];
-        assert_eq!(parts.as_array().unwrap().len(), 2);
+        assert_eq!(parts.as_array().unwrap().len(), 3);
         assert_eq!(parts[0]["text"].as_str().unwrap(), "What is in this image?");
         assert_eq!(
             parts[1]["inlineData"]["mimeType"].as_str().unwrap(),
             "image/png"
         );
+        assert_eq!(parts[2]["inlineData"]["mimeType"], "application/pdf");
+        assert_eq!(parts[2]["inlineData"]["data"], "JVBERi0=");
+    }
+
+    #[test]
+    fn test_response_format_json_schema_maps_to_response_schema() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4o",
+            "messages": [{"role": "user", "content": "List users"}],
+            "response_format": {
+                "type": "json_schema",
+                "json_schema": {
+                    "name": "users",
+                    "strict": true,
+                    "schema": {
+                        "type": "object",
+                        "$defs": {
+                            "User": {
+                                "type": "object",
+                                "properties": {
+                                    "name": {"type": "string", "minLength": 1},
+                                    "tags": {"type": "array", "items": {"type": "string"}}
+                                },
+                                "required": ["name"],
+                                "additionalProperties": false
+                            }
+                        },
+                        "properties": {
+                            "users": {"type": "array", "items": {"$ref": "#/$defs/User"}}
+                        },
+                        "required": ["users"]
+                    }
+                }
+            }
+        }))
+        .unwrap();
+
+        let result = transform_openai_request(&req, "test-v", "gemini-2.5-flash");
+        let gen_config = &result["request"]["generationConfig"];
+        assert_eq!(gen_config["responseMimeType"], "application/json");
+
+        let schema = &gen_config["responseSchema"];
+        assert!(schema.get("$defs").is_none());
+        let user = &schema["properties"]["users"]["items"];
+        assert!(user.get("$ref").is_none());
+        assert_eq!(user["properties"]["name"]["type"], "string");
+        assert!(user["properties"]["name"].get("minLength").is_none());
+        assert!(user.get("additionalProperties").is_none());
+        assert_eq!(user["properties"]["tags"]["items"]["type"], "string");
+    }
+
+    #[test]
+    fn test_response_format_json_object_has_no_schema() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4o",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "response_format": {"type": "json_object"}
+        }))
+        .unwrap();
+
+        let result = transform_openai_request(&req, "test-v", "gemini-2.5-flash");
+        let gen_config = &result["request"]["generationConfig"];
+        assert_eq!(gen_config["responseMimeType"], "application/json");
+        assert!(gen_config.get("responseSchema").is_none());
+    }
+
+    #[test]
+    fn test_sampling_params_map_to_generation_config() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4o",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "max_tokens": 100,
+            "max_completion_tokens": 200,
+            "seed": 42,
+            "presence_penalty": 0.5,
+            "frequency_penalty": -0.5,
+            "logprobs": true,
+            "top_logprobs": 3,
+            "stream_options": {"include_usage": true},
+            "user": "eval-run-7"
+        }))
+        .unwrap();
+
+        let result = transform_openai_request(&req, "test-v", "gemini-2.5-flash");
+        let gen_config = &result["request"]["generationConfig"];
+        assert_eq!(gen_config["maxOutputTokens"], 200);
+        assert_eq!(gen_config["seed"], 42);
+        assert_eq!(gen_config["presencePenalty"], 0.5);
+        assert_eq!(gen_config["frequencyPenalty"], -0.5);
+        assert_eq!(gen_config["responseLogprobs"], true);
+        assert_eq!(gen_config["logprobs"], 3);
+        assert!(req.stream_options.unwrap().include_usage);
     }
 }
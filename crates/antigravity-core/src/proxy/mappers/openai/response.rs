@@ -2,6 +2,73 @@
 use super::models::*;
 use serde_json::Value;
 
+fn top_logprob(candidate: &Value) -> Option<TopLogprob> {
+    let token = candidate.get("token")?.as_str()?.to_string();
+    Some(TopLogprob {
+        bytes: Some(token.as_bytes().to_vec()),
+        logprob: candidate.get("logProbability")?.as_f64()?,
+        token,
+    })
+}
+
+/// 候选结果序号：n > 1 时 Gemini 在 candidate 上标注 index，缺省时按位置
+pub fn candidate_index(candidate: &Value, position: usize) -> u32 {
+    candidate
+        .get("index")
+        .and_then(|v| v.as_u64())
+        .map(|i| i as u32)
+        .unwrap_or(position as u32)
+}
+
+/// Gemini candidate.logprobsResult -> OpenAI choice.logprobs
+pub fn logprobs_from_gemini(candidate: &Value) -> Option<ChoiceLogprobs> {
+    let result = candidate.get("logprobsResult")?;
+    let chosen = result.get("chosenCandidates")?.as_array()?;
+    let top = result.get("topCandidates").and_then(|t| t.as_array());
+
+    let content = chosen
+        .iter()
+        .enumerate()
+        .filter_map(|(i, chosen)| {
+            let token = top_logprob(chosen)?;
+            let top_logprobs = top
+                .and_then(|t| t.get(i))
+                .and_then(|t| t.get("candidates"))
+                .and_then(|c| c.as_array())
+                .map(|c| c.iter().filter_map(top_logprob).collect())
+                .unwrap_or_default();
+            Some(TokenLogprob {
+                token: token.token,
+                logprob: token.logprob,
+                bytes: token.bytes,
+                top_logprobs,
+            })
+        })
+        .collect();
+    Some(ChoiceLogprobs { content })
+}
+
+/// Gemini usageMetadata -> Chat Completions usage
+pub fn chat_usage_from_gemini(usage: &Value) -> OpenAIUsage {
+    let get = |key: &str| usage.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+    let prompt_tokens = get("promptTokenCount");
+    let reasoning_tokens = get("thoughtsTokenCount");
+    let completion_tokens = get("candidatesTokenCount") + reasoning_tokens;
+    let total_tokens = match get("totalTokenCount") {
+        0 => prompt_tokens + completion_tokens,
+        t => t,
+    };
+    OpenAIUsage {
+        prompt_tokens,
+        completion_tokens,
+        total_tokens,
+        prompt_tokens_details: Some(PromptTokensDetails {
+            cached_tokens: get("cachedContentTokenCount"),
+        }),
+        completion_tokens_details: Some(CompletionTokensDetails { reasoning_tokens }),
+    }
+}
+
 pub fn transform_openai_response(gemini_response: &Value) -> OpenAIResponse {
     // 解包 response 字段
     let raw = gemini_response.get("response").unwrap_or(gemini_response);
@@ -139,7 +206,7 @@ pub fn transform_openai_response(gemini_response: &Value) -> OpenAIResponse {
                 .unwrap_or("stop");
 
             choices.push(Choice {
-                index: idx as u32,
+                index: candidate_index(candidate, idx),
                 message: OpenAIMessage {
                     role: "assistant".to_string(),
                     content: if content_out.is_empty() {
@@ -160,6 +227,7 @@ pub fn transform_openai_response(gemini_response: &Value) -> OpenAIResponse {
                     tool_call_id: None,
                     name: None,
                 },
+                logprobs: logprobs_from_gemini(candidate),
                 finish_reason: Some(finish_reason.to_string()),
             });
         }
@@ -179,6 +247,7 @@ pub fn transform_openai_response(gemini_response: &Value) -> OpenAIResponse {
             .unwrap_or("unknown")
             .to_string(),
         choices,
+        usage: raw.get("usageMetadata").map(chat_usage_from_gemini),
     }
 }
 
@@ -209,4 +278,48 @@ mod tests {
         assert_eq!(content, "Hello!");
         assert_eq!(result.choices[0].finish_reason, Some("stop".to_string()));
     }
+
+    #[test]
+    fn test_transform_logprobs_usage_and_candidates() {
+        let gemini_resp = json!({
+            "candidates": [
+                {
+                    "index": 0,
+                    "content": {"parts": [{"text": "Hi"}]},
+                    "finishReason": "STOP",
+                    "logprobsResult": {
+                        "topCandidates": [{"candidates": [
+                            {"token": "Hi", "logProbability": -0.1},
+                            {"token": "Hello", "logProbability": -2.5}
+                        ]}],
+                        "chosenCandidates": [{"token": "Hi", "logProbability": -0.1}]
+                    }
+                },
+                {"index": 1, "content": {"parts": [{"text": "Hey"}]}, "finishReason": "MAX_TOKENS"}
+            ],
+            "usageMetadata": {
+                "promptTokenCount": 5,
+                "candidatesTokenCount": 3,
+                "thoughtsTokenCount": 2,
+                "totalTokenCount": 10
+            }
+        });
+
+        let result = transform_openai_response(&gemini_resp);
+        assert_eq!(result.choices.len(), 2);
+        assert_eq!(result.choices[1].finish_reason.as_deref(), Some("length"));
+        assert!(result.choices[1].logprobs.is_none());
+
+        let logprobs = result.choices[0].logprobs.as_ref().unwrap();
+        assert_eq!(logprobs.content.len(), 1);
+        assert_eq!(logprobs.content[0].token, "Hi");
+        assert_eq!(logprobs.content[0].bytes, Some(b"Hi".to_vec()));
+        assert_eq!(logprobs.content[0].top_logprobs.len(), 2);
+        assert_eq!(logprobs.content[0].top_logprobs[1].logprob, -2.5);
+
+        let usage = result.usage.unwrap();
+        assert_eq!(usage.prompt_tokens, 5);
+        assert_eq!(usage.completion_tokens, 5);
+        assert_eq!(usage.total_tokens, 10);
+    }
 }
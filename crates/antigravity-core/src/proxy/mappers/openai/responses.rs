@@ -0,0 +1,1162 @@
+// OpenAI Responses API 转换
+// Responses 输入 -> Chat 消息 (复用 Chat -> Gemini 转换)，Gemini 输出 -> Responses 输出项 / 类型化 SSE 事件
+
+use bytes::{Bytes, BytesMut};
+use futures::{Stream, StreamExt};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::pin::Pin;
+
+use super::models::{OpenAIContent, OpenAIResponse};
+use super::streaming::store_thought_signature;
+
+fn new_id(prefix: &str) -> String {
+    format!("{}_{}", prefix, uuid::Uuid::new_v4().simple())
+}
+
+/// `local_shell` 内置工具对应的函数声明
+fn local_shell_function() -> Value {
+    json!({
+        "type": "function",
+        "name": "shell",
+        "description": "Runs a shell command and returns its output.",
+        "parameters": {
+            "type": "object",
+            "properties": {
+                "command": {"type": "array", "items": {"type": "string"}},
+                "workdir": {"type": "string"},
+                "timeout_ms": {"type": "integer"}
+            },
+            "required": ["command"]
+        }
+    })
+}
+
+/// Responses 工具 -> Chat 工具 (扁平 function 格式 Chat 映射已支持)
+///
+/// `local_shell` 映射为 `shell` 函数，`web_search*` 映射为联网搜索，其余内置工具忽略
+pub fn convert_responses_tools(tools: &[Value]) -> Vec<Value> {
+    tools
+        .iter()
+        .filter_map(|tool| match tool.get("type").and_then(|t| t.as_str()) {
+            Some("function") | None => Some(tool.clone()),
+            Some("local_shell") => Some(local_shell_function()),
+            Some(t) if t.starts_with("web_search") => Some(json!({"name": "web_search"})),
+            Some(other) => {
+                tracing::warn!("[Responses] Unsupported tool type '{}', skipping", other);
+                None
+            }
+        })
+        .collect()
+}
+
+/// 请求是否声明了 `local_shell` 工具 (决定 shell 调用的输出项类型)
+pub fn has_local_shell_tool(tools: &[Value]) -> bool {
+    tools
+        .iter()
+        .any(|t| t.get("type").and_then(|v| v.as_str()) == Some("local_shell"))
+}
+
+/// Responses 内容部件 -> Chat 消息内容
+fn convert_message_content(content: Option<&Value>) -> Value {
+    let parts = match content {
+        Some(Value::String(s)) => return json!(s),
+        Some(Value::Array(parts)) => parts,
+        _ => return json!(""),
+    };
+
+    let mut text_parts = Vec::new();
+    let mut media_parts: Vec<Value> = Vec::new();
+
+    for part in parts {
+        let part_type = part.get("type").and_then(|v| v.as_str()).unwrap_or("");
+        match part_type {
+            "input_image" => {
+                // image_url 可能是字符串或 {url}
+                let url = part
+                    .get("image_url")
+                    .and_then(|v| v.as_str().or_else(|| v.get("url").and_then(|u| u.as_str())));
+                if let Some(url) = url {
+                    media_parts.push(json!({
+                        "type": "image_url",
+                        "image_url": { "url": url }
+                    }));
+                }
+            }
+            "input_file" => {
+                // file_id 引用 /v1/files，file_data 为内联 data URL
+                let file: serde_json::Map<String, Value> = ["file_id", "file_data", "filename"]
+                    .iter()
+                    .filter_map(|key| part.get(*key).map(|v| (key.to_string(), v.clone())))
+                    .collect();
+                if !file.is_empty() {
+                    media_parts.push(json!({"type": "file", "file": file}));
+                }
+            }
+            "image_url" => {
+                if let Some(url_obj) = part.get("image_url") {
+                    media_parts.push(json!({
+                        "type": "image_url",
+                        "image_url": url_obj.clone()
+                    }));
+                }
+            }
+            _ => {
+                // input_text / output_text / text / refusal
+                if let Some(text) = part
+                    .get("text")
+                    .or(part.get("refusal"))
+                    .and_then(|v| v.as_str())
+                {
+                    text_parts.push(text.to_string());
+                }
+            }
+        }
+    }
+
+    if media_parts.is_empty() {
+        return json!(text_parts.join("\n"));
+    }
+
+    let mut blocks: Vec<Value> = Vec::new();
+    if !text_parts.is_empty() {
+        blocks.push(json!({"type": "text", "text": text_parts.join("\n")}));
+    }
+    blocks.extend(media_parts);
+    json!(blocks)
+}
+
+fn call_id_of(item: &Value) -> &str {
+    item.get("call_id")
+        .and_then(|v| v.as_str())
+        .or_else(|| item.get("id").and_then(|v| v.as_str()))
+        .unwrap_or("unknown")
+}
+
+/// 将 local_shell_call / web_search_call / function_call 转为 (name, arguments)
+fn tool_call_name_and_args(item_type: &str, item: &Value) -> (String, String) {
+    match item_type {
+        "local_shell_call" => {
+            let mut args_obj = serde_json::Map::new();
+            if let Some(exec) = item.get("action").and_then(|a| a.get("exec").or(Some(a))) {
+                if let Some(cmd) = exec.get("command") {
+                    // shell 工具的 command 定义为字符串数组，必须以数组传递，否则 Gemini 返回 400
+                    let cmd_val = if cmd.is_string() {
+                        json!([cmd])
+                    } else {
+                        cmd.clone()
+                    };
+                    args_obj.insert("command".to_string(), cmd_val);
+                }
+                if let Some(wd) = exec.get("working_directory").or(exec.get("workdir")) {
+                    args_obj.insert("workdir".to_string(), wd.clone());
+                }
+            }
+            ("shell".to_string(), Value::Object(args_obj).to_string())
+        }
+        "web_search_call" => {
+            let mut args_obj = serde_json::Map::new();
+            if let Some(q) = item.get("action").and_then(|a| a.get("query")) {
+                args_obj.insert("query".to_string(), q.clone());
+            }
+            (
+                "google_search".to_string(),
+                Value::Object(args_obj).to_string(),
+            )
+        }
+        _ => (
+            item.get("name")
+                .and_then(|v| v.as_str())
+                .unwrap_or("unknown")
+                .to_string(),
+            item.get("arguments")
+                .and_then(|v| v.as_str())
+                .unwrap_or("{}")
+                .to_string(),
+        ),
+    }
+}
+
+fn tool_output_to_string(output: Option<&Value>) -> String {
+    match output {
+        Some(Value::String(s)) => s.clone(),
+        Some(o) => o
+            .get("content")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| o.to_string()),
+        None => String::new(),
+    }
+}
+
+/// Responses `input` (字符串或输入项数组) -> Chat 消息
+///
+/// `history` 为 previous_response_id 链上的既有消息，用于解析 function_call_output 对应的工具名
+pub fn responses_input_to_messages(input: Option<&Value>, history: &[Value]) -> Vec<Value> {
+    let items = match input {
+        Some(Value::String(s)) => return vec![json!({"role": "user", "content": s})],
+        Some(Value::Array(items)) => items,
+        _ => return Vec::new(),
+    };
+
+    // Pass 1: call_id -> 工具名 (历史 tool_calls + 本次输入中的调用)
+    let mut call_id_to_name: HashMap<String, String> = HashMap::new();
+    for msg in history {
+        if let Some(calls) = msg.get("tool_calls").and_then(|v| v.as_array()) {
+            for call in calls {
+                if let (Some(id), Some(name)) = (
+                    call.get("id").and_then(|v| v.as_str()),
+                    call.get("function")
+                        .and_then(|f| f.get("name"))
+                        .and_then(|v| v.as_str()),
+                ) {
+                    call_id_to_name.insert(id.to_string(), name.to_string());
+                }
+            }
+        }
+    }
+    for item in items {
+        let item_type = item.get("type").and_then(|v| v.as_str()).unwrap_or("");
+        if matches!(
+            item_type,
+            "function_call" | "local_shell_call" | "web_search_call"
+        ) {
+            let (name, _) = tool_call_name_and_args(item_type, item);
+            call_id_to_name.insert(call_id_of(item).to_string(), name);
+        }
+    }
+
+    // Pass 2: 输入项 -> 消息
+    let mut messages = Vec::new();
+    for item in items {
+        let item_type = item
+            .get("type")
+            .and_then(|v| v.as_str())
+            // EasyInputMessage 可省略 type
+            .unwrap_or(if item.get("role").is_some() {
+                "message"
+            } else {
+                ""
+            });
+
+        match item_type {
+            "message" => {
+                let role = match item.get("role").and_then(|v| v.as_str()).unwrap_or("user") {
+                    "developer" => "system",
+                    other => other,
+                };
+                messages.push(json!({
+                    "role": role,
+                    "content": convert_message_content(item.get("content"))
+                }));
+            }
+            "function_call" | "local_shell_call" | "web_search_call" => {
+                let (name, args) = tool_call_name_and_args(item_type, item);
+                messages.push(json!({
+                    "role": "assistant",
+                    "tool_calls": [{
+                        "id": call_id_of(item),
+                        "type": "function",
+                        "function": { "name": name, "arguments": args }
+                    }]
+                }));
+            }
+            "function_call_output" | "custom_tool_call_output" | "local_shell_call_output" => {
+                let call_id = call_id_of(item);
+                let name = call_id_to_name.get(call_id).cloned().unwrap_or_else(|| {
+                    // 无法匹配时多为 Codex 的 shell 调用
+                    tracing::warn!(
+                        "Unknown tool name for call_id {}, defaulting to 'shell'",
+                        call_id
+                    );
+                    "shell".to_string()
+                });
+                messages.push(json!({
+                    "role": "tool",
+                    "tool_call_id": call_id,
+                    "name": name,
+                    "content": tool_output_to_string(item.get("output"))
+                }));
+            }
+            // reasoning 等输出项无需回传上游
+            _ => {}
+        }
+    }
+
+    messages
+}
+
+/// Responses 输出项 -> Chat 消息 (用于 previous_response_id 链式对话)
+pub fn output_items_to_messages(output: &[Value]) -> Vec<Value> {
+    let mut messages = Vec::new();
+    for item in output {
+        let item_type = item.get("type").and_then(|v| v.as_str()).unwrap_or("");
+        match item_type {
+            "message" => {
+                let text: String = item
+                    .get("content")
+                    .and_then(|c| c.as_array())
+                    .map(|parts| {
+                        parts
+                            .iter()
+                            .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                messages.push(json!({"role": "assistant", "content": text}));
+            }
+            "function_call" | "local_shell_call" => {
+                let (name, args) = tool_call_name_and_args(item_type, item);
+                messages.push(json!({
+                    "role": "assistant",
+                    "tool_calls": [{
+                        "id": call_id_of(item),
+                        "type": "function",
+                        "function": { "name": name, "arguments": args }
+                    }]
+                }));
+            }
+            _ => {}
+        }
+    }
+    messages
+}
+
+/// 构造 Chat 请求体 (交由 OpenAIRequest / transform_openai_request 处理)
+pub fn build_chat_request(body: &Value, messages: Vec<Value>) -> Value {
+    let mut chat = json!({
+        "model": body.get("model").cloned().unwrap_or(json!("")),
+        "messages": messages,
+        "stream": body.get("stream").and_then(|v| v.as_bool()).unwrap_or(false),
+    });
+
+    for key in ["temperature", "top_p", "tool_choice", "parallel_tool_calls"] {
+        if let Some(v) = body.get(key).filter(|v| !v.is_null()) {
+            chat[key] = v.clone();
+        }
+    }
+
+    if let Some(max) = body.get("max_output_tokens").filter(|v| !v.is_null()) {
+        chat["max_tokens"] = max.clone();
+    }
+
+    if let Some(tools) = body.get("tools").and_then(|t| t.as_array()) {
+        let converted = convert_responses_tools(tools);
+        if !converted.is_empty() {
+            chat["tools"] = json!(converted);
+        }
+    }
+
+    // text.format: {type: "json_schema", name, schema, strict} -> response_format
+    if let Some(format) = body.get("text").and_then(|t| t.get("format")) {
+        match format.get("type").and_then(|t| t.as_str()) {
+            Some("json_schema") => {
+                chat["response_format"] = json!({
+                    "type": "json_schema",
+                    "json_schema": {
+                        "name": format.get("name"),
+                        "schema": format.get("schema"),
+                        "strict": format.get("strict")
+                    }
+                });
+            }
+            Some("json_object") => chat["response_format"] = json!({"type": "json_object"}),
+            _ => {}
+        }
+    }
+
+    chat
+}
+
+/// Gemini usageMetadata -> Responses usage
+pub fn usage_from_gemini(usage: Option<&Value>) -> Value {
+    let get = |key: &str| {
+        usage
+            .and_then(|u| u.get(key))
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0)
+    };
+    let input_tokens = get("promptTokenCount");
+    let reasoning_tokens = get("thoughtsTokenCount");
+    let output_tokens = get("candidatesTokenCount") + reasoning_tokens;
+    let total_tokens = match get("totalTokenCount") {
+        0 => input_tokens + output_tokens,
+        t => t,
+    };
+
+    json!({
+        "input_tokens": input_tokens,
+        "input_tokens_details": { "cached_tokens": get("cachedContentTokenCount") },
+        "output_tokens": output_tokens,
+        "output_tokens_details": { "reasoning_tokens": reasoning_tokens },
+        "total_tokens": total_tokens
+    })
+}
+
+fn function_call_item(call_id: &str, name: &str, arguments: &str, local_shell: bool) -> Value {
+    if local_shell && name == "shell" {
+        let args: Value = serde_json::from_str(arguments).unwrap_or(json!({}));
+        let command = match args.get("command") {
+            Some(Value::Array(arr)) => json!(arr),
+            Some(Value::String(s)) => json!([s]),
+            _ => json!([]),
+        };
+        let mut action = json!({"type": "exec", "command": command});
+        if let Some(wd) = args.get("workdir") {
+            action["working_directory"] = wd.clone();
+        }
+        return json!({
+            "type": "local_shell_call",
+            "id": new_id("lsh"),
+            "call_id": call_id,
+            "status": "completed",
+            "action": action
+        });
+    }
+
+    json!({
+        "type": "function_call",
+        "id": new_id("fc"),
+        "call_id": call_id,
+        "name": name,
+        "arguments": arguments,
+        "status": "completed"
+    })
+}
+
+fn message_item(id: &str, text: &str, status: &str) -> Value {
+    json!({
+        "type": "message",
+        "id": id,
+        "status": status,
+        "role": "assistant",
+        "content": [{"type": "output_text", "text": text, "annotations": []}]
+    })
+}
+
+fn reasoning_item(id: &str, text: &str) -> Value {
+    json!({
+        "type": "reasoning",
+        "id": id,
+        "summary": [{"type": "summary_text", "text": text}]
+    })
+}
+
+/// 响应对象的回显字段与状态
+#[derive(Debug, Clone)]
+pub struct ResponsesContext {
+    pub id: String,
+    pub model: String,
+    pub created_at: i64,
+    pub local_shell: bool,
+    /// 请求中需要在响应对象内原样回显的字段
+    echo: serde_json::Map<String, Value>,
+}
+
+impl ResponsesContext {
+    pub fn from_request(body: &Value) -> Self {
+        let mut echo = serde_json::Map::new();
+        for (key, default) in [
+            ("instructions", Value::Null),
+            ("previous_response_id", Value::Null),
+            ("max_output_tokens", Value::Null),
+            ("temperature", json!(1.0)),
+            ("top_p", json!(1.0)),
+            ("tool_choice", json!("auto")),
+            ("parallel_tool_calls", json!(true)),
+            ("tools", json!([])),
+            ("metadata", json!({})),
+            ("text", json!({"format": {"type": "text"}})),
+            ("store", json!(true)),
+        ] {
+            let value = body
+                .get(key)
+                .filter(|v| !v.is_null())
+                .cloned()
+                .unwrap_or(default);
+            echo.insert(key.to_string(), value);
+        }
+
+        let tools: Vec<Value> = body
+            .get("tools")
+            .and_then(|t| t.as_array())
+            .cloned()
+            .unwrap_or_default();
+
+        Self {
+            id: new_id("resp"),
+            model: body
+                .get("model")
+                .and_then(|m| m.as_str())
+                .unwrap_or_default()
+                .to_string(),
+            created_at: chrono::Utc::now().timestamp(),
+            local_shell: has_local_shell_tool(&tools),
+            echo,
+        }
+    }
+
+    pub fn store(&self) -> bool {
+        self.echo
+            .get("store")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true)
+    }
+
+    /// 构造完整的 response 对象
+    pub fn response_object(
+        &self,
+        status: &str,
+        output: Vec<Value>,
+        usage: Option<Value>,
+        incomplete_reason: Option<&str>,
+    ) -> Value {
+        let mut resp = json!({
+            "id": self.id,
+            "object": "response",
+            "created_at": self.created_at,
+            "status": status,
+            "error": null,
+            "incomplete_details": incomplete_reason.map(|r| json!({"reason": r})),
+            "model": self.model,
+            "output": output,
+            "usage": usage,
+        });
+        if let Some(obj) = resp.as_object_mut() {
+            for (k, v) in &self.echo {
+                obj.insert(k.clone(), v.clone());
+            }
+        }
+        resp
+    }
+}
+
+/// 非流式：Chat 响应 + Gemini usage -> Responses 响应对象
+pub fn build_responses_output(
+    ctx: &ResponsesContext,
+    chat_resp: &OpenAIResponse,
+    gemini_resp: &Value,
+) -> Value {
+    let raw = gemini_resp.get("response").unwrap_or(gemini_resp);
+    let mut output = Vec::new();
+    let mut incomplete_reason = None;
+
+    if let Some(choice) = chat_resp.choices.first() {
+        if let Some(reasoning) = choice
+            .message
+            .reasoning_content
+            .as_deref()
+            .filter(|r| !r.is_empty())
+        {
+            output.push(reasoning_item(&new_id("rs"), reasoning));
+        }
+
+        let text = match &choice.message.content {
+            Some(OpenAIContent::String(s)) => s.clone(),
+            _ => String::new(),
+        };
+        if !text.is_empty() {
+            output.push(message_item(&new_id("msg"), &text, "completed"));
+        }
+
+        for call in choice.message.tool_calls.iter().flatten() {
+            output.push(function_call_item(
+                &call.id,
+                &call.function.name,
+                &call.function.arguments,
+                ctx.local_shell,
+            ));
+        }
+
+        if choice.finish_reason.as_deref() == Some("length") {
+            incomplete_reason = Some("max_output_tokens");
+        }
+    }
+
+    let status = if incomplete_reason.is_some() {
+        "incomplete"
+    } else {
+        "completed"
+    };
+    ctx.response_object(
+        status,
+        output,
+        Some(usage_from_gemini(raw.get("usageMetadata"))),
+        incomplete_reason,
+    )
+}
+
+/// 当前处于打开状态的流式输出项
+enum OpenItem {
+    Message { id: String, text: String },
+    Reasoning { id: String, text: String },
+}
+
+/// 流式状态机：Gemini 分片 -> Responses 类型化事件
+pub struct ResponsesStreamState {
+    ctx: ResponsesContext,
+    sequence_number: u64,
+    output: Vec<Value>,
+    open: Option<OpenItem>,
+    usage: Option<Value>,
+    incomplete_reason: Option<&'static str>,
+    call_counter: usize,
+}
+
+impl ResponsesStreamState {
+    pub fn new(ctx: ResponsesContext) -> Self {
+        Self {
+            ctx,
+            sequence_number: 0,
+            output: Vec::new(),
+            open: None,
+            usage: None,
+            incomplete_reason: None,
+            call_counter: 0,
+        }
+    }
+
+    fn event(&mut self, event_type: &str, mut payload: Value) -> Value {
+        payload["type"] = json!(event_type);
+        payload["sequence_number"] = json!(self.sequence_number);
+        self.sequence_number += 1;
+        payload
+    }
+
+    /// response.created + response.in_progress
+    pub fn start(&mut self) -> Vec<Value> {
+        let resp = self
+            .ctx
+            .response_object("in_progress", Vec::new(), None, None);
+        vec![
+            self.event("response.created", json!({ "response": resp.clone() })),
+            self.event("response.in_progress", json!({ "response": resp })),
+        ]
+    }
+
+    fn close_open_item(&mut self, events: &mut Vec<Value>) {
+        let output_index = self.output.len();
+        match self.open.take() {
+            Some(OpenItem::Message { id, text }) => {
+                let part = json!({"type": "output_text", "text": text, "annotations": []});
+                let ev = self.event(
+                    "response.output_text.done",
+                    json!({"item_id": id, "output_index": output_index, "content_index": 0, "text": text}),
+                );
+                events.push(ev);
+                let ev = self.event(
+                    "response.content_part.done",
+                    json!({"item_id": id, "output_index": output_index, "content_index": 0, "part": part}),
+                );
+                events.push(ev);
+                let item = message_item(&id, &text, "completed");
+                let ev = self.event(
+                    "response.output_item.done",
+                    json!({"output_index": output_index, "item": item.clone()}),
+                );
+                events.push(ev);
+                self.output.push(item);
+            }
+            Some(OpenItem::Reasoning { id, text }) => {
+                let part = json!({"type": "summary_text", "text": text});
+                let ev = self.event(
+                    "response.reasoning_summary_text.done",
+                    json!({"item_id": id, "output_index": output_index, "summary_index": 0, "text": text}),
+                );
+                events.push(ev);
+                let ev = self.event(
+                    "response.reasoning_summary_part.done",
+                    json!({"item_id": id, "output_index": output_index, "summary_index": 0, "part": part}),
+                );
+                events.push(ev);
+                let item = reasoning_item(&id, &text);
+                let ev = self.event(
+                    "response.output_item.done",
+                    json!({"output_index": output_index, "item": item.clone()}),
+                );
+                events.push(ev);
+                self.output.push(item);
+            }
+            None => {}
+        }
+    }
+
+    fn push_text(&mut self, text: &str, is_thought: bool, events: &mut Vec<Value>) {
+        let matches_open = matches!(
+            (&self.open, is_thought),
+            (Some(OpenItem::Message { .. }), false) | (Some(OpenItem::Reasoning { .. }), true)
+        );
+        if !matches_open {
+            self.close_open_item(events);
+            let output_index = self.output.len();
+            if is_thought {
+                let id = new_id("rs");
+                let item = json!({"type": "reasoning", "id": id, "summary": []});
+                let ev = self.event(
+                    "response.output_item.added",
+                    json!({"output_index": output_index, "item": item}),
+                );
+                events.push(ev);
+                let ev = self.event(
+                    "response.reasoning_summary_part.added",
+                    json!({"item_id": id, "output_index": output_index, "summary_index": 0,
+                           "part": {"type": "summary_text", "text": ""}}),
+                );
+                events.push(ev);
+                self.open = Some(OpenItem::Reasoning {
+                    id,
+                    text: String::new(),
+                });
+            } else {
+                let id = new_id("msg");
+                let item = json!({"type": "message", "id": id, "status": "in_progress",
+                                  "role": "assistant", "content": []});
+                let ev = self.event(
+                    "response.output_item.added",
+                    json!({"output_index": output_index, "item": item}),
+                );
+                events.push(ev);
+                let ev = self.event(
+                    "response.content_part.added",
+                    json!({"item_id": id, "output_index": output_index, "content_index": 0,
+                           "part": {"type": "output_text", "text": "", "annotations": []}}),
+                );
+                events.push(ev);
+                self.open = Some(OpenItem::Message {
+                    id,
+                    text: String::new(),
+                });
+            }
+        }
+
+        let output_index = self.output.len();
+        let (event_type, payload) = match self.open.as_mut() {
+            Some(OpenItem::Message { id, text: buf }) => {
+                buf.push_str(text);
+                (
+                    "response.output_text.delta",
+                    json!({"item_id": id, "output_index": output_index, "content_index": 0, "delta": text}),
+                )
+            }
+            Some(OpenItem::Reasoning { id, text: buf }) => {
+                buf.push_str(text);
+                (
+                    "response.reasoning_summary_text.delta",
+                    json!({"item_id": id, "output_index": output_index, "summary_index": 0, "delta": text}),
+                )
+            }
+            None => return,
+        };
+        let ev = self.event(event_type, payload);
+        events.push(ev);
+    }
+
+    fn push_function_call(&mut self, func_call: &Value, events: &mut Vec<Value>) {
+        self.close_open_item(events);
+
+        let name = func_call
+            .get("name")
+            .and_then(|v| v.as_str())
+            .unwrap_or("unknown");
+        let arguments = func_call
+            .get("args")
+            .map(|v| v.to_string())
+            .unwrap_or_else(|| "{}".to_string());
+        self.call_counter += 1;
+        let call_id = func_call
+            .get("id")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| {
+                format!(
+                    "call_{}_{}",
+                    self.call_counter,
+                    uuid::Uuid::new_v4().simple()
+                )
+            });
+
+        let output_index = self.output.len();
+        let done_item = function_call_item(&call_id, name, &arguments, self.ctx.local_shell);
+        let item_id = done_item["id"].as_str().unwrap_or_default().to_string();
+
+        let mut added_item = done_item.clone();
+        added_item["status"] = json!("in_progress");
+        if added_item["type"] == "function_call" {
+            added_item["arguments"] = json!("");
+        }
+        let ev = self.event(
+            "response.output_item.added",
+            json!({"output_index": output_index, "item": added_item}),
+        );
+        events.push(ev);
+
+        if done_item["type"] == "function_call" {
+            let ev = self.event(
+                "response.function_call_arguments.delta",
+                json!({"item_id": item_id, "output_index": output_index, "delta": arguments}),
+            );
+            events.push(ev);
+            let ev = self.event(
+                "response.function_call_arguments.done",
+                json!({"item_id": item_id, "output_index": output_index, "arguments": arguments}),
+            );
+            events.push(ev);
+        }
+
+        let ev = self.event(
+            "response.output_item.done",
+            json!({"output_index": output_index, "item": done_item.clone()}),
+        );
+        events.push(ev);
+        self.output.push(done_item);
+    }
+
+    /// 处理一个 Gemini 分片 (已解包 response 字段)
+    pub fn process_chunk(&mut self, chunk: &Value) -> Vec<Value> {
+        let mut events = Vec::new();
+
+        if let Some(usage) = chunk.get("usageMetadata") {
+            self.usage = Some(usage_from_gemini(Some(usage)));
+        }
+
+        let Some(candidate) = chunk
+            .get("candidates")
+            .and_then(|c| c.as_array())
+            .and_then(|c| c.first())
+        else {
+            return events;
+        };
+
+        if let Some(parts) = candidate
+            .get("content")
+            .and_then(|c| c.get("parts"))
+            .and_then(|p| p.as_array())
+        {
+            for part in parts {
+                if let Some(sig) = part
+                    .get("thoughtSignature")
+                    .or(part.get("thought_signature"))
+                    .and_then(|s| s.as_str())
+                {
+                    store_thought_signature(sig);
+                }
+
+                let is_thought = part
+                    .get("thought")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+                    if !text.is_empty() {
+                        self.push_text(text, is_thought, &mut events);
+                    }
+                }
+                if let Some(func_call) = part.get("functionCall") {
+                    self.push_function_call(func_call, &mut events);
+                }
+            }
+        }
+
+        if candidate.get("finishReason").and_then(|f| f.as_str()) == Some("MAX_TOKENS") {
+            self.incomplete_reason = Some("max_output_tokens");
+        }
+
+        events
+    }
+
+    /// 收尾：关闭打开的输出项并发送 response.completed / response.incomplete
+    ///
+    /// 返回 (事件列表, 最终 response 对象)
+    pub fn finish(&mut self) -> (Vec<Value>, Value) {
+        let mut events = Vec::new();
+        self.close_open_item(&mut events);
+
+        let (status, event_type) = match self.incomplete_reason {
+            Some(_) => ("incomplete", "response.incomplete"),
+            None => ("completed", "response.completed"),
+        };
+        let resp = self.ctx.response_object(
+            status,
+            self.output.clone(),
+            Some(
+                self.usage
+                    .clone()
+                    .unwrap_or_else(|| usage_from_gemini(None)),
+            ),
+            self.incomplete_reason,
+        );
+        let ev = self.event(event_type, json!({ "response": resp.clone() }));
+        events.push(ev);
+        (events, resp)
+    }
+
+    /// 上游错误：response.failed
+    pub fn fail(&mut self, message: &str) -> Vec<Value> {
+        let mut resp =
+            self.ctx
+                .response_object("failed", self.output.clone(), self.usage.clone(), None);
+        resp["error"] = json!({"code": "server_error", "message": message});
+        vec![self.event("response.failed", json!({ "response": resp }))]
+    }
+}
+
+fn sse_event(ev: &Value) -> Bytes {
+    let event_type = ev.get("type").and_then(|t| t.as_str()).unwrap_or("message");
+    Bytes::from(format!("event: {}\ndata: {}\n\n", event_type, ev))
+}
+
+/// 创建 Responses API 流式响应
+///
+/// `on_complete` 在流正常结束时收到最终 response 对象 (用于本地存储)
+pub fn create_responses_sse_stream(
+    mut gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
+    ctx: ResponsesContext,
+    on_complete: Box<dyn FnOnce(Value) + Send>,
+) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
+    let mut buffer = BytesMut::new();
+    let mut state = ResponsesStreamState::new(ctx);
+
+    let stream = async_stream::stream! {
+        for ev in state.start() {
+            yield Ok::<Bytes, String>(sse_event(&ev));
+        }
+
+        while let Some(item) = gemini_stream.next().await {
+            match item {
+                Ok(bytes) => {
+                    buffer.extend_from_slice(&bytes);
+                    while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                        let line_raw = buffer.split_to(pos + 1);
+                        let Ok(line) = std::str::from_utf8(&line_raw) else { continue };
+                        let Some(json_part) = line.trim().strip_prefix("data:") else { continue };
+                        let json_part = json_part.trim();
+                        if json_part.is_empty() || json_part == "[DONE]" {
+                            continue;
+                        }
+                        if let Ok(json) = serde_json::from_str::<Value>(json_part) {
+                            let chunk = json.get("response").unwrap_or(&json);
+                            for ev in state.process_chunk(chunk) {
+                                yield Ok::<Bytes, String>(sse_event(&ev));
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    for ev in state.fail(&format!("Upstream error: {}", e)) {
+                        yield Ok::<Bytes, String>(sse_event(&ev));
+                    }
+                    return;
+                }
+            }
+        }
+
+        let (events, response) = state.finish();
+        for ev in events {
+            yield Ok::<Bytes, String>(sse_event(&ev));
+        }
+        on_complete(response);
+    };
+
+    Box::pin(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event_types(events: &[Value]) -> Vec<&str> {
+        events.iter().map(|e| e["type"].as_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_string_input_without_instructions() {
+        let messages = responses_input_to_messages(Some(&json!("Hello")), &[]);
+        assert_eq!(messages, vec![json!({"role": "user", "content": "Hello"})]);
+
+        let chat = build_chat_request(
+            &json!({"model": "gpt-4o", "input": "Hello", "max_output_tokens": 64}),
+            messages,
+        );
+        assert_eq!(chat["max_tokens"], 64);
+        assert_eq!(chat["messages"][0]["content"], "Hello");
+    }
+
+    #[test]
+    fn test_array_input_items() {
+        let input = json!([
+            {"role": "developer", "content": "Be terse."},
+            {"type": "message", "role": "user", "content": [
+                {"type": "input_text", "text": "What is this?"},
+                {"type": "input_image", "image_url": "data:image/png;base64,AAAA"},
+                {"type": "input_file", "file_id": "file-1"}
+            ]},
+            {"type": "function_call", "call_id": "call_1", "name": "lookup", "arguments": "{\"q\":1}"},
+            {"type": "function_call_output", "call_id": "call_1", "output": "42"}
+        ]);
+        let messages = responses_input_to_messages(Some(&input), &[]);
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0]["role"], "system");
+        assert_eq!(messages[1]["content"][1]["type"], "image_url");
+        assert_eq!(
+            messages[1]["content"][2],
+            json!({"type": "file", "file": {"file_id": "file-1"}})
+        );
+        assert_eq!(messages[2]["tool_calls"][0]["function"]["name"], "lookup");
+        assert_eq!(messages[3]["role"], "tool");
+        assert_eq!(messages[3]["name"], "lookup");
+        assert_eq!(messages[3]["content"], "42");
+    }
+
+    #[test]
+    fn test_tool_output_resolves_name_from_history() {
+        let history = output_items_to_messages(&[json!({
+            "type": "function_call", "call_id": "call_9", "name": "get_weather", "arguments": "{}"
+        })]);
+        let input =
+            json!([{"type": "function_call_output", "call_id": "call_9", "output": "sunny"}]);
+        let messages = responses_input_to_messages(Some(&input), &history);
+        assert_eq!(messages[0]["name"], "get_weather");
+    }
+
+    #[test]
+    fn test_text_format_maps_to_response_format() {
+        let chat = build_chat_request(
+            &json!({
+                "model": "gpt-4o",
+                "text": {"format": {"type": "json_schema", "name": "out", "schema": {"type": "object"}}}
+            }),
+            vec![],
+        );
+        assert_eq!(chat["response_format"]["type"], "json_schema");
+        assert_eq!(
+            chat["response_format"]["json_schema"]["schema"]["type"],
+            "object"
+        );
+    }
+
+    #[test]
+    fn test_stream_event_sequence_text_then_tool() {
+        let ctx = ResponsesContext::from_request(&json!({"model": "gpt-4o", "input": "hi"}));
+        let mut state = ResponsesStreamState::new(ctx);
+
+        let mut events = state.start();
+        events.extend(state.process_chunk(&json!({
+            "candidates": [{"content": {"parts": [{"text": "Let me check"}]}}]
+        })));
+        events.extend(state.process_chunk(&json!({
+            "candidates": [{"content": {"parts": [{"text": "."}]}}]
+        })));
+        events.extend(state.process_chunk(&json!({
+            "candidates": [{"content": {"parts": [
+                {"functionCall": {"name": "lookup", "args": {"q": "x"}}}
+            ]}, "finishReason": "STOP"}],
+            "usageMetadata": {"promptTokenCount": 10, "candidatesTokenCount": 5, "totalTokenCount": 15}
+        })));
+        let (finish_events, response) = state.finish();
+        events.extend(finish_events);
+
+        assert_eq!(
+            event_types(&events),
+            vec![
+                "response.created",
+                "response.in_progress",
+                "response.output_item.added",
+                "response.content_part.added",
+                "response.output_text.delta",
+                "response.output_text.delta",
+                "response.output_text.done",
+                "response.content_part.done",
+                "response.output_item.done",
+                "response.output_item.added",
+                "response.function_call_arguments.delta",
+                "response.function_call_arguments.done",
+                "response.output_item.done",
+                "response.completed",
+            ]
+        );
+
+        // sequence_number 单调递增
+        for (i, ev) in events.iter().enumerate() {
+            assert_eq!(ev["sequence_number"], i as u64);
+        }
+
+        assert_eq!(response["status"], "completed");
+        assert_eq!(response["output"][0]["content"][0]["text"], "Let me check.");
+        assert_eq!(response["output"][1]["type"], "function_call");
+        assert_eq!(response["output"][1]["arguments"], "{\"q\":\"x\"}");
+        assert_eq!(response["usage"]["input_tokens"], 10);
+        assert_eq!(response["usage"]["total_tokens"], 15);
+    }
+
+    #[test]
+    fn test_stream_reasoning_and_incomplete() {
+        let ctx = ResponsesContext::from_request(&json!({"model": "gpt-4o"}));
+        let mut state = ResponsesStreamState::new(ctx);
+        state.start();
+
+        let events = state.process_chunk(&json!({
+            "candidates": [{"content": {"parts": [{"text": "thinking...", "thought": true}]}}]
+        }));
+        assert_eq!(
+            event_types(&events),
+            vec![
+                "response.output_item.added",
+                "response.reasoning_summary_part.added",
+                "response.reasoning_summary_text.delta",
+            ]
+        );
+
+        state.process_chunk(&json!({
+            "candidates": [{"content": {"parts": [{"text": "Answer"}]}, "finishReason": "MAX_TOKENS"}]
+        }));
+        let (events, response) = state.finish();
+        assert_eq!(events.last().unwrap()["type"], "response.incomplete");
+        assert_eq!(response["status"], "incomplete");
+        assert_eq!(
+            response["incomplete_details"]["reason"],
+            "max_output_tokens"
+        );
+        assert_eq!(response["output"][0]["type"], "reasoning");
+        assert_eq!(response["output"][1]["type"], "message");
+    }
+
+    #[test]
+    fn test_local_shell_tool_emits_local_shell_call() {
+        let body = json!({"model": "gpt-4o", "tools": [{"type": "local_shell"}]});
+        let chat = build_chat_request(&body, vec![]);
+        assert_eq!(chat["tools"][0]["name"], "shell");
+
+        let ctx = ResponsesContext::from_request(&body);
+        let mut state = ResponsesStreamState::new(ctx);
+        state.process_chunk(&json!({
+            "candidates": [{"content": {"parts": [
+                {"functionCall": {"name": "shell", "args": {"command": ["ls", "-la"]}}}
+            ]}}]
+        }));
+        let (_, response) = state.finish();
+        assert_eq!(response["output"][0]["type"], "local_shell_call");
+        assert_eq!(
+            response["output"][0]["action"]["command"],
+            json!(["ls", "-la"])
+        );
+
+        // 链式对话中 local_shell_call 还原为 shell 调用
+        let history = output_items_to_messages(response["output"].as_array().unwrap());
+        assert_eq!(history[0]["tool_calls"][0]["function"]["name"], "shell");
+    }
+
+    #[test]
+    fn test_response_object_echoes_request_fields() {
+        let ctx = ResponsesContext::from_request(&json!({
+            "model": "gpt-4o",
+            "instructions": "Be nice",
+            "previous_response_id": "resp_prev",
+            "store": false
+        }));
+        assert!(!ctx.store());
+        let resp = ctx.response_object("completed", vec![], None, None);
+        assert_eq!(resp["object"], "response");
+        assert_eq!(resp["instructions"], "Be nice");
+        assert_eq!(resp["previous_response_id"], "resp_prev");
+        assert!(resp["incomplete_details"].is_null());
+    }
+}
@@ -1,15 +1,172 @@
 // API Key 认证中间件
 use axum::{
+    body::{Body, Bytes},
     extract::Request,
-    extract::State,
-    http::{header, StatusCode},
+    extract::{FromRequest, Multipart, State},
+    http::{header, HeaderMap, StatusCode},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
+    Json,
 };
+use serde_json::{json, Value};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+use super::usage;
+use crate::proxy::client_keys::{self, ClientKeyIdentity, KeyRejection};
+use crate::proxy::mappers::gemini::errors::google_error_response;
 use crate::proxy::{ProxyAuthMode, ProxySecurityConfig};
+use antigravity_shared::proxy::config::ClientApiKey;
+
+const MAX_AUTH_BODY_SIZE: usize = 100 * 1024 * 1024;
+
+/// Gemini 原生接口 (`/v1beta`) 使用 Google 风格错误体
+fn is_gemini_path(path: &str) -> bool {
+    path.starts_with("/v1beta/")
+}
+
+/// 从请求中提取 API key
+/// 支持 `Authorization: Bearer`、`x-api-key`，以及 Google SDK 使用的 `x-goog-api-key` 与 `?key=`
+pub(crate) fn extract_api_key(headers: &HeaderMap, query: Option<&str>) -> Option<String> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .map(|s| s.strip_prefix("Bearer ").unwrap_or(s))
+        .or_else(|| headers.get("x-api-key").and_then(|h| h.to_str().ok()))
+        .or_else(|| headers.get("x-goog-api-key").and_then(|h| h.to_str().ok()))
+        .map(|s| s.to_string())
+        .or_else(|| {
+            url::form_urlencoded::parse(query?.as_bytes())
+                .find(|(name, _)| name == "key")
+                .map(|(_, value)| value.into_owned())
+        })
+}
+
+fn unauthorized(path: &str) -> Result<Response, StatusCode> {
+    if is_gemini_path(path) {
+        Ok(google_error_response(
+            StatusCode::UNAUTHORIZED,
+            "API key not valid. Please pass a valid API key.",
+        ))
+    } else {
+        Err(StatusCode::UNAUTHORIZED)
+    }
+}
+
+fn rejection_response(rejection: &KeyRejection, path: &str) -> Response {
+    let mut response = if is_gemini_path(path) {
+        google_error_response(rejection.status(), &rejection.message())
+    } else {
+        (
+            rejection.status(),
+            Json(json!({
+                "error": {
+                    "type": if rejection.status() == StatusCode::BAD_REQUEST {
+                        "invalid_request_error"
+                    } else {
+                        "permission_error"
+                    },
+                    "message": rejection.message(),
+                }
+            })),
+        )
+            .into_response()
+    };
+    if let KeyRejection::RateLimited { retry_after_secs } = rejection {
+        if let Ok(value) = retry_after_secs.to_string().parse() {
+            response.headers_mut().insert(header::RETRY_AFTER, value);
+        }
+    }
+    response
+}
+
+/// multipart 请求 (如 /v1/audio/transcriptions) 的 `model` 字段
+async fn multipart_model(content_type: &str, bytes: &Bytes) -> Option<String> {
+    let request = Request::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .body(Body::from(bytes.clone()))
+        .ok()?;
+    let mut multipart = Multipart::from_request(request, &()).await.ok()?;
+    while let Ok(Some(field)) = multipart.next_field().await {
+        if field.name() == Some("model") {
+            return field.text().await.ok();
+        }
+    }
+    None
+}
+
+/// 客户端 key 的白名单、限流与预算检查；模型白名单需要读取请求体中的 `model`
+///
+/// 设置了模型白名单时，带请求体但无法确定模型的请求会被拒绝 (不放行)
+async fn admit_client_key(
+    client_key: &ClientApiKey,
+    request: Request,
+) -> Result<Request, KeyRejection> {
+    let path = request.uri().path().to_string();
+    let protocol = client_keys::protocol_for_path(&path);
+    client_keys::check_allowlists(client_key, protocol, None)?;
+
+    let request = if client_key.allowed_models.is_empty() || protocol.is_none() {
+        request
+    } else if let Some(model) = path
+        .split("/v1beta/models/")
+        .nth(1)
+        .and_then(|s| s.split(':').next())
+    {
+        client_keys::check_allowlists(client_key, None, Some(model))?;
+        request
+    } else {
+        let (parts, body) = request.into_parts();
+        let bytes = axum::body::to_bytes(body, MAX_AUTH_BODY_SIZE)
+            .await
+            .map_err(|e| KeyRejection::InvalidBody(e.to_string()))?;
+
+        // 无请求体的调用 (查询 / 取消 / 删除) 不涉及模型
+        if !bytes.is_empty() {
+            let content_type = parts
+                .headers
+                .get(header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default();
+            let body = serde_json::from_slice::<Value>(&bytes).unwrap_or_default();
+            let model = match body.get("model").and_then(|m| m.as_str()) {
+                Some(model) => Some(model.to_string()),
+                None if content_type.starts_with("multipart/form-data") => {
+                    multipart_model(content_type, &bytes).await
+                }
+                None => None,
+            };
+
+            // Message Batches: 每条请求的模型都需在白名单内
+            let batch_requests = body.get("requests").and_then(|r| r.as_array());
+            match (model, batch_requests) {
+                (Some(model), _) => client_keys::check_allowlists(client_key, None, Some(&model))?,
+                (None, Some(_)) => {}
+                (None, None) => return Err(KeyRejection::ModelRequired),
+            }
+            for request in batch_requests.into_iter().flatten() {
+                let model = request
+                    .pointer("/params/model")
+                    .and_then(|m| m.as_str())
+                    .ok_or(KeyRejection::ModelRequired)?;
+                client_keys::check_allowlists(client_key, None, Some(model))?;
+            }
+        }
+        Request::from_parts(parts, Body::from(bytes))
+    };
+
+    client_keys::tracker().admit(client_key)?;
+    Ok(request)
+}
+
+/// 响应结束后将 usage 计入客户端 key 的每日 token 预算
+pub(crate) fn charge_token_budget(key_id: String, path: &str, response: Response) -> Response {
+    usage::tap_usage(path, response, move |usage| {
+        if let Some((input, output)) = usage {
+            client_keys::tracker().record_tokens(&key_id, input + output);
+        }
+    })
+}
 
 /// API Key 认证中间件
 pub async fn auth_middleware(
@@ -43,33 +200,185 @@ pub async fn auth_middleware(
         return Ok(next.run(request).await);
     }
 
-    // 从 header 中提取 API key
-    let api_key = request
-        .headers()
-        .get(header::AUTHORIZATION)
-        .and_then(|h| h.to_str().ok())
-        .and_then(|s| s.strip_prefix("Bearer ").or(Some(s)))
-        .or_else(|| {
-            request
-                .headers()
-                .get("x-api-key")
-                .and_then(|h| h.to_str().ok())
-        });
+    // 从 header / query 中提取 API key
+    let api_key = extract_api_key(request.headers(), request.uri().query());
 
-    if security.api_key.is_empty() {
+    if security.api_key.is_empty() && security.client_keys.is_empty() {
         tracing::error!("Proxy auth is enabled but api_key is empty; denying request");
-        return Err(StatusCode::UNAUTHORIZED);
+        return unauthorized(&path);
     }
 
+    let Some(api_key) = api_key else {
+        return unauthorized(&path);
+    };
+
     // Constant-time compare is unnecessary here, but keep strict equality and avoid leaking values.
-    let authorized = api_key.map(|k| k == security.api_key).unwrap_or(false);
+    if !security.api_key.is_empty() && api_key == security.api_key {
+        return Ok(next.run(request).await);
+    }
 
-    if authorized {
-        Ok(next.run(request).await)
-    } else {
-        Err(StatusCode::UNAUTHORIZED)
+    // 客户端 key：未知、已撤销或已过期均返回 401
+    let Some(client_key) = security.find_client_key(&api_key) else {
+        return unauthorized(&path);
+    };
+    let client_key = client_key.clone();
+
+    match admit_client_key(&client_key, request).await {
+        Ok(mut request) => {
+            request
+                .extensions_mut()
+                .insert(ClientKeyIdentity::from(&client_key));
+            let response = next.run(request).await;
+            Ok(charge_token_budget(client_key.id, &path, response))
+        }
+        Err(rejection) => {
+            tracing::warn!(
+                "Client key '{}' rejected for {}: {}",
+                client_key.name,
+                path,
+                rejection.message()
+            );
+            Ok(rejection_response(&rejection, &path))
+        }
     }
 }
 
-// Tests for auth_middleware require async runtime setup.
-// Real integration tests are in tests/integration/.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn restricted_key() -> ClientApiKey {
+        ClientApiKey {
+            id: format!("auth-test-{}", uuid::Uuid::new_v4()),
+            name: "restricted".to_string(),
+            key: "sk-ag-restricted".to_string(),
+            enabled: true,
+            allowed_models: vec!["gemini-*".to_string()],
+            allowed_protocols: vec![],
+            requests_per_minute: None,
+            daily_token_budget: None,
+            expires_at: None,
+            created_at: 0,
+        }
+    }
+
+    fn post(path: &str, content_type: &str, body: impl Into<Body>) -> Request {
+        Request::builder()
+            .method("POST")
+            .uri(path)
+            .header(header::CONTENT_TYPE, content_type)
+            .body(body.into())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_model_allowlist_fails_closed() {
+        let key = restricted_key();
+
+        let missing = post(
+            "/v1/chat/completions",
+            "application/json",
+            r#"{"messages":[]}"#,
+        );
+        assert_eq!(
+            admit_client_key(&key, missing).await.err(),
+            Some(KeyRejection::ModelRequired)
+        );
+
+        let not_json = post("/v1/chat/completions", "text/plain", "hello");
+        assert_eq!(
+            admit_client_key(&key, not_json).await.err(),
+            Some(KeyRejection::ModelRequired)
+        );
+
+        let batch = post(
+            "/v1/messages/batches",
+            "application/json",
+            r#"{"requests":[{"custom_id":"a","params":{"max_tokens":1}}]}"#,
+        );
+        assert_eq!(
+            admit_client_key(&key, batch).await.err(),
+            Some(KeyRejection::ModelRequired)
+        );
+
+        // 无请求体的调用不涉及模型
+        let empty = post("/v1/messages/batches/b1/cancel", "application/json", "");
+        assert!(admit_client_key(&key, empty).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_multipart_model_checked() {
+        let key = restricted_key();
+        let form = |model: &str| {
+            format!(
+                "--XB\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.wav\"\r\n\r\nRIFF\r\n\
+                 --XB\r\nContent-Disposition: form-data; name=\"model\"\r\n\r\n{}\r\n--XB--\r\n",
+                model
+            )
+        };
+        let content_type = "multipart/form-data; boundary=XB";
+
+        let denied = post("/v1/audio/transcriptions", content_type, form("whisper-1"));
+        assert_eq!(
+            admit_client_key(&key, denied).await.err(),
+            Some(KeyRejection::ModelNotAllowed("whisper-1".to_string()))
+        );
+
+        let body = form("gemini-2.5-flash");
+        let allowed = post("/v1/audio/transcriptions", content_type, body.clone());
+        let request = admit_client_key(&key, allowed).await.unwrap();
+        // 请求体原样交给后续 handler
+        let forwarded = axum::body::to_bytes(request.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(forwarded, body.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_client_key_charged_when_response_completes() {
+        let key = ClientApiKey {
+            allowed_models: vec![],
+            daily_token_budget: Some(1000),
+            ..restricted_key()
+        };
+        let security = Arc::new(RwLock::new(ProxySecurityConfig {
+            auth_mode: ProxyAuthMode::Strict,
+            api_key: "sk-main".to_string(),
+            allow_lan_access: false,
+            client_keys: vec![key.clone()],
+        }));
+        let app = axum::Router::new()
+            .route(
+                "/v1/messages",
+                axum::routing::post(|| async {
+                    Json(json!({"usage": {"input_tokens": 12, "output_tokens": 8}}))
+                }),
+            )
+            .layer(axum::middleware::from_fn_with_state(
+                security,
+                auth_middleware,
+            ));
+        let base = crate::proxy::tests::support::spawn_server(app).await;
+
+        let response = reqwest::Client::new()
+            .post(format!("{}/v1/messages", base))
+            .header("x-api-key", &key.key)
+            .json(&json!({"model": "claude-sonnet-4-5"}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        response.bytes().await.unwrap();
+
+        // 服务端在响应体发送完毕后才释放 body，稍等计费回调
+        for _ in 0..50 {
+            if client_keys::tracker().usage(&key.id).tokens_today > 0 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let usage = client_keys::tracker().usage(&key.id);
+        assert_eq!((usage.requests_today, usage.tokens_today), (1, 20));
+        client_keys::tracker().remove(&key.id);
+    }
+}
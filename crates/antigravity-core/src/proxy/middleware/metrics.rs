@@ -0,0 +1,111 @@
+// Prometheus 指标中间件
+//
+// 与 monitor 中间件不同，指标采集不受监控开关影响：每个请求都会记录
+// provider/model/status 维度的请求数与耗时，并从响应 usage 中累计 token 数。
+use super::usage;
+use crate::proxy::prometheus;
+use axum::{extract::Request, middleware::Next, response::Response};
+use std::time::Instant;
+
+/// 去掉 X-Mapped-Model 中的路由标签，例如 "gemini-2.5-pro (rule#1 pro, fallback 1)"
+fn model_from_mapped_header(value: &str) -> &str {
+    value.split(" (").next().unwrap_or(value)
+}
+
+pub async fn metrics_middleware(request: Request, next: Next) -> Response {
+    let path = request.uri().path().to_string();
+    if path == "/metrics" || path == "/healthz" || path.contains("event_logging") {
+        return next.run(request).await;
+    }
+
+    let start = Instant::now();
+    let provider = prometheus::detect_provider_from_url(&path);
+    let url_model = path
+        .split("/v1beta/models/")
+        .nth(1)
+        .and_then(|s| s.split(':').next())
+        .map(|s| s.to_string());
+
+    let response = next.run(request).await;
+
+    let model = response
+        .headers()
+        .get("X-Mapped-Model")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| model_from_mapped_header(v).to_string())
+        .or(url_model)
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let status = response.status().as_u16();
+
+    // 流式 / JSON 响应在响应体结束 (或被丢弃) 时记录总耗时与 usage
+    usage::tap_usage(&path, response, move |usage| {
+        prometheus::record_request(
+            provider,
+            &model,
+            prometheus::status_category(status),
+            start.elapsed().as_millis() as u64,
+        );
+        if let Some((input, output)) = usage {
+            prometheus::record_tokens(provider, &model, input, output);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use serde_json::Value;
+
+    #[tokio::test]
+    async fn test_json_body_passes_through_unchanged() {
+        let large = format!("{{\"data\":\"{}\"}}", "x".repeat(10 * 1024 * 1024));
+        let large_len = large.len();
+        let app = axum::Router::new()
+            .route(
+                "/v1/large",
+                axum::routing::get(move || {
+                    let large = large.clone();
+                    async move { ([("content-type", "application/json")], large) }
+                }),
+            )
+            .route(
+                "/v1/small",
+                axum::routing::get(|| async {
+                    axum::Json(json!({"usage": {"prompt_tokens": 1, "completion_tokens": 2}}))
+                }),
+            )
+            .layer(axum::middleware::from_fn(metrics_middleware));
+
+        let base = crate::proxy::tests::support::spawn_server(app).await;
+
+        let large_body = reqwest::get(format!("{}/v1/large", base))
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        assert_eq!(large_body.len(), large_len);
+
+        let small: Value = reqwest::get(format!("{}/v1/small", base))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(small["usage"]["completion_tokens"], 2);
+    }
+
+    #[test]
+    fn test_model_from_mapped_header() {
+        assert_eq!(
+            model_from_mapped_header("gemini-2.5-pro (rule#1 pro, fallback 1)"),
+            "gemini-2.5-pro"
+        );
+        assert_eq!(
+            model_from_mapped_header("gemini-2.5-flash"),
+            "gemini-2.5-flash"
+        );
+    }
+}
@@ -3,7 +3,10 @@
 pub mod auth;
 pub mod cors;
 pub mod logging;
+pub mod metrics;
 pub mod monitor;
+pub mod usage;
 
 pub use auth::auth_middleware;
 pub use cors::cors_layer;
+pub use metrics::metrics_middleware;
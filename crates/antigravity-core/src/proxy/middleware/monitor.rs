@@ -1,4 +1,5 @@
 use crate::models::ProxyRequestLog;
+use crate::proxy::mappers::gemini::streaming;
 use crate::proxy::server::AppState;
 use axum::{
     body::Body,
@@ -13,6 +14,71 @@ use std::time::Instant;
 const MAX_REQUEST_LOG_SIZE: usize = 100 * 1024 * 1024; // 100MB
 const MAX_RESPONSE_LOG_SIZE: usize = 10 * 1024 * 1024; // 10MB for image responses
 
+/// Google SDK 可通过 `?key=` 传递 API key，记录日志前脱敏
+fn redact_query_key(uri: &str) -> String {
+    let Some((path, query)) = uri.split_once('?') else {
+        return uri.to_string();
+    };
+    let query: Vec<String> = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some(("key", _)) => "key=***".to_string(),
+            _ => pair.to_string(),
+        })
+        .collect();
+    format!("{}?{}", path, query.join("&"))
+}
+
+fn usage_u32(value: Option<&Value>) -> Option<u32> {
+    value.and_then(|v| v.as_u64()).map(|v| v as u32)
+}
+
+/// 从响应 JSON 中提取 token 用量，返回是否找到 usage
+///
+/// 兼容 OpenAI / Claude 的 `usage` 与 Gemini 的 `usageMetadata`
+/// (含 v1internal 的 `response` 包装)，并记录缓存命中的 token。
+/// `input_tokens` 统一记录为包含缓存部分的总输入，费用估算再按 `cached_tokens` 折扣。
+fn apply_usage(log: &mut ProxyRequestLog, json: &Value) -> bool {
+    if let Some(usage) = json.get("usage").filter(|u| u.is_object()) {
+        log.input_tokens = usage_u32(usage.get("prompt_tokens").or(usage.get("input_tokens")));
+        // Claude 的 input_tokens 不含缓存读取 / 创建的部分，需加回
+        let claude_cache = ["cache_read_input_tokens", "cache_creation_input_tokens"]
+            .iter()
+            .filter_map(|key| usage_u32(usage.get(*key)))
+            .reduce(|a, b| a + b);
+        if let (Some(input), Some(cache)) = (log.input_tokens, claude_cache) {
+            log.input_tokens = Some(input + cache);
+        }
+        log.output_tokens = usage_u32(
+            usage
+                .get("completion_tokens")
+                .or(usage.get("output_tokens")),
+        );
+        if log.input_tokens.is_none() && log.output_tokens.is_none() {
+            log.output_tokens = usage_u32(usage.get("total_tokens"));
+        }
+        log.cached_tokens = usage_u32(
+            usage
+                .get("cache_read_input_tokens")
+                .or(usage.pointer("/prompt_tokens_details/cached_tokens"))
+                .or(usage.pointer("/input_tokens_details/cached_tokens")),
+        )
+        .filter(|t| *t > 0);
+        return true;
+    }
+
+    let Some(metadata) = json
+        .get("usageMetadata")
+        .or_else(|| json.pointer("/response/usageMetadata"))
+    else {
+        return false;
+    };
+    log.input_tokens = usage_u32(metadata.get("promptTokenCount"));
+    log.output_tokens = usage_u32(metadata.get("candidatesTokenCount"));
+    log.cached_tokens = usage_u32(metadata.get("cachedContentTokenCount")).filter(|t| *t > 0);
+    true
+}
+
 pub async fn monitor_middleware(
     State(state): State<AppState>,
     request: Request,
@@ -24,7 +90,7 @@ pub async fn monitor_middleware(
 
     let start = Instant::now();
     let method = request.method().to_string();
-    let uri = request.uri().to_string();
+    let uri = redact_query_key(&request.uri().to_string());
 
     if uri.contains("event_logging") {
         return next.run(request).await;
@@ -39,6 +105,11 @@ pub async fn monitor_middleware(
         None
     };
 
+    let client_key = request
+        .extensions()
+        .get::<crate::proxy::client_keys::ClientKeyIdentity>()
+        .map(|identity| identity.name.clone());
+
     let request_body_str;
     let request = if method == "POST" {
         let (parts, body) = request.into_parts();
@@ -110,9 +181,13 @@ pub async fn monitor_middleware(
         response_body: None,
         input_tokens: None,
         output_tokens: None,
+        client_key,
+        cached_tokens: None,
     };
 
-    if content_type.contains("text/event-stream") {
+    let is_stream = content_type.contains("text/event-stream")
+        || (streaming::is_stream_path(&log.url) && log.status < 400);
+    if is_stream {
         log.response_body = Some("[Stream Data]".to_string());
         let (parts, body) = response.into_parts();
         let mut stream = body.into_data_stream();
@@ -137,32 +212,24 @@ pub async fn monitor_middleware(
             }
 
             if let Ok(full_tail) = std::str::from_utf8(&last_few_bytes) {
+                let mut found = false;
                 for line in full_tail.lines().rev() {
-                    if line.starts_with("data: ") && line.contains("\"usage\"") {
+                    if line.starts_with("data: ") && line.contains("\"usage") {
                         let json_str = line.trim_start_matches("data: ").trim();
                         if let Ok(json) = serde_json::from_str::<Value>(json_str) {
-                            if let Some(usage) = json.get("usage") {
-                                log.input_tokens = usage
-                                    .get("prompt_tokens")
-                                    .or(usage.get("input_tokens"))
-                                    .and_then(|v| v.as_u64())
-                                    .map(|v| v as u32);
-                                log.output_tokens = usage
-                                    .get("completion_tokens")
-                                    .or(usage.get("output_tokens"))
-                                    .and_then(|v| v.as_u64())
-                                    .map(|v| v as u32);
-                                if log.input_tokens.is_none() && log.output_tokens.is_none() {
-                                    log.output_tokens = usage
-                                        .get("total_tokens")
-                                        .and_then(|v| v.as_u64())
-                                        .map(|v| v as u32);
-                                }
+                            if apply_usage(&mut log, &json) {
+                                found = true;
                                 break;
                             }
                         }
                     }
                 }
+                // Gemini JSON 数组形式的流式响应
+                if !found {
+                    if let Some(json) = streaming::parse_json_array_tail(full_tail) {
+                        apply_usage(&mut log, &json);
+                    }
+                }
             }
 
             if log.status >= 400 {
@@ -181,24 +248,7 @@ pub async fn monitor_middleware(
             Ok(bytes) => {
                 if let Ok(s) = std::str::from_utf8(&bytes) {
                     if let Ok(json) = serde_json::from_str::<Value>(s) {
-                        if let Some(usage) = json.get("usage") {
-                            log.input_tokens = usage
-                                .get("prompt_tokens")
-                                .or(usage.get("input_tokens"))
-                                .and_then(|v| v.as_u64())
-                                .map(|v| v as u32);
-                            log.output_tokens = usage
-                                .get("completion_tokens")
-                                .or(usage.get("output_tokens"))
-                                .and_then(|v| v.as_u64())
-                                .map(|v| v as u32);
-                            if log.input_tokens.is_none() && log.output_tokens.is_none() {
-                                log.output_tokens = usage
-                                    .get("total_tokens")
-                                    .and_then(|v| v.as_u64())
-                                    .map(|v| v as u32);
-                            }
-                        }
+                        apply_usage(&mut log, &json);
                     }
                     log.response_body = Some(s.to_string());
                 } else {
@@ -223,3 +273,101 @@ pub async fn monitor_middleware(
         response
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn empty_log() -> ProxyRequestLog {
+        ProxyRequestLog {
+            id: "t".to_string(),
+            timestamp: 0,
+            method: "POST".to_string(),
+            url: "/v1/chat/completions".to_string(),
+            status: 200,
+            duration: 0,
+            model: None,
+            mapped_model: None,
+            account_email: None,
+            error: None,
+            request_body: None,
+            response_body: None,
+            input_tokens: None,
+            output_tokens: None,
+            client_key: None,
+            cached_tokens: None,
+        }
+    }
+
+    #[test]
+    fn test_apply_usage_formats() {
+        let mut log = empty_log();
+        assert!(!apply_usage(
+            &mut log,
+            &json!({"choices": [], "usage": null})
+        ));
+
+        let claude = json!({"usage": {"input_tokens": 5, "output_tokens": 2, "cache_read_input_tokens": 40}});
+        assert!(apply_usage(&mut log, &claude));
+        assert_eq!(
+            (log.input_tokens, log.output_tokens, log.cached_tokens),
+            (Some(45), Some(2), Some(40))
+        );
+
+        let gemini = json!({"response": {"usageMetadata": {
+            "promptTokenCount": 100, "candidatesTokenCount": 7, "cachedContentTokenCount": 60
+        }}});
+        let mut log = empty_log();
+        assert!(apply_usage(&mut log, &gemini));
+        assert_eq!(
+            (log.input_tokens, log.output_tokens, log.cached_tokens),
+            (Some(100), Some(7), Some(60))
+        );
+    }
+
+    fn logged_cost(body: Value) -> f64 {
+        let mut log = empty_log();
+        assert!(apply_usage(&mut log, &body));
+        crate::modules::pricing::estimate_cost_usd(
+            "claude-sonnet-4-5",
+            log.input_tokens.unwrap_or(0) as u64,
+            log.output_tokens.unwrap_or(0) as u64,
+            log.cached_tokens.unwrap_or(0) as u64,
+        )
+    }
+
+    #[test]
+    fn test_cached_usage_costs_the_same_per_protocol() {
+        // 同一次请求: 1000 输入 (其中 600 命中缓存)，100 输出
+        // 400 * 3 + 600 * 0.3 + 100 * 15 (美元 / 百万 token)
+        let expected = (1200.0 + 180.0 + 1500.0) / 1_000_000.0;
+
+        let openai = json!({"usage": {
+            "prompt_tokens": 1000, "completion_tokens": 100,
+            "prompt_tokens_details": {"cached_tokens": 600}
+        }});
+        let responses = json!({"usage": {
+            "input_tokens": 1000, "output_tokens": 100,
+            "input_tokens_details": {"cached_tokens": 600}
+        }});
+        let claude = json!({"usage": {
+            "input_tokens": 400, "output_tokens": 100,
+            "cache_read_input_tokens": 600, "cache_creation_input_tokens": 0
+        }});
+        let gemini = json!({"usageMetadata": {
+            "promptTokenCount": 1000, "candidatesTokenCount": 100, "cachedContentTokenCount": 600
+        }});
+        for body in [openai, responses, claude, gemini] {
+            let cost = logged_cost(body.clone());
+            assert!((cost - expected).abs() < 1e-12, "{}: {}", body, cost);
+        }
+
+        // 新建缓存的 Claude 请求按完整输入计费
+        let created = json!({"usage": {
+            "input_tokens": 400, "output_tokens": 100, "cache_creation_input_tokens": 600
+        }});
+        let full = (3000.0 + 1500.0) / 1_000_000.0;
+        assert!((logged_cost(created) - full).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,233 @@
+// 响应 usage 旁路提取
+//
+// 指标中间件与客户端 key 预算都需要在响应体结束后得到 token 用量：
+// 原样转发响应体，同时保留 SSE 尾部或 JSON 副本，结束 (或被丢弃) 时回调一次。
+use crate::proxy::mappers::gemini::streaming;
+use axum::{body::Body, response::Response};
+use futures::StreamExt;
+use serde_json::Value;
+
+const MAX_USAGE_BODY_SIZE: usize = 10 * 1024 * 1024;
+const SSE_TAIL_SIZE: usize = 8192;
+
+/// 从响应 JSON 中提取 (input, output) token 数
+///
+/// 兼容 OpenAI (`prompt_tokens`/`completion_tokens`)、Claude (`input_tokens`/`output_tokens`)
+/// 与 Gemini (`usageMetadata.promptTokenCount`/`candidatesTokenCount`)。
+pub fn extract_usage(json: &Value) -> Option<(u64, u64)> {
+    if let Some(usage) = json.get("usage").filter(|u| u.is_object()) {
+        let input = usage
+            .get("prompt_tokens")
+            .or(usage.get("input_tokens"))
+            .and_then(|v| v.as_u64());
+        let output = usage
+            .get("completion_tokens")
+            .or(usage.get("output_tokens"))
+            .and_then(|v| v.as_u64());
+        if input.is_some() || output.is_some() {
+            return Some((input.unwrap_or(0), output.unwrap_or(0)));
+        }
+    }
+
+    // Gemini 原生格式 (v1internal 包装在 response 字段中)
+    let metadata = json
+        .get("usageMetadata")
+        .or_else(|| json.get("response").and_then(|r| r.get("usageMetadata")))?;
+    let input = metadata
+        .get("promptTokenCount")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0);
+    let output = metadata
+        .get("candidatesTokenCount")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0);
+    Some((input, output))
+}
+
+/// 在 SSE 尾部数据中倒序查找最后一个带 usage 的事件 (兼容 Gemini JSON 数组流)
+fn extract_usage_from_sse_tail(tail: &[u8]) -> Option<(u64, u64)> {
+    let text = String::from_utf8_lossy(tail);
+    if let Some(json) = streaming::parse_json_array_tail(&text) {
+        return extract_usage(&json);
+    }
+    text.lines()
+        .rev()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .filter(|data| data.contains("\"usage"))
+        .find_map(|data| {
+            serde_json::from_str::<Value>(data.trim())
+                .ok()
+                .and_then(|json| extract_usage(&json))
+        })
+}
+
+type OnComplete = Box<dyn FnOnce(Option<(u64, u64)>) + Send>;
+
+/// 单个响应的 usage 收集器，在 Drop 时回调 (覆盖流式响应中途断开的情况)
+struct UsageTap {
+    /// 流式响应的尾部数据，usage 通常位于最后几个事件中
+    sse_tail: Vec<u8>,
+    /// JSON 响应体副本 (超过 MAX_USAGE_BODY_SIZE 后放弃，不影响转发给客户端的内容)
+    json_body: Option<Vec<u8>>,
+    on_complete: Option<OnComplete>,
+}
+
+impl UsageTap {
+    fn push_sse_chunk(&mut self, bytes: &[u8]) {
+        self.sse_tail.extend_from_slice(bytes);
+        if self.sse_tail.len() > SSE_TAIL_SIZE {
+            self.sse_tail.drain(0..self.sse_tail.len() - SSE_TAIL_SIZE);
+        }
+    }
+
+    fn push_json_chunk(&mut self, bytes: &[u8]) {
+        if let Some(body) = &mut self.json_body {
+            if body.len() + bytes.len() > MAX_USAGE_BODY_SIZE {
+                self.json_body = None;
+            } else {
+                body.extend_from_slice(bytes);
+            }
+        }
+    }
+}
+
+impl Drop for UsageTap {
+    fn drop(&mut self) {
+        let mut usage = None;
+        if !self.sse_tail.is_empty() {
+            usage = extract_usage_from_sse_tail(&self.sse_tail);
+        }
+        if let Some(body) = self.json_body.take().filter(|_| usage.is_none()) {
+            usage = serde_json::from_slice::<Value>(&body)
+                .ok()
+                .and_then(|json| extract_usage(&json));
+        }
+        if let Some(on_complete) = self.on_complete.take() {
+            on_complete(usage);
+        }
+    }
+}
+
+/// 包装响应体，在其结束 (或被丢弃) 时以提取到的 usage 调用 `on_complete`
+///
+/// 既非流式也非 JSON 的响应立即以 `None` 回调。
+pub fn tap_usage(
+    path: &str,
+    response: Response,
+    on_complete: impl FnOnce(Option<(u64, u64)>) + Send + 'static,
+) -> Response {
+    let mut tap = UsageTap {
+        sse_tail: Vec::new(),
+        json_body: None,
+        on_complete: Some(Box::new(on_complete)),
+    };
+
+    let content_type = response
+        .headers()
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+
+    let is_stream = content_type.contains("text/event-stream")
+        || (streaming::is_stream_path(path) && response.status().is_success());
+    if is_stream {
+        // 流式响应：保留尾部数据，流结束时提取 usage
+        let (parts, body) = response.into_parts();
+        let stream = body.into_data_stream().map(move |chunk| {
+            if let Ok(bytes) = &chunk {
+                tap.push_sse_chunk(bytes);
+            }
+            chunk
+        });
+        Response::from_parts(parts, Body::from_stream(stream))
+    } else if content_type.contains("application/json") {
+        // 原样转发响应体，同时保留一份副本用于提取 usage；
+        // 已知超过上限的响应不做收集
+        let too_large = response
+            .headers()
+            .get("content-length")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<usize>().ok())
+            .is_some_and(|len| len > MAX_USAGE_BODY_SIZE);
+        if !too_large {
+            tap.json_body = Some(Vec::new());
+        }
+        let (parts, body) = response.into_parts();
+        let stream = body.into_data_stream().map(move |chunk| {
+            if let Ok(bytes) = &chunk {
+                tap.push_json_chunk(bytes);
+            }
+            chunk
+        });
+        Response::from_parts(parts, Body::from_stream(stream))
+    } else {
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::response::IntoResponse;
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_extract_usage_formats() {
+        let openai = json!({"usage": {"prompt_tokens": 10, "completion_tokens": 5}});
+        assert_eq!(extract_usage(&openai), Some((10, 5)));
+
+        let claude = json!({"usage": {"input_tokens": 7, "output_tokens": 3}});
+        assert_eq!(extract_usage(&claude), Some((7, 3)));
+
+        let gemini = json!({"usageMetadata": {"promptTokenCount": 4, "candidatesTokenCount": 2}});
+        assert_eq!(extract_usage(&gemini), Some((4, 2)));
+
+        assert_eq!(extract_usage(&json!({"choices": []})), None);
+    }
+
+    #[test]
+    fn test_extract_usage_from_sse_tail() {
+        let tail = b"data: {\"choices\":[]}\n\ndata: {\"choices\":[],\"usage\":{\"prompt_tokens\":3,\"completion_tokens\":9}}\n\ndata: [DONE]\n\n";
+        assert_eq!(extract_usage_from_sse_tail(tail), Some((3, 9)));
+        assert_eq!(extract_usage_from_sse_tail(b"data: [DONE]\n\n"), None);
+    }
+
+    #[test]
+    fn test_json_copy_dropped_when_too_large() {
+        let mut tap = UsageTap {
+            sse_tail: Vec::new(),
+            json_body: Some(Vec::new()),
+            on_complete: None,
+        };
+        tap.push_json_chunk(&vec![b' '; MAX_USAGE_BODY_SIZE]);
+        assert!(tap.json_body.is_some());
+        tap.push_json_chunk(b"{}");
+        assert!(tap.json_body.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tap_usage_reports_once_body_is_consumed() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let tap = |response: Response| {
+            let seen = seen.clone();
+            tap_usage("/v1/messages", response, move |usage| {
+                seen.lock().unwrap().push(usage)
+            })
+        };
+
+        let body = json!({"usage": {"input_tokens": 7, "output_tokens": 3}});
+        let response = tap(axum::Json(body.clone()).into_response());
+        assert!(seen.lock().unwrap().is_empty());
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        // 响应体原样转发
+        assert_eq!(serde_json::from_slice::<Value>(&bytes).unwrap(), body);
+        assert_eq!(*seen.lock().unwrap(), vec![Some((7, 3))]);
+
+        drop(tap("plain".into_response()));
+        assert_eq!(seen.lock().unwrap().last(), Some(&None));
+    }
+}
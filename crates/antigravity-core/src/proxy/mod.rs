@@ -7,6 +7,7 @@
 //! - Request monitoring and logging
 
 // Core modules
+pub mod client_keys;
 pub mod monitor;
 pub mod project_resolver;
 pub mod security;
@@ -16,17 +17,21 @@ pub mod token_manager;
 // AIMD Predictive Rate Limiting System (restored 2026-01-15)
 pub mod adaptive_limit;
 pub mod health;
+pub mod limit_store;
 pub mod prometheus;
 pub mod smart_prober;
 
 // Handler modules
 pub mod audio;
+pub mod batches;
 pub mod common;
 pub mod handlers;
 pub mod mappers;
 pub mod middleware;
+pub mod prompt_cache;
 pub mod providers;
 pub mod rate_limit;
+pub mod response_store;
 pub mod session_manager;
 pub mod signature_cache;
 pub mod sticky_config;
@@ -39,17 +44,22 @@ pub use antigravity_shared::proxy::config;
 pub use antigravity_shared::proxy::config::{ProxyAuthMode, ZaiConfig, ZaiDispatchMode};
 
 // Re-export core types
-pub use monitor::{ProxyEventBus, ProxyMonitor};
+pub use monitor::{redact_request_log, BroadcastEventBus, ProxyEventBus, ProxyMonitor};
 pub use security::ProxySecurityConfig;
 pub use server::{
     build_proxy_router, build_proxy_router_with_shared_state, AxumServer, ServerStartConfig,
 };
 pub use signature_cache::SignatureCache;
-pub use token_manager::TokenManager;
+pub use token_manager::{AccountAvailability, ModelLockout, TokenManager};
 
 // Re-export AIMD types
-pub use adaptive_limit::{AIMDController, AdaptiveLimitTracker, ProbeStrategy};
-pub use common::circuit_breaker::{CircuitBreakerManager, CircuitState};
+pub use adaptive_limit::{
+    AIMDController, AdaptiveLimitManager, AdaptiveLimitSnapshot, AdaptiveLimitTracker,
+    ProbeStrategy,
+};
+pub use common::circuit_breaker::{
+    CircuitBreakerManager, CircuitBreakerSummary, CircuitSnapshot, CircuitState,
+};
 pub use health::HealthMonitor;
 pub use smart_prober::SmartProber;
 
@@ -3,10 +3,11 @@
 //! This module provides abstractions for monitoring proxy requests
 //! without any GUI-specific dependencies.
 
-use antigravity_shared::models::{ProxyRequestLog, ProxyStats};
+use crate::modules::proxy_db::{LogQuery, ProxyLogStore, UsageQuery};
+use antigravity_shared::models::{ProxyRequestLog, ProxyStats, UsageRollup};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock};
 
 /// Trait for event bus implementations to emit proxy events.
 /// Different frontends (Tauri, WebSocket, etc.) can implement this.
@@ -23,6 +24,53 @@ impl ProxyEventBus for NoopEventBus {
     }
 }
 
+/// Event bus that fans out request logs to live subscribers (SSE / WebSocket).
+///
+/// Slow subscribers that fall more than `capacity` events behind skip the
+/// missed events instead of blocking the proxy.
+pub struct BroadcastEventBus {
+    sender: broadcast::Sender<Arc<ProxyRequestLog>>,
+}
+
+impl BroadcastEventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity.max(1));
+        Self { sender }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<ProxyRequestLog>> {
+        self.sender.subscribe()
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+}
+
+impl Default for BroadcastEventBus {
+    fn default() -> Self {
+        Self::new(256)
+    }
+}
+
+impl ProxyEventBus for BroadcastEventBus {
+    fn emit_request_log(&self, log: &ProxyRequestLog) {
+        // 没有订阅者时不克隆日志 (请求体可能很大)
+        if self.sender.receiver_count() > 0 {
+            let _ = self.sender.send(Arc::new(log.clone()));
+        }
+    }
+}
+
+/// Returns a copy of `log` with request/response bodies removed.
+pub fn redact_request_log(log: &ProxyRequestLog) -> ProxyRequestLog {
+    ProxyRequestLog {
+        request_body: None,
+        response_body: None,
+        ..log.clone()
+    }
+}
+
 /// Proxy monitor for tracking requests and statistics
 pub struct ProxyMonitor {
     enabled: AtomicBool,
@@ -30,6 +78,8 @@ pub struct ProxyMonitor {
     event_bus: Arc<dyn ProxyEventBus>,
     logs: RwLock<Vec<ProxyRequestLog>>,
     max_logs: usize,
+    /// 持久化存储 (headless 模式)，未设置时仅保留内存中的最近日志
+    store: Option<Arc<ProxyLogStore>>,
 }
 
 impl ProxyMonitor {
@@ -44,7 +94,22 @@ impl ProxyMonitor {
             event_bus,
             logs: RwLock::new(Vec::new()),
             max_logs: 1000,
+            store: None,
+        }
+    }
+
+    /// Persists request logs to `store`; stats are seeded from its history.
+    pub fn with_store(mut self, store: Arc<ProxyLogStore>) -> Self {
+        match store.stats() {
+            Ok(stats) => self.stats = RwLock::new(stats),
+            Err(e) => tracing::warn!("Failed to load persisted proxy stats: {}", e),
         }
+        self.store = Some(store);
+        self
+    }
+
+    pub fn store(&self) -> Option<&Arc<ProxyLogStore>> {
+        self.store.as_ref()
     }
 
     pub fn is_enabled(&self) -> bool {
@@ -71,13 +136,18 @@ impl ProxyMonitor {
             if let Some(tokens) = log.output_tokens {
                 stats.total_output_tokens += tokens as u64;
             }
+            if let Some(tokens) = log.cached_tokens {
+                stats.total_cached_tokens += tokens as u64;
+            }
         }
 
         // Emit to event bus
         self.event_bus.emit_request_log(&log);
 
-        // Store in logs buffer
-        {
+        // Persist (batched in the background) or keep in memory
+        if let Some(store) = &self.store {
+            store.enqueue(log);
+        } else {
             let mut logs = self.logs.write().await;
             logs.push(log);
             // Trim if exceeds max
@@ -93,12 +163,77 @@ impl ProxyMonitor {
     }
 
     pub async fn get_logs(&self, limit: Option<usize>) -> Vec<ProxyRequestLog> {
+        if self.store.is_none() {
+            let logs = self.logs.read().await;
+            let limit = limit.unwrap_or(logs.len());
+            return logs.iter().rev().take(limit).cloned().collect();
+        }
+
+        let query = LogQuery {
+            limit,
+            ..Default::default()
+        };
+        match self.query_logs(&query).await {
+            Ok((logs, _)) => logs,
+            Err(e) => {
+                tracing::warn!("Failed to read request logs: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Filtered, paginated logs (newest first) and the total number of matches.
+    pub async fn query_logs(
+        &self,
+        query: &LogQuery,
+    ) -> Result<(Vec<ProxyRequestLog>, u64), String> {
+        if let Some(store) = &self.store {
+            let store = store.clone();
+            let query = query.clone();
+            return tokio::task::spawn_blocking(move || {
+                store.flush();
+                store.query(&query)
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+        }
+
         let logs = self.logs.read().await;
-        let limit = limit.unwrap_or(logs.len());
-        logs.iter().rev().take(limit).cloned().collect()
+        let matched: Vec<&ProxyRequestLog> =
+            logs.iter().rev().filter(|log| query.matches(log)).collect();
+        let page = matched
+            .iter()
+            .skip(query.page_offset())
+            .take(query.page_size())
+            .map(|log| (*log).clone())
+            .collect();
+        Ok((page, matched.len() as u64))
+    }
+
+    /// Usage rollups by period and group; requires the persistent store.
+    pub async fn usage_rollup(&self, query: &UsageQuery) -> Result<Vec<UsageRollup>, String> {
+        let Some(store) = &self.store else {
+            return Err("Request log persistence is disabled".to_string());
+        };
+        let store = store.clone();
+        let query = query.clone();
+        tokio::task::spawn_blocking(move || {
+            store.flush();
+            store.usage_rollup(&query)
+        })
+        .await
+        .map_err(|e| e.to_string())?
     }
 
     pub async fn clear_logs(&self) {
+        if let Some(store) = &self.store {
+            let store = store.clone();
+            match tokio::task::spawn_blocking(move || store.clear()).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => tracing::warn!("Failed to clear persisted request logs: {}", e),
+                Err(e) => tracing::warn!("Failed to clear persisted request logs: {}", e),
+            }
+        }
         let mut logs = self.logs.write().await;
         logs.clear();
     }
@@ -114,3 +249,49 @@ impl Default for ProxyMonitor {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_log(id: &str) -> ProxyRequestLog {
+        ProxyRequestLog {
+            id: id.to_string(),
+            timestamp: 0,
+            method: "POST".to_string(),
+            url: "/v1/messages".to_string(),
+            status: 200,
+            duration: 10,
+            model: Some("claude-sonnet-4-5".to_string()),
+            mapped_model: None,
+            account_email: None,
+            error: None,
+            request_body: Some("{\"secret\":true}".to_string()),
+            response_body: Some("{}".to_string()),
+            input_tokens: Some(1),
+            output_tokens: Some(2),
+            client_key: None,
+            cached_tokens: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_bus_delivers_logs() {
+        let bus = Arc::new(BroadcastEventBus::new(8));
+        let monitor = ProxyMonitor::with_event_bus(bus.clone());
+        let mut rx = bus.subscribe();
+
+        monitor.log_request(sample_log("a")).await;
+        let received = rx.recv().await.unwrap();
+        assert_eq!(received.id, "a");
+        assert_eq!(bus.subscriber_count(), 1);
+    }
+
+    #[test]
+    fn test_redact_request_log() {
+        let redacted = redact_request_log(&sample_log("a"));
+        assert!(redacted.request_body.is_none());
+        assert!(redacted.response_body.is_none());
+        assert_eq!(redacted.input_tokens, Some(1));
+    }
+}
@@ -5,6 +5,9 @@
 //! - `antigravity_request_duration_seconds` - Histogram of request durations
 //! - `antigravity_accounts_total` - Gauge of total accounts
 //! - `antigravity_accounts_available` - Gauge of available accounts
+//! - `antigravity_tokens_total{provider,model,type}` - Counter of input/output tokens
+//! - `antigravity_account_available{account}` - Per-account availability (1/0)
+//! - `antigravity_account_model_lockout_seconds{account,model}` - Remaining model-scoped quota lockout
 //! - `antigravity_uptime_seconds` - Gauge of server uptime
 //! - `antigravity_log_files_total` - Gauge of total log files
 //! - `antigravity_log_disk_bytes` - Gauge of log disk usage in bytes
@@ -18,13 +21,17 @@
 
 use metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram};
 use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use std::collections::HashSet;
 use std::path::Path;
-use std::sync::OnceLock;
+use std::sync::{Mutex, OnceLock};
 use std::time::Instant;
 
 /// Global Prometheus handle for rendering metrics
 static PROMETHEUS_HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
 
+/// (account, model) pairs reported by the last model lockout update, reset to 0 once cleared
+static REPORTED_MODEL_LOCKOUTS: Mutex<Option<HashSet<(String, String)>>> = Mutex::new(None);
+
 /// Global server start time for uptime calculation
 static METRICS_START_TIME: OnceLock<Instant> = OnceLock::new();
 
@@ -79,6 +86,18 @@ pub fn init_metrics() -> PrometheusHandle {
             "antigravity_accounts_available",
             "Number of accounts currently available for use"
         );
+        describe_counter!(
+            "antigravity_tokens_total",
+            "Total tokens processed, by type (input/output)"
+        );
+        describe_gauge!(
+            "antigravity_account_available",
+            "Whether an account is currently schedulable (1) or skipped (0)"
+        );
+        describe_gauge!(
+            "antigravity_account_model_lockout_seconds",
+            "Remaining seconds of a model-scoped quota lockout (account stays available for other models)"
+        );
         describe_gauge!("antigravity_uptime_seconds", "Server uptime in seconds");
 
         // Log rotation metrics
@@ -167,6 +186,54 @@ pub fn update_account_gauges(total: usize, available: usize) {
     gauge!("antigravity_accounts_available").set(available as f64);
 }
 
+/// Record token usage for a completed request.
+///
+/// # Arguments
+/// * `provider` - The API provider label
+/// * `model` - The model name
+/// * `input_tokens` / `output_tokens` - Token counts reported by the upstream
+pub fn record_tokens(provider: &str, model: &str, input_tokens: u64, output_tokens: u64) {
+    for (kind, value) in [("input", input_tokens), ("output", output_tokens)] {
+        if value == 0 {
+            continue;
+        }
+        let labels = [
+            ("provider", provider.to_string()),
+            ("model", model.to_string()),
+            ("type", kind.to_string()),
+        ];
+        counter!("antigravity_tokens_total", &labels).increment(value);
+    }
+}
+
+/// Update the per-account availability gauge.
+pub fn update_account_availability_gauge(account: &str, available: bool) {
+    let labels = [("account", account.to_string())];
+    gauge!("antigravity_account_available", &labels).set(if available { 1.0 } else { 0.0 });
+}
+
+/// Update the per-account, per-model lockout gauges.
+///
+/// `lockouts` holds every active (account, model, remaining seconds); pairs reported by the
+/// previous update that are no longer locked are reset to 0.
+pub fn update_model_lockout_gauges(lockouts: &[(String, String, u64)]) {
+    let mut reported = REPORTED_MODEL_LOCKOUTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    let previous = reported.take().unwrap_or_default();
+    let mut current = HashSet::with_capacity(lockouts.len());
+    for (account, model, seconds) in lockouts {
+        let labels = [("account", account.clone()), ("model", model.clone())];
+        gauge!("antigravity_account_model_lockout_seconds", &labels).set(*seconds as f64);
+        current.insert((account.clone(), model.clone()));
+    }
+    for (account, model) in previous.difference(&current) {
+        let labels = [("account", account.clone()), ("model", model.clone())];
+        gauge!("antigravity_account_model_lockout_seconds", &labels).set(0.0);
+    }
+    *reported = Some(current);
+}
+
 /// Update uptime gauge.
 /// Should be called periodically or on metrics render.
 pub fn update_uptime_gauge() {
@@ -270,6 +337,9 @@ pub fn detect_provider_from_url(url: &str) -> &'static str {
         "gemini"
     } else if url.contains("/v1/chat/completions")
         || url.contains("/v1/completions")
+        || url.contains("/v1/responses")
+        || url.contains("/v1/embeddings")
+        || url.contains("/v1/audio")
         || url.contains("/v1/models")
         || url.contains("/v1/images")
     {
@@ -313,5 +383,7 @@ mod tests {
             "gemini"
         );
         assert_eq!(detect_provider_from_url("/mcp/web_search"), "mcp");
+        assert_eq!(detect_provider_from_url("/v1/responses"), "openai");
+        assert_eq!(detect_provider_from_url("/v1/embeddings"), "openai");
     }
 }
@@ -0,0 +1,543 @@
+//! Anthropic prompt caching 模拟 (Gemini cachedContents)
+//!
+//! Claude 请求中的 `cache_control` 断点之前的 systemInstruction / tools / contents
+//! 按前缀哈希创建上游缓存；后续请求命中时改为引用 `cachedContent`，只发送剩余 contents。
+//! 缓存属于创建它的账号 (project)，会话通过粘性调度固定到该账号。
+
+use crate::proxy::common::token_counter;
+use crate::proxy::mappers::claude::models::{
+    ClaudeRequest, ContentBlock, MessageContent, SystemPrompt,
+};
+use crate::proxy::upstream::client::UpstreamClient;
+use dashmap::DashMap;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// v1internal 创建缓存的方法名
+const CREATE_METHOD: &str = "createCachedContent";
+/// 上游显式缓存的最小 token 数，低于此值不尝试创建
+const MIN_CACHE_TOKENS: u32 = 1024;
+/// 与 Anthropic 一致：从断点向前最多回看 20 个位置寻找已有缓存
+const LOOKBACK: usize = 20;
+const DEFAULT_TTL: Duration = Duration::from_secs(5 * 60);
+const EXTENDED_TTL: Duration = Duration::from_secs(60 * 60);
+/// 提前失效，避免引用即将过期的缓存
+const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+/// 创建失败后同一前缀的退避时间
+const FAILURE_BACKOFF: Duration = Duration::from_secs(10 * 60);
+/// 过期记录 (缓存 / 退避 / 会话) 的清理间隔
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 上游已创建的缓存
+#[derive(Debug, Clone)]
+pub struct CachedPrefix {
+    /// `cachedContents/...`
+    pub name: String,
+    /// 创建缓存的账号 (email)
+    pub account: String,
+    pub token_count: u32,
+    expires_at: Instant,
+}
+
+impl CachedPrefix {
+    fn is_live(&self) -> bool {
+        Instant::now() < self.expires_at
+    }
+}
+
+/// 本次请求对缓存的使用情况
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheUse {
+    /// 缓存所在账号
+    pub account: String,
+    pub hash: String,
+    /// 本次请求新建了缓存 (usage 中计为 cache_creation_input_tokens)
+    pub created: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct PromptCacheRegistry {
+    /// (账号, 前缀哈希) -> 缓存；缓存只能由创建它的账号引用
+    entries: DashMap<(String, String), CachedPrefix>,
+    /// (账号, 前缀哈希) -> 退避截止时间
+    failures: DashMap<(String, String), Instant>,
+    /// 会话 -> (缓存所在账号, 过期时间)
+    sessions: DashMap<String, (String, Instant)>,
+    /// 上次清理时间
+    last_sweep: Mutex<Option<Instant>>,
+}
+
+fn key(account: &str, hash: &str) -> (String, String) {
+    (account.to_string(), hash.to_string())
+}
+
+impl PromptCacheRegistry {
+    pub fn global() -> &'static PromptCacheRegistry {
+        static INSTANCE: OnceLock<PromptCacheRegistry> = OnceLock::new();
+        INSTANCE.get_or_init(PromptCacheRegistry::default)
+    }
+
+    pub fn get(&self, account: &str, hash: &str) -> Option<CachedPrefix> {
+        let key = key(account, hash);
+        let entry = self.entries.get(&key)?.clone();
+        if entry.is_live() {
+            Some(entry)
+        } else {
+            self.entries.remove(&key);
+            None
+        }
+    }
+
+    fn insert(&self, hash: &str, entry: CachedPrefix) {
+        self.sweep();
+        self.entries.insert(key(&entry.account, hash), entry);
+    }
+
+    /// 上游报告缓存不存在或已过期时移除
+    pub fn invalidate(&self, account: &str, hash: &str) {
+        self.entries.remove(&key(account, hash));
+    }
+
+    fn mark_failed(&self, account: &str, hash: &str) {
+        self.sweep();
+        self.failures
+            .insert(key(account, hash), Instant::now() + FAILURE_BACKOFF);
+    }
+
+    fn backing_off(&self, account: &str, hash: &str) -> bool {
+        let key = key(account, hash);
+        match self.failures.get(&key).map(|t| *t) {
+            Some(until) if Instant::now() < until => true,
+            Some(_) => {
+                self.failures.remove(&key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// 清理过期的缓存、退避与会话记录 (最多每 SWEEP_INTERVAL 一次)
+    ///
+    /// 未再被查询的会话和前缀不会在查找时移除，需定期清理以免无限增长。
+    fn sweep(&self) {
+        let now = Instant::now();
+        {
+            let mut last = self.last_sweep.lock().unwrap_or_else(|e| e.into_inner());
+            if last.is_some_and(|t| now.duration_since(t) < SWEEP_INTERVAL) {
+                return;
+            }
+            *last = Some(now);
+        }
+        self.entries.retain(|_, e| e.is_live());
+        self.failures.retain(|_, until| now < *until);
+        self.sessions.retain(|_, (_, expires_at)| now < *expires_at);
+    }
+
+    fn bind_session(&self, session_id: &str, entry: &CachedPrefix) {
+        self.sweep();
+        self.sessions.insert(
+            session_id.to_string(),
+            (entry.account.clone(), entry.expires_at),
+        );
+    }
+
+    /// 会话缓存所在的账号，缓存过期后不再固定
+    pub fn session_owner(&self, session_id: &str) -> Option<String> {
+        let (account, expires_at) = self.sessions.get(session_id)?.clone();
+        if Instant::now() < expires_at {
+            Some(account)
+        } else {
+            self.sessions.remove(session_id);
+            None
+        }
+    }
+}
+
+/// 请求中的断点：(覆盖的消息数, cache_control)，0 表示仅 system
+fn breakpoints(request: &ClaudeRequest) -> Vec<(usize, &Value)> {
+    let mut points = Vec::new();
+    if let Some(SystemPrompt::Array(blocks)) = &request.system {
+        points.extend(
+            blocks
+                .iter()
+                .filter_map(|b| b.cache_control.as_ref().map(|c| (0, c))),
+        );
+    }
+    for (i, msg) in request.messages.iter().enumerate() {
+        let MessageContent::Array(blocks) = &msg.content else {
+            continue;
+        };
+        for block in blocks {
+            let cache_control = match block {
+                ContentBlock::Text { cache_control, .. }
+                | ContentBlock::Thinking { cache_control, .. }
+                | ContentBlock::Image { cache_control, .. }
+                | ContentBlock::Document { cache_control, .. }
+                | ContentBlock::ToolUse { cache_control, .. }
+                | ContentBlock::ToolResult { cache_control, .. } => cache_control.as_ref(),
+                _ => None,
+            };
+            if let Some(c) = cache_control {
+                points.push((i + 1, c));
+            }
+        }
+    }
+    points
+}
+
+/// 缓存计划：候选前缀从长到短排列
+#[derive(Debug, Clone)]
+pub struct CachePlan {
+    /// (前缀覆盖的 contents 数, 前缀哈希)
+    candidates: Vec<(usize, String)>,
+    ttl: Duration,
+}
+
+/// 根据断点与转换后的 Gemini 请求体生成缓存计划，无断点时返回 None
+pub fn plan(request: &ClaudeRequest, gemini_body: &Value) -> Option<CachePlan> {
+    let points = breakpoints(request);
+    let covered = points.iter().map(|(c, _)| *c).max()?;
+    let ttl = if points
+        .iter()
+        .any(|(_, c)| c.get("ttl").and_then(|t| t.as_str()) == Some("1h"))
+    {
+        EXTENDED_TTL
+    } else {
+        DEFAULT_TTL
+    };
+
+    let model = gemini_body.get("model").and_then(|m| m.as_str())?;
+    let inner = gemini_body.get("request")?;
+    let contents = inner.get("contents")?.as_array()?;
+    // 至少保留最后一条 content 随请求发送
+    let max_k = covered.min(contents.len().checked_sub(1)?);
+    let min_k = max_k.saturating_sub(LOOKBACK);
+    let has_preamble = ["systemInstruction", "tools"]
+        .iter()
+        .any(|key| inner.get(*key).is_some());
+
+    let mut hasher = Sha256::new();
+    hasher.update(model.as_bytes());
+    for key in ["systemInstruction", "tools", "toolConfig"] {
+        hasher.update([0u8]);
+        if let Some(value) = inner.get(key) {
+            hasher.update(value.to_string().as_bytes());
+        }
+    }
+
+    let mut candidates = Vec::new();
+    for k in 0..=max_k {
+        if k >= min_k && (k > 0 || has_preamble) {
+            candidates.push((k, format!("{:x}", hasher.clone().finalize())));
+        }
+        if let Some(content) = contents.get(k) {
+            hasher.update([1u8]);
+            hasher.update(content.to_string().as_bytes());
+        }
+    }
+    candidates.reverse();
+
+    if candidates.is_empty() {
+        None
+    } else {
+        Some(CachePlan { candidates, ttl })
+    }
+}
+
+/// 改为引用缓存：移除已缓存的 systemInstruction / tools / 前 `covered` 条 contents
+pub fn use_cached_content(gemini_body: &mut Value, name: &str, covered: usize) {
+    let Some(inner) = gemini_body
+        .get_mut("request")
+        .and_then(|r| r.as_object_mut())
+    else {
+        return;
+    };
+    for key in ["systemInstruction", "tools", "toolConfig"] {
+        inner.remove(key);
+    }
+    if let Some(contents) = inner.get_mut("contents").and_then(|c| c.as_array_mut()) {
+        contents.drain(..covered.min(contents.len()));
+    }
+    inner.insert("cachedContent".to_string(), json!(name));
+}
+
+/// 缓存内容：前缀部分的 systemInstruction / tools / contents
+fn prefix_content(gemini_body: &Value, covered: usize) -> Value {
+    let inner = &gemini_body["request"];
+    let mut prefix = json!({
+        "contents": inner["contents"]
+            .as_array()
+            .map(|c| c[..covered.min(c.len())].to_vec())
+            .unwrap_or_default(),
+    });
+    for key in ["systemInstruction", "tools", "toolConfig"] {
+        if let Some(value) = inner.get(key) {
+            prefix[key] = value.clone();
+        }
+    }
+    prefix
+}
+
+/// 命中已有缓存或为最长前缀创建缓存，并改写请求体
+///
+/// 创建失败不影响请求本身 (按原样发送完整请求)，同一前缀在退避期内不再尝试。
+pub async fn prepare(
+    upstream: &UpstreamClient,
+    plan: &CachePlan,
+    gemini_body: &mut Value,
+    access_token: &str,
+    account: &str,
+    session_id: &str,
+) -> Option<CacheUse> {
+    let registry = PromptCacheRegistry::global();
+
+    for (covered, hash) in &plan.candidates {
+        if let Some(entry) = registry.get(account, hash) {
+            tracing::debug!(
+                "[PromptCache] Hit {} ({} tokens, {} contents)",
+                entry.name,
+                entry.token_count,
+                covered
+            );
+            use_cached_content(gemini_body, &entry.name, *covered);
+            registry.bind_session(session_id, &entry);
+            return Some(CacheUse {
+                account: account.to_string(),
+                hash: hash.clone(),
+                created: false,
+            });
+        }
+    }
+
+    let (covered, hash) = plan.candidates.first()?;
+    if registry.backing_off(account, hash) {
+        return None;
+    }
+    let mut cached_content = prefix_content(gemini_body, *covered);
+    let estimated_tokens = token_counter::count_gemini_request(&cached_content);
+    if estimated_tokens < MIN_CACHE_TOKENS {
+        return None;
+    }
+
+    let model = gemini_body.get("model").and_then(|m| m.as_str())?;
+    cached_content["model"] = json!(format!("models/{}", model));
+    cached_content["ttl"] = json!(format!("{}s", plan.ttl.as_secs()));
+    let body = json!({
+        "project": gemini_body.get("project").cloned().unwrap_or(Value::Null),
+        "model": model,
+        "request": cached_content,
+    });
+
+    let created = match upstream
+        .call_v1_internal(CREATE_METHOD, access_token, body, None)
+        .await
+    {
+        Ok(response) if response.status().is_success() => response.json::<Value>().await.ok(),
+        Ok(response) => {
+            tracing::debug!(
+                "[PromptCache] Create rejected: HTTP {} {}",
+                response.status(),
+                response.text().await.unwrap_or_default()
+            );
+            None
+        }
+        Err(e) => {
+            tracing::debug!("[PromptCache] Create failed: {}", e);
+            None
+        }
+    };
+    let created = created.as_ref().map(|v| v.get("response").unwrap_or(v));
+    let Some(name) = created.and_then(|v| v.get("name")).and_then(|n| n.as_str()) else {
+        registry.mark_failed(account, hash);
+        return None;
+    };
+
+    let entry = CachedPrefix {
+        name: name.to_string(),
+        account: account.to_string(),
+        token_count: created
+            .and_then(|v| v.pointer("/usageMetadata/totalTokenCount"))
+            .and_then(|t| t.as_u64())
+            .map(|t| t as u32)
+            .unwrap_or(estimated_tokens),
+        expires_at: Instant::now() + plan.ttl.saturating_sub(EXPIRY_MARGIN),
+    };
+    tracing::info!(
+        "[PromptCache] Created {} on {} ({} tokens, ttl {}s)",
+        entry.name,
+        account,
+        entry.token_count,
+        plan.ttl.as_secs()
+    );
+    use_cached_content(gemini_body, &entry.name, *covered);
+    registry.bind_session(session_id, &entry);
+    registry.insert(hash, entry);
+    Some(CacheUse {
+        account: account.to_string(),
+        hash: hash.clone(),
+        created: true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(value: Value) -> ClaudeRequest {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn gemini_body(contents: usize) -> Value {
+        json!({
+            "project": "p",
+            "model": "claude-sonnet-4-5",
+            "request": {
+                "systemInstruction": {"parts": [{"text": "You are helpful"}]},
+                "contents": (0..contents)
+                    .map(|i| json!({"role": if i.is_multiple_of(2) { "user" } else { "model" }, "parts": [{"text": format!("m{}", i)}]}))
+                    .collect::<Vec<_>>(),
+            }
+        })
+    }
+
+    #[test]
+    fn test_plan_follows_last_breakpoint() {
+        let req = request(json!({
+            "model": "claude-sonnet-4-5",
+            "system": [{"type": "text", "text": "You are helpful", "cache_control": {"type": "ephemeral"}}],
+            "messages": [
+                {"role": "user", "content": "m0"},
+                {"role": "assistant", "content": "m1"},
+                {"role": "user", "content": [{"type": "text", "text": "m2", "cache_control": {"type": "ephemeral", "ttl": "1h"}}]}
+            ]
+        }));
+        let body = gemini_body(3);
+        let plan = plan(&req, &body).unwrap();
+        // 最后一条 content 必须随请求发送
+        assert_eq!(plan.candidates[0].0, 2);
+        assert_eq!(plan.candidates.last().unwrap().0, 0);
+        assert_eq!(plan.ttl, EXTENDED_TTL);
+
+        // 下一轮追加消息后，旧前缀的哈希保持不变 (可回看命中)
+        let next = plan_for(&req, 5);
+        assert!(next
+            .candidates
+            .iter()
+            .any(|(k, h)| *k == 2 && *h == plan.candidates[0].1));
+
+        let no_breakpoints = request(json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{"role": "user", "content": "hi"}]
+        }));
+        assert!(super::plan(&no_breakpoints, &body).is_none());
+    }
+
+    fn plan_for(req: &ClaudeRequest, contents: usize) -> CachePlan {
+        let mut req = req.clone();
+        while req.messages.len() < contents {
+            let mut msg = req.messages.last().unwrap().clone();
+            msg.role = if req.messages.len().is_multiple_of(2) {
+                "user"
+            } else {
+                "assistant"
+            }
+            .to_string();
+            req.messages.push(msg);
+        }
+        plan(&req, &gemini_body(contents)).unwrap()
+    }
+
+    #[test]
+    fn test_use_cached_content_strips_prefix() {
+        let mut body = gemini_body(3);
+        use_cached_content(&mut body, "cachedContents/abc", 2);
+        let inner = &body["request"];
+        assert_eq!(inner["cachedContent"], "cachedContents/abc");
+        assert!(inner.get("systemInstruction").is_none());
+        assert_eq!(inner["contents"].as_array().unwrap().len(), 1);
+        assert_eq!(inner["contents"][0]["parts"][0]["text"], "m2");
+
+        let registry = PromptCacheRegistry::default();
+        let entry = CachedPrefix {
+            name: "cachedContents/abc".to_string(),
+            account: "a@example.com".to_string(),
+            token_count: 2048,
+            expires_at: Instant::now() + Duration::from_secs(60),
+        };
+        registry.bind_session("s1", &entry);
+        registry.insert("h", entry);
+        assert_eq!(
+            registry.session_owner("s1").as_deref(),
+            Some("a@example.com")
+        );
+        assert!(registry.get("a@example.com", "h").is_some());
+        registry.invalidate("a@example.com", "h");
+        assert!(registry.get("a@example.com", "h").is_none());
+    }
+
+    fn entry(name: &str, account: &str, ttl: Duration) -> CachedPrefix {
+        CachedPrefix {
+            name: name.to_string(),
+            account: account.to_string(),
+            token_count: 2048,
+            expires_at: Instant::now() + ttl,
+        }
+    }
+
+    #[test]
+    fn test_entries_scoped_to_account() {
+        let registry = PromptCacheRegistry::default();
+        let live = Duration::from_secs(60);
+        registry.insert("h", entry("cachedContents/a", "a@example.com", live));
+        registry.insert("h", entry("cachedContents/b", "b@example.com", live));
+
+        // 同一前缀在不同账号上的缓存互不覆盖
+        assert_eq!(
+            registry.get("a@example.com", "h").unwrap().name,
+            "cachedContents/a"
+        );
+        assert_eq!(
+            registry.get("b@example.com", "h").unwrap().name,
+            "cachedContents/b"
+        );
+        registry.invalidate("a@example.com", "h");
+        assert!(registry.get("a@example.com", "h").is_none());
+        assert!(registry.get("b@example.com", "h").is_some());
+
+        // 退避也按账号隔离
+        registry.mark_failed("a@example.com", "h2");
+        assert!(registry.backing_off("a@example.com", "h2"));
+        assert!(!registry.backing_off("b@example.com", "h2"));
+    }
+
+    #[test]
+    fn test_sweep_drops_expired_records() {
+        let registry = PromptCacheRegistry::default();
+        let expired = entry("cachedContents/old", "a@example.com", Duration::ZERO);
+        registry.sessions.insert(
+            "stale".to_string(),
+            (expired.account.clone(), expired.expires_at),
+        );
+        registry
+            .entries
+            .insert(key("a@example.com", "old"), expired);
+        registry
+            .failures
+            .insert(key("a@example.com", "old"), Instant::now());
+
+        registry.bind_session(
+            "fresh",
+            &entry(
+                "cachedContents/new",
+                "a@example.com",
+                Duration::from_secs(60),
+            ),
+        );
+        assert!(registry.entries.is_empty());
+        assert!(registry.failures.is_empty());
+        assert_eq!(registry.sessions.len(), 1);
+        assert!(registry.session_owner("fresh").is_some());
+    }
+}
@@ -1 +1,2 @@
-pub mod zai_anthropic;
+pub mod passthrough;
+pub mod registry;
@@ -1,3 +1,4 @@
+// 第三方 provider 透传 (Anthropic / OpenAI 兼容接口)
 use axum::{
     body::Body,
     http::{header, HeaderMap, HeaderValue, Method, StatusCode},
@@ -8,35 +9,39 @@ use futures::StreamExt;
 use serde_json::Value;
 use tokio::time::Duration;
 
+use crate::proxy::common::model_mapping::lookup_mapping;
 use crate::proxy::server::AppState;
+use antigravity_shared::proxy::config::{
+    DispatchMode, ProviderAuthStyle, ProviderProtocol, UpstreamProvider,
+};
 
-fn map_model_for_zai(
-    original: &str,
-    state: &antigravity_shared::proxy::config::ZaiConfig,
-) -> String {
-    let m = original.to_lowercase();
-    if let Some(mapped) = state.model_mapping.get(original) {
-        return mapped.clone();
-    }
-    if let Some(mapped) = state.model_mapping.get(&m) {
-        return mapped.clone();
-    }
-    if m.starts_with("zai:") {
-        return original[4..].to_string();
-    }
-    if m.starts_with("glm-") {
-        return original.to_string();
-    }
-    if !m.starts_with("claude-") {
-        return original.to_string();
+/// 将客户端模型名映射为 provider 的模型名
+///
+/// `<id>:` 前缀会被去掉；其余按 model_mapping 精确 / 通配符匹配 (再按小写匹配一次)，未命中原样转发。
+pub fn map_model(provider: &UpstreamProvider, original: &str) -> String {
+    if let Some(model) = strip_provider_prefix(provider, original) {
+        return model.to_string();
     }
-    if m.contains("opus") {
-        return state.models.opus.clone();
-    }
-    if m.contains("haiku") {
-        return state.models.haiku.clone();
+    let lower = original.to_lowercase();
+    lookup_mapping(&provider.model_mapping, original)
+        .or_else(|| lookup_mapping(&provider.model_mapping, &lower))
+        .map(|(_, target)| target.clone())
+        .unwrap_or_else(|| original.to_string())
+}
+
+/// `<id>:model` 形式的模型名返回去掉前缀后的部分
+pub fn strip_provider_prefix<'a>(provider: &UpstreamProvider, model: &'a str) -> Option<&'a str> {
+    let (prefix, rest) = model.split_once(':')?;
+    prefix.eq_ignore_ascii_case(&provider.id).then_some(rest)
+}
+
+/// 是否必须配置 api_key 才能使用
+pub fn requires_api_key(provider: &UpstreamProvider) -> bool {
+    match provider.auth_style {
+        ProviderAuthStyle::None => false,
+        ProviderAuthStyle::Auto => provider.protocol == ProviderProtocol::Anthropic,
+        ProviderAuthStyle::XApiKey | ProviderAuthStyle::Bearer => true,
     }
-    state.models.sonnet.clone()
 }
 
 fn join_base_url(base: &str, path: &str) -> Result<String, String> {
@@ -90,21 +95,34 @@ fn copy_passthrough_headers(incoming: &HeaderMap) -> HeaderMap {
     out
 }
 
-fn set_zai_auth(headers: &mut HeaderMap, incoming: &HeaderMap, api_key: &str) {
-    // Prefer to keep the same auth scheme as the incoming request:
-    // - If the client used x-api-key (Anthropic style), replace it.
-    // - Else if it used Authorization, replace it with Bearer.
-    // - Else default to x-api-key.
-    let has_x_api_key = incoming.contains_key("x-api-key");
-    let has_auth = incoming.contains_key(header::AUTHORIZATION);
+fn set_provider_auth(headers: &mut HeaderMap, incoming: &HeaderMap, provider: &UpstreamProvider) {
+    let api_key = provider.api_key.trim();
+    if api_key.is_empty() {
+        return;
+    }
+    let (x_api_key, bearer) = match (provider.auth_style, provider.protocol) {
+        // Prefer to keep the same auth scheme as the incoming request:
+        // - If the client used x-api-key (Anthropic style), replace it.
+        // - Else if it used Authorization, replace it with Bearer.
+        // - Else default to x-api-key.
+        (ProviderAuthStyle::Auto, ProviderProtocol::Anthropic) => {
+            let has_x_api_key = incoming.contains_key("x-api-key");
+            let has_auth = incoming.contains_key(header::AUTHORIZATION);
+            (has_x_api_key || !has_auth, has_auth)
+        }
+        (ProviderAuthStyle::Auto, ProviderProtocol::OpenAI) | (ProviderAuthStyle::Bearer, _) => {
+            (false, true)
+        }
+        (ProviderAuthStyle::XApiKey, _) => (true, false),
+        (ProviderAuthStyle::None, _) => (false, false),
+    };
 
-    if has_x_api_key || !has_auth {
+    if x_api_key {
         if let Ok(v) = HeaderValue::from_str(api_key) {
             headers.insert("x-api-key", v);
         }
     }
-
-    if has_auth {
+    if bearer {
         if let Ok(v) = HeaderValue::from_str(&format!("Bearer {}", api_key)) {
             headers.insert(header::AUTHORIZATION, v);
         }
@@ -130,30 +148,77 @@ pub fn deep_remove_cache_control(value: &mut Value) {
     }
 }
 
-pub async fn forward_anthropic_json(
+/// 将 JSON 请求原样转发给 provider (Anthropic / OpenAI 兼容接口)，响应以流的形式透传
+pub async fn forward_json(
     state: &AppState,
+    provider: &UpstreamProvider,
     method: Method,
     path: &str,
     incoming_headers: &HeaderMap,
-    mut body: Value,
+    body: Value,
 ) -> Response {
-    let zai = state.zai.read().await.clone();
-    if !zai.enabled || zai.dispatch_mode == crate::proxy::ZaiDispatchMode::Off {
-        return (StatusCode::BAD_REQUEST, "z.ai is disabled").into_response();
+    let resp = match send_json(state, provider, method, path, incoming_headers, body).await {
+        Ok(r) => r,
+        Err(response) => return response,
+    };
+
+    let status = StatusCode::from_u16(resp.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+
+    let mut out = Response::builder().status(status);
+    if let Some(ct) = resp.headers().get(header::CONTENT_TYPE) {
+        out = out.header(header::CONTENT_TYPE, ct.clone());
     }
 
-    if zai.api_key.trim().is_empty() {
-        return (StatusCode::BAD_REQUEST, "z.ai api_key is not set").into_response();
+    // Stream response body to the client (covers SSE and non-SSE).
+    let stream = resp.bytes_stream().map(|chunk| match chunk {
+        Ok(b) => Ok::<Bytes, std::io::Error>(b),
+        Err(e) => Ok(Bytes::from(format!("Upstream stream error: {}", e))),
+    });
+
+    out.body(Body::from_stream(stream)).unwrap_or_else(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to build response",
+        )
+            .into_response()
+    })
+}
+
+/// 映射模型名、设置鉴权后发送 JSON 请求，返回上游原始响应 (任意状态码)
+///
+/// 配置错误或网络错误时返回可直接交给客户端的错误响应
+pub async fn send_json(
+    state: &AppState,
+    provider: &UpstreamProvider,
+    method: Method,
+    path: &str,
+    incoming_headers: &HeaderMap,
+    mut body: Value,
+) -> Result<reqwest::Response, Response> {
+    if !provider.enabled || provider.dispatch_mode == DispatchMode::Off {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Provider '{}' is disabled", provider.id),
+        )
+            .into_response());
+    }
+
+    if requires_api_key(provider) && provider.api_key.trim().is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Provider '{}' api_key is not set", provider.id),
+        )
+            .into_response());
     }
 
     if let Some(model) = body.get("model").and_then(|v| v.as_str()) {
-        let mapped = map_model_for_zai(model, &zai);
+        let mapped = map_model(provider, model);
         body["model"] = Value::String(mapped);
     }
 
-    let url = match join_base_url(&zai.base_url, path) {
+    let url = match join_base_url(&provider.base_url, path) {
         Ok(u) => u,
-        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+        Err(e) => return Err((StatusCode::BAD_REQUEST, e).into_response()),
     };
 
     let timeout_secs = state.request_timeout.max(5);
@@ -161,11 +226,11 @@ pub async fn forward_anthropic_json(
         state.upstream_proxy.read().await.clone();
     let client = match build_client(Some(upstream_proxy), timeout_secs) {
         Ok(c) => c,
-        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e).into_response()),
     };
 
     let mut headers = copy_passthrough_headers(incoming_headers);
-    set_zai_auth(&mut headers, incoming_headers, &zai.api_key);
+    set_provider_auth(&mut headers, incoming_headers, provider);
 
     // Ensure JSON content type.
     headers
@@ -174,7 +239,9 @@ pub async fn forward_anthropic_json(
 
     // [FIX #290] Clean cache_control before sending to Anthropic API
     // This prevents "Extra inputs are not permitted" errors
-    deep_remove_cache_control(&mut body);
+    if provider.protocol == ProviderProtocol::Anthropic {
+        deep_remove_cache_control(&mut body);
+    }
 
     // [FIX #307] Explicitly serialize body to Vec<u8> to ensure Content-Length is set correctly.
     // This avoids "Transfer-Encoding: chunked" for small bodies which caused connection errors.
@@ -182,7 +249,8 @@ pub async fn forward_anthropic_json(
     let body_len = body_bytes.len();
 
     tracing::debug!(
-        "Forwarding request to z.ai (len: {} bytes): {}",
+        "Forwarding request to provider {} (len: {} bytes): {}",
+        provider.id,
         body_len,
         url
     );
@@ -192,34 +260,10 @@ pub async fn forward_anthropic_json(
         .headers(headers)
         .body(body_bytes); // Use .body(Vec<u8>) instead of .json()
 
-    let resp = match req.send().await {
-        Ok(r) => r,
-        Err(e) => {
-            return (
-                StatusCode::BAD_GATEWAY,
-                format!("Upstream request failed: {}", e),
-            )
-                .into_response();
-        }
-    };
-
-    let status = StatusCode::from_u16(resp.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
-
-    let mut out = Response::builder().status(status);
-    if let Some(ct) = resp.headers().get(header::CONTENT_TYPE) {
-        out = out.header(header::CONTENT_TYPE, ct.clone());
-    }
-
-    // Stream response body to the client (covers SSE and non-SSE).
-    let stream = resp.bytes_stream().map(|chunk| match chunk {
-        Ok(b) => Ok::<Bytes, std::io::Error>(b),
-        Err(e) => Ok(Bytes::from(format!("Upstream stream error: {}", e))),
-    });
-
-    out.body(Body::from_stream(stream)).unwrap_or_else(|_| {
+    req.send().await.map_err(|e| {
         (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Failed to build response",
+            StatusCode::BAD_GATEWAY,
+            format!("Upstream request failed: {}", e),
         )
             .into_response()
     })
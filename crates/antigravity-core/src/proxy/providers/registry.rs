@@ -0,0 +1,222 @@
+// 第三方 provider 注册表
+// 按协议 / 模型 / 调度模式决定请求交给哪个 provider，未命中时走 Google 账号池
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use antigravity_shared::proxy::config::{DispatchMode, ProviderProtocol, UpstreamProvider};
+
+use super::passthrough::{requires_api_key, strip_provider_prefix};
+use crate::proxy::common::model_mapping::wildcard_match;
+use crate::proxy::server::AppState;
+
+/// 当前配置的全部 provider (z.ai 在前，其余按配置顺序)
+pub async fn all_providers(state: &AppState) -> Vec<UpstreamProvider> {
+    let mut providers = vec![state.zai.read().await.as_provider()];
+    providers.extend(state.providers.read().await.iter().cloned());
+    providers
+}
+
+/// 已启用且配置完整
+fn is_usable(provider: &UpstreamProvider) -> bool {
+    provider.enabled
+        && provider.dispatch_mode != DispatchMode::Off
+        && !provider.base_url.trim().is_empty()
+        && (!requires_api_key(provider) || !provider.api_key.trim().is_empty())
+}
+
+/// provider 是否处理该模型 (`models` 为空表示全部；`<id>:` 前缀总是命中)
+fn serves_model(provider: &UpstreamProvider, model: &str) -> bool {
+    strip_provider_prefix(provider, model).is_some()
+        || provider.models.is_empty()
+        || provider
+            .models
+            .iter()
+            .any(|pattern| wildcard_match(pattern, model))
+}
+
+/// 可处理该协议与模型的 provider
+pub fn candidates<'a>(
+    providers: &'a [UpstreamProvider],
+    protocol: ProviderProtocol,
+    model: &str,
+) -> Vec<&'a UpstreamProvider> {
+    providers
+        .iter()
+        .filter(|p| p.protocol == protocol && is_usable(p) && serves_model(p, model))
+        .collect()
+}
+
+/// 选择处理本次请求的 provider，返回 None 时使用 Google 账号池
+///
+/// - 模型带 `<id>:` 前缀时固定使用该 provider
+/// - Exclusive: 命中的模型全部交给 provider
+/// - Pooled: 每个 provider 与每个 Google 账号各占一个轮询槽位
+/// - Fallback: 没有 Google 账号时使用
+pub fn select<'a>(
+    providers: &'a [UpstreamProvider],
+    protocol: ProviderProtocol,
+    model: &str,
+    google_accounts: usize,
+    rr: &AtomicUsize,
+) -> Option<&'a UpstreamProvider> {
+    let candidates = candidates(providers, protocol, model);
+
+    if let Some(provider) = candidates
+        .iter()
+        .find(|p| strip_provider_prefix(p, model).is_some())
+    {
+        return Some(provider);
+    }
+
+    if let Some(provider) = candidates
+        .iter()
+        .find(|p| p.dispatch_mode == DispatchMode::Exclusive)
+    {
+        return Some(provider);
+    }
+
+    let pooled: Vec<&UpstreamProvider> = candidates
+        .iter()
+        .copied()
+        .filter(|p| p.dispatch_mode == DispatchMode::Pooled)
+        .collect();
+    if !pooled.is_empty() {
+        // No strict guarantees: a provider may get 0 requests if selection never hits.
+        let total = google_accounts + pooled.len();
+        let slot = rr.fetch_add(1, Ordering::Relaxed) % total;
+        if slot < pooled.len() {
+            return Some(pooled[slot]);
+        }
+    }
+
+    if google_accounts == 0 {
+        return candidates
+            .into_iter()
+            .find(|p| p.dispatch_mode == DispatchMode::Fallback);
+    }
+    None
+}
+
+/// 按当前 AppState 选择 provider
+pub async fn select_provider(
+    state: &AppState,
+    protocol: ProviderProtocol,
+    model: &str,
+) -> Option<UpstreamProvider> {
+    let providers = all_providers(state).await;
+    select(
+        &providers,
+        protocol,
+        model,
+        state.token_manager.len(),
+        &state.provider_rr,
+    )
+    .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::providers::passthrough::map_model;
+    use antigravity_shared::proxy::config::{ProviderAuthStyle, ZaiConfig};
+
+    fn provider(id: &str, protocol: ProviderProtocol, mode: DispatchMode) -> UpstreamProvider {
+        UpstreamProvider {
+            id: id.to_string(),
+            enabled: true,
+            base_url: format!("http://{}.local", id),
+            api_key: "sk-test".to_string(),
+            auth_style: ProviderAuthStyle::Auto,
+            protocol,
+            dispatch_mode: mode,
+            models: Vec::new(),
+            model_mapping: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_select_by_mode_and_model() {
+        let mut vllm = provider("vllm", ProviderProtocol::OpenAI, DispatchMode::Exclusive);
+        vllm.models = vec!["llama-*".to_string()];
+        let backup = provider(
+            "backup",
+            ProviderProtocol::Anthropic,
+            DispatchMode::Fallback,
+        );
+        let providers = vec![vllm, backup];
+        let rr = AtomicUsize::new(0);
+
+        let pick = |protocol, model, google| {
+            select(&providers, protocol, model, google, &rr).map(|p| p.id.clone())
+        };
+        assert_eq!(
+            pick(ProviderProtocol::OpenAI, "llama-3-70b", 3),
+            Some("vllm".into())
+        );
+        assert_eq!(pick(ProviderProtocol::OpenAI, "gpt-4o", 3), None);
+        // 协议不匹配
+        assert_eq!(pick(ProviderProtocol::Anthropic, "llama-3-70b", 3), None);
+        // 回退 provider 仅在没有 Google 账号时使用
+        assert_eq!(
+            pick(ProviderProtocol::Anthropic, "claude-sonnet-4-5", 3),
+            None
+        );
+        assert_eq!(
+            pick(ProviderProtocol::Anthropic, "claude-sonnet-4-5", 0),
+            Some("backup".into())
+        );
+        // 前缀强制指定 provider，即使不在 models 中
+        assert_eq!(
+            pick(ProviderProtocol::OpenAI, "vllm:qwen2", 3),
+            Some("vllm".into())
+        );
+    }
+
+    #[test]
+    fn test_pooled_round_robin_and_unusable() {
+        let a = provider("a", ProviderProtocol::Anthropic, DispatchMode::Pooled);
+        let mut b = provider("b", ProviderProtocol::Anthropic, DispatchMode::Pooled);
+        let mut disabled = provider("c", ProviderProtocol::Anthropic, DispatchMode::Exclusive);
+        disabled.enabled = false;
+        let mut keyless = provider("d", ProviderProtocol::Anthropic, DispatchMode::Exclusive);
+        keyless.api_key.clear();
+        let providers = vec![disabled, keyless, a, b.clone()];
+        let rr = AtomicUsize::new(0);
+
+        // 2 个 provider + 2 个 Google 账号 = 4 个槽位
+        let picks: Vec<Option<String>> = (0..4)
+            .map(|_| {
+                select(
+                    &providers,
+                    ProviderProtocol::Anthropic,
+                    "claude-opus-4",
+                    2,
+                    &rr,
+                )
+                .map(|p| p.id.clone())
+            })
+            .collect();
+        assert_eq!(picks, vec![Some("a".into()), Some("b".into()), None, None]);
+
+        // OpenAI 协议的 Ollama 不需要 api_key
+        b.protocol = ProviderProtocol::OpenAI;
+        b.api_key.clear();
+        b.dispatch_mode = DispatchMode::Exclusive;
+        let providers = vec![b];
+        assert!(select(&providers, ProviderProtocol::OpenAI, "llama3", 2, &rr).is_some());
+    }
+
+    #[test]
+    fn test_zai_provider_model_mapping() {
+        let mut zai = ZaiConfig::default();
+        zai.model_mapping
+            .insert("claude-sonnet-4-5".to_string(), "glm-4.6".to_string());
+        let provider = zai.as_provider();
+
+        assert_eq!(map_model(&provider, "claude-sonnet-4-5"), "glm-4.6");
+        assert_eq!(map_model(&provider, "claude-opus-4-1"), zai.models.opus);
+        assert_eq!(map_model(&provider, "Claude-3-5-Haiku"), zai.models.haiku);
+        assert_eq!(map_model(&provider, "claude-3-7-sonnet"), zai.models.sonnet);
+        assert_eq!(map_model(&provider, "glm-4.5"), "glm-4.5");
+        assert_eq!(map_model(&provider, "zai:glm-4.5-air"), "glm-4.5-air");
+    }
+}
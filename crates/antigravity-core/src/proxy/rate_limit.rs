@@ -1,9 +1,11 @@
 use dashmap::DashMap;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::time::{Duration, SystemTime};
 
 /// 限流原因类型
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum RateLimitReason {
     /// 配额耗尽 (QUOTA_EXHAUSTED)
     QuotaExhausted,
@@ -28,16 +30,22 @@ pub struct RateLimitInfo {
     /// 检测时间
     #[allow(dead_code)]
     pub detected_at: SystemTime,
-    /// 限流原因 (used for logging and debugging)
-    #[allow(dead_code)]
+    /// 限流原因 (used for logging, debugging and persistence)
     pub reason: RateLimitReason,
     /// 关联的模型 (用于模型级别限流)
     /// None 表示账号级别限流,Some(model) 表示特定模型限流
-    #[allow(dead_code)]
     pub model: Option<String>,
 }
 
+/// 模型级别限流的存储 key (`<account>::<model>`)
+fn model_key(account_id: &str, model: &str) -> String {
+    format!("{}::{}", account_id, model)
+}
+
 /// 限流跟踪器
+///
+/// 账号级别限流以账号 key 存储；模型级别限流 (配额按模型计算) 以 `<account>::<model>` 存储，
+/// 只影响对应模型的请求。
 pub struct RateLimitTracker {
     limits: DashMap<String, RateLimitInfo>,
     /// 连续失败计数（用于智能指数退避）
@@ -107,7 +115,11 @@ impl RateLimitTracker {
             model: model.clone(), // 🆕 支持模型级别限流
         };
 
-        self.limits.insert(account_id.to_string(), info);
+        let key = match &model {
+            Some(m) => model_key(account_id, m),
+            None => account_id.to_string(),
+        };
+        self.limits.insert(key, info);
 
         if let Some(m) = &model {
             tracing::info!(
@@ -511,6 +523,44 @@ impl RateLimitTracker {
         }
     }
 
+    /// 检查账号对指定模型是否仍在限流中 (账号级别或该模型的模型级别限流)
+    pub fn is_rate_limited_for_model(&self, account_id: &str, model: Option<&str>) -> bool {
+        self.is_rate_limited(account_id)
+            || model.is_some_and(|m| self.is_rate_limited(&model_key(account_id, m)))
+    }
+
+    /// 将刚记录的配额耗尽锁定收窄为模型级别
+    ///
+    /// 上游配额按模型计算，某个模型耗尽时账号仍可服务其他模型 (例如路由回退链)。
+    /// 速率限制、容量不足等其他原因仍保持账号级别。
+    pub fn scope_to_model(&self, account_id: &str, model: &str) {
+        let scoped = self.limits.remove_if(account_id, |_, info| {
+            info.reason == RateLimitReason::QuotaExhausted && info.model.is_none()
+        });
+        if let Some((_, mut info)) = scoped {
+            info.model = Some(model.to_string());
+            self.limits.insert(model_key(account_id, model), info);
+            tracing::debug!("账号 {} 的配额耗尽锁定已限定到模型 {}", account_id, model);
+        }
+    }
+
+    /// 账号仍在生效的模型级别限流，返回 (模型, 剩余秒数)，按模型名排序
+    pub fn model_lockouts(&self, account_id: &str) -> Vec<(String, u64)> {
+        let prefix = format!("{}::", account_id);
+        let now = SystemTime::now();
+        let mut lockouts: Vec<(String, u64)> = self
+            .limits
+            .iter()
+            .filter(|e| e.key().starts_with(&prefix))
+            .filter_map(|e| {
+                let remaining = e.value().reset_time.duration_since(now).ok()?;
+                Some((e.value().model.clone()?, remaining.as_secs()))
+            })
+            .collect();
+        lockouts.sort();
+        lockouts
+    }
+
     /// 获取距离限流重置还有多少秒
     pub fn get_reset_seconds(&self, account_id: &str) -> Option<u64> {
         if let Some(info) = self.get(account_id) {
@@ -523,6 +573,28 @@ impl RateLimitTracker {
         }
     }
 
+    /// 导出仍在生效的限流记录 (用于持久化)
+    ///
+    /// 返回的 key 为账号 key，模型级别限流通过 `RateLimitInfo::model` 区分
+    pub fn active_lockouts(&self) -> Vec<(String, RateLimitInfo)> {
+        let now = SystemTime::now();
+        self.limits
+            .iter()
+            .filter(|e| e.value().reset_time > now)
+            .map(|e| {
+                let key = match &e.value().model {
+                    Some(m) => e
+                        .key()
+                        .strip_suffix(&format!("::{}", m))
+                        .unwrap_or(e.key())
+                        .to_string(),
+                    None => e.key().clone(),
+                };
+                (key, e.value().clone())
+            })
+            .collect()
+    }
+
     /// 清除过期的限流记录
     #[allow(dead_code)]
     pub fn cleanup_expired(&self) -> usize {
@@ -545,10 +617,14 @@ impl RateLimitTracker {
         count
     }
 
-    /// 清除指定账号的限流记录
+    /// 清除指定账号的限流记录 (包括模型级别限流)
     #[allow(dead_code)]
     pub fn clear(&self, account_id: &str) -> bool {
-        self.limits.remove(account_id).is_some()
+        let prefix = format!("{}::", account_id);
+        let before = self.limits.len();
+        self.limits
+            .retain(|k, _| k != account_id && !k.starts_with(&prefix));
+        self.limits.len() != before
     }
 
     /// 清除所有限流记录 (乐观重置策略)
@@ -636,4 +712,44 @@ mod tests {
         // 应该被识别为 RateLimitExceeded，而不是 QuotaExhausted
         assert_eq!(reason, RateLimitReason::RateLimitExceeded);
     }
+
+    #[test]
+    fn test_quota_lockout_scoped_to_model() {
+        let tracker = RateLimitTracker::new();
+        let body = r#"{"error":{"details":[{"reason":"QUOTA_EXHAUSTED"}]}}"#;
+        tracker.parse_from_error("acc1", 429, None, body);
+        tracker.scope_to_model("acc1", "claude-opus-4-5-thinking");
+
+        // 只锁定耗尽的模型，账号仍可服务其他模型
+        assert!(!tracker.is_rate_limited("acc1"));
+        assert!(tracker.is_rate_limited_for_model("acc1", Some("claude-opus-4-5-thinking")));
+        assert!(!tracker.is_rate_limited_for_model("acc1", Some("gemini-3-pro-high")));
+
+        let model_lockouts = tracker.model_lockouts("acc1");
+        assert_eq!(model_lockouts.len(), 1);
+        assert_eq!(model_lockouts[0].0, "claude-opus-4-5-thinking");
+        assert!(tracker.model_lockouts("acc").is_empty());
+
+        // 持久化导出使用账号 key
+        let lockouts = tracker.active_lockouts();
+        assert_eq!(lockouts[0].0, "acc1");
+        assert_eq!(
+            lockouts[0].1.model.as_deref(),
+            Some("claude-opus-4-5-thinking")
+        );
+
+        assert!(tracker.clear("acc1"));
+        assert!(!tracker.is_rate_limited_for_model("acc1", Some("claude-opus-4-5-thinking")));
+    }
+
+    #[test]
+    fn test_rate_limit_exceeded_stays_account_level() {
+        let tracker = RateLimitTracker::new();
+        let body = r#"{"error":{"details":[{"reason":"RATE_LIMIT_EXCEEDED"}]}}"#;
+        tracker.parse_from_error("acc1", 429, None, body);
+        tracker.scope_to_model("acc1", "gemini-3-flash");
+
+        assert!(tracker.is_rate_limited("acc1"));
+        assert!(tracker.is_rate_limited_for_model("acc1", Some("gemini-2.5-flash")));
+    }
 }
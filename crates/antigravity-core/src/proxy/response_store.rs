@@ -0,0 +1,173 @@
+//! Responses API 本地响应存储
+//!
+//! 保存 `store: true` 的 response 对象及其完整对话历史，供 `previous_response_id`
+//! 链式对话与 `GET /v1/responses/{id}` 使用。仅驻留内存，带 TTL 与容量上限。
+//! 每条响应归属创建它的客户端 key，其他 key 查询、续写或删除时按不存在处理。
+
+use dashmap::DashMap;
+use serde_json::Value;
+use std::time::{Duration, Instant};
+
+const DEFAULT_TTL: Duration = Duration::from_secs(6 * 60 * 60);
+const DEFAULT_CAPACITY: usize = 1000;
+
+/// 已存储的响应
+#[derive(Debug, Clone)]
+pub struct StoredResponse {
+    /// 返回给客户端的 response 对象
+    pub response: Value,
+    /// 截至本次响应的 Chat 消息历史 (不含 instructions，instructions 不跨响应继承)
+    pub messages: Vec<Value>,
+    /// 创建响应的客户端 key id (主 key 为 None)
+    pub client_key: Option<String>,
+    created_at: Instant,
+}
+
+pub struct ResponseStore {
+    entries: DashMap<String, StoredResponse>,
+    ttl: Duration,
+    capacity: usize,
+}
+
+impl Default for ResponseStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_TTL, DEFAULT_CAPACITY)
+    }
+}
+
+impl ResponseStore {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            entries: DashMap::new(),
+            ttl,
+            capacity,
+        }
+    }
+
+    pub fn insert(
+        &self,
+        id: String,
+        response: Value,
+        messages: Vec<Value>,
+        client_key: Option<&str>,
+    ) {
+        if self.entries.len() >= self.capacity {
+            self.evict();
+        }
+        self.entries.insert(
+            id,
+            StoredResponse {
+                response,
+                messages,
+                client_key: client_key.map(String::from),
+                created_at: Instant::now(),
+            },
+        );
+    }
+
+    /// 读取 `client_key` 创建的响应
+    pub fn get(&self, id: &str, client_key: Option<&str>) -> Option<StoredResponse> {
+        let entry = self.entries.get(id)?;
+        if entry.created_at.elapsed() > self.ttl {
+            drop(entry);
+            self.entries.remove(id);
+            return None;
+        }
+        (entry.client_key.as_deref() == client_key).then(|| entry.clone())
+    }
+
+    /// 删除 `client_key` 创建的响应
+    pub fn remove(&self, id: &str, client_key: Option<&str>) -> bool {
+        self.entries
+            .remove_if(id, |_, v| v.client_key.as_deref() == client_key)
+            .is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 先清理过期项，仍超出容量则淘汰最旧的条目
+    fn evict(&self) {
+        let ttl = self.ttl;
+        self.entries.retain(|_, v| v.created_at.elapsed() <= ttl);
+
+        while self.entries.len() >= self.capacity {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|e| e.created_at)
+                .map(|e| e.key().clone());
+            match oldest {
+                Some(key) => {
+                    self.entries.remove(&key);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_insert_get_remove() {
+        let store = ResponseStore::default();
+        store.insert(
+            "resp_1".to_string(),
+            json!({"id": "resp_1"}),
+            vec![json!({"role": "user", "content": "hi"})],
+            None,
+        );
+        let stored = store.get("resp_1", None).unwrap();
+        assert_eq!(stored.response["id"], "resp_1");
+        assert_eq!(stored.messages.len(), 1);
+
+        assert!(store.remove("resp_1", None));
+        assert!(store.get("resp_1", None).is_none());
+        assert!(!store.remove("resp_1", None));
+    }
+
+    #[test]
+    fn test_responses_scoped_to_client_key() {
+        let store = ResponseStore::default();
+        store.insert("resp_a".to_string(), json!({}), vec![], Some("key-a"));
+
+        assert!(store.get("resp_a", Some("key-a")).is_some());
+        assert!(store.get("resp_a", Some("key-b")).is_none());
+        assert!(store.get("resp_a", None).is_none());
+        assert!(!store.remove("resp_a", Some("key-b")));
+        assert!(!store.remove("resp_a", None));
+        assert!(store.remove("resp_a", Some("key-a")));
+    }
+
+    #[test]
+    fn test_capacity_evicts_oldest() {
+        let store = ResponseStore::new(DEFAULT_TTL, 2);
+        store.insert("a".to_string(), json!({}), vec![], None);
+        std::thread::sleep(Duration::from_millis(2));
+        store.insert("b".to_string(), json!({}), vec![], None);
+        std::thread::sleep(Duration::from_millis(2));
+        store.insert("c".to_string(), json!({}), vec![], None);
+
+        assert_eq!(store.len(), 2);
+        assert!(store.get("a", None).is_none());
+        assert!(store.get("c", None).is_some());
+    }
+
+    #[test]
+    fn test_expired_entry_is_dropped() {
+        let store = ResponseStore::new(Duration::from_millis(1), 10);
+        store.insert("a".to_string(), json!({}), vec![], None);
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(store.get("a", None).is_none());
+        assert!(store.is_empty());
+    }
+}
@@ -1,10 +1,12 @@
-use antigravity_shared::proxy::config::{ProxyAuthMode, ProxyConfig};
+use antigravity_shared::proxy::config::{ClientApiKey, ProxyAuthMode, ProxyConfig};
 
 #[derive(Debug, Clone)]
 pub struct ProxySecurityConfig {
     pub auth_mode: ProxyAuthMode,
     pub api_key: String,
     pub allow_lan_access: bool,
+    /// 附加的客户端 key (独立白名单 / 限流 / 预算)
+    pub client_keys: Vec<ClientApiKey>,
 }
 
 impl ProxySecurityConfig {
@@ -13,9 +15,28 @@ impl ProxySecurityConfig {
             auth_mode: config.auth_mode.clone(),
             api_key: config.api_key.clone(),
             allow_lan_access: config.allow_lan_access,
+            client_keys: config.api_keys.clone(),
         }
     }
 
+    /// Looks up an enabled, unexpired client key by its secret.
+    pub fn find_client_key(&self, key: &str) -> Option<&ClientApiKey> {
+        let now = chrono::Utc::now().timestamp();
+        self.client_keys
+            .iter()
+            .find(|k| k.key == key)
+            .filter(|k| k.enabled && !k.is_expired(now))
+    }
+
+    /// Looks up an enabled, unexpired client key by its id.
+    pub fn find_client_key_by_id(&self, id: &str) -> Option<&ClientApiKey> {
+        let now = chrono::Utc::now().timestamp();
+        self.client_keys
+            .iter()
+            .find(|k| k.id == id)
+            .filter(|k| k.enabled && !k.is_expired(now))
+    }
+
     pub fn effective_auth_mode(&self) -> ProxyAuthMode {
         match self.auth_mode {
             ProxyAuthMode::Auto => {
@@ -40,6 +61,7 @@ mod tests {
             auth_mode: ProxyAuthMode::Auto,
             api_key: "sk-test".to_string(),
             allow_lan_access: false,
+            client_keys: Vec::new(),
         };
         assert!(matches!(s.effective_auth_mode(), ProxyAuthMode::Off));
     }
@@ -50,10 +72,41 @@ mod tests {
             auth_mode: ProxyAuthMode::Auto,
             api_key: "sk-test".to_string(),
             allow_lan_access: true,
+            client_keys: Vec::new(),
         };
         assert!(matches!(
             s.effective_auth_mode(),
             ProxyAuthMode::AllExceptHealth
         ));
     }
+
+    #[test]
+    fn find_client_key_skips_disabled_and_expired() {
+        let key = |id: &str, enabled: bool, expires_at: Option<i64>| ClientApiKey {
+            id: id.to_string(),
+            name: id.to_string(),
+            key: format!("sk-ag-{}", id),
+            enabled,
+            allowed_models: Vec::new(),
+            allowed_protocols: Vec::new(),
+            requests_per_minute: None,
+            daily_token_budget: None,
+            expires_at,
+            created_at: 0,
+        };
+        let s = ProxySecurityConfig {
+            auth_mode: ProxyAuthMode::Strict,
+            api_key: "sk-test".to_string(),
+            allow_lan_access: false,
+            client_keys: vec![
+                key("active", true, None),
+                key("revoked", false, None),
+                key("expired", true, Some(1)),
+            ],
+        };
+        assert_eq!(s.find_client_key("sk-ag-active").unwrap().id, "active");
+        assert!(s.find_client_key("sk-ag-revoked").is_none());
+        assert!(s.find_client_key("sk-ag-expired").is_none());
+        assert!(s.find_client_key("sk-test").is_none());
+    }
 }
@@ -1,6 +1,7 @@
 use crate::proxy::TokenManager;
+use antigravity_shared::proxy::config::ModelRouteRule;
 use axum::{
-    extract::DefaultBodyLimit,
+    extract::{DefaultBodyLimit, State},
     http::StatusCode,
     response::{IntoResponse, Json, Response},
     routing::{any, get, post},
@@ -18,8 +19,9 @@ use tracing::{debug, error};
 pub struct AppState {
     pub token_manager: Arc<TokenManager>,
     pub custom_mapping: Arc<tokio::sync::RwLock<std::collections::HashMap<String, String>>>,
+    pub router_rules: Arc<RwLock<Vec<ModelRouteRule>>>, // 有序路由规则 (优先于 custom_mapping)
     #[allow(dead_code)]
-    pub request_timeout: u64, // API 请求超时(秒)
+    pub request_timeout: u64,    // API 请求超时(秒)
     #[allow(dead_code)]
     pub thought_signature_map: Arc<tokio::sync::Mutex<std::collections::HashMap<String, String>>>, // 思维链签名映射 (ID -> Signature)
     #[allow(dead_code)]
@@ -27,10 +29,13 @@ pub struct AppState {
         Arc<tokio::sync::RwLock<antigravity_shared::utils::http::UpstreamProxyConfig>>,
     pub upstream: Arc<crate::proxy::upstream::client::UpstreamClient>,
     pub zai: Arc<RwLock<antigravity_shared::proxy::config::ZaiConfig>>,
+    pub providers: Arc<RwLock<Vec<antigravity_shared::proxy::config::UpstreamProvider>>>, // 其他第三方 provider
     pub provider_rr: Arc<AtomicUsize>,
     pub zai_vision_mcp: Arc<crate::proxy::zai_vision_mcp::ZaiVisionMcpState>,
     pub monitor: Arc<crate::proxy::monitor::ProxyMonitor>,
     pub experimental: Arc<RwLock<antigravity_shared::proxy::config::ExperimentalConfig>>,
+    pub retry_policy: Arc<RwLock<antigravity_shared::proxy::config::RetryPolicyConfig>>, // 上游重试策略
+    pub response_store: Arc<crate::proxy::response_store::ResponseStore>,
 }
 
 /// Configuration for starting the Axum server
@@ -39,17 +44,21 @@ pub struct ServerStartConfig {
     pub port: u16,
     pub token_manager: Arc<TokenManager>,
     pub custom_mapping: std::collections::HashMap<String, String>,
+    pub router_rules: Vec<ModelRouteRule>,
     pub upstream_proxy: antigravity_shared::utils::http::UpstreamProxyConfig,
     pub security_config: crate::proxy::ProxySecurityConfig,
     pub zai_config: antigravity_shared::proxy::config::ZaiConfig,
+    pub providers: Vec<antigravity_shared::proxy::config::UpstreamProvider>,
     pub monitor: Arc<crate::proxy::monitor::ProxyMonitor>,
     pub experimental_config: antigravity_shared::proxy::config::ExperimentalConfig,
+    pub retry_policy: antigravity_shared::proxy::config::RetryPolicyConfig,
 }
 
 /// Axum 服务器实例
 pub struct AxumServer {
     shutdown_tx: Option<oneshot::Sender<()>>,
     custom_mapping: Arc<tokio::sync::RwLock<std::collections::HashMap<String, String>>>,
+    router_rules: Arc<RwLock<Vec<ModelRouteRule>>>,
     proxy_state: Arc<tokio::sync::RwLock<antigravity_shared::utils::http::UpstreamProxyConfig>>,
     security_state: Arc<RwLock<crate::proxy::ProxySecurityConfig>>,
     zai_state: Arc<RwLock<antigravity_shared::proxy::config::ZaiConfig>>,
@@ -60,6 +69,7 @@ impl AxumServer {
     /// Use this with headless server that manages its own listener.
     pub fn new(
         custom_mapping: std::collections::HashMap<String, String>,
+        router_rules: Vec<ModelRouteRule>,
         upstream_proxy: antigravity_shared::utils::http::UpstreamProxyConfig,
         security_config: crate::proxy::ProxySecurityConfig,
         zai_config: antigravity_shared::proxy::config::ZaiConfig,
@@ -67,6 +77,7 @@ impl AxumServer {
         Self {
             shutdown_tx: None,
             custom_mapping: Arc::new(tokio::sync::RwLock::new(custom_mapping)),
+            router_rules: Arc::new(RwLock::new(router_rules)),
             proxy_state: Arc::new(tokio::sync::RwLock::new(upstream_proxy)),
             security_state: Arc::new(RwLock::new(security_config)),
             zai_state: Arc::new(RwLock::new(zai_config)),
@@ -78,7 +89,11 @@ impl AxumServer {
             let mut m = self.custom_mapping.write().await;
             *m = config.custom_mapping.clone();
         }
-        tracing::debug!("模型映射 (Custom) 已全量热更新");
+        {
+            let mut rules = self.router_rules.write().await;
+            *rules = config.router_rules.clone();
+        }
+        tracing::debug!("模型映射 (Custom) 与路由规则已全量热更新");
     }
 
     /// 更新代理配置
@@ -108,16 +123,22 @@ impl AxumServer {
         config: ServerStartConfig,
     ) -> Result<(Self, tokio::task::JoinHandle<()>), String> {
         let custom_mapping_state = Arc::new(tokio::sync::RwLock::new(config.custom_mapping));
+        let router_rules_state = Arc::new(RwLock::new(config.router_rules));
         let proxy_state = Arc::new(tokio::sync::RwLock::new(config.upstream_proxy.clone()));
         let security_state = Arc::new(RwLock::new(config.security_config));
         let zai_state = Arc::new(RwLock::new(config.zai_config));
+        let providers_state = Arc::new(RwLock::new(config.providers));
         let provider_rr = Arc::new(AtomicUsize::new(0));
         let zai_vision_mcp_state = Arc::new(crate::proxy::zai_vision_mcp::ZaiVisionMcpState::new());
         let experimental_state = Arc::new(RwLock::new(config.experimental_config));
+        let retry_policy_state = Arc::new(RwLock::new(config.retry_policy));
+
+        crate::proxy::prometheus::init_metrics();
 
         let state = AppState {
             token_manager: config.token_manager.clone(),
             custom_mapping: custom_mapping_state.clone(),
+            router_rules: router_rules_state.clone(),
             request_timeout: 300, // 5分钟超时
             thought_signature_map: Arc::new(tokio::sync::Mutex::new(
                 std::collections::HashMap::new(),
@@ -127,89 +148,26 @@ impl AxumServer {
                 config.upstream_proxy.clone(),
             ))),
             zai: zai_state.clone(),
+            providers: providers_state,
             provider_rr: provider_rr.clone(),
             zai_vision_mcp: zai_vision_mcp_state,
             monitor: config.monitor.clone(),
             experimental: experimental_state,
+            retry_policy: retry_policy_state,
+            response_store: Arc::new(crate::proxy::response_store::ResponseStore::default()),
         };
+        crate::proxy::batches::spawn_worker(state.clone(), security_state.clone());
 
-        // 构建路由 - 使用新架构的 handlers！
-        use crate::proxy::handlers;
-        // 构建路由
-        let app = Router::new()
-            // OpenAI Protocol
-            .route("/v1/models", get(handlers::openai::handle_list_models))
-            .route(
-                "/v1/chat/completions",
-                post(handlers::openai::handle_chat_completions),
-            )
-            .route(
-                "/v1/completions",
-                post(handlers::openai::handle_completions),
-            )
-            .route("/v1/responses", post(handlers::openai::handle_completions)) // 兼容 Codex CLI
-            .route(
-                "/v1/images/generations",
-                post(handlers::openai::handle_images_generations),
-            ) // 图像生成 API
-            .route(
-                "/v1/images/edits",
-                post(handlers::openai::handle_images_edits),
-            ) // 图像编辑 API
-            .route(
-                "/v1/audio/transcriptions",
-                post(handlers::audio::handle_audio_transcription),
-            ) // 音频转录 API (PR #311)
-            // Claude Protocol
-            .route("/v1/messages", post(handlers::claude::handle_messages))
-            .route(
-                "/v1/messages/count_tokens",
-                post(handlers::claude::handle_count_tokens),
-            )
-            .route(
-                "/v1/models/claude",
-                get(handlers::claude::handle_list_models),
-            )
-            // z.ai MCP (optional reverse-proxy)
-            .route(
-                "/mcp/web_search_prime/mcp",
-                any(handlers::mcp::handle_web_search_prime),
-            )
-            .route("/mcp/web_reader/mcp", any(handlers::mcp::handle_web_reader))
-            .route(
-                "/mcp/zai-mcp-server/mcp",
-                any(handlers::mcp::handle_zai_mcp_server),
-            )
-            // Gemini Protocol (Native)
-            .route("/v1beta/models", get(handlers::gemini::handle_list_models))
-            // Handle both GET (get info) and POST (generateContent with colon) at the same route
-            .route(
-                "/v1beta/models/:model",
-                get(handlers::gemini::handle_get_model).post(handlers::gemini::handle_generate),
-            )
-            .route(
-                "/v1beta/models/:model/countTokens",
-                post(handlers::gemini::handle_count_tokens),
-            ) // Specific route priority
-            .route(
-                "/v1/models/detect",
-                post(handlers::common::handle_detect_model),
-            )
-            .route("/v1/api/event_logging/batch", post(silent_ok_handler))
-            .route("/v1/api/event_logging", post(silent_ok_handler))
+        // 反代路由与独立部署时的健康检查 (/healthz 同样经过鉴权，AllExceptHealth 模式放行)
+        let health = Router::new()
             .route("/healthz", get(health_check_handler))
-            .layer(DefaultBodyLimit::max(100 * 1024 * 1024))
-            .layer(axum::middleware::from_fn_with_state(
-                state.clone(),
-                crate::proxy::middleware::monitor::monitor_middleware,
-            ))
-            .layer(TraceLayer::new_for_http())
             .layer(axum::middleware::from_fn_with_state(
                 security_state.clone(),
                 crate::proxy::middleware::auth_middleware,
-            ))
-            .layer(crate::proxy::middleware::cors_layer())
-            .with_state(state);
+            ));
+        let app = proxy_routes(state, security_state.clone())
+            .merge(health)
+            .layer(crate::proxy::middleware::cors_layer());
 
         // 绑定地址
         let addr = format!("{}:{}", config.host, config.port);
@@ -225,6 +183,7 @@ impl AxumServer {
         let server_instance = Self {
             shutdown_tx: Some(shutdown_tx),
             custom_mapping: custom_mapping_state.clone(),
+            router_rules: router_rules_state,
             proxy_state,
             security_state,
             zai_state,
@@ -280,14 +239,18 @@ impl AxumServer {
 
 /// Build proxy router without starting a server.
 /// This allows integrating proxy routes into an existing Axum application.
+#[allow(clippy::too_many_arguments)]
 pub fn build_proxy_router(
     token_manager: Arc<TokenManager>,
     custom_mapping: std::collections::HashMap<String, String>,
+    router_rules: Vec<ModelRouteRule>,
     upstream_proxy: antigravity_shared::utils::http::UpstreamProxyConfig,
     security_config: crate::proxy::ProxySecurityConfig,
     zai_config: antigravity_shared::proxy::config::ZaiConfig,
+    providers: Vec<antigravity_shared::proxy::config::UpstreamProvider>,
     monitor: Arc<crate::proxy::monitor::ProxyMonitor>,
     experimental_config: antigravity_shared::proxy::config::ExperimentalConfig,
+    retry_policy: antigravity_shared::proxy::config::RetryPolicyConfig,
 ) -> Router<()> {
     let custom_mapping_state = Arc::new(tokio::sync::RwLock::new(custom_mapping));
     let proxy_state = Arc::new(tokio::sync::RwLock::new(upstream_proxy.clone()));
@@ -297,9 +260,12 @@ pub fn build_proxy_router(
     let zai_vision_mcp_state = Arc::new(crate::proxy::zai_vision_mcp::ZaiVisionMcpState::new());
     let experimental_state = Arc::new(RwLock::new(experimental_config));
 
+    crate::proxy::prometheus::init_metrics();
+
     let state = AppState {
         token_manager,
         custom_mapping: custom_mapping_state,
+        router_rules: Arc::new(RwLock::new(router_rules)),
         request_timeout: 300,
         thought_signature_map: Arc::new(tokio::sync::Mutex::new(std::collections::HashMap::new())),
         upstream_proxy: proxy_state,
@@ -307,107 +273,45 @@ pub fn build_proxy_router(
             upstream_proxy,
         ))),
         zai: zai_state,
+        providers: Arc::new(RwLock::new(providers)),
         provider_rr,
         zai_vision_mcp: zai_vision_mcp_state,
         monitor,
         experimental: experimental_state,
+        retry_policy: Arc::new(RwLock::new(retry_policy)),
+        response_store: Arc::new(crate::proxy::response_store::ResponseStore::default()),
     };
+    crate::proxy::batches::spawn_worker(state.clone(), security_state.clone());
 
-    use crate::proxy::handlers;
-
-    Router::new()
-        // OpenAI Protocol
-        .route("/v1/models", get(handlers::openai::handle_list_models))
-        .route(
-            "/v1/chat/completions",
-            post(handlers::openai::handle_chat_completions),
-        )
-        .route(
-            "/v1/completions",
-            post(handlers::openai::handle_completions),
-        )
-        .route("/v1/responses", post(handlers::openai::handle_completions))
-        .route(
-            "/v1/images/generations",
-            post(handlers::openai::handle_images_generations),
-        )
-        .route(
-            "/v1/images/edits",
-            post(handlers::openai::handle_images_edits),
-        )
-        .route(
-            "/v1/audio/transcriptions",
-            post(handlers::audio::handle_audio_transcription),
-        )
-        // Claude Protocol
-        .route("/v1/messages", post(handlers::claude::handle_messages))
-        .route(
-            "/v1/messages/count_tokens",
-            post(handlers::claude::handle_count_tokens),
-        )
-        .route(
-            "/v1/models/claude",
-            get(handlers::claude::handle_list_models),
-        )
-        // z.ai MCP
-        .route(
-            "/mcp/web_search_prime/mcp",
-            any(handlers::mcp::handle_web_search_prime),
-        )
-        .route("/mcp/web_reader/mcp", any(handlers::mcp::handle_web_reader))
-        .route(
-            "/mcp/zai-mcp-server/mcp",
-            any(handlers::mcp::handle_zai_mcp_server),
-        )
-        // Gemini Protocol
-        .route("/v1beta/models", get(handlers::gemini::handle_list_models))
-        .route(
-            "/v1beta/models/:model",
-            get(handlers::gemini::handle_get_model).post(handlers::gemini::handle_generate),
-        )
-        .route(
-            "/v1beta/models/:model/countTokens",
-            post(handlers::gemini::handle_count_tokens),
-        )
-        // Utility
-        .route(
-            "/v1/models/detect",
-            post(handlers::common::handle_detect_model),
-        )
-        .route("/v1/api/event_logging/batch", post(silent_ok_handler))
-        .route("/v1/api/event_logging", post(silent_ok_handler))
-        .layer(DefaultBodyLimit::max(100 * 1024 * 1024))
-        .layer(axum::middleware::from_fn_with_state(
-            state.clone(),
-            crate::proxy::middleware::monitor::monitor_middleware,
-        ))
-        .layer(TraceLayer::new_for_http())
-        .layer(axum::middleware::from_fn_with_state(
-            security_state,
-            crate::proxy::middleware::auth_middleware,
-        ))
-        .with_state(state)
+    proxy_routes(state, security_state)
 }
 
 /// Build proxy router with shared state references for hot-reload support.
 /// Unlike `build_proxy_router`, this version accepts pre-created Arc references
 /// so that external code can update the mapping at runtime.
+#[allow(clippy::too_many_arguments)]
 pub fn build_proxy_router_with_shared_state(
     token_manager: Arc<TokenManager>,
     custom_mapping: Arc<tokio::sync::RwLock<std::collections::HashMap<String, String>>>,
+    router_rules: Arc<RwLock<Vec<ModelRouteRule>>>,
     upstream_proxy: antigravity_shared::utils::http::UpstreamProxyConfig,
     security_config: Arc<RwLock<crate::proxy::ProxySecurityConfig>>,
     zai_config: Arc<RwLock<antigravity_shared::proxy::config::ZaiConfig>>,
+    providers: Arc<RwLock<Vec<antigravity_shared::proxy::config::UpstreamProvider>>>,
     monitor: Arc<crate::proxy::monitor::ProxyMonitor>,
     experimental_config: Arc<RwLock<antigravity_shared::proxy::config::ExperimentalConfig>>,
+    retry_policy: Arc<RwLock<antigravity_shared::proxy::config::RetryPolicyConfig>>,
 ) -> Router<()> {
     let proxy_state = Arc::new(tokio::sync::RwLock::new(upstream_proxy.clone()));
     let provider_rr = Arc::new(AtomicUsize::new(0));
     let zai_vision_mcp_state = Arc::new(crate::proxy::zai_vision_mcp::ZaiVisionMcpState::new());
 
+    crate::proxy::prometheus::init_metrics();
+
     let state = AppState {
         token_manager,
         custom_mapping: custom_mapping.clone(),
+        router_rules,
         request_timeout: 300,
         thought_signature_map: Arc::new(tokio::sync::Mutex::new(std::collections::HashMap::new())),
         upstream_proxy: proxy_state,
@@ -415,12 +319,24 @@ pub fn build_proxy_router_with_shared_state(
             upstream_proxy,
         ))),
         zai: zai_config.clone(),
+        providers,
         provider_rr,
         zai_vision_mcp: zai_vision_mcp_state,
         monitor,
         experimental: experimental_config.clone(),
+        retry_policy,
+        response_store: Arc::new(crate::proxy::response_store::ResponseStore::default()),
     };
+    crate::proxy::batches::spawn_worker(state.clone(), security_config.clone());
 
+    proxy_routes(state, security_config)
+}
+
+/// 反代路由表与中间件 (`build_proxy_router` 系列共用)
+pub(crate) fn proxy_routes(
+    state: AppState,
+    security_config: Arc<RwLock<crate::proxy::ProxySecurityConfig>>,
+) -> Router<()> {
     use crate::proxy::handlers;
 
     Router::new()
@@ -434,7 +350,12 @@ pub fn build_proxy_router_with_shared_state(
             "/v1/completions",
             post(handlers::openai::handle_completions),
         )
-        .route("/v1/responses", post(handlers::openai::handle_completions))
+        .route("/v1/responses", post(handlers::responses::handle_responses))
+        .route(
+            "/v1/responses/:response_id",
+            get(handlers::responses::handle_get_response)
+                .delete(handlers::responses::handle_delete_response),
+        )
         .route(
             "/v1/images/generations",
             post(handlers::openai::handle_images_generations),
@@ -447,12 +368,45 @@ pub fn build_proxy_router_with_shared_state(
             "/v1/audio/transcriptions",
             post(handlers::audio::handle_audio_transcription),
         )
+        .route(
+            "/v1/embeddings",
+            post(handlers::embeddings::handle_embeddings),
+        )
+        .route(
+            "/v1/files",
+            get(handlers::files::handle_list_files).post(handlers::files::handle_upload_file),
+        )
+        .route(
+            "/v1/files/:file_id",
+            get(handlers::files::handle_get_file).delete(handlers::files::handle_delete_file),
+        )
+        .route(
+            "/v1/files/:file_id/content",
+            get(handlers::files::handle_file_content),
+        )
         // Claude Protocol
         .route("/v1/messages", post(handlers::claude::handle_messages))
         .route(
             "/v1/messages/count_tokens",
             post(handlers::claude::handle_count_tokens),
         )
+        .route(
+            "/v1/messages/batches",
+            get(handlers::batches::handle_list_batches)
+                .post(handlers::batches::handle_create_batch),
+        )
+        .route(
+            "/v1/messages/batches/:batch_id",
+            get(handlers::batches::handle_get_batch).delete(handlers::batches::handle_delete_batch),
+        )
+        .route(
+            "/v1/messages/batches/:batch_id/cancel",
+            post(handlers::batches::handle_cancel_batch),
+        )
+        .route(
+            "/v1/messages/batches/:batch_id/results",
+            get(handlers::batches::handle_batch_results),
+        )
         .route(
             "/v1/models/claude",
             get(handlers::claude::handle_list_models),
@@ -484,11 +438,15 @@ pub fn build_proxy_router_with_shared_state(
         )
         .route("/v1/api/event_logging/batch", post(silent_ok_handler))
         .route("/v1/api/event_logging", post(silent_ok_handler))
+        .route("/metrics", get(metrics_handler))
         .layer(DefaultBodyLimit::max(100 * 1024 * 1024))
         .layer(axum::middleware::from_fn_with_state(
             state.clone(),
             crate::proxy::middleware::monitor::monitor_middleware,
         ))
+        .layer(axum::middleware::from_fn(
+            crate::proxy::middleware::metrics_middleware,
+        ))
         .layer(TraceLayer::new_for_http())
         .layer(axum::middleware::from_fn_with_state(
             security_config,
@@ -507,6 +465,43 @@ async fn health_check_handler() -> Response {
     .into_response()
 }
 
+/// Prometheus 指标处理器 (受反代鉴权保护)
+///
+/// 抓取时刷新账号可用性 gauge，保证与调度器的实时状态一致
+async fn metrics_handler(State(state): State<AppState>) -> Response {
+    let accounts = state.token_manager.get_account_availability().await;
+    let available = accounts.iter().filter(|a| a.available).count();
+    crate::proxy::prometheus::update_account_gauges(accounts.len(), available);
+    for account in &accounts {
+        crate::proxy::prometheus::update_account_availability_gauge(
+            &account.email,
+            account.available,
+        );
+    }
+    let model_lockouts: Vec<(String, String, u64)> = accounts
+        .iter()
+        .flat_map(|account| {
+            account.model_lockouts.iter().map(|lockout| {
+                (
+                    account.email.clone(),
+                    lockout.model.clone(),
+                    lockout.reset_seconds,
+                )
+            })
+        })
+        .collect();
+    crate::proxy::prometheus::update_model_lockout_gauges(&model_lockouts);
+
+    (
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        crate::proxy::prometheus::render_metrics(),
+    )
+        .into_response()
+}
+
 /// 静默成功处理器 (用于拦截遥测日志等)
 async fn silent_ok_handler() -> Response {
     StatusCode::OK.into_response()
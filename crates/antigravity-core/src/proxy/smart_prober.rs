@@ -9,9 +9,11 @@
 //! - **Immediate Hedge**: Parallel requests when near limit
 
 use crate::proxy::adaptive_limit::{AdaptiveLimitManager, ProbeStrategy};
+use crate::proxy::prometheus;
+use antigravity_shared::proxy::config::HedgingConfig;
 use std::future::Future;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
 
 /// Configuration for smart probing
@@ -38,6 +40,17 @@ impl Default for SmartProberConfig {
     }
 }
 
+impl From<&HedgingConfig> for SmartProberConfig {
+    fn from(config: &HedgingConfig) -> Self {
+        Self {
+            p95_latency: Duration::from_millis(config.p95_latency_ms),
+            jitter_percent: config.jitter_percent,
+            enable_cheap_probes: config.enabled && config.cheap_probes,
+            enable_hedging: config.enabled,
+        }
+    }
+}
+
 /// Result of a hedged request execution
 #[derive(Debug)]
 pub enum HedgeResult<T> {
@@ -61,9 +74,28 @@ impl<T> HedgeResult<T> {
     }
 }
 
+/// Outcome of a hedged race
+///
+/// `discarded_error` is the failure of the request whose result was not returned
+/// (e.g. the secondary's 429 when the primary still succeeded), so the caller
+/// can attribute it to the right account. Aborted requests leave it `None`.
+pub struct HedgeOutcome<T, E> {
+    pub result: Result<HedgeResult<T>, E>,
+    pub discarded_error: Option<E>,
+}
+
+impl<T, E> HedgeOutcome<T, E> {
+    fn single(result: Result<HedgeResult<T>, E>) -> Self {
+        Self {
+            result,
+            discarded_error: None,
+        }
+    }
+}
+
 /// Smart prober for adaptive rate limiting
 pub struct SmartProber {
-    config: SmartProberConfig,
+    config: RwLock<SmartProberConfig>,
     limits: Arc<AdaptiveLimitManager>,
 
     // Metrics
@@ -76,7 +108,7 @@ pub struct SmartProber {
 impl SmartProber {
     pub fn new(config: SmartProberConfig, limits: Arc<AdaptiveLimitManager>) -> Self {
         Self {
-            config,
+            config: RwLock::new(config),
             limits,
             probes_fired: AtomicU64::new(0),
             hedges_fired: AtomicU64::new(0),
@@ -85,6 +117,22 @@ impl SmartProber {
         }
     }
 
+    /// Current configuration snapshot
+    pub fn config(&self) -> SmartProberConfig {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Replace configuration (hot reload)
+    pub fn update_config(&self, config: SmartProberConfig) {
+        *self.config.write().unwrap() = config;
+    }
+
+    /// Whether any probing strategy is enabled
+    pub fn is_enabled(&self) -> bool {
+        let config = self.config.read().unwrap();
+        config.enable_hedging || config.enable_cheap_probes
+    }
+
     /// Get probe strategy for an account
     pub fn strategy_for(&self, account_id: &str) -> ProbeStrategy {
         self.limits.probe_strategy(account_id)
@@ -107,8 +155,9 @@ impl SmartProber {
 
     /// Calculate delay with jitter for hedging
     fn calculate_hedge_delay(&self) -> Duration {
-        let base_ms = self.config.p95_latency.as_millis() as f64;
-        let jitter_range = base_ms * self.config.jitter_percent;
+        let config = self.config();
+        let base_ms = config.p95_latency.as_millis() as f64;
+        let jitter_range = base_ms * config.jitter_percent;
         let jitter = (rand::random::<f64>() - 0.5) * 2.0 * jitter_range;
         Duration::from_millis((base_ms + jitter).max(0.0) as u64)
     }
@@ -127,7 +176,7 @@ impl SmartProber {
         PFut: Future<Output = Result<(), E>> + Send + 'static,
         E: Send + 'static,
     {
-        if !self.config.enable_cheap_probes {
+        if !self.config.read().unwrap().enable_cheap_probes {
             return primary_fn().await;
         }
 
@@ -135,6 +184,7 @@ impl SmartProber {
 
         if result.is_ok() {
             self.probes_fired.fetch_add(1, Ordering::Relaxed);
+            prometheus::record_adaptive_probe("cheap_probe");
             let limits = self.limits.clone();
             let account = account_id.to_string();
 
@@ -155,142 +205,167 @@ impl SmartProber {
     }
 
     /// Execute with delayed hedging (secondary fires after P95)
+    ///
+    /// The first *successful* result wins and the other request is aborted.
+    /// If both fail the primary error is returned and the secondary error is
+    /// kept in `discarded_error`. AIMD feedback for the
+    /// winning account is left to the caller (`TokenManager::mark_account_success`).
     pub async fn execute_with_delayed_hedge<F, Fut, T, E>(
         &self,
         primary_account: &str,
         secondary_account: &str,
         primary_fn: F,
         secondary_fn: F,
-    ) -> Result<HedgeResult<T>, E>
+    ) -> HedgeOutcome<T, E>
     where
         F: FnOnce() -> Fut + Send + 'static,
         Fut: Future<Output = Result<T, E>> + Send + 'static,
         T: Send + 'static,
         E: Send + 'static,
     {
-        if !self.config.enable_hedging {
-            return primary_fn().await.map(HedgeResult::NoHedge);
+        if !self.config.read().unwrap().enable_hedging {
+            return HedgeOutcome::single(primary_fn().await.map(HedgeResult::NoHedge));
         }
 
-        self.hedges_fired.fetch_add(1, Ordering::Relaxed);
-
-        let hedge_delay = self.calculate_hedge_delay();
-        let limits = self.limits.clone();
-        let primary_id = primary_account.to_string();
-        let secondary_id = secondary_account.to_string();
-
-        let primary_handle = tokio::spawn(async move { primary_fn().await });
-
-        let secondary_handle = tokio::spawn(async move {
-            tokio::time::sleep(hedge_delay).await;
-            secondary_fn().await
-        });
-
-        tokio::pin!(primary_handle);
-        tokio::pin!(secondary_handle);
-
-        tokio::select! {
-            biased;
-
-            primary_result = &mut primary_handle => {
-                secondary_handle.abort();
-
-                match primary_result {
-                    Ok(Ok(value)) => {
-                        self.primary_wins.fetch_add(1, Ordering::Relaxed);
-                        limits.record_success(&primary_id);
-                        Ok(HedgeResult::PrimaryWon(value))
-                    }
-                    Ok(Err(e)) => {
-                        limits.record_429(&primary_id);
-                        Err(e)
-                    }
-                    Err(e) => panic!("Primary task panicked: {e}"),
-                }
-            }
-            secondary_result = &mut secondary_handle => {
-                match secondary_result {
-                    Ok(Ok(value)) => {
-                        self.hedge_wins.fetch_add(1, Ordering::Relaxed);
-                        limits.record_success(&secondary_id);
-                        Ok(HedgeResult::HedgeWon(value))
-                    }
-                    Ok(Err(e)) => {
-                        limits.record_429(&secondary_id);
-                        Err(e)
-                    }
-                    Err(e) => panic!("Secondary task panicked: {e}"),
-                }
-            }
-        }
+        prometheus::record_adaptive_probe("delayed_hedge");
+        let delay = self.calculate_hedge_delay();
+        self.race(
+            primary_account,
+            secondary_account,
+            primary_fn,
+            secondary_fn,
+            delay,
+        )
+        .await
     }
 
     /// Execute with immediate hedging (both fire immediately)
+    ///
+    /// Same winner semantics as [`Self::execute_with_delayed_hedge`].
     pub async fn execute_with_immediate_hedge<F, Fut, T, E>(
         &self,
         primary_account: &str,
         secondary_account: &str,
         primary_fn: F,
         secondary_fn: F,
-    ) -> Result<HedgeResult<T>, E>
+    ) -> HedgeOutcome<T, E>
     where
         F: FnOnce() -> Fut + Send + 'static,
         Fut: Future<Output = Result<T, E>> + Send + 'static,
         T: Send + 'static,
         E: Send + 'static,
     {
-        if !self.config.enable_hedging {
-            return primary_fn().await.map(HedgeResult::NoHedge);
+        if !self.config.read().unwrap().enable_hedging {
+            return HedgeOutcome::single(primary_fn().await.map(HedgeResult::NoHedge));
         }
 
-        self.hedges_fired.fetch_add(1, Ordering::Relaxed);
-
-        let limits = self.limits.clone();
-        let primary_id = primary_account.to_string();
-        let secondary_id = secondary_account.to_string();
+        prometheus::record_adaptive_probe("immediate_hedge");
+        self.race(
+            primary_account,
+            secondary_account,
+            primary_fn,
+            secondary_fn,
+            Duration::ZERO,
+        )
+        .await
+    }
 
-        let primary_handle = tokio::spawn(async move { primary_fn().await });
-        let secondary_handle = tokio::spawn(async move { secondary_fn().await });
+    /// Run primary and (delayed) secondary concurrently, first success wins
+    async fn race<F, Fut, T, E>(
+        &self,
+        primary_account: &str,
+        secondary_account: &str,
+        primary_fn: F,
+        secondary_fn: F,
+        delay: Duration,
+    ) -> HedgeOutcome<T, E>
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<T, E>> + Send + 'static,
+        T: Send + 'static,
+        E: Send + 'static,
+    {
+        self.hedges_fired.fetch_add(1, Ordering::Relaxed);
 
-        tokio::pin!(primary_handle);
-        tokio::pin!(secondary_handle);
+        let mut primary_handle = tokio::spawn(async move { primary_fn().await });
+        let mut secondary_handle = tokio::spawn(async move {
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+            secondary_fn().await
+        });
 
         tokio::select! {
             biased;
 
             primary_result = &mut primary_handle => {
-                secondary_handle.abort();
-
-                match primary_result {
-                    Ok(Ok(value)) => {
-                        self.primary_wins.fetch_add(1, Ordering::Relaxed);
-                        limits.record_success(&primary_id);
-                        Ok(HedgeResult::PrimaryWon(value))
+                match join_result(primary_result, "Primary") {
+                    Ok(value) => {
+                        secondary_handle.abort();
+                        self.record_primary_win(primary_account);
+                        HedgeOutcome::single(Ok(HedgeResult::PrimaryWon(value)))
                     }
-                    Ok(Err(e)) => {
-                        limits.record_429(&primary_id);
-                        Err(e)
+                    Err(primary_err) => {
+                        tracing::debug!(
+                            "Hedge: primary {} failed, waiting for secondary {}",
+                            primary_account,
+                            secondary_account
+                        );
+                        match join_result(secondary_handle.await, "Secondary") {
+                            Ok(value) => {
+                                self.record_hedge_win(secondary_account);
+                                HedgeOutcome {
+                                    result: Ok(HedgeResult::HedgeWon(value)),
+                                    discarded_error: Some(primary_err),
+                                }
+                            }
+                            Err(secondary_err) => HedgeOutcome {
+                                result: Err(primary_err),
+                                discarded_error: Some(secondary_err),
+                            },
+                        }
                     }
-                    Err(e) => panic!("Primary task panicked: {e}"),
                 }
             }
             secondary_result = &mut secondary_handle => {
-                match secondary_result {
-                    Ok(Ok(value)) => {
-                        self.hedge_wins.fetch_add(1, Ordering::Relaxed);
-                        limits.record_success(&secondary_id);
-                        Ok(HedgeResult::HedgeWon(value))
+                match join_result(secondary_result, "Secondary") {
+                    Ok(value) => {
+                        primary_handle.abort();
+                        self.record_hedge_win(secondary_account);
+                        HedgeOutcome::single(Ok(HedgeResult::HedgeWon(value)))
                     }
-                    Ok(Err(e)) => {
-                        limits.record_429(&secondary_id);
-                        Err(e)
+                    Err(secondary_err) => {
+                        tracing::debug!(
+                            "Hedge: secondary {} failed, waiting for primary {}",
+                            secondary_account,
+                            primary_account
+                        );
+                        let result = join_result(primary_handle.await, "Primary").map(|value| {
+                            self.record_primary_win(primary_account);
+                            HedgeResult::PrimaryWon(value)
+                        });
+                        HedgeOutcome {
+                            result,
+                            discarded_error: Some(secondary_err),
+                        }
                     }
-                    Err(e) => panic!("Secondary task panicked: {e}"),
                 }
             }
         }
     }
 
+    fn record_primary_win(&self, account: &str) {
+        self.primary_wins.fetch_add(1, Ordering::Relaxed);
+        prometheus::record_primary_win();
+        tracing::debug!("Hedge: primary {} won", account);
+    }
+
+    fn record_hedge_win(&self, account: &str) {
+        self.hedge_wins.fetch_add(1, Ordering::Relaxed);
+        prometheus::record_hedge_win();
+        tracing::info!("⚡ Hedge: secondary {} won", account);
+    }
+
     // Metrics getters
     pub fn probes_fired(&self) -> u64 {
         self.probes_fired.load(Ordering::Relaxed)
@@ -318,6 +393,16 @@ impl SmartProber {
     }
 }
 
+fn join_result<T, E>(
+    result: Result<Result<T, E>, tokio::task::JoinError>,
+    role: &str,
+) -> Result<T, E> {
+    match result {
+        Ok(inner) => inner,
+        Err(e) => panic!("{role} task panicked: {e}"),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -350,6 +435,92 @@ mod tests {
         assert!(config.enable_hedging);
     }
 
+    fn attempt(
+        delay_ms: u64,
+        result: Result<&'static str, &'static str>,
+    ) -> impl FnOnce() -> std::pin::Pin<
+        Box<dyn Future<Output = Result<&'static str, &'static str>> + Send>,
+    > + Send
+           + 'static {
+        move || {
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+                result
+            })
+        }
+    }
+
+    fn hedging_prober() -> SmartProber {
+        let config = SmartProberConfig {
+            p95_latency: Duration::from_millis(10),
+            jitter_percent: 0.0,
+            ..Default::default()
+        };
+        SmartProber::new(config, Arc::new(AdaptiveLimitManager::default()))
+    }
+
+    #[tokio::test]
+    async fn test_hedge_wins_when_primary_fails() {
+        let prober = hedging_prober();
+        let result = prober
+            .execute_with_immediate_hedge("a", "b", attempt(0, Err("429")), attempt(20, Ok("b")))
+            .await;
+        assert_eq!(result.discarded_error, Some("429"));
+        assert!(matches!(result.result.unwrap(), HedgeResult::HedgeWon("b")));
+        assert_eq!(prober.hedge_wins(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_primary_wins_before_delayed_hedge() {
+        let prober = hedging_prober();
+        let result = prober
+            .execute_with_delayed_hedge("a", "b", attempt(0, Ok("a")), attempt(0, Ok("b")))
+            .await
+            .result
+            .unwrap();
+        assert!(matches!(result, HedgeResult::PrimaryWon("a")));
+        assert_eq!(prober.primary_wins(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_both_fail_returns_primary_error() {
+        let prober = hedging_prober();
+        let result = prober
+            .execute_with_immediate_hedge("a", "b", attempt(10, Err("a")), attempt(0, Err("b")))
+            .await;
+        assert_eq!(result.result.unwrap_err(), "a");
+        assert_eq!(result.discarded_error, Some("b"));
+    }
+
+    #[tokio::test]
+    async fn test_secondary_error_kept_when_primary_wins() {
+        let prober = hedging_prober();
+        let result = prober
+            .execute_with_immediate_hedge("a", "b", attempt(20, Ok("a")), attempt(0, Err("429")))
+            .await;
+        assert!(matches!(
+            result.result.unwrap(),
+            HedgeResult::PrimaryWon("a")
+        ));
+        assert_eq!(result.discarded_error, Some("429"));
+    }
+
+    #[tokio::test]
+    async fn test_disabled_hedging_runs_primary_only() {
+        let prober = SmartProber::new(
+            SmartProberConfig::from(&HedgingConfig::default()),
+            Arc::new(AdaptiveLimitManager::default()),
+        );
+        assert!(!prober.is_enabled());
+        let result = prober
+            .execute_with_immediate_hedge("a", "b", attempt(0, Ok("a")), attempt(0, Ok("b")))
+            .await
+            .result
+            .unwrap();
+        assert!(!result.was_hedged());
+        assert_eq!(prober.hedges_fired(), 0);
+    }
+
     #[tokio::test]
     async fn test_prober_metrics() {
         let limits = Arc::new(AdaptiveLimitManager::default());
@@ -90,6 +90,7 @@ mod tests {
                     tool_use_id: "call_1".to_string(),
                     content: json!("Sunny"),
                     is_error: None,
+                    cache_control: None,
                 }]),
             },
         ];
@@ -0,0 +1,107 @@
+// /v1/files 端到端测试
+// 经完整路由以 multipart 上传文件，再在 chat 请求中以 file_id 引用，
+// 验证发往上游的 inlineData 使用存储文件的内容与 MIME 类型。
+#[cfg(test)]
+mod tests {
+    use crate::proxy::server::proxy_routes;
+    use crate::proxy::tests::support;
+    use crate::proxy::{ProxyAuthMode, ProxySecurityConfig};
+    use axum::http::StatusCode;
+    use base64::Engine as _;
+    use serde_json::{json, Value};
+    use std::sync::{Arc, Mutex};
+    use tokio::sync::RwLock;
+
+    const API_KEY: &str = "sk-ag-files";
+    const BOUNDARY: &str = "ag-files-boundary";
+
+    fn multipart_body(filename: &str, content_type: &str, data: &[u8]) -> Vec<u8> {
+        let mut body = format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"purpose\"\r\n\r\nuser_data\r\n\
+             --{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{f}\"\r\n\
+             Content-Type: {c}\r\n\r\n",
+            b = BOUNDARY,
+            f = filename,
+            c = content_type
+        )
+        .into_bytes();
+        body.extend_from_slice(data);
+        body.extend_from_slice(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+        body
+    }
+
+    #[tokio::test]
+    async fn test_uploaded_file_referenced_in_chat() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let base_url = support::spawn_scripted_upstream(Vec::new(), calls.clone()).await;
+        let state = support::app_state(base_url, &["a"]).await;
+        let security = Arc::new(RwLock::new(ProxySecurityConfig {
+            auth_mode: ProxyAuthMode::Strict,
+            api_key: API_KEY.to_string(),
+            allow_lan_access: false,
+            client_keys: Vec::new(),
+        }));
+        let base = support::spawn_server(proxy_routes(state, security)).await;
+        let client = reqwest::Client::new();
+
+        let content = b"%PDF-1.4\nfiles e2e test\n%%EOF";
+        let response = client
+            .post(format!("{}/v1/files", base))
+            .bearer_auth(API_KEY)
+            .header(
+                "content-type",
+                format!("multipart/form-data; boundary={}", BOUNDARY),
+            )
+            .body(multipart_body("report.pdf", "application/pdf", content))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let file: Value = response.json().await.unwrap();
+        let file_id = file["id"].as_str().unwrap().to_string();
+        assert_eq!(file["object"], "file");
+        assert_eq!(file["bytes"], content.len());
+
+        // 第二个 part 携带无 MIME 的 file_data，应以存储文件为准而非猜测类型
+        let response = client
+            .post(format!("{}/v1/chat/completions", base))
+            .bearer_auth(API_KEY)
+            .json(&json!({
+                "model": "gemini-2.5-flash",
+                "messages": [{"role": "user", "content": [
+                    {"type": "text", "text": "Summarize"},
+                    {"type": "file", "file": {"file_id": file_id}},
+                    {"type": "file", "file": {"file_id": file_id, "file_data": "data:;base64,AAAA"}}
+                ]}]
+            }))
+            .send()
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = response.text().await.unwrap();
+        assert_eq!(status, StatusCode::OK, "{}", body);
+
+        let calls = calls.lock().unwrap().clone();
+        assert_eq!(calls.len(), 1);
+        let parts = calls[0].1["request"]["contents"][0]["parts"]
+            .as_array()
+            .unwrap()
+            .clone();
+        let inline: Vec<&Value> = parts.iter().filter_map(|p| p.get("inlineData")).collect();
+        let expected = base64::engine::general_purpose::STANDARD.encode(content);
+        assert_eq!(inline.len(), 2, "{:?}", parts);
+        for data in inline {
+            assert_eq!(data["mimeType"], "application/pdf");
+            assert_eq!(data["data"], expected.as_str());
+        }
+
+        // 清理共享文件存储
+        let response = client
+            .delete(format!("{}/v1/files/{}", base, file_id))
+            .bearer_auth(API_KEY)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,216 @@
+// Gemini 原生接口 golden 测试
+// fixtures/gemini 下为官方 SDK 录制的请求与上游 v1internal 流，
+// 期望输出与公开 Generative Language API 字节一致。
+#[cfg(test)]
+mod tests {
+    use crate::proxy::mappers::gemini::errors::google_error_response;
+    use crate::proxy::mappers::gemini::streaming::{GeminiStreamFormat, GeminiStreamTransformer};
+    use crate::proxy::middleware::auth::extract_api_key;
+    use crate::proxy::server::proxy_routes;
+    use crate::proxy::tests::support;
+    use crate::proxy::{ProxyAuthMode, ProxySecurityConfig};
+    use axum::extract::Request;
+    use axum::response::IntoResponse;
+    use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+    use serde_json::{json, Value};
+    use std::sync::{Arc, Mutex};
+    use tokio::sync::RwLock;
+
+    const SDK_REQUESTS: &str = include_str!("fixtures/gemini/sdk_requests.json");
+    const UPSTREAM_STREAM: &str = include_str!("fixtures/gemini/upstream_stream.sse");
+    const STREAM_ALT_SSE: &str = include_str!("fixtures/gemini/stream_alt_sse.golden");
+    const STREAM_JSON_ARRAY: &str = include_str!("fixtures/gemini/stream_json_array.golden");
+    const ERROR_UNAUTHENTICATED: &str =
+        include_str!("fixtures/gemini/error_unauthenticated.golden");
+
+    fn replay(format: GeminiStreamFormat, chunk_size: usize) -> String {
+        let mut transformer = GeminiStreamTransformer::new(format);
+        let mut out = Vec::new();
+        for chunk in UPSTREAM_STREAM.as_bytes().chunks(chunk_size) {
+            out.extend(transformer.push(chunk));
+        }
+        out.extend(transformer.finish());
+        String::from_utf8(out.concat()).unwrap()
+    }
+
+    #[test]
+    fn test_sdk_requests_replay() {
+        let requests: Vec<Value> = serde_json::from_str(SDK_REQUESTS).unwrap();
+        for request in &requests {
+            let name = request["name"].as_str().unwrap();
+            let expect = &request["expect"];
+
+            let mut headers = HeaderMap::new();
+            for (key, value) in request["headers"].as_object().unwrap() {
+                headers.insert(
+                    HeaderName::from_bytes(key.as_bytes()).unwrap(),
+                    HeaderValue::from_str(value.as_str().unwrap()).unwrap(),
+                );
+            }
+            let query = request["query"].as_str().filter(|q| !q.is_empty());
+            assert_eq!(
+                extract_api_key(&headers, query).as_deref(),
+                expect["api_key"].as_str(),
+                "{}",
+                name
+            );
+
+            let alt = query.and_then(|q| {
+                url::form_urlencoded::parse(q.as_bytes())
+                    .find(|(k, _)| k == "alt")
+                    .map(|(_, v)| v.into_owned())
+            });
+            let format = match GeminiStreamFormat::from_alt(alt.as_deref()) {
+                GeminiStreamFormat::Sse => "sse",
+                GeminiStreamFormat::JsonArray => "json_array",
+            };
+            assert_eq!(format, expect["stream_format"], "{}", name);
+
+            let path = request["path"].as_str().unwrap();
+            let (model, method) = path
+                .strip_prefix("/v1beta/models/")
+                .and_then(|s| s.rsplit_once(':'))
+                .unwrap();
+            assert_eq!(model, expect["model"], "{}", name);
+            assert_eq!(method, expect["method"], "{}", name);
+            assert!(request["body"]["contents"].is_array(), "{}", name);
+        }
+    }
+
+    /// 非流式 generateContent 的上游 (v1internal) 响应
+    fn upstream_generate_response() -> Value {
+        json!({
+            "response": {
+                "candidates": [{"content": {"role": "model", "parts": [{"text": "Rayleigh scattering."}]}, "finishReason": "STOP"}],
+                "modelVersion": "gemini-2.5-flash"
+            },
+            "traceId": "8a1c0b6f3e2d4c51"
+        })
+    }
+
+    /// mock 上游回放录制的 v1internal 流，记录每次调用的 (path, body)
+    async fn spawn_recorded_upstream(calls: Arc<Mutex<Vec<(String, Value)>>>) -> String {
+        let app = axum::Router::new().fallback(move |req: Request| {
+            let calls = calls.clone();
+            async move {
+                let path = req.uri().path().to_string();
+                let bytes = axum::body::to_bytes(req.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                let body: Value = serde_json::from_slice(&bytes).unwrap();
+                calls.lock().unwrap().push((path.clone(), body));
+                if path.ends_with(":streamGenerateContent") {
+                    (
+                        [(axum::http::header::CONTENT_TYPE, "text/event-stream")],
+                        UPSTREAM_STREAM,
+                    )
+                        .into_response()
+                } else {
+                    axum::Json(upstream_generate_response()).into_response()
+                }
+            }
+        });
+        support::spawn_upstream(app).await
+    }
+
+    #[tokio::test]
+    async fn test_sdk_requests_through_router() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let state = support::app_state(spawn_recorded_upstream(calls.clone()).await, &["a"]).await;
+        let security = Arc::new(RwLock::new(ProxySecurityConfig {
+            auth_mode: ProxyAuthMode::Strict,
+            api_key: "sk-ag-golden".to_string(),
+            allow_lan_access: false,
+            client_keys: Vec::new(),
+        }));
+        let base = support::spawn_server(proxy_routes(state, security)).await;
+
+        let requests: Vec<Value> = serde_json::from_str(SDK_REQUESTS).unwrap();
+        let client = reqwest::Client::new();
+        for request in &requests {
+            let name = request["name"].as_str().unwrap();
+            let expect = &request["expect"];
+
+            let mut url = format!("{}{}", base, request["path"].as_str().unwrap());
+            if let Some(query) = request["query"].as_str().filter(|q| !q.is_empty()) {
+                url = format!("{}?{}", url, query);
+            }
+            let mut builder = client.post(&url).body(request["body"].to_string());
+            for (key, value) in request["headers"].as_object().unwrap() {
+                builder = builder.header(key.as_str(), value.as_str().unwrap());
+            }
+            calls.lock().unwrap().clear();
+            let response = builder.send().await.unwrap();
+            let status = response.status();
+            let body = response.text().await.unwrap();
+
+            // 未携带凭证时由 auth 中间件返回 Google 风格 401
+            if expect["api_key"].is_null() {
+                assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", name);
+                assert_eq!(body, ERROR_UNAUTHENTICATED, "{}", name);
+                assert!(calls.lock().unwrap().is_empty(), "{}", name);
+                continue;
+            }
+            assert_eq!(status, StatusCode::OK, "{}: {}", name, body);
+
+            let calls = calls.lock().unwrap().clone();
+            assert_eq!(calls.len(), 1, "{}", name);
+            let (upstream_path, upstream_body) = &calls[0];
+            assert_eq!(
+                upstream_path,
+                &format!("/v1internal:{}", expect["method"].as_str().unwrap()),
+                "{}",
+                name
+            );
+            assert_eq!(upstream_body["model"], expect["model"], "{}", name);
+            assert_eq!(upstream_body["project"], "test-project", "{}", name);
+            assert_eq!(
+                upstream_body["request"]["contents"], request["body"]["contents"],
+                "{}",
+                name
+            );
+
+            match (expect["method"].as_str(), expect["stream_format"].as_str()) {
+                (Some("streamGenerateContent"), Some("sse")) => {
+                    assert_eq!(body, STREAM_ALT_SSE, "{}", name)
+                }
+                (Some("streamGenerateContent"), _) => {
+                    assert_eq!(body, STREAM_JSON_ARRAY, "{}", name)
+                }
+                _ => {
+                    let parsed: Value = serde_json::from_str(&body).unwrap();
+                    assert_eq!(parsed, upstream_generate_response()["response"], "{}", name);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_stream_golden_outputs() {
+        // 上游分块边界不影响输出
+        for chunk_size in [1, 7, 64, UPSTREAM_STREAM.len()] {
+            assert_eq!(replay(GeminiStreamFormat::Sse, chunk_size), STREAM_ALT_SSE);
+            assert_eq!(
+                replay(GeminiStreamFormat::JsonArray, chunk_size),
+                STREAM_JSON_ARRAY
+            );
+        }
+        // JSON 数组形式可被整体解析
+        let parsed: Vec<Value> = serde_json::from_str(STREAM_JSON_ARRAY).unwrap();
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[2]["candidates"][0]["finishReason"], "STOP");
+    }
+
+    #[tokio::test]
+    async fn test_error_golden_output() {
+        let response = google_error_response(
+            StatusCode::UNAUTHORIZED,
+            "API key not valid. Please pass a valid API key.",
+        );
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(std::str::from_utf8(&body).unwrap(), ERROR_UNAUTHENTICATED);
+    }
+}
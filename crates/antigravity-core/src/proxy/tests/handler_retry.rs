@@ -0,0 +1,131 @@
+// Handler 级重试测试
+// mock 上游先返回 429 (带 Retry-After) 再返回成功，验证 Claude / OpenAI / Responses
+// 处理器的重试循环会换账号并按 Retry-After 等待。
+#[cfg(test)]
+mod tests {
+    use crate::proxy::handlers::{claude, openai, responses};
+    use crate::proxy::server::AppState;
+    use crate::proxy::tests::support::{self, ScriptedFailure, UpstreamCall};
+    use axum::extract::{Json, State};
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::response::{IntoResponse, Response};
+    use serde_json::{json, Value};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    type Calls = Arc<Mutex<Vec<UpstreamCall>>>;
+
+    const RETRY_AFTER: Duration = Duration::from_millis(300);
+    const RATE_LIMITED: ScriptedFailure = (429, Some("0.3"), "Resource has been exhausted");
+
+    /// 两个账号；429 规则自身不等待，观察到的间隔只能来自 Retry-After
+    async fn scripted_state(script: Vec<ScriptedFailure>) -> (AppState, Calls) {
+        let calls: Calls = Arc::new(Mutex::new(Vec::new()));
+        let base_url = support::spawn_scripted_upstream(script, calls.clone()).await;
+        let state = support::app_state(base_url, &["a", "b"]).await;
+        {
+            let mut policy = state.retry_policy.write().await;
+            for rule in &mut policy.rules {
+                rule.base_delay_ms = 0;
+            }
+        }
+        (state, calls)
+    }
+
+    /// 第二次调用换了账号，且两次调用间隔不短于 Retry-After
+    fn assert_rotated_after_retry_after(calls: &Calls) {
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_ne!(
+            calls[0].0, calls[1].0,
+            "429 should rotate to another account"
+        );
+        let waited = calls[1].2.duration_since(calls[0].2);
+        assert!(waited >= RETRY_AFTER, "waited only {:?}", waited);
+    }
+
+    async fn response_json(response: Response) -> Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_claude_rotates_on_429_after_retry_after() {
+        let (state, calls) = scripted_state(vec![RATE_LIMITED]).await;
+        let body = json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 16,
+            "messages": [{"role": "user", "content": "Explain retries"}]
+        });
+        let response =
+            claude::handle_messages(State(state), None, HeaderMap::new(), Json(body)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response_json(response).await["content"][0]["text"], "ok");
+        assert_rotated_after_retry_after(&calls);
+    }
+
+    #[tokio::test]
+    async fn test_claude_retries_signature_error_without_thinking() {
+        let (state, calls) =
+            scripted_state(vec![(400, None, "Invalid `signature` in thinking block")]).await;
+        let body = json!({
+            "model": "claude-sonnet-4-5-thinking",
+            "max_tokens": 2048,
+            "thinking": {"type": "enabled", "budget_tokens": 1024},
+            "messages": [{"role": "user", "content": "Explain retries"}]
+        });
+        let response =
+            claude::handle_messages(State(state), None, HeaderMap::new(), Json(body)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 2);
+        // 同一账号重试，去掉 thinking 配置与模型后缀
+        assert_eq!(calls[0].0, calls[1].0);
+        assert_eq!(calls[0].1["model"], "claude-sonnet-4-5-thinking");
+        assert!(calls[0]
+            .1
+            .pointer("/request/generationConfig/thinkingConfig")
+            .is_some());
+        assert_eq!(calls[1].1["model"], "claude-sonnet-4-5");
+        assert!(calls[1]
+            .1
+            .pointer("/request/generationConfig/thinkingConfig")
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_openai_rotates_on_429_after_retry_after() {
+        let (state, calls) = scripted_state(vec![RATE_LIMITED]).await;
+        let body = json!({
+            "model": "gemini-2.5-flash",
+            "messages": [{"role": "user", "content": "Explain retries"}]
+        });
+        let response =
+            openai::handle_chat_completions(State(state), None, HeaderMap::new(), Json(body))
+                .await
+                .map(IntoResponse::into_response)
+                .unwrap_or_else(IntoResponse::into_response);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response_json(response).await["choices"][0]["message"]["content"],
+            "ok"
+        );
+        assert_rotated_after_retry_after(&calls);
+    }
+
+    #[tokio::test]
+    async fn test_responses_rotates_on_429_after_retry_after() {
+        let (state, calls) = scripted_state(vec![RATE_LIMITED]).await;
+        let body = json!({"model": "gemini-2.5-flash", "input": "Explain retries"});
+        let response = responses::handle_responses(State(state), None, Json(body))
+            .await
+            .map(IntoResponse::into_response)
+            .unwrap_or_else(IntoResponse::into_response);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response_json(response).await["status"], "completed");
+        assert_rotated_after_retry_after(&calls);
+    }
+}
@@ -1 +1,5 @@
 pub mod comprehensive;
+pub mod files_e2e;
+pub mod gemini_golden;
+pub mod handler_retry;
+pub mod support;
@@ -0,0 +1,159 @@
+// 测试公共工具：临时目录、测试账号、本地 mock 上游与 AppState
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicUsize;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use axum::extract::Request;
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use serde_json::{json, Value};
+use tokio::sync::RwLock;
+
+use crate::proxy::server::AppState;
+use crate::proxy::TokenManager;
+
+/// 在系统临时目录下创建唯一目录 (`{prefix}-{uuid}`)
+pub fn temp_dir(prefix: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("{}-{}", prefix, uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// 在 `dir/accounts` 下写入可直接使用的账号 (token 未过期且已有 project_id)
+///
+/// 账号 `id` 的 email 为 `{id}@example.com`，access token 为 `at-{id}`。
+pub fn write_accounts(dir: &Path, ids: &[&str]) {
+    let accounts_dir = dir.join("accounts");
+    std::fs::create_dir_all(&accounts_dir).unwrap();
+
+    let far_future = chrono::Utc::now().timestamp() + 3600;
+    for id in ids {
+        let account = json!({
+            "id": id,
+            "email": format!("{}@example.com", id),
+            "token": {
+                "access_token": format!("at-{}", id),
+                "refresh_token": format!("rt-{}", id),
+                "expires_in": 3600,
+                "expiry_timestamp": far_future,
+                "project_id": "test-project",
+            },
+        });
+        std::fs::write(
+            accounts_dir.join(format!("{}.json", id)),
+            account.to_string(),
+        )
+        .unwrap();
+    }
+}
+
+/// 在本地随机端口启动 mock 上游，返回 v1internal 基础 URL
+pub async fn spawn_upstream(app: axum::Router) -> String {
+    format!("{}/v1internal", spawn_server(app).await)
+}
+
+/// 在本地随机端口启动服务，返回 `http://127.0.0.1:{port}`
+pub async fn spawn_server(app: axum::Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    format!("http://{}", addr)
+}
+
+/// mock 上游收到的一次调用：(access token, 请求体, 到达时间)
+pub type UpstreamCall = (String, Value, Instant);
+
+/// 脚本中的一次失败响应：(状态码, Retry-After 头, 错误消息)
+pub type ScriptedFailure = (u16, Option<&'static str>, &'static str);
+
+/// 按脚本依次返回失败响应的 mock 上游，脚本用完后返回成功，返回 v1internal 基础 URL
+///
+/// 成功响应对 `streamGenerateContent` 返回 SSE，其余方法返回 JSON；每次调用记录到 `calls`。
+pub async fn spawn_scripted_upstream(
+    script: Vec<ScriptedFailure>,
+    calls: Arc<Mutex<Vec<UpstreamCall>>>,
+) -> String {
+    let script = Arc::new(Mutex::new(VecDeque::from(script)));
+    let app = axum::Router::new().fallback(move |req: Request| {
+        let script = script.clone();
+        let calls = calls.clone();
+        async move {
+            let stream = req.uri().path().ends_with(":streamGenerateContent");
+            let token = req
+                .headers()
+                .get(header::AUTHORIZATION)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .trim_start_matches("Bearer ")
+                .to_string();
+            let bytes = axum::body::to_bytes(req.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body = serde_json::from_slice(&bytes).unwrap_or_default();
+            calls.lock().unwrap().push((token, body, Instant::now()));
+
+            if let Some((status, retry_after, message)) = script.lock().unwrap().pop_front() {
+                let status = StatusCode::from_u16(status).unwrap();
+                let error = json!({"error": {"code": status.as_u16(), "message": message}});
+                let mut response = (status, axum::Json(error)).into_response();
+                if let Some(retry_after) = retry_after {
+                    response
+                        .headers_mut()
+                        .insert(header::RETRY_AFTER, retry_after.parse().unwrap());
+                }
+                return response;
+            }
+
+            let event = json!({"response": {
+                "candidates": [{
+                    "content": {"role": "model", "parts": [{"text": "ok"}]},
+                    "finishReason": "STOP"
+                }],
+                "usageMetadata": {"promptTokenCount": 5, "candidatesTokenCount": 1}
+            }});
+            if stream {
+                (
+                    [(header::CONTENT_TYPE, "text/event-stream")],
+                    format!("data: {}\n\n", event),
+                )
+                    .into_response()
+            } else {
+                axum::Json(event).into_response()
+            }
+        }
+    });
+    spawn_upstream(app).await
+}
+
+/// 已加载 `account_ids` 账号、上游指向 `base_url` 的 AppState (其余配置为默认值)
+pub async fn app_state(base_url: String, account_ids: &[&str]) -> AppState {
+    let dir = temp_dir("ag-app-state");
+    write_accounts(&dir, account_ids);
+    let token_manager = Arc::new(TokenManager::new(dir));
+    token_manager.load_accounts().await.unwrap();
+
+    AppState {
+        token_manager,
+        custom_mapping: Arc::new(RwLock::new(Default::default())),
+        router_rules: Arc::new(RwLock::new(Vec::new())),
+        request_timeout: 30,
+        thought_signature_map: Arc::new(tokio::sync::Mutex::new(Default::default())),
+        upstream_proxy: Arc::new(RwLock::new(Default::default())),
+        upstream: Arc::new(
+            crate::proxy::upstream::client::UpstreamClient::new(None)
+                .with_base_urls(vec![base_url]),
+        ),
+        zai: Arc::new(RwLock::new(Default::default())),
+        providers: Arc::new(RwLock::new(Vec::new())),
+        provider_rr: Arc::new(AtomicUsize::new(0)),
+        zai_vision_mcp: Arc::new(crate::proxy::zai_vision_mcp::ZaiVisionMcpState::new()),
+        monitor: Arc::new(crate::proxy::monitor::ProxyMonitor::new()),
+        experimental: Arc::new(RwLock::new(Default::default())),
+        retry_policy: Arc::new(RwLock::new(Default::default())),
+        response_store: Arc::new(crate::proxy::response_store::ResponseStore::default()),
+    }
+}
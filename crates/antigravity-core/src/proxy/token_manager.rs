@@ -1,13 +1,20 @@
 use crate::modules::{oauth, quota};
 // 移除冗余的顶层导入，因为这些在代码中已由 full path 或局部导入处理
 use dashmap::DashMap;
+use serde::Serialize;
 use std::collections::HashSet;
 use std::path::PathBuf;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use crate::proxy::adaptive_limit::{AdaptiveLimitManager, AdaptiveLimitSnapshot, ProbeStrategy};
+use crate::proxy::common::circuit_breaker::{CircuitBreakerManager, CircuitSnapshot, CircuitState};
+use crate::proxy::health::{AccountHealthResponse, HealthMonitor};
+use crate::proxy::limit_store::{self, LimitSnapshot, PersistedAdaptiveLimit, PersistedRateLimit};
 use crate::proxy::rate_limit::RateLimitTracker;
-use antigravity_shared::proxy::config::StickySessionConfig;
+use crate::proxy::smart_prober::{SmartProber, SmartProberConfig};
+use antigravity_shared::proxy::config::{HedgingConfig, StickySessionConfig};
 
 #[derive(Debug, Clone)]
 pub struct ProxyToken {
@@ -22,6 +29,31 @@ pub struct ProxyToken {
     pub subscription_tier: Option<String>, // "FREE" | "PRO" | "ULTRA"
 }
 
+/// 账号对单个模型的限流
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ModelLockout {
+    pub model: String,
+    pub reset_seconds: u64,
+}
+
+/// 单个账号的可用性快照，用于管理界面解释账号为何被调度跳过
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountAvailability {
+    pub account_id: String,
+    pub email: String,
+    /// 是否会被 `get_token` 选中 (不考虑 AIMD 软约束)
+    pub available: bool,
+    /// 被跳过的原因 (按优先级取第一个)
+    pub skip_reason: Option<String>,
+    pub rate_limited: bool,
+    pub rate_limit_reset_seconds: Option<u64>,
+    /// 模型级别限流 (配额耗尽)：账号仍可服务其他模型
+    pub model_lockouts: Vec<ModelLockout>,
+    pub health: Option<AccountHealthResponse>,
+    pub circuit: CircuitSnapshot,
+    pub adaptive: AdaptiveLimitSnapshot,
+}
+
 pub struct TokenManager {
     tokens: Arc<DashMap<String, ProxyToken>>, // account_id -> ProxyToken
     current_index: Arc<AtomicUsize>,
@@ -30,11 +62,21 @@ pub struct TokenManager {
     rate_limit_tracker: Arc<RateLimitTracker>, // 新增: 限流跟踪器
     sticky_config: Arc<tokio::sync::RwLock<StickySessionConfig>>, // 新增：调度配置
     session_accounts: Arc<DashMap<String, String>>, // 新增：会话与账号映射 (SessionID -> AccountID)
+    adaptive_limits: Arc<AdaptiveLimitManager>, // AIMD 预测性限流 (account_id -> tracker)
+    health_monitor: Arc<HealthMonitor>,        // 连续错误自动禁用
+    circuit_breaker: Arc<CircuitBreakerManager>, // 账号级熔断
+    smart_prober: Arc<SmartProber>,            // 接近 AIMD 阈值时的探测与对冲
+    limits_restored: AtomicBool,               // 限流快照仅在首次加载账号时恢复
 }
 
 impl TokenManager {
     /// 创建新的 TokenManager
     pub fn new(data_dir: PathBuf) -> Self {
+        let adaptive_limits = Arc::new(AdaptiveLimitManager::default());
+        let smart_prober = Arc::new(SmartProber::new(
+            SmartProberConfig::from(&HedgingConfig::default()),
+            adaptive_limits.clone(),
+        ));
         Self {
             tokens: Arc::new(DashMap::new()),
             current_index: Arc::new(AtomicUsize::new(0)),
@@ -43,9 +85,74 @@ impl TokenManager {
             rate_limit_tracker: Arc::new(RateLimitTracker::new()),
             sticky_config: Arc::new(tokio::sync::RwLock::new(StickySessionConfig::default())),
             session_accounts: Arc::new(DashMap::new()),
+            adaptive_limits,
+            health_monitor: HealthMonitor::new(),
+            circuit_breaker: Arc::new(CircuitBreakerManager::new()),
+            smart_prober,
+            limits_restored: AtomicBool::new(false),
         }
     }
 
+    /// AIMD 自适应限流管理器 (按 account_id 维度)
+    pub fn adaptive_limits(&self) -> &Arc<AdaptiveLimitManager> {
+        &self.adaptive_limits
+    }
+
+    /// 账号健康监控器
+    pub fn health_monitor(&self) -> &Arc<HealthMonitor> {
+        &self.health_monitor
+    }
+
+    /// 账号级熔断器
+    pub fn circuit_breaker(&self) -> &Arc<CircuitBreakerManager> {
+        &self.circuit_breaker
+    }
+
+    /// 对冲 / 廉价探测执行器 (与 AIMD 共享同一个 `AdaptiveLimitManager`)
+    pub fn smart_prober(&self) -> &Arc<SmartProber> {
+        &self.smart_prober
+    }
+
+    /// 热更新对冲配置
+    pub fn update_hedging_config(&self, config: &HedgingConfig) {
+        self.smart_prober
+            .update_config(SmartProberConfig::from(config));
+    }
+
+    /// 按 AIMD 使用率给出账号的探测策略。`account` 也可以是 email
+    pub fn probe_strategy(&self, account: &str) -> ProbeStrategy {
+        match self.resolve_account_id(account) {
+            Some(id) => self.smart_prober.strategy_for(&id),
+            None => ProbeStrategy::None,
+        }
+    }
+
+    /// 为对冲请求挑选第二个账号 (不影响粘性会话与轮询位置)
+    ///
+    /// 只考虑可用、未超过 AIMD 阈值、token 未临近过期且已有 project_id 的账号，
+    /// 优先选择 AIMD 使用率最低的。返回 (access_token, project_id, email)。
+    pub fn get_hedge_token(
+        &self,
+        exclude: &str,
+        model: Option<&str>,
+    ) -> Option<(String, String, String)> {
+        let now = chrono::Utc::now().timestamp();
+        self.tokens
+            .iter()
+            .map(|e| e.value().clone())
+            .filter(|t| t.email != exclude && t.account_id != exclude)
+            .filter(|t| now < t.timestamp - 300 && t.project_id.is_some())
+            .filter(|t| self.is_account_available(t, model))
+            // 只读查询，不为候选账号创建 AIMD tracker
+            .map(|t| (self.adaptive_limits.peek(&t.account_id).usage_ratio, t))
+            .filter(|(ratio, _)| *ratio < 1.0)
+            .min_by(|(a, _), (b, _)| a.total_cmp(b))
+            .and_then(|(_, t)| {
+                let project_id = t.project_id?;
+                Some((t.access_token, project_id, t.email))
+            })
+    }
+
     /// 从主应用账号目录加载所有账号
     pub async fn load_accounts(&self) -> Result<usize, String> {
         let accounts_dir = self.data_dir.join("accounts");
@@ -79,6 +186,8 @@ impl TokenManager {
             match self.load_single_account(&path).await {
                 Ok(Some(token)) => {
                     let account_id = token.account_id.clone();
+                    self.health_monitor
+                        .register_account(account_id.clone(), token.email.clone());
                     self.tokens.insert(account_id, token);
                     count += 1;
                 }
@@ -91,6 +200,12 @@ impl TokenManager {
             }
         }
 
+        // 热重载时内存中的状态更新，只在首次加载到账号时从磁盘恢复
+        // (首次启动尚未完成 OAuth 时没有账号，快照中的记录会被全部跳过)
+        if count > 0 && !self.limits_restored.swap(true, Ordering::SeqCst) {
+            self.restore_limits();
+        }
+
         Ok(count)
     }
 
@@ -194,12 +309,24 @@ impl TokenManager {
         quota_group: &str,
         force_rotate: bool,
         session_id: Option<&str>,
+    ) -> Result<(String, String, String), String> {
+        self.get_token_for_model(quota_group, force_rotate, session_id, None)
+            .await
+    }
+
+    /// 同 `get_token`，额外跳过对 `model` 处于模型级别限流 (配额耗尽) 的账号
+    pub async fn get_token_for_model(
+        &self,
+        quota_group: &str,
+        force_rotate: bool,
+        session_id: Option<&str>,
+        model: Option<&str>,
     ) -> Result<(String, String, String), String> {
         // 【优化 Issue #284】添加 5 秒超时，防止死锁
         let timeout_duration = std::time::Duration::from_secs(5);
         match tokio::time::timeout(
             timeout_duration,
-            self.get_token_internal(quota_group, force_rotate, session_id),
+            self.get_token_internal(quota_group, force_rotate, session_id, model),
         )
         .await
         {
@@ -216,6 +343,7 @@ impl TokenManager {
         quota_group: &str,
         force_rotate: bool,
         session_id: Option<&str>,
+        model: Option<&str>,
     ) -> Result<(String, String, String), String> {
         let mut tokens_snapshot: Vec<ProxyToken> =
             self.tokens.iter().map(|e| e.value().clone()).collect();
@@ -242,7 +370,7 @@ impl TokenManager {
 
         // 【优化 Issue #284】将锁操作移到循环外，避免重复获取锁
         // 预先获取 last_used_account 的快照，避免在循环中多次加锁
-        let last_used_account_id = if quota_group != "image_gen" {
+        let last_used_account_id = if !is_stateless_group(quota_group) {
             let last_used = self.last_used_account.lock().await;
             last_used.clone()
         } else {
@@ -278,8 +406,10 @@ impl TokenManager {
                             self.session_accounts.remove(sid);
                         } else if !attempted.contains(&bound_id) {
                             // 3. 账号可用且未被标记为尝试失败，优先复用
-                            if let Some(found) =
-                                tokens_snapshot.iter().find(|t| t.account_id == bound_id)
+                            if let Some(found) = tokens_snapshot
+                                .iter()
+                                .find(|t| t.account_id == bound_id)
+                                .filter(|t| self.is_account_available(t, model))
                             {
                                 tracing::debug!(
                                     "Sticky Session: Successfully reusing bound account {} for session {}",
@@ -294,12 +424,14 @@ impl TokenManager {
             }
 
             // 模式 B: 原子化 60s 全局锁定 (针对无 session_id 情况的默认保护)
-            if target_token.is_none() && !rotate && quota_group != "image_gen" {
+            if target_token.is_none() && !rotate && !is_stateless_group(quota_group) {
                 // 【优化】使用预先获取的快照，不再在循环内加锁
                 if let Some((account_id, last_time)) = &last_used_account_id {
                     if last_time.elapsed().as_secs() < 60 && !attempted.contains(account_id) {
-                        if let Some(found) =
-                            tokens_snapshot.iter().find(|t| &t.account_id == account_id)
+                        if let Some(found) = tokens_snapshot
+                            .iter()
+                            .find(|t| &t.account_id == account_id)
+                            .filter(|t| self.is_account_available(t, model))
                         {
                             tracing::debug!(
                                 "60s Window: Force reusing last account: {}",
@@ -313,18 +445,10 @@ impl TokenManager {
                 // 若无锁定，则轮询选择新账号
                 if target_token.is_none() {
                     let start_idx = self.current_index.fetch_add(1, Ordering::SeqCst) % total;
-                    for offset in 0..total {
-                        let idx = (start_idx + offset) % total;
-                        let candidate = &tokens_snapshot[idx];
-                        if attempted.contains(&candidate.account_id) {
-                            continue;
-                        }
-
-                        // 【新增】主动避开限流或 5xx 锁定的账号 (来自 PR #28 的高可用思路)
-                        if self.is_rate_limited(&candidate.account_id) {
-                            continue;
-                        }
-
+                    // 【新增】主动避开限流、熔断或被健康监控禁用的账号 (来自 PR #28 的高可用思路)
+                    if let Some(candidate) =
+                        self.select_candidate(&tokens_snapshot, start_idx, &attempted, model)
+                    {
                         target_token = Some(candidate.clone());
                         // 【优化】标记需要更新，稍后统一写回
                         need_update_last_used =
@@ -342,30 +466,19 @@ impl TokenManager {
                                 );
                             }
                         }
-                        break;
                     }
                 }
             } else if target_token.is_none() {
                 // 模式 C: 纯轮询模式 (Round-robin) 或强制轮换
                 let start_idx = self.current_index.fetch_add(1, Ordering::SeqCst) % total;
-                for offset in 0..total {
-                    let idx = (start_idx + offset) % total;
-                    let candidate = &tokens_snapshot[idx];
-                    if attempted.contains(&candidate.account_id) {
-                        continue;
-                    }
-
-                    // 【新增】主动避开限流或 5xx 锁定的账号
-                    if self.is_rate_limited(&candidate.account_id) {
-                        continue;
-                    }
-
+                if let Some(candidate) =
+                    self.select_candidate(&tokens_snapshot, start_idx, &attempted, model)
+                {
                     target_token = Some(candidate.clone());
 
                     if rotate {
                         tracing::debug!("Force Rotation: Switched to account: {}", candidate.email);
                     }
-                    break;
                 }
             }
 
@@ -378,7 +491,11 @@ impl TokenManager {
                     // 计算最短等待时间
                     let min_wait = tokens_snapshot
                         .iter()
-                        .filter_map(|t| self.rate_limit_tracker.get_reset_seconds(&t.account_id))
+                        .filter_map(|t| {
+                            self.rate_limit_tracker
+                                .get_reset_seconds(&t.account_id)
+                                .or_else(|| self.rate_limit_tracker.get_reset_seconds(&t.email))
+                        })
                         .min();
 
                     // Layer 1: 如果最短等待时间 <= 2秒,执行缓冲延迟
@@ -395,7 +512,7 @@ impl TokenManager {
                             // 重新尝试选择账号
                             let retry_token = tokens_snapshot.iter().find(|t| {
                                 !attempted.contains(&t.account_id)
-                                    && !self.is_rate_limited(&t.account_id)
+                                    && self.is_account_available(t, model)
                             });
 
                             if let Some(t) = retry_token {
@@ -414,10 +531,11 @@ impl TokenManager {
                                 // 清除所有限流记录
                                 self.rate_limit_tracker.clear_all();
 
-                                // 再次尝试选择账号
-                                let final_token = tokens_snapshot
-                                    .iter()
-                                    .find(|t| !attempted.contains(&t.account_id));
+                                // 再次尝试选择账号 (熔断/健康禁用仍然生效)
+                                let final_token = tokens_snapshot.iter().find(|t| {
+                                    !attempted.contains(&t.account_id)
+                                        && self.is_account_available(t, model)
+                                });
 
                                 if let Some(t) = final_token {
                                     tracing::info!(
@@ -496,7 +614,7 @@ impl TokenManager {
                         attempted.insert(token.account_id.clone());
 
                         // 【优化】标记需要清除锁定，避免在循环内加锁
-                        if quota_group != "image_gen"
+                        if !is_stateless_group(quota_group)
                             && matches!(&last_used_account_id, Some((id, _)) if id == &token.account_id)
                         {
                             need_update_last_used =
@@ -530,7 +648,7 @@ impl TokenManager {
                         attempted.insert(token.account_id.clone());
 
                         // 【优化】标记需要清除锁定，避免在循环内加锁
-                        if quota_group != "image_gen"
+                        if !is_stateless_group(quota_group)
                             && matches!(&last_used_account_id, Some((id, _)) if id == &token.account_id)
                         {
                             need_update_last_used =
@@ -544,7 +662,7 @@ impl TokenManager {
 
             // 【优化】在成功返回前，统一更新 last_used_account（如果需要）
             if let Some((new_account_id, new_time)) = need_update_last_used {
-                if quota_group != "image_gen" {
+                if !is_stateless_group(quota_group) {
                     let mut last_used = self.last_used_account.lock().await;
                     if new_account_id.is_empty() {
                         // 空字符串表示需要清除锁定
@@ -561,6 +679,69 @@ impl TokenManager {
         Err(last_error.unwrap_or_else(|| "All accounts failed".to_string()))
     }
 
+    /// 统一的账号可用性信号：限流锁定、熔断打开、健康监控禁用，任一命中即不可用
+    ///
+    /// `model` 为 Some 时同时检查该模型的模型级别限流
+    fn is_account_available(&self, token: &ProxyToken, model: Option<&str>) -> bool {
+        // handlers 以 email 记录限流，这里两种 key 都要检查
+        !self
+            .rate_limit_tracker
+            .is_rate_limited_for_model(&token.account_id, model)
+            && !self
+                .rate_limit_tracker
+                .is_rate_limited_for_model(&token.email, model)
+            && self.circuit_breaker.should_allow(&token.account_id).is_ok()
+            && self.health_monitor.is_available(&token.account_id)
+    }
+
+    /// 从 `start_idx` 开始轮询挑选候选账号
+    ///
+    /// AIMD 工作阈值是软约束：超过阈值的账号只在没有其他可用账号时兜底，
+    /// 避免单账号场景下因预测性限流而直接拒绝请求。
+    fn select_candidate<'a>(
+        &self,
+        tokens: &'a [ProxyToken],
+        start_idx: usize,
+        attempted: &HashSet<String>,
+        model: Option<&str>,
+    ) -> Option<&'a ProxyToken> {
+        let total = tokens.len();
+        let mut over_threshold: Option<&'a ProxyToken> = None;
+
+        for offset in 0..total {
+            let candidate = &tokens[(start_idx + offset) % total];
+            if attempted.contains(&candidate.account_id)
+                || !self.is_account_available(candidate, model)
+            {
+                continue;
+            }
+            if !self.adaptive_limits.should_allow(&candidate.account_id) {
+                over_threshold.get_or_insert(candidate);
+                continue;
+            }
+            return Some(candidate);
+        }
+
+        if let Some(candidate) = over_threshold {
+            tracing::debug!(
+                "All available accounts above AIMD threshold, falling back to {}",
+                candidate.email
+            );
+        }
+        over_threshold
+    }
+
+    /// handlers 只持有 email，统一解析为 account_id
+    fn resolve_account_id(&self, account: &str) -> Option<String> {
+        if self.tokens.contains_key(account) {
+            return Some(account.to_string());
+        }
+        self.tokens
+            .iter()
+            .find(|e| e.value().email == account)
+            .map(|e| e.key().clone())
+    }
+
     async fn disable_account(&self, account_id: &str, reason: &str) -> Result<(), String> {
         let path = if let Some(entry) = self.tokens.get(account_id) {
             entry.account_path.clone()
@@ -649,12 +830,15 @@ impl TokenManager {
     // ===== 限流管理方法 =====
 
     /// 标记账号限流(从外部调用,通常在 handler 中)
+    ///
+    /// 传入 `model` 时，配额耗尽 (QUOTA_EXHAUSTED) 只锁定该模型
     pub fn mark_rate_limited(
         &self,
         account_id: &str,
         status: u16,
         retry_after_header: Option<&str>,
         error_body: &str,
+        model: Option<&str>,
     ) {
         self.rate_limit_tracker.parse_from_error(
             account_id,
@@ -662,6 +846,9 @@ impl TokenManager {
             retry_after_header,
             error_body,
         );
+        if let Some(m) = model {
+            self.rate_limit_tracker.scope_to_model(account_id, m);
+        }
     }
 
     /// 检查账号是否在限流中
@@ -681,18 +868,139 @@ impl TokenManager {
         self.rate_limit_tracker.cleanup_expired()
     }
 
-    /// 清除指定账号的限流记录
-    #[allow(dead_code)]
+    /// 清除指定账号的限流记录 (同时清除以 email 记录的限流)
     pub fn clear_rate_limit(&self, account_id: &str) -> bool {
-        self.rate_limit_tracker.clear(account_id)
+        let mut cleared = self.rate_limit_tracker.clear(account_id);
+        if let Some(email) = self.tokens.get(account_id).map(|t| t.email.clone()) {
+            cleared |= self.rate_limit_tracker.clear(&email);
+        }
+        cleared
+    }
+
+    /// 手动重置账号熔断 (`account_id` 也可以是 email)，账号未加载时返回 false
+    pub fn reset_circuit(&self, account_id: &str) -> bool {
+        let Some(id) = self.resolve_account_id(account_id) else {
+            return false;
+        };
+        self.circuit_breaker.reset(&id);
+        true
+    }
+
+    /// 汇总限流 / 熔断 / 健康 / AIMD 状态，返回每个已加载账号的可用性
+    pub async fn get_account_availability(&self) -> Vec<AccountAvailability> {
+        let mut tokens: Vec<ProxyToken> = self.tokens.iter().map(|e| e.value().clone()).collect();
+        tokens.sort_by(|a, b| a.email.cmp(&b.email));
+
+        let mut result = Vec::with_capacity(tokens.len());
+        for token in tokens {
+            let rate_limit_reset_seconds = self
+                .rate_limit_tracker
+                .get_reset_seconds(&token.account_id)
+                .or_else(|| self.rate_limit_tracker.get_reset_seconds(&token.email));
+            let rate_limited =
+                self.is_rate_limited(&token.account_id) || self.is_rate_limited(&token.email);
+            // 限流可能以 account_id 或 email 记录
+            let mut model_lockouts: Vec<ModelLockout> = self
+                .rate_limit_tracker
+                .model_lockouts(&token.account_id)
+                .into_iter()
+                .chain(self.rate_limit_tracker.model_lockouts(&token.email))
+                .map(|(model, reset_seconds)| ModelLockout {
+                    model,
+                    reset_seconds,
+                })
+                .collect();
+            model_lockouts.sort_by(|a, b| a.model.cmp(&b.model));
+            model_lockouts.dedup_by(|a, b| a.model == b.model);
+            let health = self.health_monitor.get_health(&token.account_id).await;
+            let circuit = self.circuit_breaker.snapshot(&token.account_id);
+            let adaptive = self.adaptive_limits.peek(&token.account_id);
+
+            let skip_reason = if rate_limited {
+                Some(format!(
+                    "Rate limited ({}s remaining)",
+                    rate_limit_reset_seconds.unwrap_or(0)
+                ))
+            } else if circuit.state == CircuitState::Open {
+                Some(format!(
+                    "Circuit open: {}",
+                    circuit.last_failure_reason.as_deref().unwrap_or("unknown")
+                ))
+            } else if health.as_ref().is_some_and(|h| h.is_disabled) {
+                Some("Auto-disabled by health monitor".to_string())
+            } else if adaptive.usage_ratio >= 1.0 {
+                Some(format!(
+                    "Above AIMD threshold ({}/{} this minute, used as fallback only)",
+                    adaptive.requests_this_minute, adaptive.working_threshold
+                ))
+            } else if !model_lockouts.is_empty() {
+                Some(format!(
+                    "Quota exhausted for {}",
+                    model_lockouts
+                        .iter()
+                        .map(|l| format!("{} ({}s remaining)", l.model, l.reset_seconds))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))
+            } else {
+                None
+            };
+
+            result.push(AccountAvailability {
+                available: !rate_limited
+                    && circuit.state != CircuitState::Open
+                    && !health.as_ref().is_some_and(|h| h.is_disabled),
+                account_id: token.account_id,
+                email: token.email,
+                skip_reason,
+                rate_limited,
+                rate_limit_reset_seconds,
+                model_lockouts,
+                health,
+                circuit,
+                adaptive,
+            });
+        }
+        result
     }
 
     /// 标记账号请求成功，重置连续失败计数
     ///
     /// 在请求成功完成后调用，将该账号的失败计数归零，
     /// 下次失败时从最短的锁定时间开始（智能限流）。
+    /// 同时向熔断器、健康监控与 AIMD 反馈一次成功。`account_id` 也可以是 email。
     pub fn mark_account_success(&self, account_id: &str) {
         self.rate_limit_tracker.mark_success(account_id);
+
+        if let Some(id) = self.resolve_account_id(account_id) {
+            self.circuit_breaker.record_success(&id);
+            self.health_monitor.record_success(&id);
+            self.adaptive_limits.record_success(&id);
+        }
+    }
+
+    /// 上游返回错误时反馈给熔断器、健康监控与 AIMD
+    ///
+    /// - 429: AIMD 立即收缩工作阈值，并计入熔断
+    /// - 5xx: 计入熔断
+    /// - 401/403/429/5xx: 交给健康监控判断是否自动禁用
+    ///
+    /// 限流锁定仍由 `mark_rate_limited*` 负责。`account_id` 也可以是 email。
+    pub async fn report_upstream_error(&self, account_id: &str, status: u16, error_body: &str) {
+        let Some(id) = self.resolve_account_id(account_id) else {
+            return;
+        };
+
+        if status == 429 {
+            self.adaptive_limits.record_429(&id);
+        }
+        if status == 429 || status >= 500 {
+            self.circuit_breaker
+                .record_failure(&id, &format!("HTTP {}", status));
+        }
+        self.health_monitor
+            .record_error(&id, status, error_body)
+            .await;
     }
 
     /// 从账号文件获取配额刷新时间
@@ -882,6 +1190,9 @@ impl TokenManager {
                 retry_after_header,
                 error_body,
             );
+            if let Some(m) = model {
+                self.rate_limit_tracker.scope_to_model(account_id, m);
+            }
             return;
         }
 
@@ -932,6 +1243,128 @@ impl TokenManager {
             retry_after_header,
             error_body,
         );
+        if let Some(m) = model {
+            self.rate_limit_tracker.scope_to_model(account_id, m);
+        }
+    }
+
+    // ===== 限流状态持久化 =====
+
+    /// 生成 AIMD 限额与限流锁定的快照
+    pub fn limit_snapshot(&self) -> LimitSnapshot {
+        let adaptive_limits = self
+            .adaptive_limits
+            .all_for_persistence()
+            .into_iter()
+            .map(
+                |(account_id, confirmed_limit, ceiling, calibration_age_seconds)| {
+                    PersistedAdaptiveLimit {
+                        account_id,
+                        confirmed_limit,
+                        ceiling,
+                        calibration_age_seconds,
+                    }
+                },
+            )
+            .collect();
+
+        let rate_limits = self
+            .rate_limit_tracker
+            .active_lockouts()
+            .into_iter()
+            .filter_map(|(key, info)| {
+                let reset_at = info.reset_time.duration_since(UNIX_EPOCH).ok()?.as_secs();
+                Some(PersistedRateLimit {
+                    key,
+                    reset_at,
+                    reason: info.reason,
+                    model: info.model,
+                })
+            })
+            .collect();
+
+        LimitSnapshot {
+            saved_at: unix_now(),
+            adaptive_limits,
+            rate_limits,
+        }
+    }
+
+    /// 将限流状态写入数据目录
+    ///
+    /// 快照恢复之前不写入，避免空状态覆盖上次保存的快照
+    pub fn save_limits(&self) -> Result<(), String> {
+        if !self.limits_restored.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        limit_store::save(&self.data_dir, &self.limit_snapshot())
+    }
+
+    /// 从数据目录恢复限流状态，忽略已不存在的账号与已过期的锁定
+    fn restore_limits(&self) {
+        let snapshot = match limit_store::load(&self.data_dir) {
+            Ok(Some(snapshot)) => snapshot,
+            Ok(None) => return,
+            Err(e) => {
+                tracing::warn!("Failed to load rate limit snapshot: {}", e);
+                return;
+            }
+        };
+
+        let now = unix_now();
+        let downtime = now.saturating_sub(snapshot.saved_at);
+        let mut restored_limits = 0;
+        let mut restored_lockouts = 0;
+
+        for limit in snapshot.adaptive_limits {
+            if !self.tokens.contains_key(&limit.account_id) {
+                continue;
+            }
+            self.adaptive_limits.load_persisted(
+                &limit.account_id,
+                limit.confirmed_limit,
+                limit.ceiling,
+                limit.calibration_age_seconds.saturating_add(downtime),
+            );
+            restored_limits += 1;
+        }
+
+        for lockout in snapshot.rate_limits {
+            if lockout.reset_at <= now || self.resolve_account_id(&lockout.key).is_none() {
+                continue;
+            }
+            self.rate_limit_tracker.set_lockout_until(
+                &lockout.key,
+                UNIX_EPOCH + Duration::from_secs(lockout.reset_at),
+                lockout.reason,
+                lockout.model,
+            );
+            restored_lockouts += 1;
+        }
+
+        tracing::info!(
+            "Restored {} adaptive limit(s) and {} rate-limit lockout(s) from snapshot",
+            restored_limits,
+            restored_lockouts
+        );
+    }
+
+    /// 后台定期保存限流快照
+    pub fn start_limit_persistence_task(
+        self: &Arc<Self>,
+        interval: Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        let manager = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await; // 第一次 tick 立即返回，跳过
+            loop {
+                ticker.tick().await;
+                if let Err(e) = manager.save_limits() {
+                    tracing::warn!("Failed to save rate limit snapshot: {}", e);
+                }
+            }
+        })
     }
 
     // ===== 调度配置相关方法 =====
@@ -948,6 +1381,14 @@ impl TokenManager {
         tracing::debug!("Scheduling configuration updated: {:?}", *config);
     }
 
+    /// 将会话固定到指定账号 (例如持有该会话 prompt cache 的账号)
+    pub fn pin_session(&self, session_id: &str, account: &str) {
+        if let Some(account_id) = self.resolve_account_id(account) {
+            self.session_accounts
+                .insert(session_id.to_string(), account_id);
+        }
+    }
+
     /// 清除特定会话的粘性映射
     #[allow(dead_code)]
     pub fn clear_session_binding(&self, session_id: &str) {
@@ -960,6 +1401,18 @@ impl TokenManager {
     }
 }
 
+/// 无状态请求组 (图像生成、Embedding) 不参与 60s 账号锁定
+fn is_stateless_group(quota_group: &str) -> bool {
+    matches!(quota_group, "image_gen" | "embedding")
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
 fn truncate_reason(reason: &str, max_len: usize) -> String {
     if reason.chars().count() <= max_len {
         return reason.to_string();
@@ -968,3 +1421,270 @@ fn truncate_reason(reason: &str, max_len: usize) -> String {
     s.push('…');
     s
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 在临时目录中写入若干可直接使用的账号 (token 未过期且已有 project_id)
+    fn manager_with_accounts(ids: &[&str]) -> (TokenManager, PathBuf) {
+        let dir = crate::proxy::tests::support::temp_dir("ag-token-manager");
+        crate::proxy::tests::support::write_accounts(&dir, ids);
+        (TokenManager::new(dir.clone()), dir)
+    }
+
+    #[tokio::test]
+    async fn test_open_circuit_skips_account() {
+        let (manager, dir) = manager_with_accounts(&["a", "b"]);
+        assert_eq!(manager.load_accounts().await.unwrap(), 2);
+
+        // handlers 以 email 上报，5 次 5xx 打开熔断
+        for _ in 0..5 {
+            manager
+                .report_upstream_error("a@example.com", 500, "internal")
+                .await;
+        }
+        assert_eq!(manager.circuit_breaker().get_state("a"), CircuitState::Open);
+
+        for _ in 0..4 {
+            let (_, _, email) = manager.get_token("claude", true, None).await.unwrap();
+            assert_eq!(email, "b@example.com");
+        }
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_reset_circuit_unknown_account() {
+        let (manager, dir) = manager_with_accounts(&["a"]);
+        manager.load_accounts().await.unwrap();
+
+        for _ in 0..5 {
+            manager.report_upstream_error("a", 500, "internal").await;
+        }
+        assert!(!manager.reset_circuit("missing"));
+        assert_eq!(manager.circuit_breaker().get_state("a"), CircuitState::Open);
+        assert!(manager.reset_circuit("a@example.com"));
+        assert_eq!(
+            manager.circuit_breaker().get_state("a"),
+            CircuitState::Closed
+        );
+        // 状态查询不会为账号创建 AIMD tracker
+        manager.get_account_availability().await;
+        assert!(manager.adaptive_limits().get("a").is_none());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_hedge_token_skips_saturated_accounts_without_creating_trackers() {
+        let (manager, dir) = manager_with_accounts(&["a", "b", "c"]);
+        manager.load_accounts().await.unwrap();
+
+        for _ in 0..12 {
+            manager.mark_account_success("b@example.com");
+        }
+        let (_, _, email) = manager.get_hedge_token("a@example.com", None).unwrap();
+        assert_eq!(email, "c@example.com");
+        assert!(manager.adaptive_limits().get("c").is_none());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_health_disabled_account_is_skipped() {
+        let (manager, dir) = manager_with_accounts(&["a", "b"]);
+        manager.load_accounts().await.unwrap();
+
+        for _ in 0..5 {
+            manager.report_upstream_error("b", 403, "forbidden").await;
+        }
+        assert!(!manager.health_monitor().is_available("b"));
+
+        for _ in 0..4 {
+            let (_, _, email) = manager.get_token("claude", true, None).await.unwrap();
+            assert_eq!(email, "a@example.com");
+        }
+
+        // 成功反馈会重置连续错误，但不会自动解除禁用
+        manager.mark_account_success("b@example.com");
+        assert!(!manager.health_monitor().is_available("b"));
+        assert!(manager.health_monitor().force_enable("b").await);
+        assert!(manager.health_monitor().is_available("b"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_aimd_threshold_prefers_other_accounts_then_falls_back() {
+        let (manager, dir) = manager_with_accounts(&["a", "b"]);
+        manager.load_accounts().await.unwrap();
+
+        // 默认工作阈值为 12 次/分钟，把 a 推到阈值之上
+        for _ in 0..12 {
+            manager.mark_account_success("a@example.com");
+        }
+        assert!(!manager.adaptive_limits().should_allow("a"));
+
+        for _ in 0..4 {
+            let (_, _, email) = manager.get_token("claude", true, None).await.unwrap();
+            assert_eq!(email, "b@example.com");
+        }
+
+        // 所有账号都超阈值时仍然可以兜底，而不是直接拒绝
+        for _ in 0..12 {
+            manager.mark_account_success("b");
+        }
+        assert!(manager.get_token("claude", true, None).await.is_ok());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_account_availability_explains_skips() {
+        let (manager, dir) = manager_with_accounts(&["a", "b", "c"]);
+        manager.load_accounts().await.unwrap();
+
+        manager.mark_rate_limited("a@example.com", 429, Some("120"), "", None);
+        for _ in 0..5 {
+            manager.report_upstream_error("b", 503, "unavailable").await;
+        }
+
+        let view = manager.get_account_availability().await;
+        assert_eq!(view.len(), 3);
+
+        let a = &view[0];
+        assert!(!a.available && a.rate_limited);
+        assert!(a
+            .skip_reason
+            .as_deref()
+            .unwrap()
+            .starts_with("Rate limited"));
+
+        let b = &view[1];
+        assert!(!b.available);
+        assert_eq!(b.circuit.state, CircuitState::Open);
+        assert!(b.circuit.open_remaining_seconds.is_some());
+        assert_eq!(b.skip_reason.as_deref(), Some("Circuit open: HTTP 503"));
+
+        let c = &view[2];
+        assert!(c.available && c.skip_reason.is_none());
+        assert_eq!(c.adaptive.working_threshold, 12);
+
+        // 模型级别配额耗尽：账号仍可用，但在可用性视图中可见
+        let quota = r#"{"error":{"details":[{"reason":"QUOTA_EXHAUSTED"}]}}"#;
+        manager.mark_rate_limited("c", 429, None, quota, Some("gemini-3-pro-high"));
+        let c = manager.get_account_availability().await.remove(2);
+        assert!(c.available && !c.rate_limited);
+        assert_eq!(c.model_lockouts.len(), 1);
+        assert_eq!(c.model_lockouts[0].model, "gemini-3-pro-high");
+        assert!(c
+            .skip_reason
+            .as_deref()
+            .unwrap()
+            .starts_with("Quota exhausted for gemini-3-pro-high"));
+        assert!(manager.clear_rate_limit("c"));
+
+        // 清除限流时同时清除以 email 记录的条目
+        assert!(manager.clear_rate_limit("a"));
+        // 5 次 5xx 同时触发熔断与健康监控禁用，需要分别恢复
+        manager.circuit_breaker().reset("b");
+        assert!(manager.health_monitor().force_enable("b").await);
+        assert!(manager
+            .get_account_availability()
+            .await
+            .iter()
+            .all(|a| a.available));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_limits_survive_restart() {
+        let (manager, dir) = manager_with_accounts(&["a", "b"]);
+        manager.load_accounts().await.unwrap();
+
+        // 配额耗尽锁定 (以 email 记录) + 一次 429 校准后的 AIMD 限额
+        manager.rate_limit_tracker.set_lockout_until(
+            "a@example.com",
+            SystemTime::now() + Duration::from_secs(3600),
+            crate::proxy::rate_limit::RateLimitReason::QuotaExhausted,
+            None,
+        );
+        for _ in 0..20 {
+            manager.adaptive_limits().record_success("b");
+        }
+        manager.adaptive_limits().record_429("b");
+        let learned = manager.adaptive_limits().snapshot("b").confirmed_limit;
+        manager.save_limits().unwrap();
+
+        let restarted = TokenManager::new(dir.clone());
+        restarted.load_accounts().await.unwrap();
+
+        assert!(restarted.is_rate_limited("a@example.com"));
+        assert!(
+            restarted
+                .get_rate_limit_reset_seconds("a@example.com")
+                .unwrap()
+                > 3500
+        );
+        assert_eq!(
+            restarted.adaptive_limits().snapshot("b").confirmed_limit,
+            learned
+        );
+        // 未校准的账号不持久化，保持默认限额
+        assert!(restarted.adaptive_limits().get("a").is_none());
+
+        for _ in 0..4 {
+            let (_, _, email) = restarted.get_token("claude", true, None).await.unwrap();
+            assert_eq!(email, "b@example.com");
+        }
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_limits_restored_after_first_account_loads() {
+        let (manager, dir) = manager_with_accounts(&["a", "b"]);
+        manager.load_accounts().await.unwrap();
+        manager.rate_limit_tracker.set_lockout_until(
+            "a@example.com",
+            SystemTime::now() + Duration::from_secs(3600),
+            crate::proxy::rate_limit::RateLimitReason::QuotaExhausted,
+            None,
+        );
+        manager.save_limits().unwrap();
+
+        // 模拟账号文件尚未就绪 (如 OAuth 完成前) 的启动
+        let accounts_dir = dir.join("accounts");
+        let parked = dir.join("parked");
+        std::fs::rename(&accounts_dir, &parked).unwrap();
+        std::fs::create_dir_all(&accounts_dir).unwrap();
+
+        let restarted = TokenManager::new(dir.clone());
+        assert_eq!(restarted.load_accounts().await.unwrap(), 0);
+        // 未恢复前的保存不能覆盖快照
+        restarted.save_limits().unwrap();
+
+        std::fs::remove_dir_all(&accounts_dir).unwrap();
+        std::fs::rename(&parked, &accounts_dir).unwrap();
+        assert_eq!(restarted.load_accounts().await.unwrap(), 2);
+        assert!(restarted.is_rate_limited("a@example.com"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_recorded_by_email_is_respected() {
+        let (manager, dir) = manager_with_accounts(&["a", "b"]);
+        manager.load_accounts().await.unwrap();
+
+        manager.mark_rate_limited("a@example.com", 429, Some("120"), "", None);
+        for _ in 0..4 {
+            let (_, _, email) = manager.get_token("claude", true, None).await.unwrap();
+            assert_eq!(email, "b@example.com");
+        }
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
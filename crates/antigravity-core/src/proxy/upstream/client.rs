@@ -17,6 +17,7 @@ const V1_INTERNAL_BASE_URL_FALLBACKS: [&str; 2] = [
 
 pub struct UpstreamClient {
     http_client: Client,
+    base_urls: Vec<String>,
 }
 
 impl UpstreamClient {
@@ -41,7 +42,21 @@ impl UpstreamClient {
 
         let http_client = builder.build().expect("Failed to create HTTP client");
 
-        Self { http_client }
+        Self {
+            http_client,
+            base_urls: V1_INTERNAL_BASE_URL_FALLBACKS
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+
+    /// 覆盖 v1internal 端点列表 (按顺序 Fallback)，用于自建网关或本地 mock
+    pub fn with_base_urls(mut self, base_urls: Vec<String>) -> Self {
+        if !base_urls.is_empty() {
+            self.base_urls = base_urls;
+        }
+        self
     }
 
     /// 构建 v1internal URL
@@ -98,9 +113,9 @@ impl UpstreamClient {
         let mut last_err: Option<String> = None;
 
         // 遍历所有端点，失败时自动切换
-        for (idx, base_url) in V1_INTERNAL_BASE_URL_FALLBACKS.iter().enumerate() {
+        for (idx, base_url) in self.base_urls.iter().enumerate() {
             let url = Self::build_url(base_url, method, query_string);
-            let has_next = idx + 1 < V1_INTERNAL_BASE_URL_FALLBACKS.len();
+            let has_next = idx + 1 < self.base_urls.len();
 
             let response = self
                 .http_client
@@ -120,7 +135,7 @@ impl UpstreamClient {
                                 base_url,
                                 status,
                                 idx + 1,
-                                V1_INTERNAL_BASE_URL_FALLBACKS.len()
+                                self.base_urls.len()
                             );
                         } else {
                             tracing::debug!(
@@ -188,7 +203,7 @@ impl UpstreamClient {
         let mut last_err: Option<String> = None;
 
         // 遍历所有端点，失败时自动切换
-        for (idx, base_url) in V1_INTERNAL_BASE_URL_FALLBACKS.iter().enumerate() {
+        for (idx, base_url) in self.base_urls.iter().enumerate() {
             let url = Self::build_url(base_url, "fetchAvailableModels", None);
 
             let response = self
@@ -223,7 +238,7 @@ impl UpstreamClient {
                     }
 
                     // 如果有下一个端点且当前错误可重试，则切换
-                    let has_next = idx + 1 < V1_INTERNAL_BASE_URL_FALLBACKS.len();
+                    let has_next = idx + 1 < self.base_urls.len();
                     if has_next && Self::should_try_next_endpoint(status) {
                         tracing::warn!(
                             "fetchAvailableModels returned {} at {}, trying next endpoint",
@@ -243,7 +258,7 @@ impl UpstreamClient {
                     last_err = Some(msg);
 
                     // 如果是最后一个端点，退出循环
-                    if idx + 1 >= V1_INTERNAL_BASE_URL_FALLBACKS.len() {
+                    if idx + 1 >= self.base_urls.len() {
                         break;
                     }
                     continue;
@@ -1,8 +1,13 @@
-// 429 重试策略
-// Duration 解析
+// 上游重试策略
+// Claude / OpenAI / Gemini 处理器共用：按状态码决定换账号、同账号退避或直接失败，
+// 并统一处理最大尝试次数、总时限、抖动与 Retry-After / RetryInfo
 
+use antigravity_shared::proxy::config::{
+    RetryAction, RetryBackoff, RetryPolicyConfig, RetryStatusRule,
+};
 use once_cell::sync::Lazy;
 use regex::Regex;
+use std::time::{Duration, Instant};
 
 static DURATION_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"([\d.]+)\s*(ms|s|m|h)").unwrap());
 
@@ -64,6 +69,192 @@ pub fn parse_retry_delay(error_text: &str) -> Option<u64> {
     None
 }
 
+/// 解析 `Retry-After` 响应头 (秒数或 HTTP 日期)，返回毫秒
+pub fn parse_retry_after_header(value: &str) -> Option<u64> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<f64>() {
+        return (secs >= 0.0).then(|| (secs * 1000.0).round() as u64);
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let ms = (at.with_timezone(&chrono::Utc) - chrono::Utc::now()).num_milliseconds();
+    Some(ms.max(0) as u64)
+}
+
+/// 服务端建议的等待时间上额外增加的余量
+const RETRY_AFTER_PADDING_MS: u64 = 200;
+
+/// 一次失败后的重试判定
+#[derive(Debug, Clone, PartialEq)]
+pub enum RetryDecision {
+    /// 等待 `delay` 后重试，`rotate` 为 true 时换账号
+    Retry { delay: Duration, rotate: bool },
+    /// 尝试次数或总时限已用尽
+    Exhausted,
+    /// 不可重试的状态码，直接返回上游错误
+    FailFast,
+}
+
+/// 单个请求的重试状态
+pub struct RetryState {
+    policy: RetryPolicyConfig,
+    max_attempts: u32,
+    /// 已计入 max_attempts 的失败次数
+    attempts: u32,
+    started: Instant,
+    /// (状态码, 连续次数)，网络错误记为 0
+    streak: (u16, u32),
+    rotate: bool,
+    exhausted: bool,
+}
+
+impl RetryState {
+    pub fn new(policy: RetryPolicyConfig, pool_size: usize) -> Self {
+        // 没有可用账号时只尝试一次
+        let max_attempts = if pool_size == 0 {
+            1
+        } else {
+            policy.max_attempts.max(1)
+        };
+        Self {
+            policy,
+            max_attempts,
+            attempts: 0,
+            started: Instant::now(),
+            streak: (0, 0),
+            rotate: false,
+            exhausted: false,
+        }
+    }
+
+    /// 当前尝试序号 (从 1 开始，用于日志)
+    pub fn attempt(&self) -> u32 {
+        self.attempts + 1
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted || self.attempts >= self.max_attempts
+    }
+
+    /// 下一次尝试是否需要换账号 (读取后清除)
+    pub fn take_rotation(&mut self) -> bool {
+        std::mem::take(&mut self.rotate)
+    }
+
+    /// 切换到回退模型时重新计数 (总时限不重置)
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+        self.streak = (0, 0);
+        self.rotate = false;
+        self.exhausted = false;
+    }
+
+    /// 网络错误 / 上游不可达：不等待，换账号重试
+    pub fn on_network_error(&mut self) -> RetryDecision {
+        let elapsed = self.started.elapsed();
+        self.decide(None, None, "", elapsed)
+    }
+
+    /// 上游返回非 2xx 状态码
+    pub fn on_status(
+        &mut self,
+        status: u16,
+        retry_after: Option<&str>,
+        error_text: &str,
+    ) -> RetryDecision {
+        let elapsed = self.started.elapsed();
+        self.decide(Some(status), retry_after, error_text, elapsed)
+    }
+
+    fn decide(
+        &mut self,
+        status: Option<u16>,
+        retry_after: Option<&str>,
+        error_text: &str,
+        elapsed: Duration,
+    ) -> RetryDecision {
+        let rule = match status {
+            Some(code) => match self.policy.rule_for(code) {
+                Some(rule) if rule.action != RetryAction::FailFast => Some(rule.clone()),
+                _ => return RetryDecision::FailFast,
+            },
+            None => None,
+        };
+
+        let deadline = (self.policy.total_deadline_secs > 0)
+            .then(|| Duration::from_secs(self.policy.total_deadline_secs));
+        // 没有总时限时所有重试都计数，避免无限重试
+        if deadline.is_none() || rule.as_ref().is_none_or(|r| r.counts_toward_limit) {
+            self.attempts += 1;
+        }
+        let code = status.unwrap_or(0);
+        self.streak = if self.streak.0 == code {
+            (code, self.streak.1 + 1)
+        } else {
+            (code, 1)
+        };
+        if self.attempts >= self.max_attempts {
+            self.exhausted = true;
+            return RetryDecision::Exhausted;
+        }
+
+        let Some(rule) = rule else {
+            self.rotate = true;
+            return RetryDecision::Retry {
+                delay: Duration::ZERO,
+                rotate: true,
+            };
+        };
+        let delay = self
+            .server_hint_ms(retry_after, error_text)
+            .map(Duration::from_millis)
+            .unwrap_or_else(|| self.backoff(&rule, self.streak.1));
+        if deadline.is_some_and(|deadline| elapsed + delay > deadline) {
+            self.exhausted = true;
+            return RetryDecision::Exhausted;
+        }
+
+        self.rotate = rule.action == RetryAction::RotateAccount;
+        RetryDecision::Retry {
+            delay,
+            rotate: self.rotate,
+        }
+    }
+
+    /// 上游 RetryInfo / quotaResetDelay 优先，其次 `Retry-After` 头
+    fn server_hint_ms(&self, retry_after: Option<&str>, error_text: &str) -> Option<u64> {
+        if !self.policy.honor_retry_after {
+            return None;
+        }
+        let hint = parse_retry_delay(error_text)
+            .or_else(|| retry_after.and_then(parse_retry_after_header))?;
+        Some(
+            hint.saturating_add(RETRY_AFTER_PADDING_MS)
+                .min(self.policy.max_retry_after_ms),
+        )
+    }
+
+    fn backoff(&self, rule: &RetryStatusRule, streak: u32) -> Duration {
+        let n = streak.max(1);
+        let base = rule.base_delay_ms;
+        let ms = match rule.backoff {
+            RetryBackoff::Fixed => base,
+            RetryBackoff::Linear => base.saturating_mul(n as u64),
+            RetryBackoff::Exponential => {
+                base.saturating_mul(2_u64.saturating_pow(n.saturating_sub(1).min(32)))
+            }
+        }
+        .min(rule.max_delay_ms);
+
+        let jitter_range = ms as f64 * self.policy.jitter_percent;
+        let jitter = (rand::random::<f64>() - 0.5) * 2.0 * jitter_range;
+        Duration::from_millis((ms as f64 + jitter).max(0.0) as u64)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -89,4 +280,197 @@ mod tests {
 
         assert_eq!(parse_retry_delay(error_json), Some(1204));
     }
+
+    /// 上游一次调用的模拟结果
+    enum Reply {
+        Ok,
+        Status(u16, &'static str),
+        NetworkError,
+    }
+
+    /// 按脚本应答的模拟上游：驱动 RetryState，使用累计等待时间作为虚拟时钟，
+    /// 返回 (每次调用使用的账号, 每次重试的等待, 最终结果)
+    fn drive(
+        policy: RetryPolicyConfig,
+        pool_size: usize,
+        script: Vec<Reply>,
+    ) -> (Vec<usize>, Vec<u64>, Result<(), RetryDecision>) {
+        let mut state = RetryState::new(policy, pool_size);
+        let mut script = script.into_iter();
+        let mut account = 0;
+        let mut accounts = Vec::new();
+        let mut delays = Vec::new();
+        let mut elapsed = Duration::ZERO;
+        loop {
+            if state.is_exhausted() {
+                return (accounts, delays, Err(RetryDecision::Exhausted));
+            }
+            if state.take_rotation() {
+                account = (account + 1) % pool_size;
+            }
+            accounts.push(account);
+            let decision = match script.next().unwrap_or(Reply::Ok) {
+                Reply::Ok => return (accounts, delays, Ok(())),
+                Reply::Status(code, body) => state.decide(Some(code), None, body, elapsed),
+                Reply::NetworkError => state.decide(None, None, "", elapsed),
+            };
+            match decision {
+                RetryDecision::Retry { delay, .. } => {
+                    delays.push(delay.as_millis() as u64);
+                    elapsed += delay;
+                }
+                RetryDecision::Exhausted => continue,
+                other => return (accounts, delays, Err(other)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_overload_retries_same_account_with_backoff() {
+        let script = vec![Reply::Status(503, ""), Reply::Status(503, ""), Reply::Ok];
+        let (accounts, delays, result) = drive(RetryPolicyConfig::default(), 3, script);
+        assert_eq!(result, Ok(()));
+        assert_eq!(accounts, vec![0, 0, 0]);
+        assert_eq!(delays, vec![1000, 2000]);
+    }
+
+    #[test]
+    fn test_account_errors_rotate() {
+        let script = vec![
+            Reply::Status(429, ""),
+            Reply::Status(500, ""),
+            Reply::NetworkError,
+            Reply::Ok,
+        ];
+        let (accounts, delays, result) = drive(RetryPolicyConfig::default(), 4, script);
+        assert_eq!(result, Ok(()));
+        assert_eq!(accounts, vec![0, 1, 2, 3]);
+        assert_eq!(delays, vec![1000, 500, 0]);
+    }
+
+    #[test]
+    fn test_unlisted_status_fails_fast() {
+        let (accounts, _, result) = drive(
+            RetryPolicyConfig::default(),
+            2,
+            vec![Reply::Status(404, "")],
+        );
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(result, Err(RetryDecision::FailFast));
+
+        let mut policy = RetryPolicyConfig::default();
+        policy.rules[0].action = RetryAction::FailFast; // 429
+        let (_, _, result) = drive(policy, 2, vec![Reply::Status(429, "")]);
+        assert_eq!(result, Err(RetryDecision::FailFast));
+    }
+
+    #[test]
+    fn test_max_attempts_and_empty_pool() {
+        let policy = RetryPolicyConfig {
+            max_attempts: 3,
+            ..Default::default()
+        };
+        let script = (0..10).map(|_| Reply::Status(429, "")).collect();
+        let (accounts, delays, result) = drive(policy.clone(), 5, script);
+        assert_eq!(accounts.len(), 3);
+        assert_eq!(delays, vec![1000, 2000]);
+        assert_eq!(result, Err(RetryDecision::Exhausted));
+
+        let (accounts, _, _) = drive(policy, 0, vec![Reply::Status(503, "")]);
+        assert_eq!(accounts.len(), 1);
+    }
+
+    #[test]
+    fn test_uncounted_status_is_bounded_by_deadline() {
+        let policy = RetryPolicyConfig {
+            max_attempts: 2,
+            total_deadline_secs: 5,
+            ..Default::default()
+        };
+        // 529 不计入 max_attempts，可以超过 2 次
+        let mut script: Vec<Reply> = (0..4).map(|_| Reply::Status(529, "")).collect();
+        script.push(Reply::Ok);
+        let (accounts, delays, result) = drive(policy.clone(), 2, script);
+        assert_eq!(result, Ok(()));
+        assert_eq!(accounts, vec![0; 5]);
+        assert_eq!(delays, vec![1000; 4]);
+
+        // 持续过载时在 5 秒总时限内停止
+        let script = (0..100).map(|_| Reply::Status(529, "")).collect();
+        let (accounts, _, result) = drive(policy, 2, script);
+        assert_eq!(accounts.len(), 6);
+        assert_eq!(result, Err(RetryDecision::Exhausted));
+    }
+
+    #[test]
+    fn test_server_retry_hints() {
+        let retry_info = r#"{"error":{"details":[{"@type":"type.googleapis.com/google.rpc.RetryInfo","retryDelay":"1.5s"}]}}"#;
+        let mut state = RetryState::new(RetryPolicyConfig::default(), 2);
+        assert_eq!(
+            state.decide(Some(429), None, retry_info, Duration::ZERO),
+            RetryDecision::Retry {
+                delay: Duration::from_millis(1700),
+                rotate: true
+            }
+        );
+        // Retry-After 头同样适用于同账号重试的状态码
+        assert_eq!(
+            state.decide(Some(503), Some("3"), "", Duration::ZERO),
+            RetryDecision::Retry {
+                delay: Duration::from_millis(3200),
+                rotate: false
+            }
+        );
+        // 超过上限时按 max_retry_after_ms 等待
+        assert_eq!(
+            state.decide(Some(503), Some("120"), "", Duration::ZERO),
+            RetryDecision::Retry {
+                delay: Duration::from_millis(10_000),
+                rotate: false
+            }
+        );
+
+        let mut state = RetryState::new(
+            RetryPolicyConfig {
+                honor_retry_after: false,
+                ..Default::default()
+            },
+            2,
+        );
+        assert_eq!(
+            state.decide(Some(429), Some("3"), retry_info, Duration::ZERO),
+            RetryDecision::Retry {
+                delay: Duration::from_millis(1000),
+                rotate: true
+            }
+        );
+    }
+
+    #[test]
+    fn test_jitter_stays_in_range() {
+        let policy = RetryPolicyConfig {
+            jitter_percent: 0.5,
+            ..Default::default()
+        };
+        for _ in 0..50 {
+            let mut state = RetryState::new(policy.clone(), 2);
+            match state.decide(Some(529), None, "", Duration::ZERO) {
+                RetryDecision::Retry { delay, .. } => {
+                    assert!((500..=1500).contains(&(delay.as_millis() as u64)))
+                }
+                other => panic!("unexpected decision {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_retry_after_header() {
+        assert_eq!(parse_retry_after_header("2"), Some(2000));
+        assert_eq!(parse_retry_after_header(" 0.5 "), Some(500));
+        assert_eq!(
+            parse_retry_after_header("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(0)
+        );
+        assert_eq!(parse_retry_after_header("soon"), None);
+    }
 }
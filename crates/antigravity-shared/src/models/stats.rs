@@ -106,6 +106,8 @@ pub struct ProxyStats {
     pub total_input_tokens: u64,
     #[serde(default)]
     pub total_output_tokens: u64,
+    #[serde(default)]
+    pub total_cached_tokens: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -127,6 +129,78 @@ pub struct ProxyRequestLog {
     pub response_body: Option<String>,
     pub input_tokens: Option<u32>,
     pub output_tokens: Option<u32>,
+    /// 发起请求的客户端 key 名称 (使用主 api_key 或未鉴权时为空)
+    #[serde(default)]
+    pub client_key: Option<String>,
+    /// 命中上下文缓存的输入 token (Gemini `cachedContentTokenCount`)
+    #[serde(default)]
+    pub cached_tokens: Option<u32>,
+}
+
+/// 用量汇总的时间粒度 (按 UTC 对齐)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageBucket {
+    Hour,
+    #[default]
+    Day,
+}
+
+impl UsageBucket {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UsageBucket::Hour => "hour",
+            UsageBucket::Day => "day",
+        }
+    }
+
+    pub fn millis(self) -> i64 {
+        match self {
+            UsageBucket::Hour => 60 * 60 * 1000,
+            UsageBucket::Day => 24 * 60 * 60 * 1000,
+        }
+    }
+}
+
+/// 用量汇总的分组维度
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageGroupBy {
+    #[default]
+    Model,
+    MappedModel,
+    Account,
+    ClientKey,
+    /// 仅按时间汇总
+    None,
+}
+
+impl UsageGroupBy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UsageGroupBy::Model => "model",
+            UsageGroupBy::MappedModel => "mapped_model",
+            UsageGroupBy::Account => "account",
+            UsageGroupBy::ClientKey => "client_key",
+            UsageGroupBy::None => "none",
+        }
+    }
+}
+
+/// 单个时间段 + 分组的用量汇总
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct UsageRollup {
+    /// 时间段起点 (毫秒时间戳)
+    pub period_start: i64,
+    /// 分组值 (模型 / 账号 / key 名称)，未记录或 group_by=none 时为空
+    pub group: Option<String>,
+    pub requests: u64,
+    pub errors: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cached_tokens: u64,
+    /// 按公开 API 价格估算的费用 (美元)
+    pub estimated_cost_usd: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
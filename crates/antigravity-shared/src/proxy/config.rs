@@ -145,6 +145,129 @@ pub struct ZaiConfig {
     pub mcp: ZaiMcpConfig,
 }
 
+impl ZaiConfig {
+    /// z.ai 作为 Anthropic 兼容 provider 参与统一调度 (id 为 `zai`)
+    ///
+    /// 未在 model_mapping 中的 claude-* 模型按 opus / haiku / sonnet 映射到默认 GLM 模型。
+    pub fn as_provider(&self) -> UpstreamProvider {
+        let mut model_mapping = self.model_mapping.clone();
+        for (pattern, target) in [
+            ("claude-*opus*", &self.models.opus),
+            ("claude-*haiku*", &self.models.haiku),
+            ("claude-*", &self.models.sonnet),
+        ] {
+            model_mapping
+                .entry(pattern.to_string())
+                .or_insert_with(|| target.clone());
+        }
+        UpstreamProvider {
+            id: "zai".to_string(),
+            enabled: self.enabled,
+            base_url: self.base_url.clone(),
+            api_key: self.api_key.clone(),
+            auth_style: ProviderAuthStyle::Auto,
+            protocol: ProviderProtocol::Anthropic,
+            dispatch_mode: self.dispatch_mode.clone(),
+            models: Vec::new(),
+            model_mapping,
+        }
+    }
+}
+
+// --- Model Router ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteMatchKind {
+    /// 完全相等
+    #[default]
+    Exact,
+    /// 通配符 (`*` 匹配任意串，`?` 匹配单个字符)
+    Glob,
+    /// 正则表达式 (未锚定，需要全匹配请自行加 `^...$`)
+    Regex,
+}
+
+/// 有序路由规则：按配置顺序匹配，首条命中生效
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Validate)]
+pub struct ModelRouteRule {
+    /// 规则名称 (仅用于日志与监控展示)
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default, rename = "match")]
+    pub match_kind: RouteMatchKind,
+    #[validate(length(min = 1))]
+    pub pattern: String,
+    /// 限定生效的协议，留空表示全部协议
+    #[serde(default)]
+    pub protocols: Vec<Protocol>,
+    #[validate(length(min = 1))]
+    pub target: String,
+    /// 主模型配额在所有账号上耗尽时依次尝试的回退模型
+    #[serde(default)]
+    pub fallbacks: Vec<String>,
+}
+
+// --- Upstream Providers ---
+
+/// provider 调度模式 (与 z.ai 共用)
+pub type DispatchMode = ZaiDispatchMode;
+
+/// 第三方 provider 使用的 API 协议
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderProtocol {
+    /// Anthropic Messages API (`/v1/messages`)
+    #[default]
+    Anthropic,
+    /// OpenAI Chat Completions API (`/v1/chat/completions`)，如 vLLM / Ollama
+    #[serde(rename = "openai")]
+    OpenAI,
+}
+
+/// provider 的鉴权方式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderAuthStyle {
+    /// Anthropic 协议沿用客户端的鉴权头 (x-api-key / Authorization)，OpenAI 协议使用 Bearer
+    #[default]
+    Auto,
+    XApiKey,
+    Bearer,
+    /// 不发送鉴权头 (本地 Ollama 等)
+    None,
+}
+
+/// 第三方上游 provider (Anthropic / OpenAI 兼容接口)
+///
+/// `base_url` 不含 `/v1`，请求路径按协议拼接 (如 `http://127.0.0.1:11434` + `/v1/chat/completions`)。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Validate)]
+pub struct UpstreamProvider {
+    /// 唯一标识，也可作为模型前缀强制指定 provider (如 `ollama:llama3`)
+    #[validate(length(min = 1, max = 64))]
+    pub id: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[validate(url)]
+    pub base_url: String,
+    #[serde(default)]
+    pub api_key: String,
+    #[serde(default)]
+    pub auth_style: ProviderAuthStyle,
+    #[serde(default)]
+    pub protocol: ProviderProtocol,
+    #[serde(default)]
+    pub dispatch_mode: DispatchMode,
+    /// 由该 provider 处理的客户端模型 (支持 `*` / `?` 通配符)，留空表示所有模型
+    #[serde(default)]
+    pub models: Vec<String>,
+    /// 客户端模型 -> provider 模型 (支持通配符，未命中时原样转发)
+    #[serde(default)]
+    pub model_mapping: HashMap<String, String>,
+}
+
 // --- Other Config Structs ---
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default, Validate)]
@@ -155,6 +278,203 @@ pub struct ExperimentalConfig {
     pub enable_tool_loop_recovery: bool,
     #[serde(default = "default_true")]
     pub enable_cross_model_checks: bool,
+    /// count_tokens 转发至上游 countTokens (失败时回退本地估算)
+    #[serde(default)]
+    pub upstream_count_tokens: bool,
+}
+
+/// SmartProber 对冲请求配置
+///
+/// 账号接近 AIMD 工作阈值时，按使用率选择策略：廉价探测 (70%+)、
+/// P95 延迟后对冲 (85%+)、立即对冲 (95%+)。对冲请求使用另一个账号。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Validate)]
+pub struct HedgingConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// 延迟对冲的等待时间 (毫秒)，通常取上游 P95 首包延迟
+    #[serde(default = "default_hedge_p95_latency_ms")]
+    #[validate(range(min = 100, max = 60000))]
+    pub p95_latency_ms: u64,
+    /// 对冲延迟抖动比例 (0.2 = ±20%)
+    #[serde(default = "default_hedge_jitter_percent")]
+    #[validate(range(min = 0.0, max = 1.0))]
+    pub jitter_percent: f64,
+    /// 成功请求后额外发送 1 token 探测请求以更快放宽 AIMD 阈值 (会消耗少量配额)
+    #[serde(default)]
+    pub cheap_probes: bool,
+}
+
+impl Default for HedgingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            p95_latency_ms: default_hedge_p95_latency_ms(),
+            jitter_percent: default_hedge_jitter_percent(),
+            cheap_probes: false,
+        }
+    }
+}
+
+/// 上游错误的重试动作
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RetryAction {
+    /// 换一个账号重试 (账号级问题：限流、鉴权、500)
+    RotateAccount,
+    /// 退避后用同一账号重试 (服务端过载，换账号无意义)
+    SameAccount,
+    /// 不重试，直接把上游错误返回给客户端
+    FailFast,
+}
+
+/// 重试等待时间的增长方式 (n 为同一状态码的连续重试次数)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RetryBackoff {
+    /// 固定 base_delay_ms
+    #[default]
+    Fixed,
+    /// base_delay_ms * n
+    Linear,
+    /// base_delay_ms * 2^(n-1)
+    Exponential,
+}
+
+/// 单个上游状态码的重试规则
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Validate)]
+pub struct RetryStatusRule {
+    #[validate(range(min = 100, max = 599))]
+    pub status: u16,
+    pub action: RetryAction,
+    #[serde(default)]
+    pub backoff: RetryBackoff,
+    #[serde(default)]
+    pub base_delay_ms: u64,
+    /// 退避等待上限 (毫秒)
+    #[serde(default = "default_retry_max_delay_ms")]
+    pub max_delay_ms: u64,
+    /// 为 false 时该状态码的重试不消耗 max_attempts，只受 total_deadline_secs 约束 (如 529 过载)
+    #[serde(default = "default_true")]
+    pub counts_toward_limit: bool,
+}
+
+impl RetryStatusRule {
+    fn new(status: u16, action: RetryAction, backoff: RetryBackoff, base_delay_ms: u64) -> Self {
+        Self {
+            status,
+            action,
+            backoff,
+            base_delay_ms,
+            max_delay_ms: default_retry_max_delay_ms(),
+            counts_toward_limit: true,
+        }
+    }
+}
+
+/// Claude / OpenAI / Gemini 处理器共用的上游重试策略
+///
+/// 未在 `rules` 中列出的状态码直接返回错误；网络错误总是换账号重试。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Validate)]
+pub struct RetryPolicyConfig {
+    /// 单个请求的最大尝试次数 (含首次)
+    #[serde(default = "default_retry_max_attempts")]
+    #[validate(range(min = 1, max = 100))]
+    pub max_attempts: u32,
+    /// 单个请求重试的总时限 (秒)，0 表示不限制
+    #[serde(default = "default_retry_total_deadline_secs")]
+    pub total_deadline_secs: u64,
+    /// 退避等待抖动比例 (0.2 = ±20%)
+    #[serde(default)]
+    #[validate(range(min = 0.0, max = 1.0))]
+    pub jitter_percent: f64,
+    /// 优先使用上游 `Retry-After` / RetryInfo 给出的等待时间
+    #[serde(default = "default_true")]
+    pub honor_retry_after: bool,
+    /// 上游建议等待时间的上限 (毫秒)，超过时按该值等待
+    #[serde(default = "default_retry_max_retry_after_ms")]
+    pub max_retry_after_ms: u64,
+    #[serde(default = "default_retry_rules")]
+    #[validate(nested)]
+    pub rules: Vec<RetryStatusRule>,
+}
+
+impl Default for RetryPolicyConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_retry_max_attempts(),
+            total_deadline_secs: default_retry_total_deadline_secs(),
+            jitter_percent: 0.0,
+            honor_retry_after: true,
+            max_retry_after_ms: default_retry_max_retry_after_ms(),
+            rules: default_retry_rules(),
+        }
+    }
+}
+
+impl RetryPolicyConfig {
+    pub fn rule_for(&self, status: u16) -> Option<&RetryStatusRule> {
+        self.rules.iter().find(|r| r.status == status)
+    }
+}
+
+/// 请求日志持久化保留策略 (headless 模式写入 proxy_logs.db)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Validate)]
+pub struct RequestLogConfig {
+    /// 超过该天数的日志会被清理，0 表示不按时间清理
+    #[serde(default = "default_request_log_max_age_days")]
+    pub max_age_days: u32,
+    /// 数据库占用超过该大小 (MB) 时从最旧的日志开始清理，0 表示不限制
+    #[serde(default = "default_request_log_max_size_mb")]
+    pub max_size_mb: u64,
+}
+
+impl Default for RequestLogConfig {
+    fn default() -> Self {
+        Self {
+            max_age_days: default_request_log_max_age_days(),
+            max_size_mb: default_request_log_max_size_mb(),
+        }
+    }
+}
+
+/// 客户端 API Key：多人 / CI 共享代理时按 key 区分用量并单独限额
+///
+/// 主 `api_key` 不受这些限制；限制字段留空表示不限制。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Validate)]
+pub struct ClientApiKey {
+    /// 稳定标识 (管理接口撤销时使用)
+    #[validate(length(min = 1))]
+    pub id: String,
+    /// 显示名称，记录在请求日志中
+    #[validate(length(min = 1, max = 64))]
+    pub name: String,
+    #[validate(length(min = 8))]
+    pub key: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 允许请求的模型 (支持 `*` / `?` 通配符，匹配客户端请求的原始模型名)
+    #[serde(default)]
+    pub allowed_models: Vec<String>,
+    #[serde(default)]
+    pub allowed_protocols: Vec<Protocol>,
+    #[serde(default)]
+    #[validate(range(min = 1))]
+    pub requests_per_minute: Option<u32>,
+    /// 每日 (UTC) 输入 + 输出 token 预算
+    #[serde(default)]
+    #[validate(range(min = 1))]
+    pub daily_token_budget: Option<u64>,
+    /// 过期时间 (Unix 秒)
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+    #[serde(default)]
+    pub created_at: i64,
+}
+
+impl ClientApiKey {
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Validate)]
@@ -180,8 +500,16 @@ pub struct ProxyConfig {
     #[validate(length(min = 1))]
     pub api_key: String,
     pub auto_start: bool,
+    /// 附加的客户端 API Key (带独立的模型/协议白名单、限流与预算)
+    #[serde(default)]
+    #[validate(nested)]
+    pub api_keys: Vec<ClientApiKey>,
     #[serde(default)]
     pub custom_mapping: HashMap<String, String>,
+    /// 有序路由规则，优先于 custom_mapping
+    #[serde(default)]
+    #[validate(nested)]
+    pub router_rules: Vec<ModelRouteRule>,
     #[validate(range(min = 30, max = 3600))]
     #[serde(default = "default_request_timeout")]
     pub request_timeout: u64,
@@ -193,12 +521,25 @@ pub struct ProxyConfig {
     #[serde(default)]
     #[validate(nested)]
     pub zai: ZaiConfig,
+    /// 其他 Anthropic / OpenAI 兼容 provider
+    #[serde(default)]
+    #[validate(nested)]
+    pub providers: Vec<UpstreamProvider>,
     #[serde(default)]
     #[validate(nested)]
     pub scheduling: StickySessionConfig,
     #[serde(default)]
     #[validate(nested)]
     pub experimental: ExperimentalConfig,
+    #[serde(default)]
+    #[validate(nested)]
+    pub hedging: HedgingConfig,
+    #[serde(default)]
+    #[validate(nested)]
+    pub request_log: RequestLogConfig,
+    #[serde(default)]
+    #[validate(nested)]
+    pub retry: RetryPolicyConfig,
 }
 
 impl Default for ProxyConfig {
@@ -210,13 +551,19 @@ impl Default for ProxyConfig {
             port: 8045,
             api_key: String::new(),
             auto_start: true,
+            api_keys: Vec::new(),
             custom_mapping: HashMap::new(),
+            router_rules: Vec::new(),
             request_timeout: 120,
             enable_logging: false,
             upstream_proxy: UpstreamProxyConfig::default(),
             zai: ZaiConfig::default(),
+            providers: Vec::new(),
             scheduling: StickySessionConfig::default(),
             experimental: ExperimentalConfig::default(),
+            hedging: HedgingConfig::default(),
+            request_log: RequestLogConfig::default(),
+            retry: RetryPolicyConfig::default(),
         }
     }
 }
@@ -256,3 +603,55 @@ fn default_zai_haiku_model() -> String {
 fn default_request_timeout() -> u64 {
     120
 }
+
+fn default_hedge_p95_latency_ms() -> u64 {
+    2500
+}
+
+fn default_hedge_jitter_percent() -> f64 {
+    0.2
+}
+
+fn default_request_log_max_age_days() -> u32 {
+    30
+}
+
+fn default_request_log_max_size_mb() -> u64 {
+    512
+}
+
+fn default_retry_max_attempts() -> u32 {
+    10
+}
+
+fn default_retry_total_deadline_secs() -> u64 {
+    3600
+}
+
+fn default_retry_max_delay_ms() -> u64 {
+    10_000
+}
+
+fn default_retry_max_retry_after_ms() -> u64 {
+    10_000
+}
+
+fn default_retry_rules() -> Vec<RetryStatusRule> {
+    use RetryAction::*;
+    use RetryBackoff::*;
+    vec![
+        RetryStatusRule::new(429, RotateAccount, Linear, 1000),
+        RetryStatusRule::new(500, RotateAccount, Linear, 500),
+        RetryStatusRule::new(401, RotateAccount, Fixed, 100),
+        RetryStatusRule::new(403, RotateAccount, Fixed, 100),
+        RetryStatusRule {
+            max_delay_ms: 8000,
+            ..RetryStatusRule::new(503, SameAccount, Exponential, 1000)
+        },
+        // 529 过载按 1 秒间隔持续重试，直到总时限
+        RetryStatusRule {
+            counts_toward_limit: false,
+            ..RetryStatusRule::new(529, SameAccount, Fixed, 1000)
+        },
+    ]
+}
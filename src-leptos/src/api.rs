@@ -6,7 +6,9 @@
 use serde::{Serialize, de::DeserializeOwned};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::JsFuture;
-use web_sys::{Request, RequestInit, Response};
+use web_sys::{EventSource, MessageEvent, Request, RequestInit, Response};
+
+use crate::types::ProxyRequestLog;
 
 const API_BASE: &str = "/api";
 
@@ -93,6 +95,79 @@ pub async fn api_post<A: Serialize, R: DeserializeOwned>(
     serde_wasm_bindgen::from_value(json).map_err(|e| format!("Deserialize failed: {}", e))
 }
 
+/// Make a DELETE request to the API
+pub async fn api_delete<R: DeserializeOwned>(endpoint: &str) -> Result<R, String> {
+    let url = format!("{}{}", API_BASE, endpoint);
+
+    let opts = RequestInit::new();
+    opts.set_method("DELETE");
+
+    let request = Request::new_with_str_and_init(&url, &opts)
+        .map_err(|e| format!("Failed to create request: {:?}", e))?;
+
+    let window = web_sys::window().ok_or("No window")?;
+    let resp_value = JsFuture::from(window.fetch_with_request(&request))
+        .await
+        .map_err(|e| format!("Fetch failed: {:?}", e))?;
+
+    let resp: Response = resp_value
+        .dyn_into()
+        .map_err(|_| "Response is not a Response")?;
+
+    if !resp.ok() {
+        return Err(format!("HTTP error: {}", resp.status()));
+    }
+
+    let json = JsFuture::from(
+        resp.json()
+            .map_err(|e| format!("JSON parse failed: {:?}", e))?,
+    )
+    .await
+    .map_err(|e| format!("JSON future failed: {:?}", e))?;
+
+    serde_wasm_bindgen::from_value(json).map_err(|e| format!("Deserialize failed: {}", e))
+}
+
+/// Live request feed subscription (`/api/monitor/stream`, Server-Sent Events)
+///
+/// The underlying `EventSource` is closed when this value is dropped.
+pub struct ProxyLogStream {
+    source: EventSource,
+    _on_request: Closure<dyn Fn(MessageEvent)>,
+}
+
+impl Drop for ProxyLogStream {
+    fn drop(&mut self) {
+        self.source.close();
+    }
+}
+
+/// Subscribe to completed proxy requests. Bodies are redacted server-side.
+pub fn subscribe_proxy_logs(
+    on_log: impl Fn(ProxyRequestLog) + 'static,
+) -> Result<ProxyLogStream, String> {
+    let url = format!("{}/monitor/stream?redact=true", API_BASE);
+    let source = EventSource::new(&url).map_err(|e| format!("Failed to open stream: {:?}", e))?;
+
+    let on_request = Closure::<dyn Fn(MessageEvent)>::new(move |event: MessageEvent| {
+        let Some(data) = event.data().as_string() else {
+            return;
+        };
+        match serde_json::from_str::<ProxyRequestLog>(&data) {
+            Ok(log) => on_log(log),
+            Err(e) => log::warn!("Invalid monitor event: {}", e),
+        }
+    });
+    source
+        .add_event_listener_with_callback("request", on_request.as_ref().unchecked_ref())
+        .map_err(|e| format!("Failed to subscribe: {:?}", e))?;
+
+    Ok(ProxyLogStream {
+        source,
+        _on_request: on_request,
+    })
+}
+
 // Re-export common command wrappers
 pub mod commands {
     use super::*;
@@ -132,6 +207,8 @@ pub mod commands {
         pub email: String,
         pub name: Option<String>,
         pub disabled: bool,
+        #[serde(default)]
+        pub proxy_disabled: bool,
         pub is_current: bool,
         pub gemini_quota: Option<i32>,
         pub claude_quota: Option<i32>,
@@ -177,7 +254,7 @@ pub mod commands {
                 disabled: self.disabled,
                 disabled_reason: None,
                 disabled_at: None,
-                proxy_disabled: false,
+                proxy_disabled: self.proxy_disabled,
                 proxy_disabled_reason: None,
                 proxy_disabled_at: None,
                 created_at: 0,
@@ -212,42 +289,231 @@ pub mod commands {
         Ok(())
     }
 
-    pub async fn delete_account(_account_id: &str) -> Result<(), String> {
-        // TODO: Implement delete endpoint
-        Err("Not implemented".to_string())
+    pub async fn add_account(refresh_token: &str) -> Result<Account, String> {
+        let api_account: ApiAccount = api_post(
+            "/accounts",
+            &serde_json::json!({ "refresh_token": refresh_token }),
+        )
+        .await?;
+        Ok(api_account.into_account())
     }
 
-    pub async fn delete_accounts(_account_ids: &[String]) -> Result<(), String> {
-        // TODO: Implement batch delete endpoint
-        Err("Not implemented".to_string())
+    pub async fn delete_account(account_id: &str) -> Result<(), String> {
+        let _: bool = api_delete(&format!("/accounts/{}", account_id)).await?;
+        Ok(())
+    }
+
+    pub async fn delete_accounts(account_ids: &[String]) -> Result<(), String> {
+        let _: bool = api_post(
+            "/accounts/delete",
+            &serde_json::json!({ "account_ids": account_ids }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn reorder_accounts(account_ids: &[String]) -> Result<(), String> {
+        let _: bool = api_post(
+            "/accounts/reorder",
+            &serde_json::json!({ "account_ids": account_ids }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn toggle_proxy_status(
+        account_id: &str,
+        enable: bool,
+        reason: Option<&str>,
+    ) -> Result<(), String> {
+        let _: bool = api_post(
+            &format!("/accounts/{}/proxy", account_id),
+            &serde_json::json!({ "enable": enable, "reason": reason }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    // ========== Account Health ==========
+
+    #[derive(serde::Deserialize, Clone, Debug, PartialEq)]
+    pub struct AccountHealthOverview {
+        pub accounts: Vec<AccountHealthEntry>,
+        pub healthy_count: usize,
+        pub disabled_count: usize,
+    }
+
+    /// Why (or whether) the proxy pool skips an account
+    #[derive(serde::Deserialize, Clone, Debug, PartialEq)]
+    pub struct AccountHealthEntry {
+        pub account_id: String,
+        pub email: String,
+        pub available: bool,
+        pub skip_reason: Option<String>,
+        pub rate_limited: bool,
+        pub rate_limit_reset_seconds: Option<u64>,
+        #[serde(default)]
+        pub model_lockouts: Vec<ModelLockoutDetails>,
+        pub health: Option<HealthDetails>,
+        pub circuit: CircuitDetails,
+        pub adaptive: AdaptiveLimitDetails,
+    }
+
+    /// Quota exhausted for one model; the account still serves other models
+    #[derive(serde::Deserialize, Clone, Debug, PartialEq)]
+    pub struct ModelLockoutDetails {
+        pub model: String,
+        pub reset_seconds: u64,
+    }
+
+    #[derive(serde::Deserialize, Clone, Debug, PartialEq)]
+    pub struct HealthDetails {
+        pub status: String,
+        pub consecutive_errors: u32,
+        pub is_disabled: bool,
+        pub cooldown_remaining_seconds: Option<u64>,
+        pub last_error_message: Option<String>,
+        pub success_rate: f64,
+    }
+
+    #[derive(serde::Deserialize, Clone, Debug, PartialEq)]
+    pub struct CircuitDetails {
+        pub state: String,
+        pub consecutive_failures: u32,
+        pub open_remaining_seconds: Option<u64>,
+    }
+
+    #[derive(serde::Deserialize, Clone, Debug, PartialEq)]
+    pub struct AdaptiveLimitDetails {
+        pub working_threshold: u64,
+        pub ceiling: u64,
+        pub requests_this_minute: u64,
+    }
+
+    pub async fn get_accounts_health() -> Result<AccountHealthOverview, String> {
+        api_get("/accounts/health").await
+    }
+
+    pub async fn force_enable_account(account_id: &str) -> Result<bool, String> {
+        api_post(
+            &format!("/accounts/{}/health/enable", account_id),
+            &serde_json::json!({}),
+        )
+        .await
+    }
+
+    pub async fn reset_account_circuit(account_id: &str) -> Result<(), String> {
+        let _: bool = api_post(
+            &format!("/accounts/{}/circuit/reset", account_id),
+            &serde_json::json!({}),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn clear_account_rate_limit(account_id: &str) -> Result<bool, String> {
+        api_post(
+            &format!("/accounts/{}/rate-limit/clear", account_id),
+            &serde_json::json!({}),
+        )
+        .await
     }
 
     // ========== OAuth ==========
 
+    thread_local! {
+        /// State of the flow this tab started, so cancel only affects our own flow.
+        static OAUTH_STATE: std::cell::RefCell<Option<String>> =
+            const { std::cell::RefCell::new(None) };
+    }
+
+    fn remember_oauth_state(state: &str) {
+        OAUTH_STATE.with(|s| *s.borrow_mut() = Some(state.to_string()));
+    }
+
+    #[derive(serde::Deserialize)]
+    struct OAuthUrlResponse {
+        auth_url: String,
+        state: String,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct OAuthStatusResponse {
+        status: String,
+        account: Option<ApiAccount>,
+        error: Option<String>,
+    }
+
+    /// Open the Google consent page in a new tab and wait for the server-side callback.
     pub async fn start_oauth_login() -> Result<Account, String> {
-        // TODO: Implement OAuth endpoint
-        Err("OAuth not implemented in headless mode".to_string())
+        let resp: OAuthUrlResponse = api_get("/oauth/url").await?;
+        remember_oauth_state(&resp.state);
+
+        let window = web_sys::window().ok_or("No window")?;
+        window
+            .open_with_url_and_target(&resp.auth_url, "_blank")
+            .map_err(|e| format!("Failed to open browser tab: {:?}", e))?;
+
+        // Poll for up to 5 minutes
+        for _ in 0..150 {
+            gloo_timers::future::TimeoutFuture::new(2_000).await;
+            let status: OAuthStatusResponse =
+                api_get(&format!("/oauth/status?state={}", resp.state)).await?;
+            match status.status.as_str() {
+                "completed" => {
+                    return status
+                        .account
+                        .map(|a| a.into_account())
+                        .ok_or_else(|| "OAuth completed without account".to_string());
+                }
+                "failed" => {
+                    return Err(status.error.unwrap_or_else(|| "OAuth failed".to_string()));
+                }
+                _ => {}
+            }
+        }
+
+        Err("OAuth timed out waiting for callback".to_string())
     }
 
     pub async fn prepare_oauth_url() -> Result<String, String> {
-        // TODO: Implement OAuth URL endpoint
-        Err("OAuth not implemented in headless mode".to_string())
+        let resp: OAuthUrlResponse = api_get("/oauth/url").await?;
+        remember_oauth_state(&resp.state);
+        Ok(resp.auth_url)
+    }
+
+    /// Finish OAuth by pasting the redirect URL (or bare code) when the callback is unreachable.
+    pub async fn complete_oauth_login(callback_url: &str) -> Result<Account, String> {
+        // A bare code carries no state; fall back to the flow this tab started.
+        let state = OAUTH_STATE.with(|s| s.borrow().clone());
+        let api_account: ApiAccount = api_post(
+            "/oauth/exchange",
+            &serde_json::json!({ "callback_url": callback_url, "state": state }),
+        )
+        .await?;
+        Ok(api_account.into_account())
     }
 
     pub async fn cancel_oauth_login() -> Result<(), String> {
+        let Some(state) = OAUTH_STATE.with(|s| s.borrow_mut().take()) else {
+            return Ok(());
+        };
+        let _: bool = api_post("/oauth/cancel", &serde_json::json!({ "state": state })).await?;
         Ok(())
     }
 
     // ========== Quota ==========
 
-    pub async fn fetch_account_quota(_account_id: &str) -> Result<QuotaData, String> {
-        // TODO: Implement quota refresh endpoint
-        Err("Not implemented".to_string())
+    pub async fn fetch_account_quota(account_id: &str) -> Result<QuotaData, String> {
+        api_post(
+            &format!("/accounts/{}/quota", account_id),
+            &serde_json::json!({}),
+        )
+        .await
     }
 
     pub async fn refresh_all_quotas() -> Result<RefreshStats, String> {
-        // TODO: Implement refresh endpoint
-        Err("Not implemented".to_string())
+        api_post("/accounts/refresh", &serde_json::json!({})).await
     }
 
     // ========== Import ==========
@@ -292,6 +558,34 @@ pub mod commands {
         api_get(&endpoint).await
     }
 
+    // ========== Usage analytics ==========
+
+    fn usage_query(since: i64, bucket: UsageBucket, group_by: UsageGroupBy) -> String {
+        format!(
+            "/stats/usage?since={}&bucket={}&group_by={}",
+            since,
+            bucket.as_str(),
+            group_by.as_str()
+        )
+    }
+
+    pub async fn get_usage_stats(
+        since: i64,
+        bucket: UsageBucket,
+        group_by: UsageGroupBy,
+    ) -> Result<Vec<UsageRollup>, String> {
+        api_get(&usage_query(since, bucket, group_by)).await
+    }
+
+    /// Download URL for the same rollup as CSV.
+    pub fn usage_csv_url(since: i64, bucket: UsageBucket, group_by: UsageGroupBy) -> String {
+        format!(
+            "{}{}&format=csv",
+            API_BASE,
+            usage_query(since, bucket, group_by)
+        )
+    }
+
     pub async fn set_proxy_monitor_enabled(_enabled: bool) -> Result<(), String> {
         // TODO: Implement monitor enable/disable endpoint if needed
         Ok(())
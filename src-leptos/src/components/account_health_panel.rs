@@ -0,0 +1,194 @@
+//! Account health panel: explains why the proxy pool skips an account
+
+use crate::api::commands::{self, AccountHealthEntry};
+use leptos::prelude::*;
+use leptos::task::spawn_local;
+
+#[component]
+pub fn AccountHealthPanel() -> impl IntoView {
+    let entries = RwSignal::new(Vec::<AccountHealthEntry>::new());
+    let summary = RwSignal::new((0usize, 0usize));
+    let loading = RwSignal::new(false);
+    let error = RwSignal::new(Option::<String>::None);
+
+    let reload = move || {
+        loading.set(true);
+        spawn_local(async move {
+            match commands::get_accounts_health().await {
+                Ok(overview) => {
+                    summary.set((overview.healthy_count, overview.disabled_count));
+                    entries.set(overview.accounts);
+                    error.set(None);
+                }
+                Err(e) => error.set(Some(e)),
+            }
+            loading.set(false);
+        });
+    };
+
+    // Initial load
+    Effect::new(move |_| reload());
+
+    let run_action = move |action: &'static str, account_id: String| {
+        spawn_local(async move {
+            let result = match action {
+                "enable" => commands::force_enable_account(&account_id)
+                    .await
+                    .map(|_| ()),
+                "circuit" => commands::reset_account_circuit(&account_id).await,
+                _ => commands::clear_account_rate_limit(&account_id)
+                    .await
+                    .map(|_| ()),
+            };
+            if let Err(e) = result {
+                error.set(Some(e));
+            }
+            reload();
+        });
+    };
+
+    view! {
+        <div class="health-panel">
+            <div class="health-panel__header">
+                <span class="health-panel__summary">
+                    {move || {
+                        let (healthy, disabled) = summary.get();
+                        let skipped = entries.get().iter().filter(|e| !e.available).count();
+                        format!("{} healthy · {} auto-disabled · {} skipped", healthy, disabled, skipped)
+                    }}
+                </span>
+                <button
+                    class=move || format!("btn btn--icon {}", if loading.get() { "loading" } else { "" })
+                    title="Refresh"
+                    on:click=move |_| reload()
+                >"🔄"</button>
+            </div>
+
+            <Show when=move || error.get().is_some()>
+                <div class="alert alert--error">
+                    <span>{move || error.get().unwrap_or_default()}</span>
+                </div>
+            </Show>
+
+            <table class="accounts-table health-table">
+                <thead>
+                    <tr>
+                        <th>"Email"</th>
+                        <th>"Status"</th>
+                        <th>"Health"</th>
+                        <th>"Circuit"</th>
+                        <th>"AIMD"</th>
+                        <th class="col-actions">"Actions"</th>
+                    </tr>
+                </thead>
+                <tbody>
+                    <For
+                        each=move || entries.get()
+                        // Key on the full entry so rows re-render after each refresh
+                        key=|e| format!("{:?}", e)
+                        children=move |entry| {
+                            let health_disabled = entry.health.as_ref().is_some_and(|h| h.is_disabled);
+                            let circuit_tripped = entry.circuit.state != "closed";
+                            let rate_limited = entry.rate_limited;
+
+                            let health_text = entry.health.as_ref().map(|h| {
+                                let mut text = format!("{} · {:.0}%", h.status, h.success_rate);
+                                if h.consecutive_errors > 0 {
+                                    text.push_str(&format!(" · {} errors", h.consecutive_errors));
+                                }
+                                if let Some(secs) = h.cooldown_remaining_seconds {
+                                    text.push_str(&format!(" · {}s cooldown", secs));
+                                }
+                                text
+                            }).unwrap_or_else(|| "—".to_string());
+                            let health_title = entry.health.as_ref()
+                                .and_then(|h| h.last_error_message.clone())
+                                .unwrap_or_default();
+
+                            let circuit_text = match entry.circuit.open_remaining_seconds {
+                                Some(secs) => format!("{} ({}s)", entry.circuit.state, secs),
+                                None => entry.circuit.state.clone(),
+                            };
+
+                            let aimd_text = format!(
+                                "{}/{} rpm · ceiling {}",
+                                entry.adaptive.requests_this_minute,
+                                entry.adaptive.working_threshold,
+                                entry.adaptive.ceiling
+                            );
+
+                            let status_text = entry.skip_reason.clone().unwrap_or_else(|| "Available".to_string());
+                            let status_class = if !entry.available {
+                                "status-badge--error"
+                            } else if entry.skip_reason.is_some() {
+                                "status-badge--warning"
+                            } else {
+                                "status-badge--success"
+                            };
+
+                            let id_enable = entry.account_id.clone();
+                            let id_circuit = entry.account_id.clone();
+                            let id_rate = entry.account_id.clone();
+
+                            view! {
+                                <tr class="account-row">
+                                    <td class="col-email">
+                                        <span class="email-text">{entry.email.clone()}</span>
+                                    </td>
+                                    <td>
+                                        <span class=format!("status-badge {}", status_class)>{status_text}</span>
+                                    </td>
+                                    <td title=health_title>{health_text}</td>
+                                    <td>
+                                        <span class=format!("circuit-state circuit-state--{}", entry.circuit.state)>
+                                            {circuit_text}
+                                        </span>
+                                    </td>
+                                    <td class="health-table__aimd">{aimd_text}</td>
+                                    <td class="col-actions">
+                                        <Show when=move || health_disabled>
+                                            <button
+                                                class="btn btn--secondary btn--sm"
+                                                title="Re-enable account in health monitor"
+                                                on:click={
+                                                    let id = id_enable.clone();
+                                                    move |_| run_action("enable", id.clone())
+                                                }
+                                            >"Re-enable"</button>
+                                        </Show>
+                                        <Show when=move || circuit_tripped>
+                                            <button
+                                                class="btn btn--secondary btn--sm"
+                                                title="Close the circuit breaker"
+                                                on:click={
+                                                    let id = id_circuit.clone();
+                                                    move |_| run_action("circuit", id.clone())
+                                                }
+                                            >"Reset circuit"</button>
+                                        </Show>
+                                        <Show when=move || rate_limited>
+                                            <button
+                                                class="btn btn--secondary btn--sm"
+                                                title="Clear rate-limit lockout"
+                                                on:click={
+                                                    let id = id_rate.clone();
+                                                    move |_| run_action("rate", id.clone())
+                                                }
+                                            >"Clear limit"</button>
+                                        </Show>
+                                    </td>
+                                </tr>
+                            }
+                        }
+                    />
+                </tbody>
+            </table>
+
+            <Show when=move || entries.get().is_empty() && !loading.get()>
+                <div class="empty-state">
+                    <p>"No accounts in the proxy pool"</p>
+                </div>
+            </Show>
+        </div>
+    }
+}
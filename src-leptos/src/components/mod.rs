@@ -1,6 +1,7 @@
 //! Reusable UI components
 
 mod account_card;
+mod account_health_panel;
 mod button;
 mod collapsible_card;
 mod modal;
@@ -8,8 +9,10 @@ mod pagination;
 mod select;
 mod sidebar;
 mod stats_card;
+mod usage_chart;
 
 pub use account_card::AccountCard;
+pub use account_health_panel::AccountHealthPanel;
 pub use button::{Button, ButtonVariant};
 pub use collapsible_card::CollapsibleCard;
 pub use modal::{Modal, ModalType};
@@ -17,3 +20,4 @@ pub use pagination::Pagination;
 pub use select::Select;
 pub use sidebar::Sidebar;
 pub use stats_card::StatsCard;
+pub use usage_chart::UsageChart;
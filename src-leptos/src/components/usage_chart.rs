@@ -0,0 +1,233 @@
+//! Token usage chart (daily / hourly rollups from /api/stats/usage)
+
+use crate::api::commands;
+use crate::types::{UsageBucket, UsageGroupBy, UsageRollup};
+use leptos::prelude::*;
+use leptos::task::spawn_local;
+use std::collections::BTreeMap;
+
+const CHART_WIDTH: f64 = 600.0;
+const CHART_HEIGHT: f64 = 160.0;
+
+/// Selectable ranges: (label, lookback in ms, bucket)
+const RANGES: [(&str, i64, UsageBucket); 3] = [
+    ("24h", 24 * 60 * 60 * 1000, UsageBucket::Hour),
+    ("7d", 7 * 24 * 60 * 60 * 1000, UsageBucket::Day),
+    ("30d", 30 * 24 * 60 * 60 * 1000, UsageBucket::Day),
+];
+
+const GROUPS: [(&str, UsageGroupBy); 4] = [
+    ("Model", UsageGroupBy::Model),
+    ("Mapped model", UsageGroupBy::MappedModel),
+    ("Account", UsageGroupBy::Account),
+    ("API key", UsageGroupBy::ClientKey),
+];
+
+fn format_tokens(tokens: u64) -> String {
+    match tokens {
+        t if t >= 1_000_000 => format!("{:.1}M", t as f64 / 1_000_000.0),
+        t if t >= 1_000 => format!("{:.1}K", t as f64 / 1_000.0),
+        t => t.to_string(),
+    }
+}
+
+fn button_class(active: bool) -> &'static str {
+    if active {
+        "btn btn--sm btn--primary"
+    } else {
+        "btn btn--sm btn--secondary"
+    }
+}
+
+/// Input / output tokens per period, summed across groups.
+fn totals_by_period(rows: &[UsageRollup]) -> Vec<(i64, u64, u64)> {
+    let mut periods: BTreeMap<i64, (u64, u64)> = BTreeMap::new();
+    for row in rows {
+        let entry = periods.entry(row.period_start).or_default();
+        entry.0 += row.input_tokens;
+        entry.1 += row.output_tokens;
+    }
+    periods
+        .into_iter()
+        .map(|(period, (input, output))| (period, input, output))
+        .collect()
+}
+
+/// Per-group totals for the whole range, largest first.
+fn totals_by_group(rows: &[UsageRollup]) -> Vec<(String, u64, u64, u64, f64)> {
+    let mut groups: BTreeMap<String, (u64, u64, u64, f64)> = BTreeMap::new();
+    for row in rows {
+        let name = row.group.clone().unwrap_or_else(|| "(unknown)".to_string());
+        let entry = groups.entry(name).or_default();
+        entry.0 += row.requests;
+        entry.1 += row.input_tokens + row.output_tokens;
+        entry.2 += row.cached_tokens;
+        entry.3 += row.estimated_cost_usd;
+    }
+    let mut totals: Vec<_> = groups
+        .into_iter()
+        .map(|(name, (requests, tokens, cached, cost))| (name, requests, tokens, cached, cost))
+        .collect();
+    totals.sort_by(|a, b| b.2.cmp(&a.2));
+    totals
+}
+
+#[component]
+pub fn UsageChart() -> impl IntoView {
+    let range_index = RwSignal::new(1usize);
+    let group_by = RwSignal::new(UsageGroupBy::Model);
+    let rows = RwSignal::new(Vec::<UsageRollup>::new());
+    let error = RwSignal::new(Option::<String>::None);
+
+    let since = move || {
+        let (_, lookback, _) = RANGES[range_index.get()];
+        chrono::Utc::now().timestamp_millis() - lookback
+    };
+
+    Effect::new(move |_| {
+        let (_, _, bucket) = RANGES[range_index.get()];
+        let group = group_by.get();
+        let since = since();
+        spawn_local(async move {
+            match commands::get_usage_stats(since, bucket, group).await {
+                Ok(data) => {
+                    rows.set(data);
+                    error.set(None);
+                }
+                Err(e) => error.set(Some(e)),
+            }
+        });
+    });
+
+    let periods = Memo::new(move |_| totals_by_period(&rows.get()));
+    let groups = Memo::new(move |_| totals_by_group(&rows.get()));
+    let csv_url = move || {
+        let (_, _, bucket) = RANGES[range_index.get()];
+        commands::usage_csv_url(since(), bucket, group_by.get())
+    };
+
+    let bars = move || {
+        let periods = periods.get();
+        let max = periods
+            .iter()
+            .map(|(_, input, output)| input + output)
+            .max()
+            .unwrap_or(0)
+            .max(1) as f64;
+        let slot = CHART_WIDTH / periods.len().max(1) as f64;
+        let hourly = RANGES[range_index.get_untracked()].2 == UsageBucket::Hour;
+        periods
+            .into_iter()
+            .enumerate()
+            .map(|(i, (period, input, output))| {
+                let x = i as f64 * slot + slot * 0.15;
+                let width = slot * 0.7;
+                let input_height = input as f64 / max * CHART_HEIGHT;
+                let output_height = output as f64 / max * CHART_HEIGHT;
+                let label = chrono::DateTime::from_timestamp_millis(period)
+                    .map(|t| {
+                        if hourly {
+                            t.format("%m-%d %H:00").to_string()
+                        } else {
+                            t.format("%Y-%m-%d").to_string()
+                        }
+                    })
+                    .unwrap_or_default();
+                view! {
+                    <g data-label=label>
+                        <rect
+                            class="usage-bar usage-bar--input"
+                            x=x
+                            y=CHART_HEIGHT - input_height
+                            width=width
+                            height=input_height
+                        />
+                        <rect
+                            class="usage-bar usage-bar--output"
+                            x=x
+                            y=CHART_HEIGHT - input_height - output_height
+                            width=width
+                            height=output_height
+                        />
+                    </g>
+                }
+            })
+            .collect_view()
+    };
+
+    view! {
+        <section class="dashboard-card usage-card">
+            <div class="usage-header">
+                <h2>"Token Usage"</h2>
+                <div class="usage-controls">
+                    {RANGES.iter().enumerate().map(|(i, (label, _, _))| view! {
+                        <button
+                            class=move || button_class(range_index.get() == i)
+                            on:click=move |_| range_index.set(i)
+                        >
+                            {*label}
+                        </button>
+                    }).collect_view()}
+                    {GROUPS.iter().map(|(label, group)| {
+                        let group = *group;
+                        view! {
+                            <button
+                                class=move || button_class(group_by.get() == group)
+                                on:click=move |_| group_by.set(group)
+                            >
+                                {*label}
+                            </button>
+                        }
+                    }).collect_view()}
+                    <a class="btn btn--sm btn--secondary" href=csv_url download="usage.csv">
+                        "⬇ CSV"
+                    </a>
+                </div>
+            </div>
+
+            <Show when=move || error.get().is_some()>
+                <p class="empty-text">{move || error.get().unwrap_or_default()}</p>
+            </Show>
+
+            <Show
+                when=move || !periods.get().is_empty()
+                fallback=|| view! { <p class="empty-text">"No usage recorded in this range"</p> }
+            >
+                <svg
+                    class="usage-chart"
+                    viewBox=format!("0 0 {} {}", CHART_WIDTH, CHART_HEIGHT)
+                    preserveAspectRatio="none"
+                >
+                    {bars}
+                </svg>
+                <div class="usage-legend">
+                    <span class="usage-legend__input">"Input"</span>
+                    <span class="usage-legend__output">"Output"</span>
+                </div>
+
+                <table class="usage-table">
+                    <thead>
+                        <tr>
+                            <th>"Group"</th>
+                            <th>"Requests"</th>
+                            <th>"Tokens"</th>
+                            <th>"Cached"</th>
+                            <th>"Est. cost"</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {move || groups.get().into_iter().map(|(name, requests, tokens, cached, cost)| view! {
+                            <tr>
+                                <td>{name}</td>
+                                <td>{requests}</td>
+                                <td>{format_tokens(tokens)}</td>
+                                <td>{format_tokens(cached)}</td>
+                                <td>{format!("${:.2}", cost)}</td>
+                            </tr>
+                        }).collect_view()}
+                    </tbody>
+                </table>
+            </Show>
+        </section>
+    }
+}
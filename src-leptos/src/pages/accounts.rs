@@ -2,7 +2,10 @@
 
 use crate::api::commands;
 use crate::app::AppState;
-use crate::components::{AccountCard, Button, ButtonVariant, Modal, ModalType, Pagination};
+use crate::components::{
+    AccountCard, AccountHealthPanel, Button, ButtonVariant, CollapsibleCard, Modal, ModalType,
+    Pagination,
+};
 use leptos::prelude::*;
 use leptos::task::spawn_local;
 use std::collections::HashSet;
@@ -170,6 +173,7 @@ pub fn Accounts() -> impl IntoView {
     let state_refresh_account = state.clone();
     let state_delete = state.clone();
     let state_batch_delete = state.clone();
+    let state_toggle_proxy = state.clone();
 
     // Actions
     let _on_refresh_list = move || {
@@ -310,18 +314,22 @@ pub fn Accounts() -> impl IntoView {
     };
 
     let execute_toggle_proxy = move || {
-        if let Some((_id, enable)) = toggle_proxy_confirm.get() {
+        if let Some((id, enable)) = toggle_proxy_confirm.get() {
             toggle_proxy_confirm.set(None);
+            let s = state_toggle_proxy.clone();
             spawn_local(async move {
-                // This would need a toggle_proxy_status command
-                // For now we'll just refresh the list
-                show_message(
-                    format!(
-                        "Proxy {} (TODO: implement)",
-                        if enable { "enabled" } else { "disabled" }
-                    ),
-                    false,
-                );
+                match commands::toggle_proxy_status(&id, enable, None).await {
+                    Ok(()) => {
+                        if let Ok(accounts) = commands::list_accounts().await {
+                            s.accounts.set(accounts);
+                        }
+                        show_message(
+                            format!("Proxy {}", if enable { "enabled" } else { "disabled" }),
+                            false,
+                        );
+                    }
+                    Err(e) => show_message(format!("Failed: {}", e), true),
+                }
             });
         }
     };
@@ -727,6 +735,11 @@ pub fn Accounts() -> impl IntoView {
                 />
             </Show>
 
+            // Proxy pool health (rate limits, circuit breaker, AIMD)
+            <CollapsibleCard title="Proxy Pool Health".to_string() initial_expanded=false>
+                <AccountHealthPanel />
+            </CollapsibleCard>
+
             // Modals
             <Modal
                 is_open=Signal::derive(move || delete_confirm.get().is_some())
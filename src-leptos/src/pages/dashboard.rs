@@ -2,7 +2,7 @@
 
 use crate::api::commands;
 use crate::app::AppState;
-use crate::components::{Button, ButtonVariant, StatsCard};
+use crate::components::{Button, ButtonVariant, StatsCard, UsageChart};
 use crate::types::DashboardStats;
 use leptos::prelude::*;
 use leptos::task::spawn_local;
@@ -335,6 +335,9 @@ pub fn Dashboard() -> impl IntoView {
                 </section>
             </div>
 
+            // Token usage over time
+            <UsageChart />
+
             // Tier breakdown
             <section class="tier-section">
                 <h2>"Account Tiers"</h2>
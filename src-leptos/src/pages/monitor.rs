@@ -1,12 +1,15 @@
 //! Monitor page - Real-time request logging
 
-use crate::api::commands;
+use crate::api::{commands, subscribe_proxy_logs};
 use crate::app::AppState;
 use crate::components::{Button, ButtonVariant};
 use crate::types::{ProxyRequestLog, ProxyStats};
 use leptos::prelude::*;
 use leptos::task::spawn_local;
 
+/// Rows kept in the live table (matches the initial fetch)
+const MAX_LIVE_LOGS: usize = 100;
+
 #[component]
 pub fn Monitor() -> impl IntoView {
     let state = expect_context::<AppState>();
@@ -23,7 +26,7 @@ pub fn Monitor() -> impl IntoView {
         loading.set(true);
         spawn_local(async move {
             // Load logs
-            if let Ok(new_logs) = commands::get_proxy_logs(Some(100)).await {
+            if let Ok(new_logs) = commands::get_proxy_logs(Some(MAX_LIVE_LOGS)).await {
                 logs.set(new_logs);
             }
             // Load stats
@@ -39,8 +42,41 @@ pub fn Monitor() -> impl IntoView {
         load_data();
     });
 
-    // Auto-refresh every 2 seconds when enabled
+    // Live tail via /api/monitor/stream (dropped with the page, which closes the stream)
+    let live_stream = StoredValue::new_local(
+        subscribe_proxy_logs(move |log| {
+            if !logging_enabled.get_untracked() {
+                return;
+            }
+            stats.update(|s| {
+                s.total_requests += 1;
+                if log.status >= 400 {
+                    s.error_count += 1;
+                } else {
+                    s.success_count += 1;
+                }
+                s.total_input_tokens += log.input_tokens.unwrap_or(0) as u64;
+                s.total_output_tokens += log.output_tokens.unwrap_or(0) as u64;
+            });
+            logs.update(|list| {
+                list.insert(0, log);
+                list.truncate(MAX_LIVE_LOGS);
+            });
+        })
+        .map_err(|e| {
+            log::warn!(
+                "Live monitor stream unavailable, falling back to polling: {}",
+                e
+            )
+        })
+        .ok(),
+    );
+
+    // Fallback: refresh every 2 seconds when the live stream is unavailable
     Effect::new(move |_| {
+        if live_stream.with_value(|s| s.is_some()) {
+            return;
+        }
         if logging_enabled.get() && state.proxy_status.get().running {
             spawn_local(async move {
                 gloo_timers::future::TimeoutFuture::new(2000).await;
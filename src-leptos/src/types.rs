@@ -2,7 +2,7 @@
 
 pub use antigravity_shared::models::{
     Account, AppConfig, DashboardStats, ProxyRequestLog, ProxyStats, ProxyStatus, QuotaData,
-    RefreshStats, UpdateInfo,
+    RefreshStats, UpdateInfo, UsageBucket, UsageGroupBy, UsageRollup,
 };
 pub use antigravity_shared::proxy::config::{
     Protocol, ProxyAuthMode, ProxyConfig, ZaiConfig, ZaiDispatchMode,